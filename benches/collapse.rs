@@ -0,0 +1,42 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+
+use hostlist_iter::{collapse_hosts, collapse_hosts_multi};
+
+/// Host names like an inventory dump: racks of nodes, shuffled out of order by a fixed stride
+fn rack_hosts(racks: u32, nodes: u32) -> Vec<String> {
+    let total = racks * nodes;
+    (0..total)
+        .map(|i| (i * 7919) % total)
+        .map(|i| format!("r{}n{:03}", i / nodes + 1, i % nodes + 1))
+        .collect()
+}
+
+fn criterion_benchmark_collapse(c: &mut Criterion) {
+    let hosts: Vec<String> = (1..=100_000).map(|i| format!("n{i}")).collect();
+    c.bench_function("collapse 100k", |b| {
+        b.iter(|| collapse_hosts(black_box(&hosts)));
+    });
+}
+
+fn criterion_benchmark_collapse_racks(c: &mut Criterion) {
+    let hosts = rack_hosts(100, 1000);
+    c.bench_function("collapse 100 racks x 1k", |b| {
+        b.iter(|| collapse_hosts(black_box(&hosts)));
+    });
+}
+
+fn criterion_benchmark_collapse_multi(c: &mut Criterion) {
+    let hosts = rack_hosts(100, 1000);
+    c.bench_function("collapse multi 100 racks x 1k", |b| {
+        b.iter(|| collapse_hosts_multi(black_box(&hosts)));
+    });
+}
+
+criterion_group!(
+    benches,
+    criterion_benchmark_collapse,
+    criterion_benchmark_collapse_racks,
+    criterion_benchmark_collapse_multi
+);
+criterion_main!(benches);
@@ -7,16 +7,28 @@ fn hostlist_n(n: u32) -> Result<()> {
     let hostlist_expr = format!("n[1-{n}]");
     let hostlist = Hostlist::new(&hostlist_expr)?;
 
-    let mut i: u32 = 1;
-    for host in hostlist {
+    for (i, host) in (1_u32..).zip(hostlist) {
         let expected = format!("n{i}");
         assert_eq!(host, expected);
-        i += 1;
     }
 
     Ok(())
 }
 
+/// Like `hostlist_n()`, but writing every host into one reused buffer
+fn hostlist_n_into(n: u32) -> Result<usize> {
+    let hostlist = Hostlist::new(&format!("n[1-{n}]"))?;
+    let mut hosts = hostlist.iter();
+    let mut buf = String::new();
+    let mut bytes = 0;
+    while hosts.next_into(&mut buf) {
+        bytes += buf.len();
+        buf.clear();
+    }
+
+    Ok(bytes)
+}
+
 fn criterion_benchmark_100k(c: &mut Criterion) {
     c.bench_function("hostlist 100k", |b| {
         b.iter(|| hostlist_n(black_box(100_000)));
@@ -29,6 +41,12 @@ fn criterion_benchmark_1m(c: &mut Criterion) {
     });
 }
 
+fn criterion_benchmark_next_into_1m(c: &mut Criterion) {
+    c.bench_function("hostlist next_into 1m", |b| {
+        b.iter(|| hostlist_n_into(black_box(1_000_000)));
+    });
+}
+
 // Custom configuration function
 fn custom_criterion() -> Criterion {
     Criterion::default()
@@ -41,7 +59,7 @@ fn custom_criterion() -> Criterion {
 criterion_group! {
     name = benches;
     config = custom_criterion();
-    targets = criterion_benchmark_100k, criterion_benchmark_1m
+    targets = criterion_benchmark_100k, criterion_benchmark_1m, criterion_benchmark_next_into_1m
 }
 
 criterion_main!(benches);
@@ -0,0 +1,12 @@
+#!/bin/sh
+# Records what scontrol prints for each "<command>\t<argument>" line on stdin (command being
+# "hostnames" or "hostlist"), in the format of src/testdata/scontrol.txt:
+#
+#   printf 'hostnames\tn[1-3]\n' | scripts/record-scontrol.sh >> src/testdata/scontrol.txt
+set -eu
+
+tab=$(printf '\t')
+while IFS="$tab" read -r command argument; do
+    output=$(scontrol show "$command" "$argument" | paste -sd ' ' -)
+    printf '%s\t%s\t%s\n' "$command" "$argument" "$output"
+done
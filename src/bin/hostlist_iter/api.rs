@@ -0,0 +1,208 @@
+use std::io::BufRead;
+
+use hostlist_iter::Hostlist;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::output::Output;
+use crate::{Context, Result};
+
+/// The most hosts an operation expands when `--max-hosts` isn't given, so that a request can't
+/// make the server or a batch expand an unbounded hostlist
+pub const DEFAULT_MAX_HOSTS: usize = 1_000_000;
+
+/// The most hosts all the operations of a batch expand together, unless given
+pub const DEFAULT_MAX_BATCH_HOSTS: usize = 10 * DEFAULT_MAX_HOSTS;
+
+/// A single operation, named by its "op" field (ex: `{"op": "count", "expr": "n[1-3]"}`)
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Request {
+    /// list the hosts in `expr`
+    Expand { expr: String },
+    /// fold `hosts` into a hostlist
+    Collapse { hosts: Vec<String> },
+    /// count the hosts in `expr`
+    Count { expr: String },
+    /// hosts in any of `exprs`
+    Union { exprs: Vec<String> },
+    /// hosts in all of `exprs`
+    Intersect { exprs: Vec<String> },
+    /// hosts in the first of `exprs` but none of the others
+    Subtract { exprs: Vec<String> },
+}
+
+impl Request {
+    /// Names of all operations, as used in the "op" field
+    #[cfg(feature = "serve")]
+    pub const OPS: [&str; 6] = [
+        "expand",
+        "collapse",
+        "count",
+        "union",
+        "intersect",
+        "subtract",
+    ];
+
+    /// Runs the operation, returning its result as a JSON object. Hosts expanded are taken from
+    /// `remaining`, and it's an error to expand more than are left.
+    pub fn run(self, ctx: &Context, remaining: &mut usize) -> Result<Value> {
+        let result = match self {
+            Self::Expand { expr } => {
+                let hostlist = ctx.expand(&expr)?;
+                *remaining = remaining.checked_sub(hostlist.len()).ok_or_else(|| {
+                    format!(
+                        "\"{expr}\" expands to {} hosts, more than the {remaining} left",
+                        hostlist.len()
+                    )
+                })?;
+                json!({ "hosts": hostlist.collect::<Vec<_>>() })
+            }
+            Self::Collapse { hosts } => json!({ "hostlist": ctx.collapse(hosts)? }),
+            Self::Count { expr } => json!({ "count": ctx.hostlist(&expr)?.len() }),
+            Self::Union { exprs } => json!({
+                "hostlist": combine(ctx, &exprs, Hostlist::union)?
+            }),
+            Self::Intersect { exprs } => json!({
+                "hostlist": combine(ctx, &exprs, Hostlist::intersection)?
+            }),
+            Self::Subtract { exprs } => json!({
+                "hostlist": combine(ctx, &exprs, Hostlist::difference)?
+            }),
+        };
+
+        Ok(result)
+    }
+}
+
+/// Combines each hostlist after the first with the result so far, using `op`. Hostlists are
+/// combined without expanding them, so they can be any size.
+fn combine(
+    ctx: &Context,
+    exprs: &[String],
+    op: impl Fn(&Hostlist, &Hostlist) -> hostlist_iter::Result<Hostlist>,
+) -> Result<String> {
+    let (first, rest) = exprs
+        .split_first()
+        .ok_or("expected at least one hostlist in \"exprs\"")?;
+    let mut hostlist = ctx.hostlist(first)?;
+    for expr in rest {
+        hostlist = op(&hostlist, &ctx.hostlist(expr)?)?;
+    }
+
+    Ok(hostlist.to_string())
+}
+
+/// Runs one operation per line of `input`, writing each result as a line of JSON. Failed
+/// operations produce an object with an "error" field rather than stopping the batch. The
+/// operations together expand at most `max_total_hosts` hosts.
+pub fn batch(
+    ctx: &Context,
+    out: &mut Output,
+    input: impl BufRead,
+    max_total_hosts: usize,
+) -> Result<()> {
+    let mut remaining = max_total_hosts;
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let result = serde_json::from_str::<Request>(&line)
+            .map_err(|e| e.to_string())
+            .and_then(|req| req.run(ctx, &mut remaining).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| json!({ "error": e }));
+        out.write_line(&result.to_string());
+        // Callers typically wait for each result before sending the next request
+        out.flush();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(ctx: &Context, request: &str) -> Result<Value> {
+        let mut remaining = usize::MAX;
+        serde_json::from_str::<Request>(request)?.run(ctx, &mut remaining)
+    }
+
+    #[test]
+    fn test_request_run() -> Result<()> {
+        let ctx = Context::default();
+        let tests = [
+            (
+                r#"{"op": "expand", "expr": "n[1-3]"}"#,
+                r#"{"hosts":["n1","n2","n3"]}"#,
+            ),
+            (
+                r#"{"op": "collapse", "hosts": ["n2", "n1"]}"#,
+                r#"{"hostlist":"n[1-2]"}"#,
+            ),
+            (
+                r#"{"op": "count", "expr": "n[1-1000000000]"}"#,
+                r#"{"count":1000000000}"#,
+            ),
+            (
+                r#"{"op": "union", "exprs": ["n[1-5]", "n[4-9]", "m1"]}"#,
+                r#"{"hostlist":"m1,n[1-9]"}"#,
+            ),
+            (
+                r#"{"op": "intersect", "exprs": ["n[1-5]", "n[4-9]"]}"#,
+                r#"{"hostlist":"n[4-5]"}"#,
+            ),
+            (
+                r#"{"op": "subtract", "exprs": ["n[1-1000000000]", "n[2-1000000000]"]}"#,
+                r#"{"hostlist":"n[1]"}"#,
+            ),
+        ];
+        for (request, expected) in tests {
+            assert_eq!(
+                run(&ctx, request)?.to_string(),
+                expected,
+                "request: {request}"
+            );
+        }
+
+        assert!(run(&ctx, r#"{"op": "union", "exprs": []}"#).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_max_hosts() -> Result<()> {
+        let ctx = Context {
+            max_hosts: Some(3),
+            ..Context::default()
+        };
+        assert!(run(&ctx, r#"{"op": "expand", "expr": "n[1-3]"}"#).is_ok());
+        let err = run(&ctx, r#"{"op": "expand", "expr": "n[1-4]"}"#).unwrap_err();
+        assert!(
+            err.to_string().contains("more than the maximum of 3"),
+            "{err}"
+        );
+
+        // Each request fits, but not all of them together
+        let mut remaining = 5;
+        let request = |expr: &str| {
+            serde_json::from_value::<Request>(json!({ "op": "expand", "expr": expr })).unwrap()
+        };
+        assert!(request("n[1-3]").run(&ctx, &mut remaining).is_ok());
+        assert!(request("n[1-3]").run(&ctx, &mut remaining).is_err());
+        assert!(request("n[1-2]").run(&ctx, &mut remaining).is_ok());
+        assert_eq!(remaining, 0);
+        assert!(request("n1").run(&ctx, &mut remaining).is_err());
+
+        // Hostlists that aren't expanded can be any size
+        let request = r#"{"op": "union", "exprs": ["n[1-100]", "n[101-200]"]}"#;
+        assert_eq!(
+            run(&ctx, request)?.to_string(),
+            r#"{"hostlist":"n[1-200]"}"#
+        );
+
+        Ok(())
+    }
+}
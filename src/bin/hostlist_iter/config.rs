@@ -0,0 +1,70 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::Result;
+use crate::output::ColorChoice;
+
+/// Persistent defaults, read from `~/.config/hostlist_iter/config.toml`. Command line flags
+/// override anything set here.
+///
+/// Example:
+/// ```toml
+/// color = "never"
+/// separator = ","
+/// max_hosts = 100000
+/// groups = "/etc/hostlist_iter/groups"
+/// ignore_case = true
+/// ```
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// when to color output
+    pub color: Option<ColorChoice>,
+
+    /// separator printed between expanded hosts
+    pub separator: Option<String>,
+
+    /// refuse to expand hostlists with more hosts than this
+    pub max_hosts: Option<usize>,
+
+    /// file of named groups usable as `@name` in hostlists
+    pub groups: Option<PathBuf>,
+
+    /// lowercase host names so that names differing only in case are the same host
+    pub ignore_case: Option<bool>,
+}
+
+impl Config {
+    /// Loads the config file at `path`, or the default location if no path is given. It's only
+    /// an error for the default config file to be missing if it was explicitly requested.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Self::default());
+            }
+            Err(e) => return Err(format!("{}: {e}", path.display()).into()),
+        };
+
+        toml::from_str(&contents).map_err(|e| format!("{}: {e}", path.display()).into())
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config_dir.join("hostlist_iter").join("config.toml"))
+}
@@ -0,0 +1,209 @@
+use hostlist_iter::Hostlist;
+
+use crate::{Context, Result};
+
+/// A set operation between two hostlists
+#[derive(Clone, Copy)]
+enum Op {
+    Union,
+    Intersect,
+    Subtract,
+}
+
+enum Token<'a> {
+    Hostlist(&'a str),
+    Op(Op),
+    Open,
+    Close,
+}
+
+/// Evaluates an expression of hostlists combined with `|` or `+` (union), `&` (intersection),
+/// and ` - ` (difference, with whitespace around it so it isn't part of a host name). Operators
+/// apply left to right, and parentheses group (ex: "node[1-100] - (node[40-49] & @gpu)").
+/// Operands are combined as hostlists, so hosts are never expanded.
+pub fn eval(ctx: &Context, expr: &str) -> Result<String> {
+    let tokens = tokenize(expr);
+    let mut pos = 0;
+    let hostlist = eval_expr(ctx, &tokens, &mut pos)?;
+    if pos < tokens.len() {
+        return Err("unmatched \")\"".into());
+    }
+
+    Ok(hostlist.to_string())
+}
+
+fn tokenize(expr: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let token = match c {
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '|' | '+' => Token::Op(Op::Union),
+            '&' => Token::Op(Op::Intersect),
+            '-' if is_difference(&rest[1..]) => Token::Op(Op::Subtract),
+            _ => {
+                let len = hostlist_len(rest);
+                tokens.push(Token::Hostlist(rest[..len].trim_end()));
+                rest = rest[len..].trim_start();
+                continue;
+            }
+        };
+        tokens.push(token);
+        rest = rest[1..].trim_start();
+    }
+
+    tokens
+}
+
+/// Returns whether a "-" followed by `rest` is the difference operator rather than part of a
+/// host name
+fn is_difference(rest: &str) -> bool {
+    rest.is_empty() || rest.starts_with(|c: char| c.is_whitespace() || c == '(')
+}
+
+/// Returns the length of the hostlist at the start of `expr`, which runs up to the next operator
+/// or parenthesis outside of brackets
+fn hostlist_len(expr: &str) -> usize {
+    let mut in_range = false;
+    let mut after_space = false;
+    for (i, c) in expr.char_indices() {
+        match c {
+            '[' => in_range = true,
+            ']' => in_range = false,
+            '(' | ')' | '|' | '+' | '&' if !in_range => return i,
+            '-' if !in_range && after_space && is_difference(&expr[i + 1..]) => return i,
+            _ => {}
+        }
+        after_space = c.is_whitespace();
+    }
+    expr.len()
+}
+
+/// Evaluates operands and operators from `tokens[*pos]` up to a closing parenthesis or the end
+fn eval_expr(ctx: &Context, tokens: &[Token], pos: &mut usize) -> Result<Hostlist> {
+    let mut hostlist = eval_operand(ctx, tokens, pos)?;
+    while let Some(token) = tokens.get(*pos) {
+        let op = match token {
+            Token::Op(op) => *op,
+            Token::Close => break,
+            Token::Hostlist(_) | Token::Open => {
+                return Err("expected an operator between hostlists".into());
+            }
+        };
+        *pos += 1;
+        let other = eval_operand(ctx, tokens, pos)?;
+        hostlist = match op {
+            Op::Union => hostlist.union(&other)?,
+            Op::Intersect => hostlist.intersection(&other)?,
+            Op::Subtract => hostlist.difference(&other)?,
+        };
+    }
+
+    Ok(hostlist)
+}
+
+/// Evaluates a hostlist or a parenthesized expression
+fn eval_operand(ctx: &Context, tokens: &[Token], pos: &mut usize) -> Result<Hostlist> {
+    let token = tokens.get(*pos).ok_or("expected a hostlist at the end")?;
+    *pos += 1;
+    match token {
+        Token::Hostlist(expr) => ctx.hostlist(expr),
+        Token::Open => {
+            let hostlist = eval_expr(ctx, tokens, pos)?;
+            match tokens.get(*pos) {
+                Some(Token::Close) => {
+                    *pos += 1;
+                    Ok(hostlist)
+                }
+                _ => Err("unmatched \"(\"".into()),
+            }
+        }
+        Token::Op(_) | Token::Close => Err("expected a hostlist".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Describes tokens as text, for comparing them
+    fn describe(tokens: &[Token]) -> Vec<String> {
+        tokens
+            .iter()
+            .map(|token| match token {
+                Token::Hostlist(expr) => format!("{expr:?}"),
+                Token::Op(Op::Union) => "|".to_string(),
+                Token::Op(Op::Intersect) => "&".to_string(),
+                Token::Op(Op::Subtract) => "-".to_string(),
+                Token::Open => "(".to_string(),
+                Token::Close => ")".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tests: [(&str, &[&str]); 8] = [
+            ("", &[]),
+            ("n[1-3]", &["\"n[1-3]\""]),
+            ("n[1-3] - n2", &["\"n[1-3]\"", "-", "\"n2\""]),
+            (
+                "gpu-ib[1-3]-a - gpu-ib2-a",
+                &["\"gpu-ib[1-3]-a\"", "-", "\"gpu-ib2-a\""],
+            ),
+            ("n[1-3]-n2", &["\"n[1-3]-n2\""]),
+            (
+                "a|b+c&d",
+                &["\"a\"", "|", "\"b\"", "|", "\"c\"", "&", "\"d\""],
+            ),
+            (
+                " (n[1-9,10] & m) - (x) ",
+                &[
+                    "(",
+                    "\"n[1-9,10]\"",
+                    "&",
+                    "\"m\"",
+                    ")",
+                    "-",
+                    "(",
+                    "\"x\"",
+                    ")",
+                ],
+            ),
+            ("n1 -(n2)", &["\"n1\"", "-", "(", "\"n2\"", ")"]),
+        ];
+        for (expr, expected) in tests {
+            assert_eq!(describe(&tokenize(expr)), expected, "expr: {expr}");
+        }
+    }
+
+    #[test]
+    fn test_eval() -> Result<()> {
+        let ctx = Context::default();
+        let tests = [
+            ("n[1-5]", "n[1-5]"),
+            ("n[1-5] | n[4-8]", "n[1-8]"),
+            ("n[1-5] + m1", "m1,n[1-5]"),
+            ("n[1-5] & n[4-8]", "n[4-5]"),
+            ("n[1-5] - n[2-3]", "n[1,4-5]"),
+            // Operators apply left to right, whatever they are
+            ("n[1-5] | n[6-9] & n[5-6]", "n[5-6]"),
+            ("n[1-5] & n[5-6] | n[8-9]", "n[5,8-9]"),
+            ("n[1-9] - n[1-3] - n[7-9]", "n[4-6]"),
+            ("n[1-9] - (n[1-3] | n[7-9])", "n[4-6]"),
+            ("n[1-9] - (n[1-5] & (n[4-6]))", "n[1-3,6-9]"),
+            // Never expanded, so huge hostlists are fine
+            ("n[1-1000000000000] - n[2-1000000000000]", "n[1]"),
+        ];
+        for (expr, expected) in tests {
+            assert_eq!(eval(&ctx, expr)?, expected, "expr: {expr}");
+        }
+
+        for expr in ["", "n1 -", "(n1", "n1)", "n1 (n2)", "| n1"] {
+            assert!(eval(&ctx, expr).is_err(), "expr: {expr}");
+        }
+
+        Ok(())
+    }
+}
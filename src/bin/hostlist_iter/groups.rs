@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::Result;
+
+/// Named hostlists, referred to as `@name` inside hostlist arguments.
+///
+/// The groups file has one `name: hostlist` entry per line. Blank lines and lines starting with
+/// `#` are ignored:
+/// ```text
+/// # GPU nodes
+/// gpu: gpu[1-16]
+/// login: login[1-2]
+/// ```
+#[derive(Default)]
+pub struct Groups {
+    groups: HashMap<String, String>,
+}
+
+impl Groups {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::parse(&contents, path)
+    }
+
+    /// Reads the entries of a groups file, with `path` naming the file in errors
+    fn parse(contents: &str, path: &Path) -> Result<Self> {
+        let mut groups = HashMap::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, hostlist) = line.split_once(':').ok_or_else(|| {
+                format!("{}:{}: expected \"name: hostlist\"", path.display(), i + 1)
+            })?;
+            groups.insert(name.trim().to_string(), hostlist.trim().to_string());
+        }
+
+        Ok(Self { groups })
+    }
+
+    /// Replaces every `@name` reference in a hostlist expression with the group's hostlist
+    pub fn resolve(&self, expr: &str) -> Result<String> {
+        let mut resolved = String::with_capacity(expr.len());
+        let mut rest = expr;
+        while let Some(at) = rest.find('@') {
+            resolved.push_str(&rest[..at]);
+            rest = &rest[at + 1..];
+
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "_-.".contains(c)))
+                .unwrap_or(rest.len());
+            let name = &rest[..end];
+            let hostlist = self
+                .groups
+                .get(name)
+                .ok_or_else(|| format!("unknown group: \"@{name}\""))?;
+            resolved.push_str(hostlist);
+            rest = &rest[end..];
+        }
+        resolved.push_str(rest);
+
+        Ok(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups() -> Groups {
+        let contents = "# GPU nodes\ngpu: gpu[1-16]\n\n  login :login[1-2]  \nrack-1.a: r1n[1-4]\n";
+        Groups::parse(contents, Path::new("groups")).unwrap()
+    }
+
+    #[test]
+    fn test_groups_parse() {
+        let groups = groups();
+        assert_eq!(groups.groups.len(), 3);
+        assert_eq!(groups.groups["login"], "login[1-2]");
+        assert_eq!(groups.groups["rack-1.a"], "r1n[1-4]");
+
+        let err = Groups::parse("gpu: gpu[1-16]\nlogin\n", Path::new("groups"))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "groups:2: expected \"name: hostlist\"");
+    }
+
+    #[test]
+    fn test_groups_resolve() -> Result<()> {
+        let groups = groups();
+        let tests = [
+            ("n[1-2]", "n[1-2]"),
+            ("@gpu", "gpu[1-16]"),
+            ("@login,@gpu", "login[1-2],gpu[1-16]"),
+            ("@rack-1.a,n1", "r1n[1-4],n1"),
+            (
+                "n[1-10] - @gpu & (@login)",
+                "n[1-10] - gpu[1-16] & (login[1-2])",
+            ),
+        ];
+        for (expr, expected) in tests {
+            assert_eq!(groups.resolve(expr)?, expected, "{expr}");
+        }
+
+        let err = groups.resolve("@gpu,@missing").err().unwrap();
+        assert_eq!(err.to_string(), "unknown group: \"@missing\"");
+        assert!(Groups::default().resolve("@gpu").is_err());
+
+        Ok(())
+    }
+}
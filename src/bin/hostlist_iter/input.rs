@@ -0,0 +1,154 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use flate2::bufread::MultiGzDecoder;
+
+use crate::Result;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Opens a file for reading, transparently decompressing gzip (and, with the "zstd" feature,
+/// zstd) files. Compression is detected from the file's contents rather than its name.
+pub fn open(path: &Path) -> Result<Box<dyn BufRead>> {
+    let file = File::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let header = reader
+        .fill_buf()
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+
+    if header.starts_with(GZIP_MAGIC) {
+        return Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))));
+    }
+    if header.starts_with(ZSTD_MAGIC) {
+        return open_zstd(path, reader);
+    }
+    Ok(Box::new(reader))
+}
+
+/// Reads host names, one per line (or NUL-terminated), skipping empty entries
+pub fn read_hosts(mut reader: impl BufRead, null: bool) -> Result<Vec<String>> {
+    let delimiter = if null { b'\0' } else { b'\n' };
+    let mut hosts = Vec::new();
+    let mut buf = Vec::new();
+    while reader.read_until(delimiter, &mut buf)? > 0 {
+        if buf.last() == Some(&delimiter) {
+            buf.pop();
+        }
+        let host = String::from_utf8(std::mem::take(&mut buf))?;
+        // Newline-delimited input is usually hand-edited, so tolerate stray whitespace
+        let host = if null { host } else { host.trim().to_string() };
+        if !host.is_empty() {
+            hosts.push(host);
+        }
+    }
+    Ok(hosts)
+}
+
+#[cfg(feature = "zstd")]
+fn open_zstd(path: &Path, reader: BufReader<File>) -> Result<Box<dyn BufRead>> {
+    let decoder =
+        zstd::Decoder::with_buffer(reader).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(Box::new(BufReader::new(decoder)))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn open_zstd(path: &Path, _reader: BufReader<File>) -> Result<Box<dyn BufRead>> {
+    Err(format!(
+        "{}: reading zstd files requires building with the \"zstd\" feature",
+        path.display()
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    use super::*;
+
+    /// Writes `contents` to a file in the temp directory named for the test, returning its path
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("hostlist_iter-input-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn read_file(path: &Path, null: bool) -> Result<Vec<String>> {
+        let hosts = read_hosts(open(path)?, null);
+        std::fs::remove_file(path)?;
+        hosts
+    }
+
+    #[test]
+    fn test_read_hosts() -> Result<()> {
+        let tests: [(&[u8], bool, &[&str]); 6] = [
+            (b"n1\nn2\n", false, &["n1", "n2"]),
+            (b"n1\nn2", false, &["n1", "n2"]),
+            (b"  n1 \r\n\n\t\nn2\n", false, &["n1", "n2"]),
+            (b"", false, &[]),
+            (b"n1\0n 2\0\0", true, &["n1", "n 2"]),
+            (b"n1\nn2\0", true, &["n1\nn2"]),
+        ];
+        for (input, null, expected) in tests {
+            assert_eq!(read_hosts(input, null)?, expected, "{input:?}, {null}");
+        }
+
+        assert!(read_hosts(&b"n\xff\n"[..], false).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open() -> Result<()> {
+        let plain = temp_file("plain", b"n1\nn2\n");
+        assert_eq!(read_file(&plain, false)?, ["n1", "n2"]);
+
+        let gz = temp_file("gz", &gzip(b"n1\nn2\n"));
+        assert_eq!(read_file(&gz, false)?, ["n1", "n2"]);
+
+        // Concatenated gzip files, as left by appending to a compressed log
+        let mut members = gzip(b"n1\n");
+        members.extend(gzip(b"n2\n"));
+        let multi = temp_file("multi.gz", &members);
+        assert_eq!(read_file(&multi, false)?, ["n1", "n2"]);
+
+        let err = open(Path::new("/nonexistent/hosts.txt")).err().unwrap();
+        assert!(
+            err.to_string().starts_with("/nonexistent/hosts.txt: "),
+            "{err}"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_open_zstd() -> Result<()> {
+        let zst = temp_file("zst", &zstd::encode_all(&b"n1\nn2\n"[..], 0)?);
+        assert_eq!(read_file(&zst, false)?, ["n1", "n2"]);
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_open_zstd() {
+        let zst = temp_file("zst", &[0x28, 0xb5, 0x2f, 0xfd, 0]);
+        let err = open(&zst).err().unwrap();
+        std::fs::remove_file(&zst).unwrap();
+        assert!(err.to_string().contains("\"zstd\" feature"), "{err}");
+    }
+}
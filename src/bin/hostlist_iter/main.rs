@@ -0,0 +1,693 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{self, Write};
+use std::io;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use hostlist_iter::{
+    Hostlist, Padding, ParseOutcome, collapse_hosts, collapse_hosts_ignore_case, sort_hosts,
+};
+
+mod api;
+mod config;
+mod eval;
+mod groups;
+mod input;
+mod output;
+#[cfg(feature = "net")]
+mod probe;
+#[cfg(feature = "serve")]
+mod serve;
+
+use crate::config::Config;
+use crate::groups::Groups;
+use crate::output::{ColorChoice, GREEN, Output, RED, RESET};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Parser)]
+#[clap(author, version)]
+struct Cli {
+    /// read defaults from this file instead of the user config directory
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// refuse to expand hostlists with more than this many hosts
+    #[clap(long, global = true)]
+    max_hosts: Option<usize>,
+
+    /// file of named groups, usable as @name in hostlists
+    #[clap(long, global = true)]
+    groups: Option<PathBuf>,
+
+    /// lowercase host names, so that names differing only in case are the same host
+    #[clap(short, long, global = true)]
+    ignore_case: bool,
+
+    /// write results to this file instead of stdout. The file is replaced only once all results
+    /// have been written.
+    #[clap(short, long, global = true)]
+    output: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Parse the hostlist and print it out again
+    Parse {
+        /// report anything that was normalized while parsing (ex: duplicate hosts) on stderr
+        #[clap(long)]
+        warnings: bool,
+
+        /// print each element in its canonical form, in input order, without combining or sorting
+        /// elements
+        #[clap(long, conflicts_with = "warnings")]
+        no_merge: bool,
+
+        /// how to print zero-padded numbers: "keep" them as written, "strip" the padding, or pad
+        /// every range to a number of digits (ex: "3" for "node[001-064]") [default: strip]
+        #[clap(long, value_parser = parse_padding, conflicts_with_all = ["warnings", "no_merge"])]
+        pad: Option<Padding>,
+
+        /// hostlists to parse
+        #[clap(required = true, num_args = 1..)]
+        hostlists: Vec<String>,
+    },
+
+    /// List individual hosts in each hostlist
+    List {
+        /// sort hosts in natural order, comparing numbers by value
+        #[clap(long)]
+        sort: bool,
+
+        /// list hosts in reverse order
+        #[clap(long)]
+        reverse: bool,
+
+        /// prefix each host with its zero-based index across all hostlists
+        #[clap(long)]
+        enumerate: bool,
+
+        /// print this between hosts instead of a newline
+        #[clap(long, conflicts_with = "null")]
+        separator: Option<String>,
+
+        /// end each host with a NUL character instead of a newline
+        #[clap(short = '0', long)]
+        null: bool,
+
+        /// hostlists to expand
+        #[clap(required = true, num_args = 1..)]
+        hostlists: Vec<String>,
+    },
+
+    /// Collapse a list of individual hosts into a hostlist
+    Collapse {
+        /// host names read from stdin or a file are separated by NUL characters instead of
+        /// newlines
+        #[clap(short = '0', long)]
+        null: bool,
+
+        /// read host names from this file, which may be gzip (or zstd) compressed
+        #[clap(short, long, conflicts_with = "hosts")]
+        file: Option<PathBuf>,
+
+        /// host names to collapse, read from stdin if none are given
+        hosts: Vec<String>,
+    },
+
+    /// Count hosts in each hostlist
+    Count {
+        /// print a table with the host, prefix, and range counts of each hostlist
+        #[clap(long)]
+        table: bool,
+
+        /// print the number of distinct hosts across all hostlists combined
+        #[clap(long, conflicts_with = "table")]
+        unique: bool,
+
+        /// hostlists to count
+        #[clap(required = true, num_args = 1..)]
+        hostlists: Vec<String>,
+    },
+
+    /// Show hosts removed from and added to a hostlist
+    Diff {
+        /// when to color the output [default: auto]
+        #[clap(long, value_enum)]
+        color: Option<ColorChoice>,
+
+        /// the original hostlist
+        old: String,
+
+        /// the updated hostlist
+        new: String,
+    },
+
+    /// Report hosts that appear in more than one hostlist, and which hostlists they appear in
+    Overlap {
+        /// hostlists to compare, referred to by their position (starting at 1) in the output
+        #[clap(required = true, num_args = 2..)]
+        hostlists: Vec<String>,
+    },
+
+    /// Combine hostlists with set operations: "|" or "+" for union, "&" for intersection, and " - "
+    /// for difference, applied left to right, with parentheses for grouping
+    /// (ex: "node[1-100] - node[40-49] & @gpu")
+    Eval {
+        /// the expression to evaluate, joined with spaces if given as several arguments
+        #[clap(required = true, num_args = 1..)]
+        expression: Vec<String>,
+    },
+
+    /// Print the folded set of numeric indices used with a prefix (ex: "[1-64,70]")
+    Indices {
+        /// only consider hosts made up of this prefix followed by a number
+        #[clap(long)]
+        prefix: String,
+
+        /// hostlist to examine
+        hostlist: String,
+    },
+
+    /// Run operations read from stdin, one JSON object per line, writing one JSON result per line
+    /// (ex: {"op": "expand", "expr": "n[1-3]"}). Each operation expands at most --max-hosts
+    /// (1000000 unless given) hosts.
+    Batch {
+        /// the most hosts all the operations expand together
+        #[clap(long, default_value_t = api::DEFAULT_MAX_BATCH_HOSTS)]
+        max_total_hosts: usize,
+    },
+
+    /// Check which hosts accept TCP connections, printing the reachable and unreachable hosts. Only
+    /// TCP is checked, not ICMP ping, which needs raw sockets and so root privileges.
+    #[cfg(feature = "net")]
+    Probe {
+        /// TCP port to connect to
+        #[clap(long, default_value_t = 22)]
+        port: u16,
+
+        /// how long to wait for each host, including resolving its name (ex: "2s", "500ms")
+        #[clap(long, default_value = "2s", value_parser = probe::parse_duration)]
+        timeout: std::time::Duration,
+
+        /// maximum number of hosts to probe at once
+        #[clap(long, default_value_t = 128)]
+        parallel: usize,
+
+        /// hostlist to probe
+        hostlist: String,
+    },
+
+    /// Serve expand, collapse, count, and set operations as a JSON API over HTTP. Expanding more
+    /// hosts than --max-hosts (1000000 unless given) is an error, answered with status 413.
+    #[cfg(feature = "serve")]
+    Serve {
+        /// address to listen on
+        #[clap(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+    },
+}
+
+/// Settings shared by all subcommands, from the command line or the config file
+#[derive(Default)]
+struct Context {
+    config: Config,
+    max_hosts: Option<usize>,
+    groups: Groups,
+    ignore_case: bool,
+}
+
+impl Context {
+    /// Parses a hostlist argument, resolving any group references
+    fn hostlist(&self, expr: &str) -> Result<Hostlist> {
+        Ok(self.parse(expr)?.hostlist)
+    }
+
+    /// Parses a hostlist argument, along with warnings about anything normalized while parsing
+    fn parse(&self, expr: &str) -> Result<ParseOutcome> {
+        Ok(Hostlist::parse_with_warnings(&self.resolve(expr)?)?)
+    }
+
+    /// Resolves group references in a hostlist argument, lowercasing it with `--ignore-case`
+    fn resolve(&self, expr: &str) -> Result<String> {
+        let mut expr = self.groups.resolve(expr)?;
+        if self.ignore_case {
+            expr.make_ascii_lowercase();
+        }
+        Ok(expr)
+    }
+
+    /// Parses a hostlist argument that is about to be expanded, enforcing `--max-hosts`
+    fn expand(&self, expr: &str) -> Result<Hostlist> {
+        let hostlist = self.hostlist(expr)?;
+        if let Some(max_hosts) = self.max_hosts
+            && hostlist.len() > max_hosts
+        {
+            return Err(TooManyHosts {
+                expr: expr.to_string(),
+                len: hostlist.len(),
+                max_hosts,
+            }
+            .into());
+        }
+        Ok(hostlist)
+    }
+
+    /// Applies the default `--max-hosts` if none was given, for subcommands answering requests,
+    /// which must never expand an unbounded hostlist
+    fn limited(self) -> Self {
+        Self {
+            max_hosts: Some(self.max_hosts.unwrap_or(api::DEFAULT_MAX_HOSTS)),
+            ..self
+        }
+    }
+
+    /// Folds host names into a hostlist
+    fn collapse(&self, hosts: impl IntoIterator<Item = impl AsRef<str>>) -> Result<String> {
+        if self.ignore_case {
+            Ok(collapse_hosts_ignore_case(hosts)?)
+        } else {
+            Ok(collapse_hosts(hosts)?)
+        }
+    }
+}
+
+/// A hostlist with more hosts than `--max-hosts`, refused rather than expanded
+#[derive(Debug)]
+struct TooManyHosts {
+    expr: String,
+    len: usize,
+    max_hosts: usize,
+}
+
+impl fmt::Display for TooManyHosts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "\"{}\" expands to {} hosts, more than the maximum of {}",
+            self.expr, self.len, self.max_hosts
+        )
+    }
+}
+
+impl std::error::Error for TooManyHosts {}
+
+fn main_real() -> Result<()> {
+    let cli = Cli::parse();
+
+    let mut out = match &cli.output {
+        Some(path) => Output::file(path).map_err(|e| format!("{}: {e}", path.display()))?,
+        None => Output::stdout(),
+    };
+
+    // Command line flags take precedence over the config file
+    let config = Config::load(cli.config.as_deref())?;
+    let groups = match cli.groups.as_ref().or(config.groups.as_ref()) {
+        Some(path) => Groups::load(path)?,
+        None => Groups::default(),
+    };
+    let ctx = Context {
+        max_hosts: cli.max_hosts.or(config.max_hosts),
+        groups,
+        ignore_case: cli.ignore_case || config.ignore_case.unwrap_or(false),
+        config,
+    };
+
+    // Match on the subcommand
+    match cli.command {
+        Commands::Parse {
+            warnings,
+            no_merge,
+            pad,
+            hostlists,
+        } => parse(&ctx, &mut out, &hostlists, warnings, no_merge, pad)?,
+        Commands::List {
+            sort,
+            reverse,
+            enumerate,
+            separator,
+            null,
+            hostlists,
+        } => {
+            let (separator, terminator) = if null {
+                ("\0".to_string(), "\0")
+            } else {
+                let separator = separator
+                    .or_else(|| ctx.config.separator.clone())
+                    .unwrap_or_else(|| "\n".to_string());
+                (separator, "\n")
+            };
+            let opts = ListOptions {
+                sort,
+                reverse,
+                enumerate,
+                separator: &separator,
+                terminator,
+            };
+            list(&ctx, &mut out, &hostlists, &opts)?;
+        }
+        Commands::Collapse { null, file, hosts } => {
+            let hosts = if let Some(path) = file {
+                input::read_hosts(input::open(&path)?, null)?
+            } else if hosts.is_empty() {
+                input::read_hosts(io::stdin().lock(), null)?
+            } else {
+                hosts
+            };
+            out.write_line(&ctx.collapse(hosts)?);
+        }
+        Commands::Count {
+            table,
+            unique,
+            hostlists,
+        } => count(&ctx, &mut out, &hostlists, table, unique)?,
+        Commands::Diff { color, old, new } => {
+            let color = color.or(ctx.config.color).unwrap_or(ColorChoice::Auto);
+            let color = color.enabled(&out);
+            diff(&ctx, &mut out, &old, &new, color)?;
+        }
+        Commands::Overlap { hostlists } => overlap(&ctx, &mut out, &hostlists)?,
+        Commands::Eval { expression } => {
+            out.write_line(&eval::eval(&ctx, &expression.join(" "))?);
+        }
+        Commands::Indices { prefix, hostlist } => indices(&ctx, &mut out, &prefix, &hostlist)?,
+        Commands::Batch { max_total_hosts } => {
+            let ctx = ctx.limited();
+            api::batch(&ctx, &mut out, io::stdin().lock(), max_total_hosts)?;
+        }
+        #[cfg(feature = "net")]
+        Commands::Probe {
+            port,
+            timeout,
+            parallel,
+            hostlist,
+        } => {
+            let hosts: Vec<String> = ctx.expand(&hostlist)?.collect();
+            let (reachable, unreachable) = probe::probe(hosts, port, timeout, parallel);
+            out.write_line(&format!("reachable: {}", collapse_hosts(reachable)?));
+            out.write_line(&format!("unreachable: {}", collapse_hosts(unreachable)?));
+        }
+        #[cfg(feature = "serve")]
+        Commands::Serve { listen } => serve::serve(&ctx.limited(), &listen)?,
+    }
+
+    out.finish();
+
+    Ok(())
+}
+
+fn parse(
+    ctx: &Context,
+    out: &mut Output,
+    hostlists: &[String],
+    warnings: bool,
+    no_merge: bool,
+    pad: Option<Padding>,
+) -> Result<()> {
+    for h in hostlists {
+        if let Some(padding) = pad {
+            let hostlist = Hostlist::new_with_padding(&ctx.resolve(h)?, padding)?;
+            out.write_line(&hostlist.to_string());
+            continue;
+        }
+        if no_merge {
+            let elems = Hostlist::normalize_elements(&ctx.resolve(h)?)?;
+            out.write_line(&elems.join(","));
+            continue;
+        }
+
+        let outcome = ctx.parse(h)?;
+        if warnings {
+            for warning in &outcome.warnings {
+                eprintln!("warning: {warning}");
+            }
+        }
+        out.write_line(&outcome.hostlist.to_string());
+    }
+
+    Ok(())
+}
+
+/// Parses a `--pad` value: "keep", "strip", or a number of digits
+fn parse_padding(s: &str) -> std::result::Result<Padding, String> {
+    match s {
+        "keep" => Ok(Padding::Keep),
+        "strip" => Ok(Padding::Strip),
+        _ => s.parse().map(Padding::Width).map_err(|_| {
+            format!("invalid padding: \"{s}\" (expected keep, strip, or a number of digits)")
+        }),
+    }
+}
+
+struct ListOptions<'a> {
+    sort: bool,
+    reverse: bool,
+    enumerate: bool,
+    separator: &'a str,  // printed between hosts
+    terminator: &'a str, // printed after the last host
+}
+
+/// Hosts are gathered into chunks of about this many bytes before being written out
+const LIST_CHUNK_SIZE: usize = 1 << 20;
+
+fn list(ctx: &Context, out: &mut Output, hostlists: &[String], opts: &ListOptions) -> Result<()> {
+    // Writing hosts one at a time is slow for huge hostlists, so build up large chunks instead
+    let mut chunk = String::with_capacity(LIST_CHUNK_SIZE + 256);
+    let mut index: usize = 0;
+
+    for h in hostlists {
+        let hostlist = ctx.expand(h)?;
+        if !opts.sort && !opts.reverse {
+            // Expand straight into the chunk, without allocating a String per host
+            let mut hosts = hostlist.iter();
+            loop {
+                let start = chunk.len();
+                start_host(&mut chunk, index, opts);
+                if !hosts.next_into(&mut chunk) {
+                    chunk.truncate(start);
+                    break;
+                }
+                index += 1;
+                if chunk.len() >= LIST_CHUNK_SIZE {
+                    out.write(&chunk);
+                    chunk.clear();
+                }
+            }
+            continue;
+        }
+
+        // Reordering requires holding every host in memory
+        let mut hosts: Vec<String> = hostlist.collect();
+        if opts.sort {
+            hosts = sort_hosts(hosts)?;
+        }
+        if opts.reverse {
+            hosts.reverse();
+        }
+        for host in &hosts {
+            start_host(&mut chunk, index, opts);
+            chunk.push_str(host);
+            index += 1;
+            if chunk.len() >= LIST_CHUNK_SIZE {
+                out.write(&chunk);
+                chunk.clear();
+            }
+        }
+    }
+
+    if index > 0 {
+        chunk.push_str(opts.terminator);
+    }
+    out.write(&chunk);
+
+    Ok(())
+}
+
+/// Adds what comes before the host at `index`: the separator after the previous host, and the
+/// index itself with `--enumerate`
+fn start_host(chunk: &mut String, index: usize, opts: &ListOptions) {
+    if index > 0 {
+        chunk.push_str(opts.separator);
+    }
+    if opts.enumerate {
+        // Writing to a String can't fail
+        let _ = write!(chunk, "{index} ");
+    }
+}
+
+fn count(
+    ctx: &Context,
+    out: &mut Output,
+    hostlists: &[String],
+    table: bool,
+    unique: bool,
+) -> Result<()> {
+    if table {
+        return count_table(ctx, out, hostlists);
+    }
+
+    if unique {
+        // Combined without expanding, so hostlists of any size can be counted
+        let hostlists = hostlists
+            .iter()
+            .map(|h| ctx.hostlist(h))
+            .collect::<Result<Vec<_>>>()?;
+        out.write_line(&Hostlist::union_all(hostlists)?.len().to_string());
+    } else {
+        for h in hostlists {
+            let hostlist = ctx.hostlist(h)?;
+            out.write_line(&format!("{}", hostlist.len()));
+        }
+    }
+
+    Ok(())
+}
+
+fn count_table(ctx: &Context, out: &mut Output, hostlists: &[String]) -> Result<()> {
+    let mut rows = vec![[
+        "EXPRESSION".to_string(),
+        "HOSTS".to_string(),
+        "PREFIXES".to_string(),
+        "RANGES".to_string(),
+    ]];
+    for h in hostlists {
+        let hostlist = ctx.hostlist(h)?;
+        rows.push([
+            h.trim().to_string(),
+            hostlist.len().to_string(),
+            hostlist.prefixes().len().to_string(),
+            hostlist.range_count().to_string(),
+        ]);
+    }
+
+    let mut widths = [0; 4];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let [w0, w1, w2, w3] = widths;
+    for [expr, hosts, prefixes, ranges] in &rows {
+        // Left-align the expression, right-align the numbers
+        out.write_line(&format!(
+            "{expr:<w0$}  {hosts:>w1$}  {prefixes:>w2$}  {ranges:>w3$}"
+        ));
+    }
+
+    Ok(())
+}
+
+fn diff(ctx: &Context, out: &mut Output, old: &str, new: &str, color: bool) -> Result<()> {
+    let old: BTreeSet<String> = ctx.expand(old)?.collect();
+    let new: BTreeSet<String> = ctx.expand(new)?.collect();
+    let removed = collapse_hosts(old.difference(&new))?;
+    let added = collapse_hosts(new.difference(&old))?;
+
+    for (sign, hosts, ansi) in [("-", removed, RED), ("+", added, GREEN)] {
+        if hosts.is_empty() {
+            continue;
+        }
+        if color {
+            out.write_line(&format!("{ansi}{sign} {hosts}{RESET}"));
+        } else {
+            out.write_line(&format!("{sign} {hosts}"));
+        }
+    }
+
+    Ok(())
+}
+
+fn overlap(ctx: &Context, out: &mut Output, hostlists: &[String]) -> Result<()> {
+    // Which hostlists (by position) each host appears in
+    let mut membership: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, h) in hostlists.iter().enumerate() {
+        for host in ctx.expand(h)? {
+            let lists = membership.entry(host).or_default();
+            if lists.last() != Some(&(i + 1)) {
+                lists.push(i + 1);
+            }
+        }
+    }
+
+    // Fold the hosts shared by each combination of hostlists
+    let mut shared: BTreeMap<Vec<usize>, Vec<String>> = BTreeMap::new();
+    for (host, lists) in membership {
+        if lists.len() > 1 {
+            shared.entry(lists).or_default().push(host);
+        }
+    }
+    for (lists, hosts) in shared {
+        let lists = lists
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        out.write_line(&format!("{lists}: {}", collapse_hosts(hosts)?));
+    }
+
+    Ok(())
+}
+
+fn indices(ctx: &Context, out: &mut Output, prefix: &str, hostlist: &str) -> Result<()> {
+    let indices: Vec<String> = ctx
+        .expand(hostlist)?
+        .filter_map(|host| {
+            let index = host.strip_prefix(prefix)?;
+            let numeric = !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit());
+            numeric.then(|| index.to_string())
+        })
+        .collect();
+    if !indices.is_empty() {
+        // Bare numbers are valid host names, so collapsing them folds just the indices
+        out.write_line(&collapse_hosts(indices)?);
+    }
+
+    Ok(())
+}
+
+fn main() {
+    // Run the real main function and print any errors using their Display trait
+    if let Err(err) = main_real() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_max_hosts() -> Result<()> {
+        let ctx = Context {
+            max_hosts: Some(3),
+            ..Context::default()
+        };
+        assert_eq!(ctx.expand("n[1-3]")?.len(), 3);
+
+        let err = ctx.expand("n[1-4]").err().unwrap();
+        let too_many = err.downcast_ref::<TooManyHosts>().unwrap();
+        assert_eq!((too_many.len, too_many.max_hosts), (4, 3));
+        assert_eq!(
+            err.to_string(),
+            "\"n[1-4]\" expands to 4 hosts, more than the maximum of 3"
+        );
+
+        // Parsing alone is never limited
+        assert_eq!(ctx.hostlist("n[1-4]")?.len(), 4);
+
+        // Requests get the default limit, unless one was given
+        assert_eq!(ctx.limited().max_hosts, Some(3));
+        let ctx = Context::default();
+        assert!(ctx.expand("n[1-2000000]").is_ok());
+        let ctx = ctx.limited();
+        assert_eq!(ctx.max_hosts, Some(api::DEFAULT_MAX_HOSTS));
+        assert!(ctx.expand("n[1-2000000]").unwrap_err().is::<TooManyHosts>());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs::File;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+pub const RED: &str = "\x1b[31m";
+pub const GREEN: &str = "\x1b[32m";
+pub const RESET: &str = "\x1b[0m";
+
+#[derive(Clone, Copy, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// color when writing to a terminal
+    Auto,
+    /// always color
+    Always,
+    /// never color
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self, out: &Output) -> bool {
+        match self {
+            Self::Auto => out.is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+enum Sink {
+    Stdout(io::StdoutLock<'static>),
+    File(File),
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdout(stdout) => stdout.write(buf),
+            Self::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdout(stdout) => stdout.flush(),
+            Self::File(file) => file.flush(),
+        }
+    }
+}
+
+/// Where results are written: buffered stdout that exits quietly once the reading end of a pipe
+/// goes away, or a file that is only put in place once everything has been written.
+pub struct Output {
+    writer: BufWriter<Sink>,
+    // When writing to a file: the temporary file being written, and its final path
+    paths: Option<(PathBuf, PathBuf)>,
+}
+
+impl Output {
+    pub fn stdout() -> Self {
+        Self {
+            writer: BufWriter::new(Sink::Stdout(io::stdout().lock())),
+            paths: None,
+        }
+    }
+
+    /// Writes to a temporary file next to `path`, which is renamed over `path` by `finish()`.
+    /// Readers of `path` never see partial output, even if we're interrupted.
+    pub fn file(path: &Path) -> io::Result<Self> {
+        let file_name = path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "output path is not a file")
+        })?;
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(".{}.tmp", process::id()));
+        let temp_path = path.with_file_name(temp_name);
+
+        let file = File::create(&temp_path)?;
+        Ok(Self {
+            writer: BufWriter::new(Sink::File(file)),
+            paths: Some((temp_path, path.to_path_buf())),
+        })
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.paths.is_none() && io::stdout().is_terminal()
+    }
+
+    pub fn write(&mut self, s: &str) {
+        if let Err(e) = self.writer.write_all(s.as_bytes()) {
+            self.fail("writing", &e);
+        }
+    }
+
+    pub fn write_line(&mut self, line: &str) {
+        self.write(line);
+        self.write("\n");
+    }
+
+    /// Writes out anything buffered so far, for readers waiting on a response
+    pub fn flush(&mut self) {
+        if let Err(e) = self.writer.flush() {
+            self.fail("flushing", &e);
+        }
+    }
+
+    /// Flushes all output, moving it into place when writing to a file
+    pub fn finish(mut self) {
+        self.flush();
+
+        if let Some((temp_path, path)) = self.paths.take() {
+            let result = match self.writer.get_ref() {
+                Sink::File(file) => file.sync_all(),
+                Sink::Stdout(_) => Ok(()),
+            }
+            .and_then(|()| std::fs::rename(&temp_path, &path));
+
+            if let Err(e) = result {
+                let _ = std::fs::remove_file(&temp_path);
+                eprintln!("Error writing {}: {e}", path.display());
+                process::exit(1);
+            }
+        }
+    }
+
+    fn fail(&mut self, action: &str, e: &io::Error) -> ! {
+        match self.paths.take() {
+            None if e.kind() == io::ErrorKind::BrokenPipe => process::exit(0),
+            None => eprintln!("Error {action} stdout: {e}"),
+            Some((temp_path, path)) => {
+                let _ = std::fs::remove_file(temp_path);
+                eprintln!("Error {action} {}: {e}", path.display());
+            }
+        }
+        process::exit(1);
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        // Output that was never finished (ex: we hit an error) must not replace the target file
+        if let Some((temp_path, _)) = self.paths.take() {
+            let _ = std::fs::remove_file(temp_path);
+        }
+    }
+}
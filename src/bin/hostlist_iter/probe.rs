@@ -0,0 +1,136 @@
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Checks which hosts accept TCP connections on `port`, trying up to `parallel` hosts at once.
+/// Returns the reachable and unreachable hosts.
+pub fn probe(
+    hosts: Vec<String>,
+    port: u16,
+    timeout: Duration,
+    parallel: usize,
+) -> (Vec<String>, Vec<String>) {
+    let next = AtomicUsize::new(0);
+    let reachable: Vec<bool> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..parallel.clamp(1, hosts.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(host) = hosts.get(i) else {
+                            break;
+                        };
+                        results.push((i, is_reachable(host, port, timeout)));
+                    }
+                    results
+                })
+            })
+            .collect();
+
+        let mut reachable = vec![false; hosts.len()];
+        for worker in workers {
+            for (i, result) in worker.join().unwrap_or_default() {
+                reachable[i] = result;
+            }
+        }
+        reachable
+    });
+
+    let (up, down): (Vec<_>, Vec<_>) = hosts.into_iter().zip(reachable).partition(|(_, r)| *r);
+    (
+        up.into_iter().map(|(host, _)| host).collect(),
+        down.into_iter().map(|(host, _)| host).collect(),
+    )
+}
+
+/// Checks whether `host` accepts a TCP connection on `port`, resolving its name and connecting
+/// to it all within `timeout`
+fn is_reachable(host: &str, port: u16, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    // Name resolution failures count as unreachable
+    let Some(addrs) = resolve(host, port, timeout) else {
+        return false;
+    };
+    addrs.into_iter().any(|addr| {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        !remaining.is_zero() && TcpStream::connect_timeout(&addr, remaining).is_ok()
+    })
+}
+
+/// Resolves `host`, giving up after `timeout`. The system resolver can't be cancelled, so it runs
+/// on a thread of its own that's left to finish in the background if it takes too long.
+fn resolve(host: &str, port: u16, timeout: Duration) -> Option<Vec<SocketAddr>> {
+    let (sender, receiver) = mpsc::channel();
+    let host = host.to_string();
+    std::thread::spawn(move || {
+        let addrs = (host.as_str(), port).to_socket_addrs();
+        // The receiver is gone if resolving took too long, which is fine
+        let _ = sender.send(addrs.map(Iterator::collect));
+    });
+    receiver.recv_timeout(timeout).ok()?.ok()
+}
+
+/// Parses a duration such as "2s", "500ms", "1m", or a bare number of seconds
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration: \"{s}\""))?;
+    let seconds = match unit {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        _ => {
+            return Err(format!(
+                "invalid duration unit: \"{unit}\" (expected ms, s, or m)"
+            ));
+        }
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("invalid duration: \"{s}\": {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        let tests = [
+            ("2s", Duration::from_secs(2)),
+            ("500ms", Duration::from_millis(500)),
+            ("1m", Duration::from_mins(1)),
+            ("3", Duration::from_secs(3)),
+            ("1.5s", Duration::from_millis(1500)),
+            (" 0 ", Duration::ZERO),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_duration(input), Ok(expected), "{input}");
+        }
+
+        let invalid = ["", "s", "2h", "1.2.3s", "-1s", "1e400"];
+        for input in invalid {
+            assert!(parse_duration(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let hosts = vec!["127.0.0.1".to_string(), "nonexistent.invalid".to_string()];
+        let (up, down) = probe(hosts, port, Duration::from_secs(5), 2);
+        assert_eq!(up, ["127.0.0.1"]);
+        assert_eq!(down, ["nonexistent.invalid"]);
+
+        // Resolving the name counts against the timeout too
+        assert!(!is_reachable("127.0.0.1", port, Duration::ZERO));
+    }
+}
@@ -0,0 +1,64 @@
+use serde_json::{Value, json};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::api;
+use crate::{Context, Result, TooManyHosts};
+
+/// Answers API requests over HTTP until the process is killed.
+///
+/// Each operation is a `POST` to `/<op>` with the operation's fields as a JSON object (ex:
+/// `POST /count` with `{"expr": "n[1-3]"}`). Responses are JSON objects, with an "error" field
+/// on failure.
+pub fn serve(ctx: &Context, listen: &str) -> Result<()> {
+    let server = Server::http(listen).map_err(|e| format!("{listen}: {e}"))?;
+    eprintln!("Listening on http://{}", server.server_addr());
+
+    let content_type = Header::from_bytes("Content-Type", "application/json")
+        .map_err(|()| "invalid Content-Type header")?;
+    for mut request in server.incoming_requests() {
+        let (status, body) = handle(ctx, &mut request);
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(content_type.clone());
+        // The client going away is their problem, not a reason to stop serving
+        let _ = request.respond(response);
+    }
+
+    Ok(())
+}
+
+fn handle(ctx: &Context, request: &mut tiny_http::Request) -> (u16, Value) {
+    let path = request.url().split('?').next().unwrap_or_default();
+    let op = path.trim_start_matches('/').to_string();
+    if !api::Request::OPS.contains(&op.as_str()) {
+        return error(404, &format!("unknown operation: \"{path}\""));
+    }
+    if *request.method() != Method::Post {
+        return error(405, "expected a POST request");
+    }
+
+    let mut fields: Value = match serde_json::from_reader(request.as_reader()) {
+        Ok(fields) => fields,
+        Err(e) => return error(400, &format!("invalid request body: {e}")),
+    };
+    let Value::Object(map) = &mut fields else {
+        return error(400, "request body must be a JSON object");
+    };
+    map.insert("op".to_string(), Value::String(op));
+
+    let request = match serde_json::from_value::<api::Request>(fields) {
+        Ok(request) => request,
+        Err(e) => return error(400, &e.to_string()),
+    };
+    // Each request is limited only by --max-hosts
+    let mut remaining = usize::MAX;
+    match request.run(ctx, &mut remaining) {
+        Ok(result) => (200, result),
+        Err(e) if e.is::<TooManyHosts>() => error(413, &e.to_string()),
+        Err(e) => error(400, &e.to_string()),
+    }
+}
+
+fn error(status: u16, message: &str) -> (u16, Value) {
+    (status, json!({ "error": message }))
+}
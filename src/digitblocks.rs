@@ -0,0 +1,631 @@
+use std::collections::BTreeMap;
+
+use crate::error::{Error, Result};
+use crate::hostlistelem::{Component, HostlistElem, push_static};
+use crate::range::Range;
+use crate::simplerange::SimpleRange;
+
+/// The most digits of a static part put in one segment, so its value fits in a `u64`
+const MAX_DIGITS: usize = 18;
+
+/// The most combinations of leading digits written out when joining segments back into a range,
+/// past which they're left as separate ranges
+const MAX_PREFIXES: usize = 1024;
+
+/// Part of a run of digits in a host name: digits written in a static part, or a range
+enum Part {
+    Digits(String),
+    Range(Range),
+}
+
+/// An element split into the text between its numbers and the parts making up each number (ex:
+/// "n", "-", "" and `[[1-20]]`, `["1", [0-9]]` for "n[1-20]-1[0-9]"). Two elements can only have
+/// hosts in common if the text between their numbers is the same.
+struct Shape {
+    texts: Vec<String>,
+    runs: Vec<Vec<Part>>,
+}
+
+/// Values of a part rendered with exactly `len` digits, or some of those digits
+#[derive(Clone)]
+struct Segment {
+    part: usize,
+    len: usize,
+    values: Range,
+}
+
+/// Hosts of an element with a fixed number of digits for each of its segments, so each host is
+/// one combination of segment values. Has the segments of each run of digits.
+type Block = Vec<Vec<Segment>>;
+
+/// Returns the hosts of `elem` that aren't in `other` and the hosts that are, or `None` if they
+/// have no hosts in common. Numbers are compared by their digits, so this works whichever way the
+/// hosts are split across ranges (ex: "n12" is in both "n[10-13]" and "n[1-5][2]").
+pub fn split(
+    elem: &HostlistElem,
+    other: &HostlistElem,
+) -> Result<Option<(Vec<HostlistElem>, Vec<HostlistElem>)>> {
+    if elem.fingerprint() == other.fingerprint() && same_layout(elem, other) {
+        let inside = elem.intersect(other)?;
+        if inside.len() == 0 {
+            return Ok(None);
+        }
+        let mut outside = elem.subtract(other)?;
+        outside.retain(|piece| piece.len() > 0);
+        return Ok(Some((outside, vec![inside])));
+    }
+    if !skeleton(elem).eq(skeleton(other)) {
+        return Ok(None);
+    }
+
+    let shape = Shape::new(elem);
+    let other_blocks = Shape::new(other).blocks()?;
+    let (mut outside, mut inside) = (Vec::new(), Vec::new());
+    for block in shape.blocks()? {
+        let lengths = lengths(&block);
+        let others: Vec<&Block> = other_blocks
+            .iter()
+            .filter(|other| self::lengths(other) == lengths)
+            .collect();
+        let (block_outside, block_inside) = split_blocks(block, &others)?;
+        outside.extend(block_outside);
+        inside.extend(block_inside);
+    }
+    if inside.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some((shape.elems(outside)?, shape.elems(inside)?)))
+}
+
+/// Returns elements with the hosts of `elem` once each, or `None` if it has no host more than
+/// once. Hosts can repeat when a number is made of several ranges (ex: "n111" is both "n[1][11]"
+/// and "n[11][1]" in "n[1-11][1-11]"), or a range has values padded to different widths.
+pub fn distinct(elem: &HostlistElem) -> Result<Option<Vec<HostlistElem>>> {
+    if !may_repeat(elem) {
+        return Ok(None);
+    }
+
+    let shape = Shape::new(elem);
+    let mut kept: Vec<Block> = Vec::new();
+    for block in shape.blocks()? {
+        let lengths = lengths(&block);
+        let others: Vec<Block> = kept
+            .iter()
+            .filter(|other| self::lengths(other) == lengths)
+            .cloned()
+            .collect();
+        let others: Vec<&Block> = others.iter().collect();
+        kept.extend(split_blocks(block, &others)?.0);
+    }
+
+    let kept_len = kept
+        .iter()
+        .map(block_len)
+        .fold(0usize, usize::saturating_add);
+    if kept_len == elem.len() {
+        return Ok(None);
+    }
+    shape.elems(kept).map(Some)
+}
+
+/// Returns whether two elements with the same fingerprint render each range value the same way
+/// and have at most one range in each number, so they can be compared range by range
+fn same_layout(a: &HostlistElem, b: &HostlistElem) -> bool {
+    let mut range_in_number = false;
+    for pair in a.components.iter().zip(&b.components) {
+        match pair {
+            (Component::Static(s), _) => {
+                if !s.bytes().all(|c| c.is_ascii_digit()) {
+                    range_in_number = false;
+                }
+            }
+            (Component::Range(r), Component::Range(s)) => {
+                if range_in_number {
+                    return false;
+                }
+                range_in_number = true;
+                let mut widths = r.ranges.iter().chain(&s.ranges).map(|r| r.width);
+                let width = widths.next();
+                if !widths.all(|w| Some(w) == width) {
+                    return false;
+                }
+            }
+            (Component::Range(_), Component::Static(_)) => return false,
+        }
+    }
+    true
+}
+
+/// Returns whether an element's hosts might not all be different, see `distinct()`
+fn may_repeat(elem: &HostlistElem) -> bool {
+    let mut range_in_number = false;
+    for component in &elem.components {
+        match component {
+            Component::Static(s) => {
+                if !s.bytes().all(|c| c.is_ascii_digit()) {
+                    range_in_number = false;
+                }
+            }
+            Component::Range(r) => {
+                if range_in_number || r.ranges.iter().any(|s| s.width != r.ranges[0].width) {
+                    return true;
+                }
+                range_in_number = true;
+            }
+        }
+    }
+    false
+}
+
+/// Returns the text of an element with each number, however it's written, as `None`
+fn skeleton(elem: &HostlistElem) -> impl Iterator<Item = Option<char>> + '_ {
+    elem.components
+        .iter()
+        .flat_map(|component| {
+            let (text, range) = match component {
+                Component::Static(s) => (s.as_str(), None),
+                Component::Range(_) => ("", Some(None)),
+            };
+            text.chars()
+                .map(|c| (!c.is_ascii_digit()).then_some(c))
+                .chain(range)
+        })
+        .scan(false, |in_number, token| {
+            let repeated = *in_number && token.is_none();
+            *in_number = token.is_none();
+            Some((repeated, token))
+        })
+        .filter_map(|(repeated, token)| (!repeated).then_some(token))
+}
+
+impl Shape {
+    fn new(elem: &HostlistElem) -> Self {
+        let mut shape = Self {
+            texts: Vec::new(),
+            runs: Vec::new(),
+        };
+        let mut text = String::new();
+        let mut run = Vec::new();
+        let push_part = |shape: &mut Self, text: &mut String, run: &mut Vec<Part>, part| {
+            if run.is_empty() {
+                shape.texts.push(std::mem::take(text));
+            }
+            run.push(part);
+        };
+
+        for component in &elem.components {
+            match component {
+                Component::Static(s) => {
+                    let mut rest = s.as_str();
+                    while !rest.is_empty() {
+                        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+                        if digits > 0 {
+                            let part = Part::Digits(rest[..digits].to_string());
+                            push_part(&mut shape, &mut text, &mut run, part);
+                        }
+                        rest = &rest[digits..];
+                        let chars = rest.bytes().take_while(|c| !c.is_ascii_digit()).count();
+                        if chars > 0 && !run.is_empty() {
+                            shape.runs.push(std::mem::take(&mut run));
+                        }
+                        text.push_str(&rest[..chars]);
+                        rest = &rest[chars..];
+                    }
+                }
+                Component::Range(r) => {
+                    push_part(&mut shape, &mut text, &mut run, Part::Range(r.clone()));
+                }
+            }
+        }
+        if !run.is_empty() {
+            shape.runs.push(run);
+        }
+        shape.texts.push(text);
+
+        shape
+    }
+
+    /// Returns the blocks making up the element, one for each combination of lengths of its
+    /// ranges' values
+    fn blocks(&self) -> Result<Vec<Block>> {
+        let mut blocks: Vec<Block> = vec![Vec::new()];
+        for run in &self.runs {
+            let mut choices: Vec<Vec<Segment>> = vec![Vec::new()];
+            for (part, p) in run.iter().enumerate() {
+                let segments = match p {
+                    Part::Digits(digits) => vec![digit_segments(part, digits)?],
+                    Part::Range(r) => by_length(r)?
+                        .into_iter()
+                        .map(|(len, values)| vec![Segment { part, len, values }])
+                        .collect(),
+                };
+                choices = choices
+                    .iter()
+                    .flat_map(|choice| {
+                        segments.iter().map(move |segments| {
+                            let mut choice = choice.clone();
+                            choice.extend(segments.iter().cloned());
+                            choice
+                        })
+                    })
+                    .collect();
+            }
+            blocks = blocks
+                .iter()
+                .flat_map(|block| {
+                    choices.iter().map(move |choice| {
+                        let mut block = block.clone();
+                        block.push(choice.clone());
+                        block
+                    })
+                })
+                .collect();
+        }
+        Ok(blocks)
+    }
+
+    /// Returns an element for each block, with the segments of each part joined back together
+    fn elems(&self, blocks: Vec<Block>) -> Result<Vec<HostlistElem>> {
+        let mut elems = Vec::with_capacity(blocks.len());
+        for block in blocks {
+            let mut components = Vec::new();
+            for (r, run) in block.iter().enumerate() {
+                push_static(&mut components, &self.texts[r]);
+                for segments in run.chunk_by(|a, b| a.part == b.part) {
+                    match &self.runs[r][segments[0].part] {
+                        Part::Digits(digits) => push_static(&mut components, digits),
+                        Part::Range(original) => components
+                            .extend(join(original, segments)?.into_iter().map(Component::Range)),
+                    }
+                }
+            }
+            if let Some(text) = self.texts.last() {
+                push_static(&mut components, text);
+            }
+            components.retain(|c| !matches!(c, Component::Static(s) if s.is_empty()));
+            elems.push(HostlistElem::from_components(components)?);
+        }
+        Ok(elems)
+    }
+}
+
+/// Returns the number of digits in each run of a block's hosts
+fn lengths(block: &Block) -> Vec<usize> {
+    block
+        .iter()
+        .map(|run| run.iter().map(|s| s.len).sum())
+        .collect()
+}
+
+/// Returns the number of hosts in a block
+fn block_len(block: &Block) -> usize {
+    block
+        .iter()
+        .flatten()
+        .map(|s| s.values.total_len())
+        .fold(1usize, usize::saturating_mul)
+}
+
+/// Returns the parts of `block` outside all of `others` and the parts inside any of them. A part
+/// with nothing in common with one of `others` is left whole.
+fn split_blocks(block: Block, others: &[&Block]) -> Result<(Vec<Block>, Vec<Block>)> {
+    let mut outside = vec![block];
+    let mut inside = Vec::new();
+    for other in others {
+        let mut remaining = Vec::new();
+        for piece in outside {
+            let cuts = cuts(&piece, other);
+            let other_pieces = refine((*other).clone(), &cuts)?;
+            let mut pieces = refine(piece.clone(), &cuts)?;
+            let mut overlap = false;
+            for other_piece in &other_pieces {
+                let mut next = Vec::new();
+                for piece in pieces {
+                    match split_block(piece, other_piece)? {
+                        Ok((piece_outside, piece_inside)) => {
+                            overlap = true;
+                            next.extend(piece_outside);
+                            inside.push(piece_inside);
+                        }
+                        Err(piece) => next.push(piece),
+                    }
+                }
+                pieces = next;
+            }
+            if overlap {
+                remaining.extend(pieces);
+            } else {
+                remaining.push(piece);
+            }
+        }
+        outside = remaining;
+    }
+    Ok((outside, inside))
+}
+
+/// Splits `block` into the blocks outside `other` and the block inside it, or returns it back if
+/// they have no hosts in common. Both must have segments of the same lengths.
+fn split_block(
+    block: Block,
+    other: &Block,
+) -> Result<std::result::Result<(Vec<Block>, Block), Block>> {
+    let mut inside = block.clone();
+    for (run, other_run) in inside.iter_mut().zip(other) {
+        for (segment, other_segment) in run.iter_mut().zip(other_run) {
+            segment.values = segment.values.intersect(&other_segment.values)?;
+            if segment.values.total_len() == 0 {
+                return Ok(Err(block));
+            }
+        }
+    }
+
+    // A host outside `other` has a first segment whose value is outside `other`'s, so split off
+    // one block per segment: outside `other` in that segment, inside it in the segments before
+    let mut outside = Vec::new();
+    let mut current = block;
+    for r in 0..current.len() {
+        for i in 0..current[r].len() {
+            let values = current[r][i].values.subtract(&other[r][i].values)?;
+            if values.total_len() > 0 {
+                let mut piece = current.clone();
+                piece[r][i].values = values;
+                outside.push(piece);
+            }
+            current[r][i].values = inside[r][i].values.clone();
+        }
+    }
+    Ok(Ok((outside, inside)))
+}
+
+/// Returns the positions between digits where either block starts a new segment, for each run
+fn cuts(a: &Block, b: &Block) -> Vec<Vec<usize>> {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| {
+            let mut cuts: Vec<usize> = [a, b]
+                .into_iter()
+                .flat_map(|run| {
+                    run.iter().scan(0, |pos, segment| {
+                        *pos += segment.len;
+                        Some(*pos)
+                    })
+                })
+                .collect();
+            cuts.sort_unstable();
+            cuts.dedup();
+            cuts
+        })
+        .collect()
+}
+
+/// Splits the segments of a block at `cuts`, which can take several blocks to describe (ex:
+/// "[1][8-9]" and "[2][0-1]" for "[18-21]" cut after its first digit)
+fn refine(block: Block, cuts: &[Vec<usize>]) -> Result<Vec<Block>> {
+    let mut refined = Vec::new();
+    let mut todo = vec![block];
+    while let Some(block) = todo.pop() {
+        let Some((r, i, at)) = find_cut(&block, cuts) else {
+            refined.push(block);
+            continue;
+        };
+        let segment = &block[r][i];
+        for (prefix, suffix) in split_values(&segment.values, segment.len, at)? {
+            let mut piece = block.clone();
+            let (part, len) = (segment.part, segment.len);
+            piece[r].splice(
+                i..=i,
+                [
+                    Segment {
+                        part,
+                        len: at,
+                        values: prefix,
+                    },
+                    Segment {
+                        part,
+                        len: len - at,
+                        values: suffix,
+                    },
+                ],
+            );
+            todo.push(piece);
+        }
+    }
+    refined.reverse();
+    Ok(refined)
+}
+
+/// Returns the run, segment, and digit within the segment of the first cut inside a segment
+fn find_cut(block: &Block, cuts: &[Vec<usize>]) -> Option<(usize, usize, usize)> {
+    for (r, (run, cuts)) in block.iter().zip(cuts).enumerate() {
+        let mut pos = 0;
+        for (i, segment) in run.iter().enumerate() {
+            if let Some(cut) = cuts
+                .iter()
+                .find(|cut| pos < **cut && **cut < pos + segment.len)
+            {
+                return Some((r, i, cut - pos));
+            }
+            pos += segment.len;
+        }
+    }
+    None
+}
+
+/// Splits values rendered with `len` digits into their first `at` digits and the rest, as pairs
+/// of ranges whose combinations make up the values
+fn split_values(values: &Range, len: usize, at: usize) -> Result<Vec<(Range, Range)>> {
+    let m = pow10(len - at).ok_or(Error::Internal(format!("no {len} digit values")))?;
+    let mut pieces: BTreeMap<(u64, u64), Vec<SimpleRange>> = BTreeMap::new();
+    let mut push = |prefix: (u64, u64), suffix: (u64, u64)| -> Result<()> {
+        let suffix = SimpleRange::with_width(suffix.0, suffix.1, len - at)?;
+        pieces.entry(prefix).or_default().push(suffix);
+        Ok(())
+    };
+    for range in &values.ranges {
+        for range in range.unstepped() {
+            let (lo_prefix, lo_suffix) = (range.start / m, range.start % m);
+            let (hi_prefix, hi_suffix) = (range.end / m, range.end % m);
+            if lo_prefix == hi_prefix {
+                push((lo_prefix, lo_prefix), (lo_suffix, hi_suffix))?;
+                continue;
+            }
+
+            // Leading digits that take every value of the rest make up a single piece
+            let mut full = (lo_prefix, hi_prefix);
+            if lo_suffix > 0 {
+                push((lo_prefix, lo_prefix), (lo_suffix, m - 1))?;
+                full.0 += 1;
+            }
+            if hi_suffix < m - 1 {
+                push((hi_prefix, hi_prefix), (0, hi_suffix))?;
+                full.1 -= 1;
+            }
+            if full.0 <= full.1 {
+                push(full, (0, m - 1))?;
+            }
+        }
+    }
+
+    pieces
+        .into_iter()
+        .map(|((start, end), suffixes)| {
+            let prefix = Range::from_ranges([SimpleRange::with_width(start, end, at)?])?;
+            Ok((prefix, Range::from_ranges(suffixes)?))
+        })
+        .collect()
+}
+
+/// Returns the segments of digits written in a static part
+fn digit_segments(part: usize, digits: &str) -> Result<Vec<Segment>> {
+    digits
+        .as_bytes()
+        .chunks(MAX_DIGITS)
+        .map(|chunk| {
+            let value = chunk
+                .iter()
+                .fold(0, |value, digit| value * 10 + u64::from(digit - b'0'));
+            Ok(Segment {
+                part,
+                len: chunk.len(),
+                values: Range::from_ranges([SimpleRange::with_width(value, value, chunk.len())?])?,
+            })
+        })
+        .collect()
+}
+
+/// Splits the values of a range by how many digits they're rendered with, rendering each group
+/// zero-padded to exactly that many digits
+fn by_length(range: &Range) -> Result<Vec<(usize, Range)>> {
+    let mut lengths: BTreeMap<usize, Vec<SimpleRange>> = BTreeMap::new();
+    for r in &range.ranges {
+        let mut start = r.start;
+        loop {
+            let len = digit_len(start).max(r.width);
+            let last = pow10(len).map_or(u64::MAX - 1, |p| p - 1);
+            let piece = SimpleRange::with_step(start, r.end.min(last), r.step, len)?;
+            let next = piece.end.checked_add(r.step);
+            lengths.entry(len).or_default().push(piece);
+            match next {
+                Some(next) if next <= r.end => start = next,
+                _ => break,
+            }
+        }
+    }
+
+    lengths
+        .into_iter()
+        .map(|(len, ranges)| Ok((len, Range::from_ranges(ranges)?)))
+        .collect()
+}
+
+/// Joins the segments of a range's values back into a range with the padding the range was
+/// written with, where that renders the same digits. Segments that can't be written as one range
+/// are left as separate ranges.
+fn join(original: &Range, segments: &[Segment]) -> Result<Vec<Range>> {
+    let full = |s: &Segment| pow10(s.len).is_some_and(|p| s.values.total_len() as u64 == p);
+    let tail = segments
+        .iter()
+        .rev()
+        .take_while(|s| full(s))
+        .count()
+        .min(segments.len() - 1);
+    let (head, tail) = segments.split_at(segments.len() - tail);
+    let (middle, prefixes) = head
+        .split_last()
+        .ok_or(Error::Internal("no segments to join".to_string()))?;
+    let combinations = prefixes
+        .iter()
+        .map(|s| s.values.total_len())
+        .fold(1usize, usize::saturating_mul);
+    if combinations > MAX_PREFIXES {
+        return Ok(segments.iter().map(|s| s.values.clone()).collect());
+    }
+
+    let overflow = || Error::Internal("joined value too large".to_string());
+    let tail_len: usize = tail.iter().map(|s| s.len).sum();
+    let tail_size = pow10(tail_len).ok_or_else(overflow)?;
+    let middle_size = pow10(middle.len).ok_or_else(overflow)?;
+    let mut values = vec![0u64];
+    for segment in prefixes {
+        let size = pow10(segment.len).ok_or_else(overflow)?;
+        values = values
+            .iter()
+            .flat_map(|value| segment.values.iter().map(move |v| (value, v)))
+            .map(|(value, v)| value.checked_mul(size).and_then(|x| x.checked_add(v)))
+            .collect::<Option<_>>()
+            .ok_or_else(overflow)?;
+    }
+
+    let mut ranges = Vec::new();
+    for prefix in values {
+        let base = prefix.checked_mul(middle_size).ok_or_else(overflow)?;
+        for range in &middle.values.ranges {
+            let pieces = if tail.is_empty() {
+                vec![range.clone()]
+            } else {
+                range.unstepped()
+            };
+            for piece in pieces {
+                let start = base
+                    .checked_add(piece.start)
+                    .and_then(|x| x.checked_mul(tail_size))
+                    .ok_or_else(overflow)?;
+                let end = base
+                    .checked_add(piece.end)
+                    .and_then(|x| x.checked_mul(tail_size))
+                    .and_then(|x| x.checked_add(tail_size - 1))
+                    .ok_or_else(overflow)?;
+                ranges.push((start, end, piece.step));
+            }
+        }
+    }
+
+    let len = middle.len + tail_len + prefixes.iter().map(|s| s.len).sum::<usize>();
+    let min = ranges.iter().map(|r| r.0).min().unwrap_or(0);
+    let natural = len == 1 || pow10(len - 1).is_some_and(|p| min >= p);
+    let width = original
+        .ranges
+        .iter()
+        .map(|r| r.width)
+        .filter(|w| *w == len || (*w < len && natural))
+        .min()
+        .unwrap_or(len);
+    let ranges = ranges
+        .into_iter()
+        .map(|(start, end, step)| SimpleRange::with_step(start, end, step, width))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(vec![Range::from_ranges(ranges)?])
+}
+
+/// Returns how many digits `value` has
+fn digit_len(value: u64) -> usize {
+    value.checked_ilog10().map_or(1, |log| log as usize + 1)
+}
+
+/// Returns 10 to the power of `exp`, or `None` if it doesn't fit in a `u64`
+fn pow10(exp: usize) -> Option<u64> {
+    u32::try_from(exp)
+        .ok()
+        .and_then(|exp| 10u64.checked_pow(exp))
+}
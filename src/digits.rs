@@ -0,0 +1,406 @@
+//! Expansion and collapsing with numbers kept as digit strings, for host names with numbers too
+//! long for any integer type (ex: 20+ digit device identifiers)
+//!
+//! Numbers are compared by their digits (fewer digits first, then lexicographically) and counted
+//! up one digit at a time, so nothing overflows. Like `Padding::Keep`, every range keeps the width
+//! its start was written with (ex: "n[08-10]" is "n08", "n09", "n10").
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use pest::Parser;
+use pest::iterators::Pair;
+
+use crate::Affixes;
+use crate::error::{Error, Result};
+use crate::expand::{Part, Values, expand_elements, unsupported};
+use crate::hostlist::{HostlistParser, Rule};
+use crate::hostlistelem::unescape;
+
+/// Expands a hostlist expression, keeping each number as a digit string. Hosts are returned in
+/// the order they were given, with the last range of an element changing fastest.
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if there are issues parsing the provided hostlist
+/// expression, if it expands to more than `MAX_EXPANDED_HOSTS` hosts, or `Error::Unsupported` if
+/// it has steps, letter ranges, or set operators.
+/// ```
+/// use hostlist_iter::digits;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let hosts = digits::expand("dev[123456789012345678901-123456789012345678902]")?;
+///   assert_eq!(hosts, ["dev123456789012345678901", "dev123456789012345678902"]);
+///
+///   Ok(())
+/// }
+/// ```
+pub fn expand(expr: &str) -> Result<Vec<String>> {
+    expand_elements(HostlistParser::parse(Rule::hostlist, expr)?, element_parts)
+}
+
+/// Collapses a list of host names into a hostlist expression, keeping each numeric suffix as a
+/// digit string. Numbers are only combined with numbers padded to the same width, as with
+/// `collapse_hosts_with_padding(hosts, Padding::Keep)`.
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if any host name cannot be parsed.
+/// ```
+/// use hostlist_iter::digits;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let hosts = ["dev99999999999999999999", "dev100000000000000000000", "n01", "n02"];
+///   assert_eq!(
+///     digits::collapse(hosts)?,
+///     "dev[99999999999999999999-100000000000000000000],n[01-02]"
+///   );
+///
+///   Ok(())
+/// }
+/// ```
+pub fn collapse(hosts: impl IntoIterator<Item = impl AsRef<str>>) -> Result<String> {
+    let mut hostlist_elems: Vec<String> = Vec::new();
+    let mut seen_elems: HashSet<String> = HashSet::new();
+    // Numbers by prefix and the width they're padded to (0 if they aren't), each keyed by its
+    // length so that the set is in numeric order
+    let mut prefix_map: BTreeMap<(Affixes, usize), BTreeSet<(usize, String)>> = BTreeMap::new();
+
+    for host in hosts {
+        let host = host.as_ref();
+        if host.is_empty() {
+            return Err(Error::InvalidHostname(host.into()));
+        }
+
+        let mut prefix = None;
+        let mut number = None;
+        let mut suffix = "";
+        for pair in HostlistParser::parse(Rule::simple_hostname, host)? {
+            match pair.as_rule() {
+                Rule::prefix => prefix = Some(pair.as_str()),
+                Rule::numeric_suffix => number = Some(pair.as_str()),
+                Rule::host_suffix => suffix = pair.as_str(),
+                Rule::EOI => break,
+                rule => return Err(Error::UnexpectedParserState(rule)),
+            }
+        }
+
+        let prefix = prefix
+            .ok_or_else(|| Error::InvalidHostname(host.to_string()))?
+            .to_string();
+
+        if let Some(number) = number {
+            let width = if number.len() > 1 && number.starts_with('0') {
+                number.len()
+            } else {
+                0
+            };
+            prefix_map
+                .entry(((prefix, suffix.to_string()), width))
+                .or_default()
+                .insert((number.len(), number.to_string()));
+        } else if seen_elems.insert(prefix.clone()) {
+            hostlist_elems.push(prefix);
+        }
+    }
+
+    // Unpadded numbers with as many digits as a padded width join that width's numbers
+    let padded: HashSet<(Affixes, usize)> = prefix_map
+        .keys()
+        .filter(|(_, width)| *width > 0)
+        .cloned()
+        .collect();
+    let mut moved = Vec::new();
+    for ((prefix, width), nums) in &mut prefix_map {
+        if *width == 0 {
+            nums.retain(|num| {
+                let key = (prefix.clone(), num.0);
+                if padded.contains(&key) {
+                    moved.push((key, num.clone()));
+                    return false;
+                }
+                true
+            });
+        }
+    }
+    for (key, num) in moved {
+        prefix_map.entry(key).or_default().insert(num);
+    }
+
+    for (((mut host, suffix), _), nums) in prefix_map {
+        if !nums.is_empty() {
+            host.push_str(&collapse_range(nums.into_iter().map(|(_, num)| num)));
+            host.push_str(&suffix);
+            hostlist_elems.push(host);
+        }
+    }
+
+    Ok(hostlist_elems.join(","))
+}
+
+/// Compares two digit strings by value, ignoring leading zeros
+fn compare(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// Adds one to a digit string, keeping its width unless every digit is a 9 (ex: "099" becomes
+/// "100", and "99" becomes "100")
+fn increment(digits: &mut String) {
+    let mut bytes = std::mem::take(digits).into_bytes();
+    let carried = bytes.iter_mut().rev().all(|b| {
+        if *b == b'9' {
+            *b = b'0';
+            true
+        } else {
+            *b += 1;
+            false
+        }
+    });
+    if carried {
+        bytes.insert(0, b'1');
+    }
+    // Only ASCII digits were changed
+    *digits = String::from_utf8(bytes).unwrap_or_default();
+}
+
+/// Returns the number of values from `start` to `end` inclusive, if it fits in a `usize`.
+/// `start` must not be greater than `end`.
+fn count(start: &str, end: &str) -> Option<usize> {
+    let (start, end) = (start.as_bytes(), end.as_bytes());
+    let digit = |digits: &[u8], i: usize| {
+        digits
+            .len()
+            .checked_sub(i + 1)
+            .map_or(0, |j| usize::from(digits[j] - b'0'))
+    };
+
+    // Subtract from the last digit up, then read the difference from the first digit down
+    let mut difference = Vec::with_capacity(end.len().max(start.len()));
+    let mut borrow = 0;
+    for i in 0..end.len().max(start.len()) {
+        let (a, b) = (digit(end, i), digit(start, i) + borrow);
+        borrow = usize::from(a < b);
+        difference.push(a + borrow * 10 - b);
+    }
+    difference
+        .iter()
+        .rev()
+        .try_fold(0, |len: usize, d| len.checked_mul(10)?.checked_add(*d))?
+        .checked_add(1)
+}
+
+/// The digits of the start and end of a range in brackets
+struct DigitRange<'a> {
+    start: &'a str,
+    end: &'a str,
+}
+
+impl Values for DigitRange<'_> {
+    fn count(&self) -> Option<usize> {
+        count(self.start, self.end)
+    }
+
+    fn for_each(&self, mut f: impl FnMut(&str)) {
+        let mut value = self.start.to_string();
+        loop {
+            f(&value);
+            if compare(&value, self.end) != Ordering::Less {
+                break;
+            }
+            increment(&mut value);
+        }
+    }
+}
+
+fn element_parts(pair: Pair<'_, Rule>) -> Result<Vec<Part<'_, DigitRange<'_>>>> {
+    let mut parts = Vec::new();
+    for component in pair.into_inner() {
+        match component.as_rule() {
+            Rule::static_elem => parts.push(Part::Literal(unescape(component.as_str()))),
+            Rule::range => {
+                let mut ranges = Vec::new();
+                for range_elem in component.into_inner() {
+                    let (start, end) = match range_elem.as_rule() {
+                        Rule::simple_range => {
+                            let text = range_elem.as_str();
+                            let mut bounds = range_elem.into_inner();
+                            let start = bounds.next().map_or("", |n| n.as_str());
+                            let end = bounds.next().map_or("", |n| n.as_str());
+                            // Steps would need more than adding one to a digit string
+                            if bounds.next().is_some() {
+                                return Err(unsupported("steps", text));
+                            }
+                            (start, end)
+                        }
+                        Rule::number => (range_elem.as_str(), range_elem.as_str()),
+                        rule => return Err(Error::UnexpectedParserState(rule)),
+                    };
+                    if compare(start, end) == Ordering::Greater {
+                        return Err(Error::InvalidBoundsReversed {
+                            start: start.to_string(),
+                            end: end.to_string(),
+                        });
+                    }
+                    ranges.push(DigitRange { start, end });
+                }
+                parts.push(Part::Ranges(ranges));
+            }
+            Rule::letter_range => return Err(unsupported("letter ranges", component.as_str())),
+            rule => return Err(Error::UnexpectedParserState(rule)),
+        }
+    }
+
+    Ok(parts)
+}
+
+/// Converts digit strings in numeric order into a range expression
+fn collapse_range(nums: impl Iterator<Item = String>) -> String {
+    let mut ranges: Vec<(String, String)> = Vec::new();
+    for num in nums {
+        if let Some((_, end)) = ranges.last_mut() {
+            let mut next = end.clone();
+            increment(&mut next);
+            if next == num {
+                *end = num;
+                continue;
+            }
+        }
+        ranges.push((num.clone(), num));
+    }
+
+    let rendered: Vec<String> = ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.clone()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect();
+    match rendered.as_slice() {
+        [number] if !number.contains('-') => number.clone(),
+        _ => format!("[{}]", rendered.join(",")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digits_expand() -> Result<()> {
+        let tests: [(&str, &[&str]); 7] = [
+            ("", &[]),
+            ("n[1-3]", &["n1", "n2", "n3"]),
+            ("n[08-10],login", &["n08", "n09", "n10", "login"]),
+            ("n[9-010]", &["n9", "n10"]),
+            ("r[1-2]n[1-2]", &["r1n1", "r1n2", "r2n1", "r2n2"]),
+            (
+                "d[99999999999999999999998-100000000000000000000000]",
+                &[
+                    "d99999999999999999999998",
+                    "d99999999999999999999999",
+                    "d100000000000000000000000",
+                ],
+            ),
+            (
+                "d123456789012345678901234[5,7]",
+                &["d1234567890123456789012345", "d1234567890123456789012347"],
+            ),
+        ];
+        for (expr, expected) in tests {
+            assert_eq!(expand(expr)?, expected, "expr: {expr}");
+        }
+
+        assert_eq!(
+            expand("n[100000000000000000000-99999999999999999999]"),
+            Err(Error::InvalidBoundsReversed {
+                start: "100000000000000000000".to_string(),
+                end: "99999999999999999999".to_string(),
+            })
+        );
+        assert_eq!(
+            expand("n[0-99999999999999999999999]"),
+            Err(Error::HostlistTooLarge)
+        );
+        // Too many hosts to hold, though the count fits in a `usize`
+        assert_eq!(expand("n[1-99999999999]"), Err(Error::HostlistTooLarge));
+        assert_eq!(
+            expand("r[1-100000]n[1-100000]"),
+            Err(Error::HostlistTooLarge)
+        );
+        assert!(matches!(expand("n[1-3"), Err(Error::ParseError(_))));
+        let tests = [
+            ("n[1-9/2]", "steps", "1-9/2"),
+            ("rack[a-b]n1", "letter ranges", "[a-b]"),
+            ("n[1-5]!n3", "set operators", "!"),
+            ("n[1-5]&n[2-3]", "set operators", "&"),
+        ];
+        for (expr, syntax, text) in tests {
+            assert_eq!(
+                expand(expr),
+                Err(Error::Unsupported {
+                    syntax,
+                    text: text.to_string()
+                }),
+                "expr: {expr}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_digits_collapse() -> Result<()> {
+        let tests: [(&[&str], &str); 8] = [
+            (&[], ""),
+            (&["n3", "n1", "n2", "n1"], "n[1-3]"),
+            (&["n01", "n02", "n1"], "n1,n[01-02]"),
+            (&["n098", "n099", "n100"], "n[098-100]"),
+            (&["n9", "n10", "login"], "login,n[9-10]"),
+            (&["n1", "n3"], "n[1,3]"),
+            (
+                &["d12345678901234567890123", "d12345678901234567890122"],
+                "d[12345678901234567890122-12345678901234567890123]",
+            ),
+            (
+                &["d99999999999999999999", "d100000000000000000000", "d5"],
+                "d[5,99999999999999999999-100000000000000000000]",
+            ),
+        ];
+        for (hosts, expected) in tests {
+            assert_eq!(collapse(hosts)?, expected, "hosts: {hosts:?}");
+        }
+
+        assert_eq!(
+            collapse(["n1", ""]),
+            Err(Error::InvalidHostname(String::new()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_digits_round_trip() -> Result<()> {
+        for expr in [
+            "n[1-3,5]",
+            "login,n[01-10]",
+            "d[12345678901234567890-12345678901234567899]",
+        ] {
+            assert_eq!(collapse(expand(expr)?)?, expr, "expr: {expr}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_digits_count() {
+        assert_eq!(count("1", "1"), Some(1));
+        assert_eq!(count("08", "10"), Some(3));
+        assert_eq!(
+            count("99999999999999999999", "100000000000000000001"),
+            Some(3)
+        );
+        assert_eq!(count("0", "99999999999999999999999"), None);
+    }
+}
@@ -6,27 +6,64 @@ pub type Result<T> = core::result::Result<T, Error>;
 
 // Prod
 #[non_exhaustive]
-#[derive(Debug, From, Display)]
+#[derive(Debug, Clone, PartialEq, Eq, From, Display)]
 pub enum Error {
     // -- lib
     #[display("invalid range \"[{start}-{end}]\": start greater than end")]
-    InvalidRangeReversed { start: u32, end: u32 },
+    InvalidRangeReversed { start: u64, end: u64 },
+
+    /// A reversed range whose bounds aren't integers: digit strings in `digits`, or letters
+    #[display("invalid range \"[{start}-{end}]\": start greater than end")]
+    InvalidBoundsReversed { start: String, end: String },
 
     #[display("integer value {_0} exceeds limits")]
-    TooLarge(u32),
+    TooLarge(u64),
 
     #[display("hostlist is too large")]
     HostlistTooLarge,
 
+    #[display("hostlist is empty")]
+    EmptyHostlist,
+
     #[display("unexpected parser state while processing rule:\n{_0:?}")]
     UnexpectedParserState(Rule),
 
     #[display("invalid hostname: \"{_0}\"")]
     InvalidHostname(String),
 
+    /// An IPv6 address that doesn't have 8 groups, or more than 8 with "::"
+    #[display("invalid IPv6 address: \"{_0}\"")]
+    InvalidAddress(String),
+
     #[display("internal error: \"{_0}\"")]
     Internal(String),
 
+    /// An argument outside the values a function accepts (ex: a chunk size of 0)
+    #[display("invalid argument: {_0}")]
+    InvalidArgument(String),
+
+    /// Syntax that's valid in a hostlist expression, but not in the mode it was parsed in (ex: a
+    /// letter range in `digits::expand()`)
+    #[display("{syntax} aren't supported here: \"{text}\"")]
+    Unsupported { syntax: &'static str, text: String },
+
+    /// An error in one element of a hostlist expression. `index` counts elements from 0, and
+    /// `offset` is the byte offset of the element within the expression.
+    #[display("in \"{element}\" (element {index}, offset {offset}): {source}")]
+    InElement {
+        element: String,
+        index: usize,
+        offset: usize,
+        source: Box<Error>,
+    },
+
+    /// A syntax error, along with a corrected expression that does parse
+    #[display("{source}\nhelp: did you mean \"{suggestion}\"?")]
+    Suggestion {
+        suggestion: String,
+        source: Box<Error>,
+    },
+
     // -- Externals
     #[display("parse error:\n{_0}")]
     ParseError(Box<pest::error::Error<Rule>>),
@@ -34,9 +71,88 @@ pub enum Error {
     #[from]
     #[display("integer parse error: {_0}")]
     ParseIntError(std::num::ParseIntError),
+
+    /// An error reading a hostlist. Only the error's kind and message are kept, so that `Error`
+    /// can still be cloned and compared.
+    #[display("I/O error: {message}")]
+    Io {
+        kind: std::io::ErrorKind,
+        message: String,
+    },
+}
+
+impl Error {
+    /// Returns what kind of error this is. Errors that add context to another error (`InElement`
+    /// and `Suggestion`) report the kind of the error they wrap.
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::InvalidRangeReversed { .. } | Self::InvalidBoundsReversed { .. } => {
+                ErrorKind::InvalidRangeReversed
+            }
+            Self::TooLarge(_) => ErrorKind::TooLarge,
+            Self::HostlistTooLarge => ErrorKind::HostlistTooLarge,
+            Self::EmptyHostlist => ErrorKind::EmptyHostlist,
+            Self::UnexpectedParserState(_) => ErrorKind::UnexpectedParserState,
+            Self::InvalidHostname(_) => ErrorKind::InvalidHostname,
+            Self::InvalidAddress(_) => ErrorKind::InvalidAddress,
+            Self::Internal(_) => ErrorKind::Internal,
+            Self::InvalidArgument(_) => ErrorKind::InvalidArgument,
+            Self::Unsupported { .. } => ErrorKind::Unsupported,
+            Self::InElement { source, .. } | Self::Suggestion { source, .. } => source.kind(),
+            Self::ParseError(_) => ErrorKind::ParseError,
+            Self::ParseIntError(_) => ErrorKind::ParseIntError,
+            Self::Io { .. } => ErrorKind::Io,
+        }
+    }
+
+    /// Returns the stable numeric code of this error's kind, see `ErrorKind::code()`
+    #[must_use]
+    pub fn code(&self) -> u32 {
+        self.kind().code()
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InElement { source, .. } | Self::Suggestion { source, .. } => Some(source),
+            Self::ParseError(err) => Some(err),
+            Self::ParseIntError(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
-impl std::error::Error for Error {}
+/// The kind of an `Error`, without any of its details. Useful for matching on errors, or passing
+/// them somewhere that can't hold the error itself.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum ErrorKind {
+    InvalidRangeReversed = 1,
+    TooLarge = 2,
+    HostlistTooLarge = 3,
+    UnexpectedParserState = 4,
+    InvalidHostname = 5,
+    Internal = 6,
+    ParseError = 7,
+    ParseIntError = 8,
+    Io = 9,
+    EmptyHostlist = 10,
+    InvalidAddress = 11,
+    Unsupported = 12,
+    InvalidArgument = 13,
+}
+
+impl ErrorKind {
+    /// Returns a numeric code for this kind of error. Codes never change between releases, and
+    /// are never reused.
+    #[must_use]
+    pub const fn code(self) -> u32 {
+        self as u32
+    }
+}
 
 // The pest error type is quite large, so to reduce Result size (and fix clippy warnings) we Box
 // it. But this means we have to write our own From implementation.
@@ -45,3 +161,51 @@ impl From<pest::error::Error<Rule>> for Error {
         Self::ParseError(Box::new(err))
     }
 }
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io {
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use super::*;
+    use crate::Hostlist;
+
+    #[test]
+    fn test_error_kind_and_source() {
+        let tests = [
+            ("node[5-1]", ErrorKind::InvalidRangeReversed, 1),
+            ("node[18446744073709551615]", ErrorKind::TooLarge, 2),
+            ("node[99999999999999999999]", ErrorKind::ParseIntError, 8),
+            ("node[1-5", ErrorKind::ParseError, 7),
+            ("node[1-5]?", ErrorKind::ParseError, 7),
+        ];
+
+        for (input, kind, code) in tests {
+            let err = Hostlist::new(input).unwrap_err();
+            assert_eq!(err.kind(), kind, "input: \"{input}\"");
+            assert_eq!(err.code(), code, "input: \"{input}\"");
+
+            // Each of these either wraps another error, or an underlying pest or integer error
+            assert!(err.source().is_some(), "input: \"{input}\"");
+        }
+    }
+
+    #[test]
+    fn test_error_clone_eq() {
+        let err = Hostlist::new("n[1-3],m[3-1]").unwrap_err();
+        assert_eq!(err.clone(), err);
+        assert_ne!(err, Hostlist::new("n[1-3],m[4-1]").unwrap_err());
+        assert_eq!(
+            err.source().map(ToString::to_string),
+            Some("invalid range \"[3-1]\": start greater than end".to_string())
+        );
+    }
+}
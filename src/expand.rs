@@ -0,0 +1,108 @@
+//! Expansion of parsed hostlist elements into a list of hosts, shared by `digits::expand()`,
+//! `hex::expand()`, and `slurm::expand()`, which each read ranges their own way
+
+use std::borrow::Cow;
+
+use pest::iterators::Pair;
+
+use crate::error::{Error, Result};
+use crate::hostlist::Rule;
+
+/// The most hosts `digits::expand()`, `hex::expand()`, and `slurm::expand()` return. Larger
+/// expressions are an `Error::HostlistTooLarge` rather than an allocation that can't be made.
+pub const MAX_EXPANDED_HOSTS: usize = 1 << 27;
+
+/// A part of a hostlist element: literal text, or the ranges in brackets
+pub(crate) enum Part<'a, R> {
+    Literal(Cow<'a, str>),
+    Ranges(Vec<R>),
+}
+
+/// A range in brackets, read by one of the expansion modules
+pub(crate) trait Values {
+    /// The number of values in the range, if it fits in a `usize`
+    fn count(&self) -> Option<usize>;
+
+    /// Calls `f` with each value in the range, as it's written in a host name
+    fn for_each(&self, f: impl FnMut(&str));
+}
+
+/// Expands the elements in `pairs`, reading each with `element_parts`. Hosts are returned in the
+/// order they were given, with the last range of an element changing fastest.
+pub(crate) fn expand_elements<'a, R: Values>(
+    pairs: impl IntoIterator<Item = Pair<'a, Rule>>,
+    element_parts: impl Fn(Pair<'a, Rule>) -> Result<Vec<Part<'a, R>>>,
+) -> Result<Vec<String>> {
+    let mut elements = Vec::new();
+    let mut len: usize = 0;
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::hostlist_elem | Rule::hex_hostlist_elem => {
+                let parts = element_parts(pair)?;
+                len = count_hosts(&parts)
+                    .and_then(|count| len.checked_add(count))
+                    .filter(|&len| len <= MAX_EXPANDED_HOSTS)
+                    .ok_or(Error::HostlistTooLarge)?;
+                elements.push(parts);
+            }
+            Rule::difference_op | Rule::intersection_op => {
+                return Err(unsupported("set operators", pair.as_str()));
+            }
+            Rule::EOI => (),
+            rule => return Err(Error::UnexpectedParserState(rule)),
+        }
+    }
+
+    let mut hosts = Vec::with_capacity(len);
+    for parts in &elements {
+        expand_parts(&mut String::new(), parts, &mut hosts);
+    }
+
+    Ok(hosts)
+}
+
+/// Returns an error for syntax the grammar accepts but the current mode doesn't (ex: a letter range
+/// in `digits::expand()`)
+pub(crate) fn unsupported(syntax: &'static str, text: &str) -> Error {
+    Error::Unsupported {
+        syntax,
+        text: text.to_string(),
+    }
+}
+
+/// The number of hosts an element expands to, if it fits in a `usize`
+fn count_hosts<R: Values>(parts: &[Part<'_, R>]) -> Option<usize> {
+    parts.iter().try_fold(1, |len: usize, part| match part {
+        Part::Literal(_) => Some(len),
+        Part::Ranges(ranges) => ranges
+            .iter()
+            .try_fold(0, |sum: usize, range| sum.checked_add(range.count()?))
+            .and_then(|count| len.checked_mul(count)),
+    })
+}
+
+/// Appends the hosts starting with `prefix` and followed by `parts` to `hosts`, with the last
+/// range changing fastest
+fn expand_parts<R: Values>(prefix: &mut String, parts: &[Part<'_, R>], hosts: &mut Vec<String>) {
+    let Some((part, rest)) = parts.split_first() else {
+        hosts.push(prefix.clone());
+        return;
+    };
+    let len = prefix.len();
+    match part {
+        Part::Literal(s) => {
+            prefix.push_str(s);
+            expand_parts(prefix, rest, hosts);
+            prefix.truncate(len);
+        }
+        Part::Ranges(ranges) => {
+            for range in ranges {
+                range.for_each(|value| {
+                    prefix.push_str(value);
+                    expand_parts(prefix, rest, hosts);
+                    prefix.truncate(len);
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! The pest grammar behind hostlist expressions, for embedding hostlist syntax in other parsers
+//!
+//! Crates with their own pest grammar (ex: a job-spec DSL) can copy the rules from `GRAMMAR`, or
+//! hand off to `parse_prefix()` wherever a hostlist is expected and continue after it.
+
+use pest::Parser;
+use pest::iterators::Pairs;
+
+use crate::error::Result;
+use crate::hostlist::Hostlist;
+pub use crate::hostlist::{HostlistParser, Rule};
+
+/// The source of the pest grammar used by `HostlistParser`
+pub const GRAMMAR: &str = include_str!("hostlist.pest");
+
+/// Parses the hostlist at the start of `input`, returning it along with the rest of the input.
+/// Whitespace before the hostlist is not skipped, and whitespace after it is left in the rest.
+///
+/// # Errors
+/// Will return `Err` if `input` doesn't start with a valid hostlist expression.
+/// ```
+/// use hostlist_iter::grammar::parse_prefix;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let (hostlist, rest) = parse_prefix("node[1-3], gpu1 & @rack1")?;
+///   assert_eq!(hostlist.to_string(), "gpu1,node[1-3]");
+///   assert_eq!(rest, " & @rack1");
+///
+///   Ok(())
+/// }
+/// ```
+pub fn parse_prefix(input: &str) -> Result<(Hostlist, &str)> {
+    let pairs = HostlistParser::parse(Rule::hostlist_prefix, input)?;
+    let end = pairs.clone().last().map_or(0, |pair| pair.as_span().end());
+    let hostlist = from_pairs(pairs)?;
+    Ok((hostlist, &input[end..]))
+}
+
+/// Constructs a `Hostlist` from pairs produced by `HostlistParser` for `Rule::hostlist`,
+/// `Rule::hostlist_prefix`, or a sequence of `Rule::hostlist_elem`
+///
+/// # Errors
+/// Will return `Err` if an element is invalid (ex: a reversed range), or a pair is for some
+/// other rule.
+pub fn from_pairs(pairs: Pairs<'_, Rule>) -> Result<Hostlist> {
+    Hostlist::from_pairs(pairs, &mut Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn test_parse_prefix() -> Result<()> {
+        let (hostlist, rest) = parse_prefix("n[1-3],m1 - n2")?;
+        assert_eq!(hostlist.to_string(), "m1,n[1-3]");
+        assert_eq!(rest, " - n2");
+
+        let (hostlist, rest) = parse_prefix("n[1-3]")?;
+        assert_eq!(hostlist.len(), 3);
+        assert_eq!(rest, "");
+
+        // A trailing comma isn't part of the hostlist
+        let (_, rest) = parse_prefix("n1,")?;
+        assert_eq!(rest, ",");
+
+        assert!(matches!(parse_prefix(" n1"), Err(Error::ParseError(_))));
+        assert!(matches!(
+            parse_prefix("n[3-1] x"),
+            Err(Error::InElement { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_pairs() -> Result<()> {
+        let pairs = HostlistParser::parse(Rule::hostlist, "n[1-2],n[3]")?;
+        assert_eq!(from_pairs(pairs)?.to_string(), "n[1-3]");
+
+        let pairs = HostlistParser::parse(Rule::static_elem, "n1")?;
+        assert_eq!(
+            from_pairs(pairs),
+            Err(Error::UnexpectedParserState(Rule::static_elem))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_grammar_source() {
+        assert!(GRAMMAR.contains("hostlist_elem"));
+    }
+}
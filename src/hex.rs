@@ -0,0 +1,171 @@
+//! Expansion of hostlist expressions with hexadecimal ranges, for host names derived from hex IDs
+//! (ex: BMCs named after board IDs)
+//!
+//! Every range is read as base 16 (ex: "bmc[0x0a-0x0c]" or "node-[00a-00c]"). Each range keeps
+//! the width, "0x" prefix, and letter case its start was written with, so "n[0x0e-0x10]" is
+//! "n0x0e", "n0x0f", "n0x10", and "n[0E-10]" is "n0E", "n0F", "n10".
+
+use pest::Parser;
+use pest::iterators::Pair;
+
+use crate::error::{Error, Result};
+use crate::expand::{Part, Values, expand_elements};
+use crate::hostlist::{HostlistParser, Rule};
+use crate::hostlistelem::unescape;
+
+/// Expands a hostlist expression with hexadecimal ranges. Hosts are returned in the order they
+/// were given, with the last range of an element changing fastest.
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if there are issues parsing the provided hostlist
+/// expression, a number doesn't fit in a `u64`, or it expands to more than `MAX_EXPANDED_HOSTS`
+/// hosts.
+/// ```
+/// use hostlist_iter::hex;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let hosts = hex::expand("bmc[0x09-0x0b],node-[00f-010]")?;
+///   assert_eq!(hosts, ["bmc0x09", "bmc0x0a", "bmc0x0b", "node-00f", "node-010"]);
+///
+///   Ok(())
+/// }
+/// ```
+pub fn expand(expr: &str) -> Result<Vec<String>> {
+    expand_elements(
+        HostlistParser::parse(Rule::hex_hostlist, expr)?,
+        element_parts,
+    )
+}
+
+/// How the numbers of a range are written, taken from how its start was written
+#[derive(Clone, Copy)]
+struct Format {
+    prefix: &'static str,
+    uppercase: bool,
+    width: usize,
+}
+
+impl Format {
+    fn new(number: &str) -> Self {
+        let (prefix, digits) = split_prefix(number);
+        Self {
+            prefix,
+            uppercase: digits.bytes().any(|b| b.is_ascii_uppercase()),
+            width: digits.len(),
+        }
+    }
+
+    fn render(self, value: u64) -> String {
+        let (prefix, width) = (self.prefix, self.width);
+        if self.uppercase {
+            format!("{prefix}{value:0width$X}")
+        } else {
+            format!("{prefix}{value:0width$x}")
+        }
+    }
+}
+
+/// Splits a number into its "0x" or "0X" prefix, if any, and its digits
+fn split_prefix(number: &str) -> (&'static str, &str) {
+    for prefix in ["0x", "0X"] {
+        if let Some(digits) = number.strip_prefix(prefix) {
+            return (prefix, digits);
+        }
+    }
+    ("", number)
+}
+
+/// A range in brackets, with how its numbers are written
+struct HexRange {
+    format: Format,
+    start: u64,
+    end: u64,
+}
+
+impl Values for HexRange {
+    fn count(&self) -> Option<usize> {
+        usize::try_from(self.end - self.start).ok()?.checked_add(1)
+    }
+
+    fn for_each(&self, mut f: impl FnMut(&str)) {
+        for value in self.start..=self.end {
+            f(&self.format.render(value));
+        }
+    }
+}
+
+fn element_parts(pair: Pair<'_, Rule>) -> Result<Vec<Part<'_, HexRange>>> {
+    let parse = |number: &str| u64::from_str_radix(split_prefix(number).1, 16);
+
+    let mut parts = Vec::new();
+    for component in pair.into_inner() {
+        match component.as_rule() {
+            Rule::static_elem => parts.push(Part::Literal(unescape(component.as_str()))),
+            Rule::hex_list => {
+                let mut ranges = Vec::new();
+                for range_elem in component.into_inner() {
+                    let (start, end) = match range_elem.as_rule() {
+                        Rule::hex_list_range => {
+                            let mut bounds = range_elem.into_inner();
+                            let start = bounds.next().map_or("", |n| n.as_str());
+                            let end = bounds.next().map_or("", |n| n.as_str());
+                            (start, end)
+                        }
+                        Rule::hex_value => (range_elem.as_str(), range_elem.as_str()),
+                        rule => return Err(Error::UnexpectedParserState(rule)),
+                    };
+                    let (format, start, end) = (Format::new(start), parse(start)?, parse(end)?);
+                    if start > end {
+                        return Err(Error::InvalidRangeReversed { start, end });
+                    }
+                    ranges.push(HexRange { format, start, end });
+                }
+                parts.push(Part::Ranges(ranges));
+            }
+            rule => return Err(Error::UnexpectedParserState(rule)),
+        }
+    }
+
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_expand() -> Result<()> {
+        let tests: [(&str, &[&str]); 9] = [
+            ("", &[]),
+            ("bmc[0x0a-0x0c]", &["bmc0x0a", "bmc0x0b", "bmc0x0c"]),
+            ("node-[00e-010]", &["node-00e", "node-00f", "node-010"]),
+            ("n[0E-10]", &["n0E", "n0F", "n10"]),
+            ("n[0X9-0XB]", &["n0X9", "n0Xa", "n0Xb"]),
+            ("n[f-11]", &["nf", "n10", "n11"]),
+            ("n[a,1]", &["na", "n1"]),
+            ("r[1-2]n[e-f]", &["r1ne", "r1nf", "r2ne", "r2nf"]),
+            ("login,n[ff]", &["login", "nff"]),
+        ];
+        for (expr, expected) in tests {
+            assert_eq!(expand(expr)?, expected, "expr: {expr}");
+        }
+
+        assert_eq!(
+            expand("n[0x1f-0x0a]"),
+            Err(Error::InvalidRangeReversed { start: 31, end: 10 })
+        );
+        assert!(matches!(
+            expand("n[0-1ffffffffffffffff]"),
+            Err(Error::ParseIntError(_))
+        ));
+        assert_eq!(
+            expand("n[0-ffffffffffffffff]"),
+            Err(Error::HostlistTooLarge)
+        );
+        assert_eq!(expand("n[0-ffffffffffff]"), Err(Error::HostlistTooLarge));
+        assert_eq!(expand("n[0-8000000]"), Err(Error::HostlistTooLarge));
+        assert!(matches!(expand("n[0x-1]"), Err(Error::ParseError(_))));
+
+        Ok(())
+    }
+}
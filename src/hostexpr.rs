@@ -0,0 +1,146 @@
+//! A common interface to representations of a set of hosts
+
+use crate::error::Result;
+use crate::hostlist::Hostlist;
+use crate::hostlistelem::HostlistElem;
+
+/// Operations shared by representations of a set of hosts, so applications can switch between
+/// them (ex: a compact symbolic `Hostlist` and a faster bitset) behind one interface
+///
+/// Every method covers every host in the set. For `Hostlist`, that includes hosts already
+/// iterated over, unlike its own `len()` and `iter()`, which only count the hosts left to iterate
+/// over like any `ExactSizeIterator`, hence `host_count()` rather than `len()`.
+/// ```
+/// use hostlist_iter::{HostExpr, Hostlist};
+///
+/// fn drained<T: HostExpr>(all: &T, down: &T) -> hostlist_iter::Result<String> {
+///   all.difference(down)?.to_expression()
+/// }
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let all = Hostlist::new("node[1-8]")?;
+///   let down = Hostlist::new("node[3-4]")?;
+///   assert_eq!(drained(&all, &down)?, "node[1-2,5-8]");
+///
+///   Ok(())
+/// }
+/// ```
+pub trait HostExpr: Sized {
+    /// Number of hosts
+    fn host_count(&self) -> usize;
+
+    /// Returns true if there are no hosts
+    fn has_no_hosts(&self) -> bool {
+        self.host_count() == 0
+    }
+
+    /// Returns whether `host` is one of the hosts
+    fn contains(&self, host: &str) -> bool;
+
+    /// Iterates over the host names
+    fn hosts(&self) -> impl Iterator<Item = String> + '_;
+
+    /// Folds the hosts into a hostlist expression
+    ///
+    /// # Errors
+    /// Will return `Err` if the hosts can't be folded into an expression.
+    fn to_expression(&self) -> Result<String>;
+
+    /// Returns the hosts in either `self` or `other`
+    ///
+    /// # Errors
+    /// Will return `Err` if the result can't be represented.
+    fn union(&self, other: &Self) -> Result<Self>;
+
+    /// Returns the hosts in both `self` and `other`
+    ///
+    /// # Errors
+    /// Will return `Err` if the result can't be represented.
+    fn intersection(&self, other: &Self) -> Result<Self>;
+
+    /// Returns the hosts in `self` but not `other`
+    ///
+    /// # Errors
+    /// Will return `Err` if the result can't be represented.
+    fn difference(&self, other: &Self) -> Result<Self>;
+}
+
+impl HostExpr for Hostlist {
+    fn host_count(&self) -> usize {
+        self.hostlist_elems
+            .iter()
+            .map(HostlistElem::total_len)
+            .sum()
+    }
+
+    fn contains(&self, host: &str) -> bool {
+        Self::contains(self, host)
+    }
+
+    fn hosts(&self) -> impl Iterator<Item = String> + '_ {
+        // Restarting an element that was already constructed can't fail
+        self.hostlist_elems
+            .iter()
+            .filter_map(|elem| elem.restarted().ok())
+            .flatten()
+    }
+
+    fn to_expression(&self) -> Result<String> {
+        Ok(self.to_string())
+    }
+
+    fn union(&self, other: &Self) -> Result<Self> {
+        Self::union(self, other)
+    }
+
+    fn intersection(&self, other: &Self) -> Result<Self> {
+        Self::intersection(self, other)
+    }
+
+    fn difference(&self, other: &Self) -> Result<Self> {
+        Self::difference(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hostlist_host_expr() -> Result<()> {
+        let mut hostlist = Hostlist::new("n[1-4],login1")?;
+        hostlist.next();
+        hostlist.next();
+
+        // Every host, including those already iterated over
+        assert_eq!(hostlist.host_count(), 5);
+        assert_eq!(hostlist.len(), 3);
+        assert!(!hostlist.has_no_hosts());
+        let hosts: Vec<String> = HostExpr::hosts(&hostlist).collect();
+        assert_eq!(hosts, ["login1", "n1", "n2", "n3", "n4"]);
+        assert!(HostExpr::contains(&hostlist, "login1"));
+        assert_eq!(hostlist.to_expression()?, "login1,n[1-4]");
+
+        let other = Hostlist::new("n[3-9]")?;
+        assert_eq!(
+            HostExpr::union(&hostlist, &other)?.to_expression()?,
+            "login1,n[1-9]"
+        );
+        assert_eq!(
+            HostExpr::intersection(&hostlist, &other)?.to_expression()?,
+            "n[3-4]"
+        );
+        assert_eq!(
+            HostExpr::difference(&hostlist, &other)?.to_expression()?,
+            "login1,n[1-2]"
+        );
+
+        // Iterating over every host empties the iterator, not the set
+        hostlist.by_ref().for_each(drop);
+        assert!(hostlist.is_empty());
+        assert!(!hostlist.has_no_hosts());
+        assert_eq!(hostlist.host_count(), 5);
+
+        Ok(())
+    }
+}
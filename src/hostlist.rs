@@ -1,13 +1,29 @@
 use core::fmt;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, BinaryHeap, HashMap, HashSet};
 use std::iter::FusedIterator;
+use std::ops::{Add, BitAnd, BitOr, BitXor, Sub};
 use std::str::FromStr;
 
 use pest::Parser;
+use pest::iterators::Pairs;
 use pest_derive::Parser;
 
+use crate::NaturalPart;
+use crate::collapse_elems;
+use crate::digitblocks;
 use crate::error::{Error, Result};
-use crate::hostlistelem::{Component, HostlistElem};
+use crate::hostlistelem::{Component, Cursor, Fingerprint, HostPart, HostlistElem};
+use crate::lazy::LazyHostlist;
+use crate::natural_key;
+use crate::options::{CollapseOptions, FoldAxis, ParseOptions, braces_to_brackets};
+use crate::padding::Padding;
+use crate::range::Range;
+use crate::split::Split;
+use crate::suggest::suggest;
+use crate::warning::{ParseOutcome, Warning};
 
 #[derive(Parser)]
 #[grammar = "src/hostlist.pest"]
@@ -34,58 +50,203 @@ pub struct HostlistParser;
 /// ```
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Hostlist {
-    hostlist_elems: Vec<HostlistElem>,
+    pub(crate) hostlist_elems: Vec<HostlistElem>,
 }
 
 impl fmt::Display for Hostlist {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let joined = self
-            .hostlist_elems
-            .iter()
-            .map(std::string::ToString::to_string)
-            .collect::<Vec<_>>()
-            .join(",");
+        let joined = self.iter_expressions().collect::<Vec<_>>().join(",");
 
         f.write_str(&joined)
     }
 }
 
 impl Hostlist {
-    /// Constructs a new `Hostlist` from a hostlist expression
+    /// Constructs a new `Hostlist` from a hostlist expression. Each host is only included once,
+    /// even if several elements describe it (ex: "node1,node[1-3]" has 3 hosts). Zero-padding is
+    /// kept as written in both the hosts and `to_string()` (ex: "node[04-06]" is "node04" to
+    /// "node06"), see `new_with_padding()` to drop it.
     ///
     /// # Errors
     /// Will return `Err` if there are issues parsing the provided expression.
     pub fn new(expr: &str) -> Result<Self> {
-        let mut hostlist_elems_by_fingerprint = HashMap::new();
-        let pairs = HostlistParser::parse(Rule::hostlist, expr)?;
+        Self::parse(expr, &mut Vec::new())
+    }
+
+    /// Constructs a new `Hostlist` from a hostlist expression, along with warnings about anything
+    /// that was silently normalized (ex: the duplicate hosts in "node[1-5,3-7]")
+    ///
+    /// # Errors
+    /// Will return `Err` if there are issues parsing the provided expression.
+    /// ```
+    /// use hostlist_iter::{Hostlist, Warning};
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let outcome = Hostlist::parse_with_warnings("node[1-5,3-7]")?;
+    ///   assert_eq!(outcome.hostlist.to_string(), "node[1-7]");
+    ///   assert_eq!(
+    ///     outcome.warnings[0],
+    ///     Warning::DuplicatesRemoved { element: "node[1-5,3-7]".into(), count: 3 }
+    ///   );
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn parse_with_warnings(expr: &str) -> Result<ParseOutcome> {
+        let mut warnings = Vec::new();
+        let hostlist = Self::parse(expr, &mut warnings)?;
+        Ok(ParseOutcome { hostlist, warnings })
+    }
+
+    /// Returns each element of a hostlist expression in its canonical form (ex: "node[1-3,5]" for
+    /// "node[5,1-3]"), in input order, without combining or sorting the elements like `new()`
+    ///
+    /// # Errors
+    /// Will return `Err` if there are issues parsing the provided expression.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let elems = Hostlist::normalize_elements("node[5,1-3], login1, node[8]")?;
+    ///   assert_eq!(elems, vec!["node[1-3,5]", "login1", "node[8]"]);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn normalize_elements(expr: &str) -> Result<Vec<String>> {
+        let elems = Self::elems_from_pairs(Self::parse_pairs(expr)?, &mut Vec::new(), true)?;
+        Ok(elems.iter().map(ToString::to_string).collect())
+    }
+
+    fn parse(expr: &str, warnings: &mut Vec<Warning>) -> Result<Self> {
+        Self::from_pairs(Self::parse_pairs(expr)?, warnings)
+    }
+
+    /// Parses a hostlist expression, suggesting a correction if it's invalid
+    fn parse_pairs(expr: &str) -> Result<Pairs<'_, Rule>> {
+        HostlistParser::parse(Rule::hostlist, expr).map_err(|e| {
+            let err = Error::from(e);
+            match suggest(expr) {
+                Some(suggestion) => Error::Suggestion {
+                    suggestion,
+                    source: Box::new(err),
+                },
+                None => err,
+            }
+        })
+    }
+
+    /// Constructs a `Hostlist` from the `hostlist_elem` pairs produced by `HostlistParser`
+    pub(crate) fn from_pairs(pairs: Pairs<'_, Rule>, warnings: &mut Vec<Warning>) -> Result<Self> {
+        let elems = Self::elems_from_pairs(pairs, warnings, true)?;
+        Self::from_elems(elems, warnings)
+    }
 
+    /// Constructs an element from each of the `hostlist_elem` pairs produced by `HostlistParser`,
+    /// applying any set operators between them as they're reached
+    fn elems_from_pairs(
+        pairs: Pairs<'_, Rule>,
+        warnings: &mut Vec<Warning>,
+        keep_padding: bool,
+    ) -> Result<Vec<HostlistElem>> {
+        let mut elems = Vec::new();
+        let mut set_op = None;
+        let mut index = 0;
         for hostlist in pairs {
             match hostlist.as_rule() {
-                Rule::hostlist_elem => {
-                    let elem = HostlistElem::new(hostlist)?;
-                    let fingerprint = elem.fingerprint();
-                    hostlist_elems_by_fingerprint
-                        .entry(fingerprint)
-                        .or_insert_with(Vec::new)
-                        .push(elem);
+                Rule::hostlist_elem | Rule::extended_hostlist_elem => {
+                    let span = hostlist.as_span();
+                    let parsed =
+                        HostlistElem::new(hostlist, warnings, keep_padding).map_err(|e| {
+                            Error::InElement {
+                                element: span.as_str().to_string(),
+                                index,
+                                offset: span.start(),
+                                source: Box::new(e),
+                            }
+                        })?;
+                    index += 1;
+                    match set_op.take() {
+                        Some(op) => elems = Self::apply_set_op(op, elems, parsed, warnings)?,
+                        None => elems.extend(parsed),
+                    }
                 }
+                rule @ (Rule::difference_op | Rule::intersection_op) => set_op = Some(rule),
                 Rule::EOI => break,
                 rule => return Err(Error::UnexpectedParserState(rule)),
             }
         }
 
+        Ok(elems)
+    }
+
+    /// Applies the set operator `op` to the elements before it and the element after it
+    fn apply_set_op(
+        op: Rule,
+        elems: Vec<HostlistElem>,
+        operand: Vec<HostlistElem>,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<Vec<HostlistElem>> {
+        let hostlist = Self::from_elems(elems, warnings)?;
+        let operand = Self::from_elems(operand, &mut Vec::new())?;
+        let result = match op {
+            Rule::difference_op => hostlist.difference(&operand)?,
+            Rule::intersection_op => hostlist.intersection(&operand)?,
+            rule => return Err(Error::UnexpectedParserState(rule)),
+        };
+        Ok(result.hostlist_elems)
+    }
+
+    /// Constructs a `Hostlist` from its elements, combining elements where possible
+    pub(crate) fn from_elems(
+        elems: Vec<HostlistElem>,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<Self> {
+        // Grouped in order of first appearance, so warnings come out in a predictable order
+        let mut group_by_fingerprint = HashMap::new();
+        let mut hostlist_elems_by_fingerprint: Vec<(Fingerprint, Vec<HostlistElem>)> = Vec::new();
+        // Transformations can leave elements with no hosts, which have no expression
+        for elem in elems.into_iter().filter(|elem| elem.len() > 0) {
+            let fingerprint = elem.fingerprint();
+            let group = *group_by_fingerprint
+                .entry(fingerprint.clone())
+                .or_insert_with(|| {
+                    hostlist_elems_by_fingerprint.push((fingerprint, Vec::new()));
+                    hostlist_elems_by_fingerprint.len() - 1
+                });
+            hostlist_elems_by_fingerprint[group].1.push(elem);
+        }
+
         // Combine any hostlists that:
         //   a) have the same fingerprint
-        //   b) have only 1 range component (for simplicity)
+        //   b) have only 1 range component, or differ in only 1 of their ranges
         let mut hostlist_elems: Vec<HostlistElem> = Vec::new();
         for (fingerprint, elems) in hostlist_elems_by_fingerprint {
-            if fingerprint.count_ranges() != 1 || elems.len() == 1 {
-                // We don't (currently) support merging hostlist elements with multiple ranges.
-                // So if there are, or if there's only one entry, just add them/it and move on.
+            if fingerprint.count_ranges() == 0 || elems.len() == 1 {
                 hostlist_elems.extend(elems);
                 continue;
             }
+            if fingerprint.count_ranges() > 1 {
+                for (combined_elem, count, written_len) in merge_multi_range(elems)? {
+                    if count > 1 {
+                        let combined = combined_elem.to_string();
+                        if written_len > combined_elem.len() {
+                            warnings.push(Warning::DuplicatesRemoved {
+                                element: combined.clone(),
+                                count: written_len - combined_elem.len(),
+                            });
+                        }
+                        warnings.push(Warning::ElementsCombined { combined, count });
+                    }
+                    hostlist_elems.push(combined_elem);
+                }
+                continue;
+            }
 
+            let count = elems.len();
+            let written_len = elems
+                .iter()
+                .fold(0, |len: usize, elem| len.saturating_add(elem.len()));
             let mut elems_iter = elems.into_iter();
             let mut combined_elem = elems_iter.next().ok_or(Error::Internal(
                 "no next value when combining ranges".to_string(),
@@ -108,9 +269,20 @@ impl Hostlist {
                 combined_elem.update_len()?;
             }
 
+            let combined = combined_elem.to_string();
+            if written_len > combined_elem.len() {
+                warnings.push(Warning::DuplicatesRemoved {
+                    element: combined.clone(),
+                    count: written_len - combined_elem.len(),
+                });
+            }
+            warnings.push(Warning::ElementsCombined { combined, count });
+
             hostlist_elems.push(combined_elem);
         }
 
+        let mut hostlist_elems = remove_duplicates(hostlist_elems, warnings)?;
+
         // Check for overflow
         let mut len: usize = 0;
         for elem in &hostlist_elems {
@@ -122,302 +294,4166 @@ impl Hostlist {
         Ok(Self { hostlist_elems })
     }
 
-    /// Returns whether the hostlist is empty
-    #[must_use]
-    pub fn is_empty(&self) -> bool {
-        self.hostlist_elems.iter().all(|e| e.len() == 0)
-    }
-
-    /// Returns the number of hosts in the hostlist
-    pub fn len(&self) -> usize {
-        self.hostlist_elems.iter().map(HostlistElem::len).sum()
+    /// Constructs a new `Hostlist` from a hostlist expression, like `new()`, but treats an
+    /// expression with no hosts (ex: "" or "  ") as an error rather than an empty hostlist. Empty
+    /// elements (ex: "a,,b" or "a,") are always an error.
+    ///
+    /// # Errors
+    /// Will return `Err` if there are issues parsing the provided expression, or it's empty.
+    pub fn new_strict(expr: &str) -> Result<Self> {
+        let hostlist = Self::new(expr)?;
+        if hostlist.is_empty() {
+            return Err(Error::EmptyHostlist);
+        }
+        Ok(hostlist)
     }
 
-    #[must_use]
-    pub fn iter(&self) -> Self {
-        Self {
-            hostlist_elems: self.hostlist_elems.clone(),
+    /// Constructs a new `Hostlist` from a hostlist expression, handling zero-padded numbers as
+    /// given by `padding`. Numbers with different padding are different hosts, so "n[1],n[01]"
+    /// kept as written is two hosts.
+    ///
+    /// # Errors
+    /// Will return `Err` if there are issues parsing the provided expression.
+    /// ```
+    /// use hostlist_iter::{Hostlist, Padding};
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new_with_padding("node[098-101]", Padding::Keep)?;
+    ///   assert_eq!(hostlist.to_string(), "node[098-101]");
+    ///   assert_eq!(hostlist.to_vec(), vec!["node098", "node099", "node100", "node101"]);
+    ///
+    ///   let hostlist = Hostlist::new_with_padding("node[1-64]", Padding::Width(3))?;
+    ///   assert_eq!(hostlist.to_string(), "node[001-064]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn new_with_padding(expr: &str, padding: Padding) -> Result<Self> {
+        let mut warnings = Vec::new();
+        let pairs = Self::parse_pairs(expr)?;
+        let elems = Self::elems_from_pairs(pairs, &mut warnings, padding == Padding::Keep)?;
+        let hostlist = Self::from_elems(elems, &mut warnings)?;
+        match padding {
+            Padding::Width(width) => hostlist.pad_to(width),
+            Padding::Keep | Padding::Strip => Ok(hostlist),
         }
     }
-}
 
-impl FromStr for Hostlist {
-    type Err = Error;
+    /// Returns a copy of the hostlist with every range rendered with at least `width` digits, both
+    /// when expanding and in its expression. Numbers outside of brackets are left alone, and a
+    /// width of 0 removes any padding.
+    ///
+    /// # Errors
+    /// Will return `Err` if the result has more hosts than fit in a `usize`.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("rack[1-2]-node[8-10]")?.pad_to(2)?;
+    ///   assert_eq!(hostlist.to_string(), "rack[01-02]-node[08-10]");
+    ///   assert_eq!(hostlist.to_vec()[..2], ["rack01-node08", "rack01-node09"]);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn pad_to(&self, width: usize) -> Result<Self> {
+        let elems = self
+            .hostlist_elems
+            .iter()
+            .map(|elem| elem.with_width(width))
+            .collect::<Result<_>>()?;
 
-    fn from_str(s: &str) -> Result<Self> {
-        Self::new(s)
+        // Elements that only differed in padding may now be combined
+        Self::from_elems(elems, &mut Vec::new())
     }
-}
 
-impl Iterator for Hostlist {
-    type Item = String;
+    /// Returns a copy of the hostlist with every number rendered with `width` digits, zero-padded
+    /// if it's shorter. Unlike `pad_to()`, numbers outside of brackets are padded too, so that
+    /// host names migrate to a padded naming scheme (ex: "n1,n2" becomes "n[001-002]" with a
+    /// width of 3). Digits that start a host name are left alone. Use
+    /// `collapse_hosts_with_padding(hosts, Padding::Width(width))` to do the same while collapsing.
+    ///
+    /// # Errors
+    /// Will return `Err` if the result has more hosts than fit in a `usize`.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("n[1-3],r2n7")?.apply_padding(3)?;
+    ///   assert_eq!(hostlist.to_string(), "n[001-003],r[002]n[007]");
+    ///   assert_eq!(hostlist.to_vec(), ["n001", "n002", "n003", "r002n007"]);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn apply_padding(&self, width: usize) -> Result<Self> {
+        let elems = self
+            .hostlist_elems
+            .iter()
+            .map(|elem| elem.with_number_width(width))
+            .collect::<Result<_>>()?;
 
-    /// Returns the next host in the hostlist
-    fn next(&mut self) -> Option<Self::Item> {
-        self.hostlist_elems.iter_mut().find_map(Iterator::next)
+        Self::from_elems(elems, &mut Vec::new())
     }
-}
 
-// This enables:
-//   `for e in &range { ... }`
-//   `for e in range.into_iter() { ... }`
-impl IntoIterator for &Hostlist {
-    type Item = String;
-    type IntoIter = Hostlist;
+    /// Constructs a new `Hostlist` from a hostlist expression, lowercasing host names so that
+    /// elements differing only in case (ex: "Node[1-2],node[3-4]") are combined
+    ///
+    /// # Errors
+    /// Will return `Err` if there are issues parsing the provided expression.
+    pub fn new_ignore_case(expr: &str) -> Result<Self> {
+        // Host names are ASCII-only, so ASCII lowercasing covers every valid expression
+        Self::new(&expr.to_ascii_lowercase())
+    }
 
-    fn into_iter(self) -> Self::IntoIter {
-        Hostlist {
-            hostlist_elems: self.hostlist_elems.clone(),
-        }
+    /// Constructs a new `Hostlist` from a hostlist expression, accepting the host names allowed by
+    /// `options`
+    ///
+    /// # Errors
+    /// Will return `Err` if there are issues parsing the provided expression.
+    /// ```
+    /// use hostlist_iter::{Hostlist, HostnameChars, ParseOptions};
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   assert!(Hostlist::new("db:primary[01-02]").is_err());
+    ///
+    ///   let options = ParseOptions::default().hostname_chars(HostnameChars::Extended);
+    ///   let hostlist = Hostlist::new_with_options("db:primary[01-02]", options)?;
+    ///   assert_eq!(hostlist.to_vec(), vec!["db:primary01", "db:primary02"]);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn new_with_options(expr: &str, options: ParseOptions) -> Result<Self> {
+        let expr = if options.braces {
+            Cow::Owned(braces_to_brackets(expr))
+        } else {
+            Cow::Borrowed(expr)
+        };
+        let pairs = match options.hostlist_rule() {
+            Rule::hostlist => Self::parse_pairs(&expr)?,
+            rule => HostlistParser::parse(rule, &expr)?,
+        };
+        Self::from_pairs(pairs, &mut Vec::new())
     }
-}
 
-// This trait guarantees that once the iterator returns None, it will always return None.
-// No additional methods needed, it's a marker trait.
-impl FusedIterator for Hostlist {}
+    /// Constructs a new `Hostlist` from a hostlist expression, like `new()`, but skipping empty
+    /// elements (ex: "a,,b" or a trailing comma) rather than treating them as an error
+    ///
+    /// # Errors
+    /// Will return `Err` if there are issues parsing the provided expression.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   assert!(Hostlist::new(" node[1-3],,gpu1,\n").is_err());
+    ///   assert_eq!(Hostlist::new_lenient(" node[1-3],,gpu1,\n")?.to_string(), "gpu1,node[1-3]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn new_lenient(expr: &str) -> Result<Self> {
+        Self::new_with_options(expr, ParseOptions::default().lenient(true))
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Constructs a new `Hostlist` from a hostlist expression whose elements are separated by
+    /// commas or whitespace (spaces, tabs, or newlines), like pdsh accepts (ex: "n[1-3] gpu1" or a
+    /// file with one element per line). `new()` accepts the same expressions, so this only makes
+    /// the intent clearer at the call site.
+    ///
+    /// # Errors
+    /// Will return `Err` if there are issues parsing the provided expression.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new_whitespace_separated("node[1-3] gpu1\ngpu2, login1\n")?;
+    ///   assert_eq!(hostlist.to_string(), "gpu1,gpu2,login1,node[1-3]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn new_whitespace_separated(expr: &str) -> Result<Self> {
+        Self::new(expr)
+    }
 
-    #[test]
-    fn test_hostlist_len1() {
-        let hostlist = Hostlist::new("node[1-1000000000]").unwrap();
-        assert_eq!(hostlist.len(), 1_000_000_000);
+    /// Constructs a new `Hostlist` from a list of host names, collapsing them like
+    /// `collapse_hosts()` without going through an expression string. Zero-padding is kept,
+    /// like `new()`.
+    ///
+    /// # Errors
+    /// Will return `Err` if any host name cannot be parsed, or there are more hosts than fit in a
+    /// `usize`.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::from_hosts(["node2", "node1", "node3", "login1"])?;
+    ///   assert_eq!(hostlist.len(), 4);
+    ///   assert!(hostlist.contains("node3"));
+    ///   assert_eq!(hostlist.to_string(), "login1,node[1-3]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn from_hosts(hosts: impl IntoIterator<Item = impl AsRef<str>>) -> Result<Self> {
+        let elems = collapse_elems(hosts, &CollapseOptions::default())?
+            .iter()
+            .map(|elem| elem.to_hostlist_elem(false))
+            .collect::<Result<Vec<_>>>()?;
+        Self::from_elems(elems, &mut Vec::new())
     }
 
-    #[test]
-    fn test_hostlist_len2() {
-        let hostlist = Hostlist::new("blah2,node[1-3,2-5],n[11-20],blah").unwrap();
-        assert_eq!(hostlist.len(), 17);
+    /// Adds host names, joining the ranges they fit like `push_host()`. Names that are empty or
+    /// only whitespace (ex: blank lines) are skipped. Iteration starts over from the first host.
+    ///
+    /// # Errors
+    /// Will return `Err` if any other host name cannot be parsed, or the result has more hosts than
+    /// fit in a `usize`. The hostlist is left as it was.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let mut hostlist = Hostlist::new("node[1-4]")?;
+    ///   hostlist.try_extend(["node5", "", "node6", "login1"])?;
+    ///   assert_eq!(hostlist.to_string(), "login1,node[1-6]");
+    ///   assert!(hostlist.try_extend(["node7", "node?"]).is_err());
+    ///   assert_eq!(hostlist.len(), 7);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn try_extend(&mut self, hosts: impl IntoIterator<Item = impl AsRef<str>>) -> Result<()> {
+        // Single hosts are added as ranges where that extends one (ex: "n9" onto "n[1-8]")
+        let fingerprints = self.fingerprints();
+        let elems = collapse_elems(non_blank(hosts), &CollapseOptions::default())?
+            .iter()
+            .map(|elem| {
+                let ranged = elem.to_hostlist_elem(true)?;
+                if fingerprints.contains(&ranged.fingerprint()) {
+                    Ok(ranged)
+                } else {
+                    elem.to_hostlist_elem(false)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        *self = self.union(&Self::from_elems(elems, &mut Vec::new())?)?;
+        Ok(())
     }
 
-    #[test]
-    fn test_hostlist_len_overflow() {
-        let inputs =
-            ["n[1-1000000][1-1000000][1-1000000][1-10],o[1-1000000][1-1000000][1-1000000][1-10]"];
+    /// Returns whether every host has been iterated over, or there were none, see
+    /// `HostExpr::has_no_hosts()` for whether there were none
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.hostlist_elems.iter().all(|e| e.len() == 0)
+    }
 
-        for input in inputs {
-            let result = Hostlist::new(input);
-            assert!(matches!(result, Err(Error::HostlistTooLarge)));
+    /// Returns the number of hosts not yet iterated over, like `ExactSizeIterator::len()`, see
+    /// `HostExpr::host_count()` for every host in the hostlist
+    pub fn len(&self) -> usize {
+        self.hostlist_elems.iter().map(HostlistElem::len).sum()
+    }
+
+    /// Returns the host at `index` among the hosts not yet iterated over, in iteration order,
+    /// working it out from the ranges' lengths rather than expanding the hosts before it (ex: to
+    /// map MPI ranks to hosts)
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[1-1000000]gpu[0-7],login1")?;
+    ///   assert_eq!(hostlist.get(0), Some("login1".to_string()));
+    ///   assert_eq!(hostlist.get(8_000_000), Some("node1000000gpu7".to_string()));
+    ///   assert_eq!(hostlist.get(8_000_001), None);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn get(&self, mut index: usize) -> Option<String> {
+        for elem in &self.hostlist_elems {
+            let remaining = elem.remaining();
+            if index < remaining.len() {
+                return elem.host_at(remaining.start + index);
+            }
+            index -= remaining.len();
         }
+        None
     }
 
-    #[test]
-    fn test_hostlist_combine_like_prefixes() {
-        let mut hostlist = Hostlist::new("node[1-3,2-5],node[2-7]").unwrap();
-        let expected = vec![
-            "node1", "node2", "node3", "node4", "node5", "node6", "node7",
-        ];
+    /// Returns the first host not yet iterated over, without changing or cloning the hostlist
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[1-1000000000],gpu[1-16]")?;
+    ///   assert_eq!(hostlist.first_host(), Some("gpu1".to_string()));
+    ///   assert_eq!(hostlist.last_host(), Some("node1000000000".to_string()));
+    ///   assert_eq!(hostlist.len(), 1_000_000_016);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn first_host(&self) -> Option<String> {
+        self.iter().next()
+    }
 
-        assert_eq!(hostlist.len(), 7);
-        for e in expected {
-            assert_eq!(hostlist.next(), Some(e.into()));
+    /// Returns the last host not yet iterated over, without changing or cloning the hostlist.
+    /// Unlike `last()`, which is `Iterator::last()`, this doesn't consume the hostlist.
+    #[must_use]
+    pub fn last_host(&self) -> Option<String> {
+        self.iter().next_back()
+    }
+
+    /// Returns the index of `host` among the hosts not yet iterated over, in iteration order, the
+    /// reverse of `get()`. It's worked out from the ranges' lengths rather than by iterating, so
+    /// it's a cheap way to give each host a stable slot number.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[1-1000000]gpu[0-7],login1")?;
+    ///   assert_eq!(hostlist.position_of("login1"), Some(0));
+    ///   assert_eq!(hostlist.position_of("node1000000gpu7"), Some(8_000_000));
+    ///   assert_eq!(hostlist.position_of("node1gpu8"), None);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn position_of(&self, host: &str) -> Option<usize> {
+        let mut offset = 0;
+        for elem in &self.hostlist_elems {
+            let remaining = elem.remaining();
+            if let Some(index) = elem.index_of(host)
+                && remaining.contains(&index)
+            {
+                return Some(offset + index - remaining.start);
+            }
+            offset += remaining.len();
         }
-        assert_eq!(hostlist.len(), 0);
+        None
     }
 
-    #[test]
-    fn test_hostlist_fromstr() {
-        let hostlist: Hostlist = "node[1-3]".parse().unwrap();
-        let expected = vec!["node1", "node2", "node3"];
+    /// Returns the number of comma-separated elements in the hostlist, after combining elements
+    /// that differ only in their range (ex: 2 for "node[1-3],node[5],gpu[1-4]")
+    #[must_use]
+    pub fn element_count(&self) -> usize {
+        self.hostlist_elems.len()
+    }
 
-        let result: Vec<String> = hostlist.into_iter().collect();
-        assert_eq!(expected, result);
+    /// Returns the number of hosts in each element of the hostlist, in the same order as
+    /// `iter_expressions()`
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("n[1-512]m[1-3],login1")?;
+    ///   assert_eq!(hostlist.element_count(), 2);
+    ///   assert_eq!(hostlist.element_lens(), vec![1, 1536]);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn element_lens(&self) -> Vec<usize> {
+        self.hostlist_elems.iter().map(HostlistElem::len).collect()
     }
 
-    #[test]
-    fn test_hostlist_iter1() {
-        let mut hostlist: Hostlist = "n[1-5]".parse().unwrap();
-        let expected = ["n1", "n2", "n3", "n4", "n5"];
+    /// Returns the structural shape of each element of the hostlist, in the same order as
+    /// `iter_expressions()`. Elements with the same fingerprint differ only in their range values,
+    /// so hosts from them can be collapsed together.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let a = Hostlist::new("node[1-3]-ib,login1")?;
+    ///   let b = Hostlist::new("node[7-9]-ib")?;
+    ///   let shapes: Vec<String> = a.fingerprints().iter().map(ToString::to_string).collect();
+    ///   assert_eq!(shapes, vec!["login1", "node[]-ib"]);
+    ///   assert_eq!(a.fingerprints()[1], b.fingerprints()[0]);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn fingerprints(&self) -> Vec<Fingerprint> {
+        self.hostlist_elems
+            .iter()
+            .map(HostlistElem::fingerprint)
+            .collect()
+    }
 
-        for e in expected {
-            assert_eq!(hostlist.next(), Some(e.into()));
-        }
-        assert_eq!(hostlist.next(), None);
-        assert_eq!(hostlist.len(), 0);
+    /// Returns the total number of bytes in the names of all the hosts in the hostlist, computed
+    /// from the elements without expanding them, so buffers can be allocated up front. This counts
+    /// hosts already iterated over, and doesn't include separators (add `len()` for one byte per
+    /// host).
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[8-10],login")?;
+    ///   // "node8" + "node9" + "node10" + "login"
+    ///   assert_eq!(hostlist.estimated_output_bytes(), 5 + 5 + 6 + 5);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn estimated_output_bytes(&self) -> usize {
+        self.hostlist_elems
+            .iter()
+            .map(HostlistElem::rendered_bytes)
+            .fold(0, usize::saturating_add)
     }
 
-    #[test]
-    fn test_hostlist_iter2() {
-        let mut hostlist: Hostlist = "n[1-3]s".parse().unwrap();
-        let expected = ["n1s", "n2s", "n3s"];
+    /// Returns the approximate number of bytes used by the hostlist, including its heap
+    /// allocations, for monitoring long-lived caches of parsed hostlists
+    #[must_use]
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Self>()
+            + self.hostlist_elems.capacity() * size_of::<HostlistElem>()
+            + self
+                .hostlist_elems
+                .iter()
+                .map(HostlistElem::heap_size)
+                .sum::<usize>()
+    }
 
-        for e in expected {
-            assert_eq!(hostlist.next(), Some(e.into()));
+    /// Releases spare capacity left over from parsing and combining elements
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let mut hostlist = Hostlist::new("node[1-3],node[5-7],node[9]")?;
+    ///   let before = hostlist.memory_usage();
+    ///   hostlist.shrink_to_fit();
+    ///   assert!(hostlist.memory_usage() <= before);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.hostlist_elems.shrink_to_fit();
+        for elem in &mut self.hostlist_elems {
+            elem.shrink_to_fit();
         }
-        assert_eq!(hostlist.next(), None);
-        assert_eq!(hostlist.len(), 0);
     }
 
-    #[test]
-    fn test_hostlist_iter3() {
-        let mut hostlist: Hostlist = "n[1-3]s[1-2]".parse().unwrap();
-        let expected = ["n1s1", "n1s2", "n2s1", "n2s2", "n3s1", "n3s2"];
+    /// Combines elements into as few as possible: elements with the same fingerprint that differ
+    /// in only one range are combined, even if they have several ranges (ex: "r[1]n[1-2]" and
+    /// "r[1]n[3-4]" as "r[1]n[1-4]"), and hosts described by more than one element are kept once.
+    /// `new()` and the set operations already return hostlists in this form, so this is only
+    /// needed to start iteration over from the first host.
+    ///
+    /// # Errors
+    /// Will return `Err` if there's an internal error combining elements.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let mut hostlist = Hostlist::new("r[1]n[1-2],r[1]n[3-4],r[2]n[1-4]")?;
+    ///   assert_eq!(hostlist.to_string(), "r[1-2]n[1-4]");
+    ///   hostlist.next();
+    ///   hostlist.canonicalize()?;
+    ///   assert_eq!(hostlist.next(), Some("r1n1".to_string()));
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn canonicalize(&mut self) -> Result<()> {
+        let elems = self
+            .hostlist_elems
+            .iter()
+            .map(HostlistElem::restarted)
+            .collect::<Result<Vec<_>>>()?;
+        *self = Self::from_elems(elems, &mut Vec::new())?;
+        Ok(())
+    }
 
-        for e in expected {
-            assert_eq!(hostlist.next(), Some(e.into()));
-        }
-        assert_eq!(hostlist.next(), None);
-        assert_eq!(hostlist.len(), 0);
+    /// Returns the distinct leading literal parts of the hostlist's elements (ex: "node" in
+    /// "node[1-3]")
+    #[must_use]
+    pub fn prefixes(&self) -> BTreeSet<&str> {
+        self.hostlist_elems
+            .iter()
+            .filter_map(|elem| match elem.components.first() {
+                Some(Component::Static(s)) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect()
     }
 
-    #[test]
-    fn test_hostlist_iter4() -> Result<()> {
-        let mut hostlist: Hostlist = "n[1-5]".parse()?;
-        let expected_orig = vec!["n2", "n3", "n4", "n5"];
+    /// Returns the number of bracketed ranges in the hostlist (ex: 2 for "n[1-2]m[1-3]")
+    #[must_use]
+    pub fn range_count(&self) -> usize {
+        self.hostlist_elems
+            .iter()
+            .flat_map(|elem| &elem.components)
+            .filter(|component| matches!(component, Component::Range(_)))
+            .count()
+    }
 
-        assert_eq!(hostlist.len(), 5);
-        assert_eq!(hostlist.next(), Some("n1".to_string()));
-        assert_eq!(hostlist.len(), 4);
+    /// Appends the next host in the hostlist to `buf`, returning `false` once every host has been
+    /// produced, for `next()`
+    fn next_into(&mut self, buf: &mut String) -> bool {
+        self.hostlist_elems
+            .iter_mut()
+            .any(|elem| elem.write_next(buf))
+    }
 
-        let mut expected = expected_orig.clone();
-        for host in &hostlist {
-            let e = expected.remove(0);
-            assert_eq!(e, host);
+    /// Skips the next `n` hosts (or every remaining host, if there are fewer), without iterating
+    /// through them, so iteration can resume at an offset into a huge hostlist
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let mut hostlist = Hostlist::new("node[1-1000000000]")?;
+    ///   hostlist.skip_hosts(999_999_998);
+    ///   assert_eq!(hostlist.next(), Some("node999999999".to_string()));
+    ///   assert_eq!(hostlist.len(), 1);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn skip_hosts(&mut self, n: usize) {
+        if let Some(n) = n.checked_sub(1) {
+            self.nth(n);
         }
-        assert!(expected.is_empty());
-        assert_eq!(hostlist.len(), 4);
-
-        Ok(())
+    }
+
+    /// Returns an iterator over the parts of each host not yet iterated over, in the same order as
+    /// iterating over the hostlist itself, so numbers can be read without parsing host names
+    /// ```
+    /// use hostlist_iter::{HostPart, Hostlist};
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("rack[1-2]-node[7-8]")?;
+    ///   let (racks, nodes): (Vec<u64>, Vec<u64>) = hostlist
+    ///     .iter_parts()
+    ///     .map(|parts| match parts[..] {
+    ///       [_, HostPart::Number { value: rack, .. }, _, HostPart::Number { value: node, .. }] => {
+    ///         (rack, node)
+    ///       }
+    ///       _ => unreachable!(),
+    ///     })
+    ///     .unzip();
+    ///   assert_eq!(racks, vec![1, 1, 2, 2]);
+    ///   assert_eq!(nodes, vec![7, 8, 7, 8]);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn iter_parts(&self) -> PartsIter {
+        PartsIter {
+            hostlist_elems: self.hostlist_elems.clone(),
+        }
+    }
+
+    /// Starts a chain of lazy transformations of the hostlist, see `LazyHostlist`. The chain
+    /// starts from every host in the hostlist, even if some have already been iterated over.
+    #[must_use]
+    pub fn lazy<'a>(&self) -> LazyHostlist<'a> {
+        LazyHostlist::new(self.hostlist_elems.clone())
+    }
+
+    /// Splits the hostlist into hostlists of `size` hosts each, in iteration order, with fewer
+    /// hosts in the last one if they don't divide evenly. Each chunk is folded like any other
+    /// hostlist (ex: batches for a rolling restart, each passed to pdsh as an expression). The
+    /// chunks cover every host in the hostlist, even if some have already been iterated over.
+    ///
+    /// # Errors
+    /// Will return `Err` if `size` is 0, or if a chunk can't be constructed, which shouldn't happen
+    /// since each chunk is smaller than the hostlist.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[1-120]")?;
+    ///   let batches: Vec<String> = hostlist.chunks(50)?.iter().map(ToString::to_string).collect();
+    ///   assert_eq!(batches, ["node[1-50]", "node[51-100]", "node[101-120]"]);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn chunks(&self, size: usize) -> Result<Vec<Self>> {
+        if size == 0 {
+            return Err(Error::InvalidArgument(
+                "chunk size must be non-zero".to_string(),
+            ));
+        }
+
+        let elems = self.restarted_elems()?;
+        let count = elems
+            .iter()
+            .map(HostlistElem::len)
+            .sum::<usize>()
+            .div_ceil(size);
+        Self::slices(&elems, std::iter::repeat_n(size, count))
+    }
+
+    /// Splits the hostlist into `n` hostlists whose sizes differ by at most one host, assigning
+    /// hosts in iteration order either as runs of consecutive hosts or in turn, see `Split`.
+    /// There are always `n` hostlists, so some are empty if there are fewer than `n` hosts. Like
+    /// `chunks()`, this covers every host in the hostlist, even if some have already been
+    /// iterated over.
+    ///
+    /// # Errors
+    /// Will return `Err` if `n` is 0, or if a group can't be constructed, which shouldn't happen
+    /// since each group is smaller than the hostlist.
+    /// ```
+    /// use hostlist_iter::{Hostlist, Split};
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[1-10]")?;
+    ///   let groups = hostlist.split_into(3, Split::Contiguous)?;
+    ///   let groups: Vec<String> = groups.iter().map(ToString::to_string).collect();
+    ///   assert_eq!(groups, ["node[1-4]", "node[5-7]", "node[8-10]"]);
+    ///
+    ///   let groups = hostlist.split_into(3, Split::RoundRobin)?;
+    ///   let groups: Vec<String> = groups.iter().map(ToString::to_string).collect();
+    ///   assert_eq!(groups, ["node[1-10/3]", "node[2-8/3]", "node[3-9/3]"]);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn split_into(&self, n: usize, split: Split) -> Result<Vec<Self>> {
+        if n == 0 {
+            return Err(Error::InvalidArgument(
+                "number of groups must be non-zero".to_string(),
+            ));
+        }
+
+        let elems = self.restarted_elems()?;
+        match split {
+            Split::Contiguous => {
+                let total: usize = elems.iter().map(HostlistElem::len).sum();
+                let (size, extra) = (total / n, total % n);
+                Self::slices(&elems, (0..n).map(|i| size + usize::from(i < extra)))
+            }
+            Split::RoundRobin => {
+                let mut groups = vec![Vec::new(); n];
+                let mut group = 0;
+                for elem in &elems {
+                    for (offset, dealt) in Self::deal(elem, n)? {
+                        groups[(group + offset) % n].push(dealt);
+                    }
+                    group = (group + elem.len()) % n;
+                }
+                groups
+                    .into_iter()
+                    .map(|elems| Self::from_elems(elems, &mut Vec::new()))
+                    .collect()
+            }
+        }
+    }
+
+    /// Deals the hosts of `elem` out in turn to `n` groups, returning each part along with the
+    /// group it goes to, counting from the group that gets the element's first host. Each part is
+    /// every `n`th value of the element's last range (ex: "n[1-10/3]"), so hosts next to each other
+    /// don't each need an element of their own. The element is dealt a row of its last range at a
+    /// time, or all at once if `n` divides the row evenly and so every row is dealt the same way.
+    fn deal(elem: &HostlistElem, n: usize) -> Result<Vec<(usize, HostlistElem)>> {
+        let Some((last, Component::Range(range))) = elem
+            .components
+            .iter()
+            .enumerate()
+            .rfind(|(_, c)| matches!(c, Component::Range(_)))
+        else {
+            return Ok(vec![(0, elem.clone())]);
+        };
+
+        let row_len = range.total_len();
+        let rows = if row_len % n == 0 {
+            vec![elem.clone()]
+        } else {
+            (0..elem.len() / row_len.max(1))
+                .map(|row| elem.slice_hosts(row * row_len, row_len))
+                .collect::<Result<Vec<_>>>()?
+                .concat()
+        };
+
+        let mut dealt = Vec::new();
+        for (row, row_elem) in rows.iter().enumerate() {
+            let mut row_components = row_elem.components.clone();
+            let Component::Range(row_range) = &row_components[last] else {
+                return Err(Error::Internal("row lost its last range".to_string()));
+            };
+            let row_range = row_range.clone();
+            for offset in 0..n.min(row_len) {
+                row_components[last] = Component::Range(row_range.every_nth(offset, n)?);
+                dealt.push((
+                    row * row_len + offset,
+                    HostlistElem::from_components(row_components.clone())?,
+                ));
+            }
+        }
+
+        Ok(dealt)
+    }
+
+    /// Returns a copy of each element, starting iteration from the beginning
+    fn restarted_elems(&self) -> Result<Vec<HostlistElem>> {
+        self.hostlist_elems
+            .iter()
+            .map(HostlistElem::restarted)
+            .collect()
+    }
+
+    /// Returns a hostlist for each of `sizes`, taking that many hosts of `elems` in iteration
+    /// order, or fewer once every host has been taken
+    fn slices(elems: &[HostlistElem], sizes: impl IntoIterator<Item = usize>) -> Result<Vec<Self>> {
+        let mut slices = Vec::new();
+        let (mut i, mut start) = (0, 0);
+        for size in sizes {
+            let mut slice = Vec::new();
+            let mut room = size;
+            while room > 0
+                && let Some(elem) = elems.get(i)
+            {
+                let n = room.min(elem.len() - start);
+                slice.extend(elem.slice_hosts(start, n)?);
+                start += n;
+                room -= n;
+                if start == elem.len() {
+                    (i, start) = (i + 1, 0);
+                }
+            }
+            slices.push(Self::from_elems(slice, &mut Vec::new())?);
+        }
+
+        Ok(slices)
+    }
+
+    /// Returns an iterator over the hosts not yet iterated over in natural order across every
+    /// element, the order `sort_hosts()` gives: literal parts are compared as strings, and numbers
+    /// by value (ex: "n9" before "n10", wherever their elements are). Iteration itself goes
+    /// element by element.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("n[1-2]x,n[9-10],n[1-2][1-10]")?;
+    ///   let hosts: Vec<String> = hostlist.iter_sorted().collect();
+    ///   assert_eq!(hosts[..5], ["n1x", "n2x", "n9", "n10", "n11"]);
+    ///   assert_eq!(hosts[12..15], ["n19", "n21", "n22"]);
+    ///   assert_eq!(hosts.last().map(String::as_str), Some("n210"));
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn iter_sorted(&self) -> SortedIter<'_> {
+        SortedIter::new(&self.hostlist_elems)
+    }
+
+    /// Returns an iterator over the folded expression of each element of the hostlist (ex:
+    /// "node[1-3]" and "gpu[1-8]x[1-2]" for "node[1-3],gpu[1-8]x[1-2]")
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[1-3],gpu[1-8]x[1-2],node[5]")?;
+    ///   let exprs: Vec<String> = hostlist.iter_expressions().collect();
+    ///   assert_eq!(exprs, vec!["gpu[1-8]x[1-2]", "node[1-3,5]"]);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn iter_expressions(&self) -> impl Iterator<Item = String> + '_ {
+        self.hostlist_elems.iter().map(ToString::to_string)
+    }
+
+    /// Returns every host in the hostlist without consuming it, unlike `collect()`
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[1-3]")?;
+    ///   assert_eq!(hostlist.to_vec(), vec!["node1", "node2", "node3"]);
+    ///   assert_eq!(hostlist.len(), 3);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn to_vec(&self) -> Vec<String> {
+        let mut hosts = Vec::with_capacity(self.len());
+        hosts.extend(self.iter());
+        hosts
+    }
+
+    /// Returns whether `host` is in the hostlist, including hosts already iterated over. Hosts are
+    /// matched against each element without expanding it.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[1-100]-ib,login1")?;
+    ///   assert!(hostlist.contains("node42-ib"));
+    ///   assert!(!hostlist.contains("node042-ib"));
+    ///   assert!(!hostlist.contains("node101-ib"));
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn contains(&self, host: &str) -> bool {
+        self.hostlist_elems.iter().any(|elem| elem.contains(host))
+    }
+
+    /// Returns an anchored regular expression matching exactly the hosts in the hostlist, for
+    /// systems that filter by regex (ex: Prometheus relabeling). Only plain groups, alternations,
+    /// and character classes are used, so it works with most regex dialects. An empty hostlist
+    /// gives "^$", which matches no host names.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[1-16]")?;
+    ///   assert_eq!(hostlist.to_regex(), "^node([1-9]|1[0-6])$");
+    ///
+    ///   let hostlist = Hostlist::new("login1,gpu[08-10]")?;
+    ///   assert_eq!(hostlist.to_regex(), "^(gpu(0[89]|10)|login1)$");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn to_regex(&self) -> String {
+        let elems: Vec<String> = self
+            .hostlist_elems
+            .iter()
+            .map(HostlistElem::to_regex)
+            .collect();
+        match elems.as_slice() {
+            [] => "^$".to_string(),
+            [elem] => format!("^{elem}$"),
+            _ => format!("^({})$", elems.join("|")),
+        }
+    }
+
+    /// Returns a hostlist with the hosts in either hostlist
+    ///
+    /// # Errors
+    /// Will return `Err` if the result has more hosts than fit in a `usize`.
+    pub fn union(&self, other: &Self) -> Result<Self> {
+        // Elements with several ranges are only merged where they differ in one range, so only
+        // add the hosts that are new
+        let mut elems = self
+            .hostlist_elems
+            .iter()
+            .map(HostlistElem::restarted)
+            .collect::<Result<Vec<_>>>()?;
+        elems.extend(other.difference(self)?.hostlist_elems);
+
+        Self::from_elems(elems, &mut Vec::new())
+    }
+
+    /// Returns a hostlist with the hosts in any of `hostlists`. Elements are grouped by fingerprint
+    /// across all the hostlists at once, and the ranges of each group are merged in a single
+    /// sort, so this stays fast for thousands of hostlists where repeated `union()` calls wouldn't.
+    ///
+    /// # Errors
+    /// Will return `Err` if the result has more hosts than fit in a `usize`.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let windows = ["node[1-8]", "node[5-12],login1", "node[20]"].map(Hostlist::new);
+    ///   let hostlist = Hostlist::union_all(windows.into_iter().collect::<Result<Vec<_>, _>>()?)?;
+    ///   assert_eq!(hostlist.to_string(), "login1,node[1-12,20]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn union_all(hostlists: impl IntoIterator<Item = Self>) -> Result<Self> {
+        let mut group_by_fingerprint = HashMap::new();
+        let mut groups: Vec<(Fingerprint, Vec<HostlistElem>)> = Vec::new();
+        for hostlist in hostlists {
+            for elem in hostlist.hostlist_elems {
+                let fingerprint = elem.fingerprint();
+                let group = *group_by_fingerprint
+                    .entry(fingerprint.clone())
+                    .or_insert_with(|| {
+                        groups.push((fingerprint, Vec::new()));
+                        groups.len() - 1
+                    });
+                groups[group].1.push(elem);
+            }
+        }
+
+        let mut elems = Vec::new();
+        let mut hosts = Vec::new();
+        for (fingerprint, group) in groups {
+            match fingerprint.count_ranges() {
+                // Elements without ranges are a single host, the same for the whole group
+                0 => hosts.push(group[0].restarted()?),
+                1 => elems.push(merge_single_range(group)?),
+                // Overlapping elements with several ranges can't always be merged, so only add the
+                // hosts that are new
+                _ => {
+                    let mut merged: Vec<HostlistElem> = Vec::new();
+                    for elem in group {
+                        let mut pieces = vec![elem.restarted()?];
+                        for existing in &merged {
+                            let mut remaining = Vec::new();
+                            for piece in pieces {
+                                remaining.extend(piece.subtract(existing)?);
+                            }
+                            remaining.retain(|piece| piece.len() > 0);
+                            pieces = remaining;
+                        }
+                        merged.extend(pieces);
+                    }
+                    elems.extend(merged);
+                }
+            }
+        }
+        hosts.retain(|host| {
+            let host = host.to_string();
+            !elems.iter().any(|elem| elem.contains(&host))
+        });
+        elems.extend(hosts);
+
+        Self::from_elems(elems, &mut Vec::new())
+    }
+
+    /// Parses each of `exprs` and returns a hostlist with the hosts in any of them, see
+    /// `union_all()`
+    ///
+    /// # Errors
+    /// Will return `Err` if an expression can't be parsed, or the result has more hosts than fit
+    /// in a `usize`.
+    pub fn union_all_exprs(exprs: impl IntoIterator<Item = impl AsRef<str>>) -> Result<Self> {
+        let hostlists = exprs
+            .into_iter()
+            .map(|expr| Self::new(expr.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        Self::union_all(hostlists)
+    }
+
+    /// Returns a hostlist with the hosts in both hostlists. Like merging, this works element by
+    /// element, comparing numbers digit by digit however they're split into ranges (ex: "n12" is in
+    /// both "n[10-13]" and "n[1-5][2]"), so it never expands either hostlist.
+    ///
+    /// # Errors
+    /// Will return `Err` if the result has more hosts than fit in a `usize`.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let a = Hostlist::new("rack[1-4]-node[1-64]")?;
+    ///   let b = Hostlist::new("rack[3-8]-node[33-128]")?;
+    ///   assert_eq!(a.intersection(&b)?.to_string(), "rack[3-4]-node[33-64]");
+    ///   assert_eq!(a.difference(&b)?.len(), 4 * 64 - 2 * 32);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn intersection(&self, other: &Self) -> Result<Self> {
+        let mut elems = Vec::new();
+        for elem in &self.hostlist_elems {
+            if elem.fingerprint().count_ranges() == 0 {
+                if other.contains(&elem.to_string()) {
+                    elems.push(elem.restarted()?);
+                }
+                continue;
+            }
+            elems.extend(partition(&elem.restarted()?, &other.hostlist_elems)?.1);
+        }
+
+        Self::from_elems(elems, &mut Vec::new())
+    }
+
+    /// Returns a hostlist with the hosts in this hostlist that aren't in `other`, see
+    /// `intersection()`
+    ///
+    /// # Errors
+    /// Will return `Err` if the result has more hosts than fit in a `usize`.
+    pub fn difference(&self, other: &Self) -> Result<Self> {
+        let mut elems = Vec::new();
+        for elem in &self.hostlist_elems {
+            if elem.fingerprint().count_ranges() == 0 {
+                if !other.contains(&elem.to_string()) {
+                    elems.push(elem.restarted()?);
+                }
+                continue;
+            }
+            elems.extend(partition(&elem.restarted()?, &other.hostlist_elems)?.0);
+        }
+
+        Self::from_elems(elems, &mut Vec::new())
+    }
+
+    /// Returns the hosts in `universe` that aren't in this hostlist (ex: the nodes of a partition
+    /// that didn't report in), the same as `universe.difference(self)`
+    ///
+    /// # Errors
+    /// Will return `Err` if there's an internal error splitting an element.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let reported = Hostlist::new("node[1-3,5-4998,5000]")?;
+    ///   let missing = reported.complement_within(&Hostlist::new("node[1-5000]")?)?;
+    ///   assert_eq!(missing.to_string(), "node[4,4999]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn complement_within(&self, universe: &Self) -> Result<Self> {
+        universe.difference(self)
+    }
+
+    /// Returns the hosts missing from each element between the smallest and largest value of each
+    /// of its ranges (ex: "n[4-6]" for "n[1-3,7]"), to spot decommissioned or misnumbered nodes.
+    /// Elements without ranges have no gaps.
+    ///
+    /// # Errors
+    /// Will return `Err` if the gaps have more hosts than fit in a `usize`.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[1-3,7,9-12],gpu[1-4],login1")?;
+    ///   assert_eq!(hostlist.gaps()?.to_string(), "node[4-6,8]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn gaps(&self) -> Result<Self> {
+        let mut elems = Vec::new();
+        for elem in &self.hostlist_elems {
+            if elem.fingerprint().count_ranges() > 0 {
+                elems.extend(elem.span()?.subtract(elem)?);
+            }
+        }
+
+        Self::from_elems(elems, &mut Vec::new())
+    }
+
+    /// Removes the hosts in the hostlist expression `expr`, splitting ranges as needed (like pdsh's
+    /// `-x`). Iteration starts over from the first remaining host.
+    ///
+    /// # Errors
+    /// Will return `Err` if `expr` can't be parsed, in which case the hostlist is unchanged.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let mut hostlist = Hostlist::new("node[1-10],login1")?;
+    ///   hostlist.exclude_expr("node[3-4,8],login1")?;
+    ///   assert_eq!(hostlist.to_string(), "node[1-2,5-7,9-10]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn exclude_expr(&mut self, expr: &str) -> Result<()> {
+        *self = self.difference(&Self::new(expr)?)?;
+        Ok(())
+    }
+
+    /// Adds the elements of the hostlist expression `expr`, combining them with the existing
+    /// elements the same way `new()` does, as if the two expressions had been joined with a comma.
+    /// Iteration starts over from the first host.
+    ///
+    /// # Errors
+    /// Will return `Err` if `expr` can't be parsed or the result has more hosts than fit in a
+    /// `usize`, in which case the hostlist is unchanged.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let mut hostlist = Hostlist::new("node[1-4]")?;
+    ///   hostlist.push_expr("gpu[1-4],node[5-8]")?;
+    ///   hostlist.push_host("login1")?;
+    ///   assert_eq!(hostlist.to_string(), "gpu[1-4],login1,node[1-8]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn push_expr(&mut self, expr: &str) -> Result<()> {
+        let mut elems = self
+            .hostlist_elems
+            .iter()
+            .map(HostlistElem::restarted)
+            .collect::<Result<Vec<_>>>()?;
+        elems.extend(Self::elems_from_pairs(
+            Self::parse_pairs(expr)?,
+            &mut Vec::new(),
+            true,
+        )?);
+        *self = Self::from_elems(elems, &mut Vec::new())?;
+        Ok(())
+    }
+
+    /// Adds a single host, joining the range of an element it fits (ex: "n9" and "n[1-8]"), see
+    /// `push_expr()`
+    ///
+    /// # Errors
+    /// Will return `Err` if `host` isn't a single host name (ex: it's empty or has brackets), or
+    /// the result has more hosts than fit in a `usize`.
+    pub fn push_host(&mut self, host: &str) -> Result<()> {
+        let (prefix, number, suffix) = split_host(host)?;
+        // A host is a static element, which is never combined with a range, so add it as a range
+        // with a single value where that extends one (ex: "n9" onto "n[1-8]"). Padded numbers stay
+        // static, since a range would drop the padding.
+        if let Some(number) = number.filter(|n| n.len() == 1 || !n.starts_with('0')) {
+            let ranged = format!("{prefix}[{number}]{suffix}");
+            let fingerprints = self.fingerprints();
+            if Self::new(&ranged)?
+                .fingerprints()
+                .iter()
+                .all(|f| fingerprints.contains(f))
+            {
+                return self.push_expr(&ranged);
+            }
+        }
+        self.push_expr(host)
+    }
+
+    /// Removes a single host, splitting the range it's in if needed (ex: "n[1-3,5-9]" without
+    /// "n7"), and returns whether it was in the hostlist. Iteration starts over from the first
+    /// remaining host.
+    ///
+    /// # Errors
+    /// Will return `Err` if `host` isn't a single host name (ex: it's empty or has brackets).
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let mut hostlist = Hostlist::new("node[1-9]")?;
+    ///   assert!(hostlist.remove_host("node7")?);
+    ///   assert!(!hostlist.remove_host("node7")?);
+    ///   assert_eq!(hostlist.to_string(), "node[1-6,8-9]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn remove_host(&mut self, host: &str) -> Result<bool> {
+        split_host(host)?;
+        if !self.contains(host) {
+            return Ok(false);
+        }
+        *self = self.difference(&Self::new(host)?)?;
+        Ok(true)
+    }
+
+    /// Keeps only the hosts for which `f` returns `true`, splitting ranges around the hosts that
+    /// are dropped so the rest stay compact. Every host is checked, including hosts already
+    /// iterated over, and iteration starts over from the first remaining host.
+    ///
+    /// # Errors
+    /// Will return `Err` if there's an internal error splitting an element.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let down = ["node3", "node7"];
+    ///   let mut hostlist = Hostlist::new("node[1-10],login1")?;
+    ///   hostlist.retain(|host| !down.contains(&host))?;
+    ///   assert_eq!(hostlist.to_string(), "login1,node[1-2,4-6,8-10]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn retain(&mut self, mut f: impl FnMut(&str) -> bool) -> Result<()> {
+        let mut elems = Vec::new();
+        let mut host = String::new();
+        for elem in &self.hostlist_elems {
+            let mut hosts = elem.restarted()?;
+            let mut pieces = vec![elem.restarted()?];
+            loop {
+                host.clear();
+                if !hosts.write_next(&mut host) {
+                    break;
+                }
+                if f(&host) {
+                    continue;
+                }
+                let dropped = elem
+                    .reshape(&host)?
+                    .ok_or(Error::Internal(format!("host {host} not found in element")))?;
+                let mut remaining = Vec::new();
+                for piece in pieces {
+                    if piece.contains(&host) {
+                        remaining.extend(piece.subtract(&dropped)?);
+                    } else {
+                        remaining.push(piece);
+                    }
+                }
+                remaining.retain(|piece| piece.len() > 0);
+                pieces = remaining;
+            }
+            elems.extend(pieces);
+        }
+
+        *self = Self::from_elems(elems, &mut Vec::new())?;
+        Ok(())
+    }
+
+    /// Returns a hostlist with the hosts in exactly one of this hostlist and `other`, see
+    /// `intersection()`
+    ///
+    /// # Errors
+    /// Will return `Err` if the result has more hosts than fit in a `usize`.
+    pub fn symmetric_difference(&self, other: &Self) -> Result<Self> {
+        self.difference(other)?.union(&other.difference(self)?)
+    }
+
+    /// Returns whether this hostlist and `other` have the same hosts, however their elements are
+    /// written (ex: "node[1-2]" and "node2,node1"). `==` compares the elements themselves.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let a = Hostlist::new("node[1-2]")?;
+    ///   let b = Hostlist::new("node2,node1")?;
+    ///   assert_ne!(a, b);
+    ///   assert!(a.eq_hosts(&b));
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn eq_hosts(&self, other: &Self) -> bool {
+        // Neither difference can overflow, since each is smaller than a hostlist that fits
+        matches!(self.is_subset(other), Ok(true)) && matches!(other.is_subset(self), Ok(true))
+    }
+
+    /// Returns whether every host in this hostlist is also in `other`, comparing ranges rather
+    /// than expanding either hostlist
+    ///
+    /// # Errors
+    /// Will return `Err` if the hosts not in `other` don't fit in a `usize`.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let job = Hostlist::new("node[10-20]")?;
+    ///   let partition = Hostlist::new("node[1-100],login1")?;
+    ///   assert!(job.is_subset(&partition)?);
+    ///   assert!(partition.is_superset(&job)?);
+    ///   assert!(!partition.is_subset(&job)?);
+    ///   assert!(job.is_disjoint(&Hostlist::new("node[21-30]")?)?);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn is_subset(&self, other: &Self) -> Result<bool> {
+        Ok(self.difference(other)?.is_empty())
+    }
+
+    /// Returns whether every host in `other` is also in this hostlist, see `is_subset()`
+    ///
+    /// # Errors
+    /// Will return `Err` if the hosts not in this hostlist don't fit in a `usize`.
+    pub fn is_superset(&self, other: &Self) -> Result<bool> {
+        other.is_subset(self)
+    }
+
+    /// Returns whether this hostlist and `other` have no hosts in common, see `intersects()`
+    ///
+    /// # Errors
+    /// Will return `Err` if there's an internal error comparing elements.
+    pub fn is_disjoint(&self, other: &Self) -> Result<bool> {
+        Ok(!self.intersects(other)?)
+    }
+
+    /// Returns whether this hostlist and `other` have any host in common, stopping at the first
+    /// pair of elements that overlap rather than building the whole intersection. Always agrees
+    /// with `!intersection(other)?.is_empty()`.
+    ///
+    /// # Errors
+    /// Will return `Err` if there's an internal error comparing elements.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let job = Hostlist::new("node[1-64]")?;
+    ///   assert!(job.intersects(&Hostlist::new("node[60-90]")?)?);
+    ///   assert!(!job.intersects(&Hostlist::new("node[65-90],login1")?)?);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn intersects(&self, other: &Self) -> Result<bool> {
+        for elem in &self.hostlist_elems {
+            if elem.fingerprint().count_ranges() == 0 {
+                if other.contains(&elem.to_string()) {
+                    return Ok(true);
+                }
+                continue;
+            }
+            for other_elem in &other.hostlist_elems {
+                if digitblocks::split(elem, other_elem)?.is_some() {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Returns an iterator over the hosts not yet iterated over, borrowing the hostlist rather
+    /// than cloning it
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[1-3],login1")?;
+    ///   let hosts: Vec<String> = hostlist.iter().collect();
+    ///   assert_eq!(hosts, vec!["login1", "node1", "node2", "node3"]);
+    ///   assert_eq!(hostlist.iter().rev().next(), Some("node3".to_string()));
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn iter(&self) -> HostIter<'_> {
+        HostIter::new(&self.hostlist_elems)
+    }
+}
+
+/// Removes hosts found in more than one element, which merging by fingerprint doesn't catch (ex:
+/// "n1" and "n[1-3]", or "n[1-2]m[1-2]" and "n[2-3]m[2-3]"), or more than once in one element (ex:
+/// "n111" in "n[1-11][1-11]"). Single hosts are dropped in favor of the elements with ranges, and
+/// later elements with ranges are split around earlier ones.
+fn remove_duplicates(
+    elems: Vec<HostlistElem>,
+    warnings: &mut Vec<Warning>,
+) -> Result<Vec<HostlistElem>> {
+    // Elements can only share a host if one's leading text starts with the other's
+    let overlapping = |a: &HostlistElem, b: &HostlistElem| {
+        let (a, b) = (a.literal_prefix(), b.literal_prefix());
+        a.starts_with(b) || b.starts_with(a)
+    };
+
+    let mut kept: Vec<HostlistElem> = Vec::with_capacity(elems.len());
+    let (statics, ranged): (Vec<_>, Vec<_>) = elems
+        .into_iter()
+        .partition(|elem| elem.fingerprint().count_ranges() == 0);
+
+    for elem in ranged {
+        let distinct = digitblocks::distinct(&elem)?.unwrap_or_else(|| vec![elem.clone()]);
+        let mut pieces = Vec::with_capacity(distinct.len());
+        for piece in distinct {
+            let others = kept.iter().filter(|other| overlapping(&elem, other));
+            pieces.extend(partition(&piece, others)?.0);
+        }
+
+        let remaining_len = pieces.iter().map(HostlistElem::len).sum::<usize>();
+        if remaining_len < elem.len() {
+            warnings.push(Warning::DuplicatesRemoved {
+                element: elem.to_string(),
+                count: elem.len() - remaining_len,
+            });
+        }
+        kept.extend(pieces);
+    }
+
+    let ranged_len = kept.len();
+    let mut hosts = HashSet::new();
+    for elem in statics {
+        let host = elem.to_string();
+        let in_range = kept[..ranged_len]
+            .iter()
+            .any(|other| overlapping(&elem, other) && other.contains(&host));
+        if in_range || !hosts.insert(host.clone()) {
+            warnings.push(Warning::DuplicatesRemoved {
+                element: host,
+                count: 1,
+            });
+        } else {
+            kept.push(elem);
+        }
+    }
+
+    Ok(kept)
+}
+
+/// Returns the hosts of `elem` found in none of `others` and the hosts found in at least one, as
+/// elements. `others` can overlap each other, so only the hosts not found in an earlier one are
+/// checked against the next.
+fn partition<'a>(
+    elem: &HostlistElem,
+    others: impl IntoIterator<Item = &'a HostlistElem>,
+) -> Result<(Vec<HostlistElem>, Vec<HostlistElem>)> {
+    let mut outside = vec![elem.clone()];
+    let mut inside = Vec::new();
+    for other in others {
+        let mut remaining = Vec::new();
+        for piece in outside {
+            match digitblocks::split(&piece, other)? {
+                Some((piece_outside, piece_inside)) => {
+                    remaining.extend(piece_outside);
+                    inside.extend(piece_inside);
+                }
+                None => remaining.push(piece),
+            }
+        }
+        outside = remaining;
+    }
+
+    Ok((outside, inside))
+}
+
+/// Splits a single host name into the text before its last number, the number if it has one, and
+/// the text after it
+fn split_host(host: &str) -> Result<(&str, Option<&str>, &str)> {
+    if host.is_empty() {
+        return Err(Error::InvalidHostname(host.into()));
+    }
+    let mut prefix = "";
+    let mut number = None;
+    let mut suffix = "";
+    for pair in HostlistParser::parse(Rule::simple_hostname, host)? {
+        match pair.as_rule() {
+            Rule::prefix => prefix = pair.as_str(),
+            Rule::numeric_suffix => number = Some(pair.as_str()),
+            Rule::host_suffix => suffix = pair.as_str(),
+            Rule::EOI => break,
+            rule => return Err(Error::UnexpectedParserState(rule)),
+        }
+    }
+    Ok((prefix, number, suffix))
+}
+
+/// Combines elements with the same fingerprint and several ranges wherever two differ in only one
+/// of their ranges (ex: "r[1]n[1-2]" and "r[1]n[3-4]" as "r[1]n[1-4]"), until no more can be
+/// combined. The last range is combined along first, the way trailing numbers are folded by
+/// `collapse_hosts()`. Returns each element along with how many elements it was combined from and
+/// how many hosts they had between them.
+fn merge_multi_range(elems: Vec<HostlistElem>) -> Result<Vec<(HostlistElem, usize, usize)>> {
+    let mut positions = range_positions(elems.first());
+    positions.reverse();
+    merge_multi_range_along(elems, &positions)
+}
+
+/// The positions of the range components of `elem`
+fn range_positions(elem: Option<&HostlistElem>) -> Vec<usize> {
+    elem.map_or_else(Vec::new, |elem| {
+        elem.components
+            .iter()
+            .enumerate()
+            .filter(|(_, component)| matches!(component, Component::Range(_)))
+            .map(|(i, _)| i)
+            .collect()
+    })
+}
+
+/// Like `merge_multi_range()`, but only combining along the ranges at `positions`, in that order
+fn merge_multi_range_along(
+    elems: Vec<HostlistElem>,
+    positions: &[usize],
+) -> Result<Vec<(HostlistElem, usize, usize)>> {
+    let mut merged: Vec<_> = elems
+        .into_iter()
+        .map(|elem| {
+            let len = elem.len();
+            (elem, 1, len)
+        })
+        .collect();
+    loop {
+        let before = merged.len();
+        for &position in positions {
+            // Elements with the same components other than the one at `position` combine
+            let mut index_by_rest: HashMap<Vec<String>, usize> = HashMap::new();
+            let mut combined: Vec<(HostlistElem, usize, usize)> = Vec::with_capacity(merged.len());
+            for (elem, count, written_len) in merged {
+                let rest = elem
+                    .components
+                    .iter()
+                    .enumerate()
+                    .map(|(i, component)| {
+                        if i == position {
+                            String::new()
+                        } else {
+                            component.to_string()
+                        }
+                    })
+                    .collect();
+                match index_by_rest.entry(rest) {
+                    Entry::Occupied(entry) => {
+                        let (target, target_count, target_len) = &mut combined[*entry.get()];
+                        if let (Component::Range(range), Component::Range(range_to_add)) =
+                            (&mut target.components[position], &elem.components[position])
+                        {
+                            range.merge(range_to_add)?;
+                        }
+                        target.update_len()?;
+                        *target_count += count;
+                        *target_len = target_len.saturating_add(written_len);
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(combined.len());
+                        combined.push((elem, count, written_len));
+                    }
+                }
+            }
+            merged = combined;
+        }
+        if merged.len() == before {
+            return Ok(merged);
+        }
+    }
+}
+
+/// Combines elements with the same fingerprint along the ranges chosen by `axis`, see
+/// `collapse_hosts_multi_with_axis()`
+pub(crate) fn fold_multi(elems: Vec<HostlistElem>, axis: FoldAxis) -> Result<Vec<HostlistElem>> {
+    let mut groups: HashMap<Fingerprint, Vec<HostlistElem>> = HashMap::new();
+    for elem in elems {
+        groups.entry(elem.fingerprint()).or_default().push(elem);
+    }
+
+    let mut folded = Vec::new();
+    for elems in groups.into_values() {
+        let positions = range_positions(elems.first());
+        let merged = match axis {
+            FoldAxis::Only(axis) => {
+                let positions = positions.get(axis).map(std::slice::from_ref);
+                merge_multi_range_along(elems, positions.unwrap_or_default())?
+            }
+            // Try folding along each range first, starting with the last, and keep whichever is
+            // written shortest
+            FoldAxis::Auto => {
+                let mut shortest: Option<(usize, Vec<_>)> = None;
+                for &first in positions.iter().rev() {
+                    let mut order = vec![first];
+                    order.extend(positions.iter().rev().filter(|&&p| p != first));
+                    let merged = merge_multi_range_along(elems.clone(), &order)?;
+                    let written_len = merged
+                        .iter()
+                        .map(|(elem, _, _)| elem.to_compact_string().len() + 1)
+                        .sum();
+                    if shortest.as_ref().is_none_or(|(len, _)| written_len < *len) {
+                        shortest = Some((written_len, merged));
+                    }
+                }
+                match shortest {
+                    Some((_, merged)) => merged,
+                    None => merge_multi_range_along(elems, &[])?,
+                }
+            }
+        };
+        folded.extend(merged.into_iter().map(|(elem, _, _)| elem));
+    }
+
+    folded.sort_unstable();
+    folded.dedup();
+    Ok(folded)
+}
+
+/// Combines elements with the same fingerprint and a single range into one element
+fn merge_single_range(elems: Vec<HostlistElem>) -> Result<HostlistElem> {
+    let mut components = elems
+        .first()
+        .ok_or(Error::Internal("no elements to merge".to_string()))?
+        .components
+        .clone();
+    let position = components
+        .iter()
+        .position(|component| matches!(component, Component::Range(_)))
+        .ok_or(Error::Internal("no range component found".to_string()))?;
+
+    let ranges = elems
+        .into_iter()
+        .filter_map(|mut elem| match elem.components.swap_remove(position) {
+            Component::Range(range) => Some(range.ranges),
+            Component::Static(_) => None,
+        })
+        .flatten();
+    components[position] = Component::Range(Range::from_ranges(ranges)?);
+
+    HostlistElem::from_components(components)
+}
+
+impl FromStr for Hostlist {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::new(s)
+    }
+}
+
+/// Parses `rhs` and adds its hosts, so `(hostlist + "node[65-128]")?` is a shorthand for `union()`
+impl Add<&str> for Hostlist {
+    type Output = Result<Self>;
+
+    fn add(self, rhs: &str) -> Self::Output {
+        self.union(&Self::new(rhs)?)
+    }
+}
+
+/// Parses `rhs` and removes its hosts, so `(hostlist - "node[3,7]")?` is a shorthand for
+/// `difference()`
+impl Sub<&str> for Hostlist {
+    type Output = Result<Self>;
+
+    fn sub(self, rhs: &str) -> Self::Output {
+        self.difference(&Self::new(rhs)?)
+    }
+}
+
+/// Implements a set operator for owned and borrowed hostlists, with a `Result` as the output
+/// since the result can have too many hosts (ex: `(&a | &b)?` for `a.union(&b)?`)
+macro_rules! impl_set_operator {
+    ($($op:ident, $method:ident => $set_method:ident;)*) => {
+        $(
+            impl $op<&Hostlist> for &Hostlist {
+                type Output = Result<Hostlist>;
+
+                fn $method(self, rhs: &Hostlist) -> Self::Output {
+                    self.$set_method(rhs)
+                }
+            }
+
+            impl $op for Hostlist {
+                type Output = Result<Self>;
+
+                fn $method(self, rhs: Self) -> Self::Output {
+                    self.$set_method(&rhs)
+                }
+            }
+        )*
+    };
+}
+
+impl_set_operator! {
+    BitOr, bitor => union;
+    BitAnd, bitand => intersection;
+    Sub, sub => difference;
+    BitXor, bitxor => symmetric_difference;
+}
+
+/// Collects host names and collapses them into a hostlist like `Hostlist::from_hosts()`, skipping
+/// names that are empty or only whitespace (ex: blank lines). Collecting `Result`s stops at the
+/// first error, as usual. Use `from_hosts()` to get an error for an invalid host name instead.
+///
+/// # Panics
+/// Panics if any other host name can't be parsed, or there are more hosts than fit in a `usize`.
+/// ```
+/// use hostlist_iter::Hostlist;
+///
+/// let hostlist: Hostlist = (1..=4).map(|i| format!("node{i}")).collect();
+/// assert_eq!(hostlist.to_string(), "node[1-4]");
+///
+/// let lines = ["n1", "n2", "", "n3"].map(Ok::<_, std::io::Error>);
+/// let hostlist = lines.into_iter().collect::<Result<Hostlist, _>>().unwrap();
+/// assert_eq!(hostlist.to_string(), "n[1-3]");
+/// ```
+impl<S: AsRef<str>> FromIterator<S> for Hostlist {
+    fn from_iter<I: IntoIterator<Item = S>>(hosts: I) -> Self {
+        Self::from_hosts(non_blank(hosts))
+            .unwrap_or_else(|e| panic!("can't collect host names: {e}"))
+    }
+}
+
+/// Adds host names like `Hostlist::try_extend()`, skipping names that are empty or only whitespace.
+/// Iteration starts over from the first host.
+///
+/// # Panics
+/// Panics if any other host name can't be parsed, or the result is too large. Use `try_extend()`
+/// to get an error instead.
+/// ```
+/// use hostlist_iter::Hostlist;
+///
+/// let mut hostlist = Hostlist::new("node[1-4]").unwrap();
+/// hostlist.extend(["node5", "node6", " ", "login1"]);
+/// assert_eq!(hostlist.to_string(), "login1,node[1-6]");
+/// ```
+impl<S: AsRef<str>> Extend<S> for Hostlist {
+    fn extend<I: IntoIterator<Item = S>>(&mut self, hosts: I) {
+        self.try_extend(hosts)
+            .unwrap_or_else(|e| panic!("can't add host names: {e}"));
+    }
+}
+
+/// Skips host names that are empty or only whitespace, for collecting names from lines of input
+fn non_blank<S: AsRef<str>>(hosts: impl IntoIterator<Item = S>) -> impl Iterator<Item = S> {
+    hosts
+        .into_iter()
+        .filter(|host| !host.as_ref().trim().is_empty())
+}
+
+/// Iterator over the hosts of a borrowed hostlist, from `Hostlist::iter()`. Unlike iterating over
+/// the hostlist itself, this leaves the hostlist as it is.
+#[derive(Debug, Clone)]
+pub struct HostIter<'a> {
+    elems: &'a [HostlistElem],
+    front: (usize, usize), // The element and index of the next host from the front
+    back: (usize, usize),  // The element and index just past the next host from the back
+    cursor: Option<Cursor>, // Where `front` is in its element, once a host has been taken there
+    len: usize,
+}
+
+impl<'a> HostIter<'a> {
+    fn new(elems: &'a [HostlistElem]) -> Self {
+        Self {
+            elems,
+            front: (0, elems.first().map_or(0, |elem| elem.remaining().start)),
+            back: (
+                elems.len().saturating_sub(1),
+                elems.last().map_or(0, |elem| elem.remaining().end),
+            ),
+            cursor: None,
+            len: elems.iter().map(HostlistElem::len).sum(),
+        }
+    }
+
+    /// Returns the index just past the last host left in the front element
+    fn front_end(&self) -> usize {
+        if self.front.0 == self.back.0 {
+            self.back.1
+        } else {
+            self.elems[self.front.0].remaining().end
+        }
+    }
+
+    /// Appends the next host to `buf`, returning `false` once every host has been produced. This
+    /// is equivalent to `next()`, but lets callers reuse a single buffer instead of allocating a
+    /// `String` per host.
+    /// ```
+    /// use hostlist_iter::Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hostlist = Hostlist::new("node[1-3]")?;
+    ///   let mut iter = hostlist.iter();
+    ///   let mut buf = String::new();
+    ///   while iter.next_into(&mut buf) {
+    ///     buf.push(' ');
+    ///   }
+    ///   assert_eq!(buf, "node1 node2 node3 ");
+    ///   assert_eq!(hostlist.len(), 3);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn next_into(&mut self, buf: &mut String) -> bool {
+        if self.len == 0 {
+            return false;
+        }
+        while self.front.1 >= self.front_end() {
+            self.next_elem();
+        }
+
+        let elem = &self.elems[self.front.0];
+        if self.cursor.is_none() {
+            self.cursor = elem.cursor_at(self.front.1);
+        }
+        let Some(cursor) = self.cursor.as_mut() else {
+            return false;
+        };
+        elem.write_at(cursor, buf);
+        elem.advance_cursor(cursor);
+        self.front.1 += 1;
+        self.len -= 1;
+
+        true
+    }
+
+    /// Moves the front to the start of the next element
+    fn next_elem(&mut self) {
+        self.front.0 += 1;
+        self.front.1 = self.elems[self.front.0].remaining().start;
+        self.cursor = None;
+    }
+}
+
+impl Iterator for HostIter<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut host = String::new();
+        self.next_into(&mut host).then_some(host)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+
+    /// Jumps to the host `n` places ahead, skipping whole elements and working out where the host
+    /// is from the ranges' lengths rather than iterating through the hosts before it
+    fn nth(&mut self, mut n: usize) -> Option<Self::Item> {
+        if n >= self.len {
+            self.len = 0;
+            return None;
+        }
+
+        self.len -= n;
+        loop {
+            let left = self.front_end().saturating_sub(self.front.1);
+            if n < left {
+                break;
+            }
+            n -= left;
+            self.next_elem();
+        }
+        if n > 0 {
+            self.front.1 += n;
+            self.cursor = None;
+        }
+        self.next()
+    }
+
+    fn count(self) -> usize {
+        self.len
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+}
+
+/// Takes hosts from the end by working out where they are from the ranges' lengths
+impl DoubleEndedIterator for HostIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        loop {
+            let start = if self.back.0 == self.front.0 {
+                self.front.1
+            } else {
+                self.elems[self.back.0].remaining().start
+            };
+            if self.back.1 > start {
+                break;
+            }
+            self.back.0 -= 1;
+            self.back.1 = self.elems[self.back.0].remaining().end;
+        }
+
+        self.back.1 -= 1;
+        self.len -= 1;
+        self.elems[self.back.0].host_at(self.back.1)
+    }
+}
+
+impl ExactSizeIterator for HostIter<'_> {}
+
+impl FusedIterator for HostIter<'_> {}
+
+/// Iterator over the hosts of a borrowed hostlist in natural order across every element, from
+/// `Hostlist::iter_sorted()`. Each element is iterated over lazily and the elements are merged,
+/// so only elements whose own iteration order isn't natural are expanded up front.
+#[derive(Debug, Clone)]
+pub struct SortedIter<'a> {
+    sources: Vec<SortedSource<'a>>,
+    // The next host from each source that has one, with its sort key and the source's index
+    heap: BinaryHeap<Reverse<(Vec<NaturalPart>, usize, String)>>,
+    len: usize,
+}
+
+/// The hosts of one element for `SortedIter`, in natural order
+#[derive(Debug, Clone)]
+enum SortedSource<'a> {
+    Ordered(HostIter<'a>),
+    Sorted(std::vec::IntoIter<String>),
+}
+
+impl Iterator for SortedSource<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Ordered(hosts) => hosts.next(),
+            Self::Sorted(hosts) => hosts.next(),
+        }
+    }
+}
+
+impl<'a> SortedIter<'a> {
+    fn new(elems: &'a [HostlistElem]) -> Self {
+        let mut sources: Vec<SortedSource> = elems
+            .iter()
+            .map(|elem| {
+                let hosts = HostIter::new(std::slice::from_ref(elem));
+                if elem.is_naturally_ordered() {
+                    SortedSource::Ordered(hosts)
+                } else {
+                    let mut hosts: Vec<String> = hosts.collect();
+                    hosts.sort_by_cached_key(|host| natural_key(host));
+                    SortedSource::Sorted(hosts.into_iter())
+                }
+            })
+            .collect();
+
+        let mut heap = BinaryHeap::with_capacity(sources.len());
+        for (i, source) in sources.iter_mut().enumerate() {
+            if let Some(host) = source.next() {
+                heap.push(Reverse((natural_key(&host), i, host)));
+            }
+        }
+
+        Self {
+            sources,
+            heap,
+            len: elems.iter().map(HostlistElem::len).sum(),
+        }
+    }
+}
+
+impl Iterator for SortedIter<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, i, host)) = self.heap.pop()?;
+        if let Some(next) = self.sources[i].next() {
+            self.heap.push(Reverse((natural_key(&next), i, next)));
+        }
+        self.len -= 1;
+
+        Some(host)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl ExactSizeIterator for SortedIter<'_> {}
+
+impl FusedIterator for SortedIter<'_> {}
+
+/// Iterator over the parts of each host in a hostlist, from `Hostlist::iter_parts()`
+#[derive(Debug, Clone)]
+pub struct PartsIter {
+    hostlist_elems: Vec<HostlistElem>,
+}
+
+impl Iterator for PartsIter {
+    type Item = Vec<HostPart>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.hostlist_elems
+            .iter_mut()
+            .find_map(HostlistElem::next_parts)
+    }
+}
+
+impl FusedIterator for PartsIter {}
+
+impl Iterator for Hostlist {
+    type Item = String;
+
+    /// Returns the next host in the hostlist
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut host = String::new();
+        self.next_into(&mut host).then_some(host)
+    }
+
+    /// Returns the number of hosts left from the elements' lengths, rather than iterating through
+    /// every host
+    fn count(self) -> usize {
+        self.len()
+    }
+
+    /// Returns the last host, taken from the end rather than iterating through every host
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+
+    /// The hosts left are known exactly, and always fit in a `usize` since constructing a
+    /// hostlist with more is an error
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+
+    /// Jumps to the host `n` places ahead, skipping whole elements and working out where the host
+    /// is from the ranges' lengths rather than iterating through the hosts before it
+    fn nth(&mut self, mut n: usize) -> Option<Self::Item> {
+        for elem in &mut self.hostlist_elems {
+            let len = elem.len();
+            if n < len {
+                return elem.nth(n);
+            }
+            elem.nth(len);
+            n -= len;
+        }
+        None
+    }
+}
+
+/// Iterates from the last host, without expanding the hosts before it (ex: `rev()` or `last()`)
+impl DoubleEndedIterator for Hostlist {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.hostlist_elems
+            .iter_mut()
+            .rev()
+            .find_map(DoubleEndedIterator::next_back)
+    }
+}
+
+// This enables:
+//   `for e in &hostlist { ... }`
+// while `for e in hostlist { ... }` iterates over the hostlist itself
+impl<'a> IntoIterator for &'a Hostlist {
+    type Item = String;
+    type IntoIter = HostIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl ExactSizeIterator for Hostlist {}
+
+// This trait guarantees that once the iterator returns None, it will always return None.
+// No additional methods needed, it's a marker trait.
+impl FusedIterator for Hostlist {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hostlistelem::FingerprintComponent;
+    use crate::{ErrorKind, HostSet, HostnameChars, collapse_hosts};
+
+    #[test]
+    fn test_hostlist_len1() {
+        let hostlist = Hostlist::new("node[1-1000000000]").unwrap();
+        assert_eq!(hostlist.len(), 1_000_000_000);
+    }
+
+    #[test]
+    fn test_hostlist_len2() {
+        let hostlist = Hostlist::new("blah2,node[1-3,2-5],n[11-20],blah").unwrap();
+        assert_eq!(hostlist.len(), 17);
+    }
+
+    #[test]
+    fn test_hostlist_len_overflow() {
+        let inputs = [
+            "n[1-1000000]-[1-1000000]-[1-1000000]-[1-10],o[1-1000000]-[1-1000000]-[1-1000000]-[1-10]",
+        ];
+
+        for input in inputs {
+            let result = Hostlist::new(input);
+            assert!(matches!(result, Err(Error::HostlistTooLarge)));
+        }
+    }
+
+    #[test]
+    fn test_hostlist_error_element() {
+        let result = Hostlist::new("n[1-3], gpu[1-4],login[9-2]");
+        let Err(Error::InElement {
+            element,
+            index,
+            offset,
+            source,
+        }) = result
+        else {
+            panic!("unexpected result: {result:?}");
+        };
+        assert_eq!(element, "login[9-2]");
+        assert_eq!(index, 2);
+        assert_eq!(offset, 17);
+        assert!(matches!(
+            *source,
+            Error::InvalidRangeReversed { start: 9, end: 2 }
+        ));
+
+        let err = Hostlist::new("n[99999999999999999999]").unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::InElement { index: 0, offset: 0, source, .. }
+                if matches!(**source, Error::ParseIntError(_))
+        ));
+        assert!(
+            err.to_string()
+                .starts_with("in \"n[99999999999999999999]\" (element 0, offset 0): ")
+        );
+    }
+
+    #[test]
+    fn test_hostlist_duplicates_across_elements() -> Result<()> {
+        let tests = [
+            ("node1,node[1-3]", "node[1-3]", 3),
+            ("n1,n1,m1", "m1,n1", 2),
+            ("n[1-2]m[1-2],n[2-3]m[2-3]", "", 7),
+            ("n[1-4]m2,n[1-2]m[1-3]", "", 8),
+            ("n[1-2]m[1-2],n1m2,n2m[1-2]", "n[1-2]m[1-2]", 4),
+            ("n[1-2]m[1-2],x[1-2]", "n[1-2]m[1-2],x[1-2]", 6),
+            ("n[1-6][0-3,7,11],n[5-11]", "n[1-6][0-3,7,11],n[5-9]", 41),
+            ("n[1-20]5,n1[0-9]5", "n[1-20]5", 20),
+            ("n[1-12][1],n[1][11]", "n[1-12][1]", 12),
+        ];
+        for (expr, expected, len) in tests {
+            let hostlist = Hostlist::new(expr)?;
+            assert_eq!(hostlist.len(), len, "{expr}");
+            assert_eq!(
+                hostlist.iter().collect::<BTreeSet<_>>().len(),
+                len,
+                "{expr}"
+            );
+            if !expected.is_empty() {
+                assert_eq!(hostlist.to_string(), expected, "{expr}");
+            }
+        }
+
+        let outcome = Hostlist::parse_with_warnings("login1,n[1-3],n2,login1")?;
+        assert_eq!(
+            outcome.warnings,
+            vec![
+                Warning::DuplicatesRemoved {
+                    element: "login1".to_string(),
+                    count: 1,
+                },
+                Warning::DuplicatesRemoved {
+                    element: "n2".to_string(),
+                    count: 1,
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_merge_multi_range() -> Result<()> {
+        let tests = [
+            ("r[1]n[1-2],r[1]n[3-4]", "r[1]n[1-4]"),
+            ("r[1]n[1-2],r[2]n[1-2],r[3-4]n[1-2]", "r[1-4]n[1-2]"),
+            // Combining along one range lets elements combine along another
+            ("r1n[1-2]x[1],r1n[1-2]x[2],r1n[3]x[1-2]", "r1n[1-3]x[1-2]"),
+            ("r[1]n[1-2],r[2]n[2-3]", "r[1]n[1-2],r[2]n[2-3]"),
+            ("r[1-2]n[1-2],r[1-2]n[2-3]", "r[1-2]n[1-3]"),
+        ];
+        for (expr, expected) in tests {
+            let hostlist = Hostlist::new(expr)?;
+            assert_eq!(hostlist.to_string(), expected, "{expr}");
+            assert_eq!(
+                hostlist.len(),
+                Hostlist::new(expr)?.collect::<BTreeSet<_>>().len()
+            );
+        }
+
+        let outcome = Hostlist::parse_with_warnings("r[1-2]n[1-2],r[1-2]n[2-3]")?;
+        assert_eq!(
+            outcome.warnings,
+            vec![
+                Warning::DuplicatesRemoved {
+                    element: "r[1-2]n[1-3]".to_string(),
+                    count: 2,
+                },
+                Warning::ElementsCombined {
+                    combined: "r[1-2]n[1-3]".to_string(),
+                    count: 2,
+                },
+            ]
+        );
+
+        let mut hostlist = Hostlist::new("r[1]n[1-2],r[2]n[1-2]")?;
+        let canonical = hostlist.clone();
+        hostlist.next();
+        hostlist.canonicalize()?;
+        assert_eq!(hostlist, canonical);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_parse_with_warnings() -> Result<()> {
+        let outcome = Hostlist::parse_with_warnings("n[1-3],gpu[01-2],n[3-5],n[5],n[1-2]m[1-2,2]")?;
+        assert_eq!(
+            outcome.hostlist.to_string(),
+            "gpu[01-02],n[1-2]m[1-2],n[1-5]"
+        );
+        assert_eq!(
+            outcome.warnings,
+            vec![
+                Warning::DuplicatesRemoved {
+                    element: "n[1-2]m[1-2,2]".to_string(),
+                    count: 2,
+                },
+                Warning::DuplicatesRemoved {
+                    element: "n[1-5]".to_string(),
+                    count: 2,
+                },
+                Warning::ElementsCombined {
+                    combined: "n[1-5]".to_string(),
+                    count: 3,
+                },
+            ]
+        );
+
+        let outcome = Hostlist::parse_with_warnings("n[1-3],m1")?;
+        assert!(outcome.warnings.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_prefixes_and_range_count() -> Result<()> {
+        let inputs = [
+            ("", vec![], 0),
+            ("node", vec!["node"], 0),
+            ("node[1-3]", vec!["node"], 1),
+            ("node[1-3],node[5-7]", vec!["node"], 1),
+            ("node[1-3],node[5-7]x[1-2]", vec!["node"], 3),
+            (
+                "n[1-2]m[1-3],gpu[1-4],login1",
+                vec!["gpu", "login1", "n"],
+                3,
+            ),
+        ];
+
+        for (input, prefixes, range_count) in inputs {
+            let hostlist = Hostlist::new(input)?;
+            assert_eq!(
+                hostlist.prefixes().into_iter().collect::<Vec<_>>(),
+                prefixes
+            );
+            assert_eq!(hostlist.range_count(), range_count);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_surrounding_whitespace() -> Result<()> {
+        for input in [" n[1-3],m1 ", "n[1-3],m1\n", "\tn[1-3] ,\r\n m1\r\n"] {
+            assert_eq!(
+                Hostlist::new(input)?.to_string(),
+                "m1,n[1-3]",
+                "input: {input:?}"
+            );
+        }
+        // Whitespace between elements, before a range, and inside brackets
+        let tests = [
+            ("n1 n2", "n1,n2"),
+            ("n [1-3]", "n[1-3]"),
+            ("n[1, 3]", "n[1,3]"),
+            ("n[1 - 3 / 2]", "n[1-3/2]"),
+            ("rack [a - b]", "racka,rackb"),
+            ("n[1-2] gpu1", "gpu1,n[1-2]"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(
+                Hostlist::new(input)?.to_string(),
+                expected,
+                "input: {input:?}"
+            );
+        }
+        for input in ["n[1-3] [", "n[1 2]", "[1-3]", "n[1-3]  ,"] {
+            assert!(Hostlist::new(input).is_err(), "input: {input:?}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_expression_set_operators() -> Result<()> {
+        let tests = [
+            ("node[1-100]!node[40-50]", "node[1-39,51-100]"),
+            ("node[1-100]&node[30-60]", "node[30-60]"),
+            ("node[1-100] ! node[40-50]", "node[1-39,51-100]"),
+            // Applied left to right, with the same precedence as ","
+            ("n[1-5],m1!n[2-4]", "m1,n[1,5]"),
+            ("n[1-5]!n[2-4],n[3]", "n[1,3,5]"),
+            ("n[1-5]!n[2-4]&n[4-5]", "n[5]"),
+            ("login1,n[1-2]&login1", "login1"),
+            (
+                "rack[1-2]-n[1-4]!rack2-n[3-4]",
+                "rack[1]-n[1-4],rack[2]-n[1-2]",
+            ),
+            ("n[1-3]!m1", "n[1-3]"),
+            ("n[1-3]!n[1-3]", ""),
+        ];
+        for (expr, expected) in tests {
+            assert_eq!(Hostlist::new(expr)?.to_string(), expected, "expr: {expr}");
+        }
+        assert_eq!(
+            Hostlist::new_whitespace_separated("n[1-5] m1 !n[2-4]")?.to_string(),
+            "m1,n[1,5]"
+        );
+        for expr in ["n1!", "!n1", "n1!!n2", "n1,!n2", "n1&,n2"] {
+            assert!(Hostlist::new(expr).is_err(), "expr: {expr}");
+        }
+        assert!(matches!(
+            Hostlist::new("n[1-3]!n[5-4]"),
+            Err(Error::InElement { index: 1, .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_escaped() -> Result<()> {
+        let hostlist = Hostlist::new(r"vm\[prod\][1-3],a\,b,c\!d\&e\\f")?;
+        assert_eq!(hostlist.to_string(), r"a\,b,c\!d\&e\\f,vm\[prod\][1-3]");
+        assert_eq!(
+            hostlist.to_vec(),
+            ["a,b", r"c!d&e\f", "vm[prod]1", "vm[prod]2", "vm[prod]3"]
+        );
+        assert!(hostlist.contains("vm[prod]2"));
+        assert_eq!(
+            hostlist.prefixes(),
+            BTreeSet::from(["a,b", r"c!d&e\f", "vm[prod]"])
+        );
+        assert_eq!(hostlist.to_regex(), r"^(a,b|c!d&e\\f|vm\[prod\][1-3])$");
+        assert_eq!(
+            Hostlist::new(r"n\[1\],n\[2\]")?.to_string(),
+            r"n\[1\],n\[2\]"
+        );
+        for input in [r"n\", r"n\a", r"n\ 1", r"\[1-2]"] {
+            assert!(Hostlist::new(input).is_err(), "input: {input:?}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_new_lenient() -> Result<()> {
+        for input in [
+            "n[1-3],m1",
+            "n[1-3],,m1",
+            ",n[1-3], ,m1,",
+            " , n[1-3],\n,m1 ,\n",
+        ] {
+            assert_eq!(
+                Hostlist::new_lenient(input)?.to_string(),
+                "m1,n[1-3]",
+                "input: {input:?}"
+            );
+        }
+        for input in ["", ",", " , ,\n"] {
+            assert_eq!(Hostlist::new_lenient(input)?.len(), 0, "input: {input:?}");
+        }
+        assert_eq!(
+            Hostlist::new_lenient("n[1-5],,m1!n[2-4],")?.to_string(),
+            "m1,n[1,5]"
+        );
+        assert_eq!(Hostlist::new_lenient("n [1-3],, m1 m2")?.len(), 5);
+        for input in ["n[1-3],,m1", "n1,", ",n1"] {
+            assert!(Hostlist::new(input).is_err(), "input: {input:?}");
+        }
+        for input in ["n[1-3,,5]", "n[1-3],!n2", "n [1 3]", "n[3-1],"] {
+            assert!(Hostlist::new_lenient(input).is_err(), "input: {input:?}");
+        }
+
+        let options = ParseOptions::default()
+            .hostname_chars(HostnameChars::Extended)
+            .lenient(true);
+        assert_eq!(
+            Hostlist::new_with_options("db:n[1-2],,", options)?.to_string(),
+            "db:n[1-2]"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_braces() -> Result<()> {
+        let braces = ParseOptions::default().braces(true);
+        let tests = [
+            ("node{1..5}", "node[1-5]"),
+            ("node{1,3,5}", "node[1,3,5]"),
+            ("node{1..9..2}", "node[1-9/2]"),
+            ("node{1..3,7..8},gpu[1-2]", "gpu[1-2],node[1-3,7-8]"),
+            ("r{1..2}n{1..2}", "r[1-2]n[1-2]"),
+            ("rack{a..b}", "racka,rackb"),
+            ("node{prod,dev}", "nodedev,nodeprod"),
+            ("n1.example.com", "n1.example.com"),
+        ];
+        for (expr, expected) in tests {
+            assert_eq!(
+                Hostlist::new_with_options(expr, braces)?.to_string(),
+                expected,
+                "expr: {expr}"
+            );
+        }
+        assert!(Hostlist::new("node{1..5}").is_err());
+        for expr in ["node{5..1}", "node{1..5", "node{1...5}"] {
+            assert!(
+                Hostlist::new_with_options(expr, braces).is_err(),
+                "expr: {expr}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_whitespace_separated() -> Result<()> {
+        for input in [
+            "n[1-3] m1",
+            "n[1-3]\tm1",
+            "n[1]\nn[2-3]\r\nm1\n",
+            "  n[1-3] ,m1 ",
+            "n[1-2],n[3]   m1",
+        ] {
+            assert_eq!(
+                Hostlist::new_whitespace_separated(input)?.to_string(),
+                "m1,n[1-3]",
+                "input: {input:?}"
+            );
+        }
+        assert_eq!(Hostlist::new_whitespace_separated(" \n")?.len(), 0);
+        for input in ["n1 , , n2", "n1,"] {
+            assert!(
+                Hostlist::new_whitespace_separated(input).is_err(),
+                "input: {input:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_new_strict() -> Result<()> {
+        for input in ["", "  ", "\n"] {
+            assert_eq!(Hostlist::new(input)?.len(), 0);
+            assert_eq!(Hostlist::new_strict(input), Err(Error::EmptyHostlist));
+        }
+        for input in ["a,,b", "a,", ",a"] {
+            assert!(matches!(
+                Hostlist::new_strict(input),
+                Err(Error::ParseError(_))
+            ));
+        }
+        assert_eq!(Hostlist::new_strict("n[1-3]")?.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_new_ignore_case() -> Result<()> {
+        let hostlist = Hostlist::new_ignore_case("Node[1-3],node[3-4],LOGIN")?;
+        assert_eq!(hostlist.to_string(), "login,node[1-4]");
+        assert_eq!(hostlist.len(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_combine_like_prefixes() {
+        let mut hostlist = Hostlist::new("node[1-3,2-5],node[2-7]").unwrap();
+        let expected = vec![
+            "node1", "node2", "node3", "node4", "node5", "node6", "node7",
+        ];
+
+        assert_eq!(hostlist.len(), 7);
+        for e in expected {
+            assert_eq!(hostlist.next(), Some(e.into()));
+        }
+        assert_eq!(hostlist.len(), 0);
+    }
+
+    #[test]
+    fn test_hostlist_fromstr() {
+        let hostlist: Hostlist = "node[1-3]".parse().unwrap();
+        let expected = vec!["node1", "node2", "node3"];
+
+        let result: Vec<String> = hostlist.into_iter().collect();
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_hostlist_iter1() {
+        let mut hostlist: Hostlist = "n[1-5]".parse().unwrap();
+        let expected = ["n1", "n2", "n3", "n4", "n5"];
+
+        for e in expected {
+            assert_eq!(hostlist.next(), Some(e.into()));
+        }
+        assert_eq!(hostlist.next(), None);
+        assert_eq!(hostlist.len(), 0);
+    }
+
+    #[test]
+    fn test_hostlist_iter2() {
+        let mut hostlist: Hostlist = "n[1-3]s".parse().unwrap();
+        let expected = ["n1s", "n2s", "n3s"];
+
+        for e in expected {
+            assert_eq!(hostlist.next(), Some(e.into()));
+        }
+        assert_eq!(hostlist.next(), None);
+        assert_eq!(hostlist.len(), 0);
+    }
+
+    #[test]
+    fn test_hostlist_iter3() {
+        let mut hostlist: Hostlist = "n[1-3]s[1-2]".parse().unwrap();
+        let expected = ["n1s1", "n1s2", "n2s1", "n2s2", "n3s1", "n3s2"];
+
+        for e in expected {
+            assert_eq!(hostlist.next(), Some(e.into()));
+        }
+        assert_eq!(hostlist.next(), None);
+        assert_eq!(hostlist.len(), 0);
+    }
+
+    #[test]
+    fn test_hostlist_iter4() -> Result<()> {
+        let mut hostlist: Hostlist = "n[1-5]".parse()?;
+        let expected_orig = vec!["n2", "n3", "n4", "n5"];
+
+        assert_eq!(hostlist.len(), 5);
+        assert_eq!(hostlist.next(), Some("n1".to_string()));
+        assert_eq!(hostlist.len(), 4);
+
+        let mut expected = expected_orig.clone();
+        for host in &hostlist {
+            let e = expected.remove(0);
+            assert_eq!(e, host);
+        }
+        assert!(expected.is_empty());
+        assert_eq!(hostlist.len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_iter5() -> Result<()> {
+        let mut hostlist: Hostlist = "n[1-5]".parse()?;
+
+        assert_eq!(hostlist.len(), 5);
+        assert_eq!(hostlist.next(), Some("n1".to_string()));
+        assert_eq!(hostlist.len(), 4);
+        assert_eq!(hostlist.next(), Some("n2".to_string()));
+        assert_eq!(hostlist.len(), 3);
+        assert_eq!(hostlist.next(), Some("n3".to_string()));
+        assert_eq!(hostlist.len(), 2);
+        assert_eq!(hostlist.next(), Some("n4".to_string()));
+        assert_eq!(hostlist.len(), 1);
+        assert_eq!(hostlist.next(), Some("n5".to_string()));
+        assert_eq!(hostlist.len(), 0);
+        assert_eq!(hostlist.next(), None);
+        assert_eq!(hostlist.len(), 0);
+
+        #[allow(clippy::useless_conversion)]
+        let mut hostlist_iter = hostlist.into_iter();
+        assert!(hostlist_iter.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_iter6() -> Result<()> {
+        let mut hostlist: Hostlist = "n[1-5]".parse()?;
+
+        assert_eq!(hostlist.len(), 5);
+        assert_eq!(hostlist.next(), Some("n1".to_string()));
+        assert_eq!(hostlist.len(), 4);
+        assert_eq!(hostlist.next(), Some("n2".to_string()));
+        assert_eq!(hostlist.len(), 3);
+        assert_eq!(hostlist.next(), Some("n3".to_string()));
+        assert_eq!(hostlist.len(), 2);
+        assert_eq!(hostlist.next(), Some("n4".to_string()));
+        assert_eq!(hostlist.len(), 1);
+
+        let expected_orig = vec!["n5"];
+
+        let mut expected = expected_orig.clone();
+        for host in &hostlist {
+            let e = expected.remove(0);
+            assert_eq!(e, host);
+        }
+        assert!(expected.is_empty());
+
+        // hostlist is unchanged
+        assert_eq!(hostlist.len(), 1);
+
+        // and can still be modified
+        assert_eq!(hostlist.next(), Some("n5".to_string()));
+        assert_eq!(hostlist.len(), 0);
+        assert_eq!(hostlist.next(), None);
+        assert_eq!(hostlist.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_host_iter_next_into() -> Result<()> {
+        let expr = "n[1-2]m[1-3]o[1-2],login,compute[7-8]";
+        let expected: Vec<String> = Hostlist::new(expr)?.collect();
+
+        let hostlist = Hostlist::new(expr)?;
+        let mut iter = hostlist.iter();
+        let mut buf = "prefix:".to_string();
+        let mut hosts = Vec::new();
+        while iter.next_into(&mut buf) {
+            hosts.push(buf["prefix:".len()..].to_string());
+            buf.truncate("prefix:".len());
+        }
+        assert_eq!(hosts, expected);
+        assert_eq!(buf, "prefix:");
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(hostlist.len(), expected.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_to_vec() -> Result<()> {
+        let mut hostlist: Hostlist = "n[1-3],login".parse()?;
+        assert_eq!(hostlist.to_vec(), vec!["login", "n1", "n2", "n3"]);
+        assert_eq!(hostlist.len(), 4);
+
+        // Only the remaining hosts are returned once iteration has started
+        assert_eq!(hostlist.next(), Some("login".to_string()));
+        assert_eq!(hostlist.to_vec(), vec!["n1", "n2", "n3"]);
+        assert_eq!(hostlist.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_invalid() {
+        let inputs = [
+            ("[1-3]", "no prefix"),
+            ("node[1-", "unclosed bracket range"),
+            ("node]1-3[", "inverted brackets"),
+            ("node[3-1]", "reversed range"),
+            ("node[1,]", "trailing comma"),
+            ("node[,1]", "leading comma"),
+            ("node[1,,3]", "double comma"),
+            ("node[1-3", "missing closing bracket"),
+            ("node1-3]", "missing opening bracket"),
+            ("node[a-3]", "non-numeric character in range"),
+            ("node[-1-3]", "negative number in range"),
+            ("node[1.5-3]", "non-integer in range"),
+            ("node[1--3]", "double hyphen in range"),
+            ("node[[1-3]]", "nested brackets"),
+            ("node[1:2]", "using colon instead of hyphen for range"),
+        ];
+
+        for (input, description) in inputs {
+            let result = input.parse::<Hostlist>();
+            if let Ok(hostlist) = result {
+                panic!("Failure on '{input}' ({description}). Hostlist parsed to: '{hostlist}'.");
+            }
+        }
+    }
+
+    #[test]
+    fn test_hostlist_valid() -> Result<()> {
+        let inputs = [
+            ("node[1-3]", vec!["node1", "node2", "node3"]),
+            ("node[01-03]", vec!["node01", "node02", "node03"]),
+            ("node[04-06]", vec!["node04", "node05", "node06"]),
+            ("compute[1,3,5]", vec!["compute1", "compute3", "compute5"]),
+            (
+                "server[1-3,5,7-9]",
+                vec![
+                    "server1", "server2", "server3", "server5", "server7", "server8", "server9",
+                ],
+            ),
+            (
+                "host[1-3]-rack[1-2]",
+                vec![
+                    "host1-rack1",
+                    "host1-rack2",
+                    "host2-rack1",
+                    "host2-rack2",
+                    "host3-rack1",
+                    "host3-rack2",
+                ],
+            ),
+            (
+                "node[4-6,8,10-12]",
+                vec![
+                    "node4", "node5", "node6", "node8", "node10", "node11", "node12",
+                ],
+            ),
+            (
+                "prefix[1-3]suffix",
+                vec!["prefix1suffix", "prefix2suffix", "prefix3suffix"],
+            ),
+            (
+                "node[1-3],server[1-2]",
+                vec!["node1", "node2", "node3", "server1", "server2"],
+            ),
+            ("", vec![]),
+            ("singlenode", vec!["singlenode"]),
+            ("node[0-0]", vec!["node0"]),
+            ("node[42]", vec!["node42"]),
+        ];
+
+        for (input, expected) in inputs {
+            eprintln!("input: \"{input}\"");
+            let hosts = input.parse::<Hostlist>()?.collect::<Vec<_>>();
+            assert_eq!(hosts, expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_contains() -> Result<()> {
+        let hostlist = Hostlist::new("n[1-20]5,rack[1-2]-n[08-10],login")?;
+        for host in ["n15", "n205", "n55", "rack2-n09", "rack1-n10", "login"] {
+            assert!(hostlist.contains(host), "host: {host}");
+        }
+        for host in ["n1", "n215", "n05", "rack2-n9", "rack3-n09", "login1", ""] {
+            assert!(!hostlist.contains(host), "host: {host}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_contains_without_expanding() -> Result<()> {
+        // Far too many hosts to expand into a set
+        let hostlist = Hostlist::new("r[1-100000]n[1-100000],login[1-4000000000]")?;
+        assert!(hostlist.contains("r99999n5"));
+        assert!(hostlist.contains("login3999999999"));
+        assert!(!hostlist.contains("r100001n5"));
+        assert!(!hostlist.contains("login04"));
+        assert!(!hostlist.contains("r[1-2]n1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_to_regex() -> Result<()> {
+        assert_eq!(Hostlist::new("")?.to_regex(), "^$");
+        assert_eq!(Hostlist::new("a.b")?.to_regex(), "^a\\.b$");
+        assert_eq!(Hostlist::new("n[1-3,7]x")?.to_regex(), "^n([1-3]|7)x$");
+
+        let exprs = [
+            "n[0-1050]",
+            "n[5-7,60-99,998-1003]-ib",
+            "rack[1-2]-n[08-12],login",
+            "n[007-012,95-101]",
+        ];
+        for expr in exprs {
+            let hostlist = Hostlist::new_with_padding(expr, Padding::Keep)?;
+            let regex = regex::Regex::new(&hostlist.to_regex()).expect("valid regex");
+            for n in 0..1100 {
+                for host in [
+                    format!("n{n}"),
+                    format!("n{n:03}"),
+                    format!("n{n}-ib"),
+                    format!("n{n:02}-ib"),
+                    format!("rack{}-n{n:02}", n % 4),
+                    format!("rack{}-n{n}", n % 4),
+                    format!("login{n}"),
+                ] {
+                    assert_eq!(
+                        regex.is_match(&host),
+                        hostlist.contains(&host),
+                        "expr: {expr}, host: {host}"
+                    );
+                }
+            }
+            assert_eq!(regex.is_match("login"), hostlist.contains("login"));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_set_operations() -> Result<()> {
+        let inputs = [
+            ("n[1-10]", "n[5-15]"),
+            ("n[1-10],m[1-3]", "n[3,7],gpu1"),
+            ("a[1-4]b[1-4]", "a[2-3]b[2-3]"),
+            ("a[1-4]b[1-4]c[1-2]", "a[2-5]b[3]c[2-3],a1b1c1"),
+            ("login,n[1-3]", "login"),
+            ("n[1-5]m[1-2]", "n3m2,n9m1,x"),
+            ("n3m2,n7,x", "n[1-5]m[1-2],n[5-9]"),
+            ("", "n[1-3]"),
+            ("a[1-4]b[1-4]", "a[2-5]b3,a1b[2-3],a2b[1-9]"),
+            ("n[1-2]m[1-3]x", "n2m[2-5]x,n1m1x"),
+            ("n[01-12]m1", "n[1-12]m[1-2],n0[1-3]m[1-5]"),
+            ("n[6,10-13]", "n[1-5,9-13][2]"),
+            ("n[1-20]5", "n1[0-9]5,n[2-3]"),
+            ("n[1-5][0-9]", "n[10-60/5]"),
+        ];
+
+        for (a, b) in inputs {
+            let (a, b) = (Hostlist::new(a)?, Hostlist::new(b)?);
+            let a_hosts: BTreeSet<String> = a.iter().collect();
+            let b_hosts: BTreeSet<String> = b.iter().collect();
+            let hosts = |hostlist: Hostlist| hostlist.collect::<BTreeSet<String>>();
+
+            let union = a.union(&b)?;
+            assert_eq!(union.len(), a_hosts.union(&b_hosts).count(), "{a} | {b}");
+            assert_eq!(hosts(union), &a_hosts | &b_hosts, "{a} | {b}");
+            let intersection = a.intersection(&b)?;
+            assert_eq!(intersection.len(), a_hosts.intersection(&b_hosts).count());
+            assert_eq!(hosts(intersection), &a_hosts & &b_hosts, "{a} & {b}");
+            let difference = a.difference(&b)?;
+            assert_eq!(difference.len(), a_hosts.difference(&b_hosts).count());
+            assert_eq!(hosts(difference), &a_hosts - &b_hosts, "{a} - {b}");
+        }
+
+        // Hosts already iterated over are still part of the hostlist
+        let mut a = Hostlist::new("n[1-3]")?;
+        a.next();
+        assert!(a.contains("n1"));
+        assert_eq!(a.union(&Hostlist::new("n4")?)?.len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_set_operations_large() -> Result<()> {
+        // Far too many hosts to expand, so these only finish if done range by range
+        let pool = Hostlist::new("n[1-1000000000],gpu[1-1000000]r[1-1000]")?;
+        let allocation = Hostlist::new("n[500-999999999],gpu[7-8]r[1-1000]")?;
+
+        let free = pool.difference(&allocation)?;
+        assert_eq!(free.len(), 499 + 1 + 1_000_000_000 - 2_000);
+        assert!(free.contains("n1000000000") && !free.contains("n500"));
+        assert_eq!(pool.intersection(&allocation)?, allocation);
+        assert_eq!(free.union(&allocation)?.len(), pool.len());
+
+        Ok(())
+    }
+
+    /// Returns a random number below `n`, from a linear congruential generator
+    fn random(seed: &mut u64, n: u64) -> u64 {
+        *seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (*seed >> 33) % n
+    }
+
+    /// Returns a random expression of one to three elements, each with one or two numbers made of
+    /// ranges and digits that can share hosts in different ways (ex: "n[1-5][2]" and "n[10-13]"),
+    /// or have the same host twice themselves (ex: "n[1-11][1-11]")
+    fn random_expr(seed: &mut u64) -> String {
+        let part = |seed: &mut u64, last: bool| {
+            let (max, width) = if last {
+                (25, [0, 0, 2][usize::try_from(random(seed, 3)).unwrap()])
+            } else {
+                (13, 0)
+            };
+            if random(seed, 3) == 0 {
+                return random(seed, max).to_string();
+            }
+            let start = random(seed, max);
+            let end = start + random(seed, max - start + 1);
+            format!("[{start:0width$}-{end:0width$}]")
+        };
+
+        let mut elems = Vec::new();
+        for _ in 0..=random(seed, 3) {
+            let mut numbers = Vec::new();
+            for _ in 0..=random(seed, 2) {
+                let mut number = String::new();
+                if random(seed, 2) == 0 {
+                    number.push_str(&part(seed, false));
+                }
+                number.push_str(&part(seed, true));
+                numbers.push(number);
+            }
+            elems.push(format!("n{}", numbers.join("-")));
+        }
+        elems.join(",")
+    }
+
+    #[test]
+    fn test_hostlist_set_operations_random() -> Result<()> {
+        let mut seed = 2501;
+        for _ in 0..300 {
+            let (a, b) = (random_expr(&mut seed), random_expr(&mut seed));
+            for padding in [Padding::Strip, Padding::Keep] {
+                let a = Hostlist::new_with_padding(&a, padding)?;
+                let b = Hostlist::new_with_padding(&b, padding)?;
+                let a_hosts: BTreeSet<String> = a.iter().collect();
+                let b_hosts: BTreeSet<String> = b.iter().collect();
+                assert_eq!(a.len(), a_hosts.len(), "{a}");
+                assert_eq!(
+                    a.intersects(&b)?,
+                    !a_hosts.is_disjoint(&b_hosts),
+                    "{a} & {b}"
+                );
+
+                for (op, result, expected) in [
+                    ("|", a.union(&b)?, &a_hosts | &b_hosts),
+                    ("&", a.intersection(&b)?, &a_hosts & &b_hosts),
+                    ("-", a.difference(&b)?, &a_hosts - &b_hosts),
+                ] {
+                    assert_eq!(result.len(), expected.len(), "{a} {op} {b} = {result}");
+                    assert_eq!(result.collect::<BTreeSet<_>>(), expected, "{a} {op} {b}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_subset_superset_disjoint() -> Result<()> {
+        // (a, b, a is a subset of b, a and b are disjoint)
+        let inputs = [
+            ("n[2-3]", "n[1-5]", true, false),
+            ("n[1-5]", "n[2-3]", false, false),
+            ("n[1-5]", "n[1-5]", true, false),
+            ("n[1-2]", "n[3-4]", false, true),
+            ("", "n[1-2]", true, true),
+            ("n3,login", "n[1-5],login", true, false),
+            ("a[1-2]b[1-2]", "a[1-4]b[1-3]", true, false),
+            ("a2b2,a1b1", "a[1-2]b[1-2]", true, false),
+            ("a[1-2]b[1-2]", "a[3-4]b[1-2],a1b3", false, true),
+            ("n[1-1000000000]", "n[1-999999999]", false, false),
+        ];
+        for (a, b, subset, disjoint) in inputs {
+            let (a, b) = (Hostlist::new(a)?, Hostlist::new(b)?);
+            assert_eq!(a.is_subset(&b)?, subset, "{a} <= {b}");
+            assert_eq!(b.is_superset(&a)?, subset, "{b} >= {a}");
+            assert_eq!(a.is_disjoint(&b)?, disjoint, "{a} disjoint {b}");
+            assert_eq!(b.is_disjoint(&a)?, disjoint, "{b} disjoint {a}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_complement_within() -> Result<()> {
+        let universe = Hostlist::new("n[1-10],m[1-2]x[1-2],login1")?;
+        let reported = Hostlist::new("n[2-9],m1x[1-2],m2x2,gpu1")?;
+        let missing = reported.complement_within(&universe)?;
+        assert_eq!(missing.to_string(), "login1,m[2]x[1],n[1,10]");
+        assert!(!missing.intersects(&reported)?);
+        assert!(missing.union(&reported)?.is_superset(&universe)?);
+
+        assert!(universe.complement_within(&universe)?.is_empty());
+        assert!(
+            Hostlist::new("")?
+                .complement_within(&universe)?
+                .eq_hosts(&universe)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_gaps() -> Result<()> {
+        let tests = [
+            ("n[1-3,7]", "n[4-6]"),
+            ("n[1-3],m[5-6],login1", ""),
+            ("n[1,4],m[2,5-6,9]", "m[3-4,7-8],n[2-3]"),
+            ("r[1,3]n[1-2]", "r[2]n[1-2]"),
+            ("r[1-2]n[1,3]", "r[1-2]n[2]"),
+            ("", ""),
+        ];
+        for (expr, expected) in tests {
+            let hostlist = Hostlist::new(expr)?;
+            let gaps = hostlist.gaps()?;
+            assert_eq!(gaps.to_string(), expected, "{expr}");
+            assert!(!gaps.intersects(&hostlist)?, "{expr}");
+        }
+
+        let padded = Hostlist::new_with_padding("n[01-02,05]", Padding::Keep)?;
+        assert_eq!(padded.gaps()?.to_string(), "n[03-04]");
+
+        let huge = Hostlist::new("n[1,1000000000]")?;
+        assert_eq!(huge.gaps()?.len(), 999_999_998);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_next_back() -> Result<()> {
+        for expr in ["n[1-3,7]", "r[1-2]n[1,5-6]x[8-9],login1", "n[08-11]", ""] {
+            let forward: Vec<String> = Hostlist::new(expr)?.collect();
+            let mut backward: Vec<String> = Hostlist::new(expr)?.rev().collect();
+            backward.reverse();
+            assert_eq!(backward, forward, "{expr}");
+            assert_eq!(Hostlist::new(expr)?.last(), forward.last().cloned());
+        }
+
+        // Taking from both ends meets in the middle
+        let mut hostlist = Hostlist::new("m[1-2]x[1-2],n[1-3]")?;
+        assert_eq!(hostlist.next_back(), Some("n3".to_string()));
+        assert_eq!(hostlist.next(), Some("m1x1".to_string()));
+        assert_eq!(hostlist.next_back(), Some("n2".to_string()));
+        assert_eq!(hostlist.len(), 4);
+        assert_eq!(
+            hostlist.by_ref().collect::<Vec<_>>(),
+            ["m1x2", "m2x1", "m2x2", "n1"]
+        );
+        assert_eq!(hostlist.next_back(), None);
+
+        // Far too many hosts to iterate through from the front
+        let mut hostlist = Hostlist::new("n[1-1000000000]m[1-1000]")?;
+        assert_eq!(hostlist.next_back(), Some("n1000000000m1000".to_string()));
+        assert_eq!(hostlist.nth_back(1000), Some("n999999999m999".to_string()));
+        assert_eq!(hostlist.next(), Some("n1m1".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_get() -> Result<()> {
+        for expr in ["n[1-3,7]", "r[1-2]n[1,5-6]x[8-9],login1", "n[08-11],m1", ""] {
+            let hostlist = Hostlist::new(expr)?;
+            let hosts: Vec<String> = hostlist.iter().collect();
+            for (i, host) in hosts.iter().enumerate() {
+                assert_eq!(hostlist.get(i).as_ref(), Some(host), "{expr}");
+            }
+            assert_eq!(hostlist.get(hosts.len()), None, "{expr}");
+        }
+
+        // Indices are relative to the hosts not yet iterated over
+        let mut hostlist = Hostlist::new("m[1-2]x[1-2],n[1-3]")?;
+        hostlist.next();
+        hostlist.next_back();
+        assert_eq!(hostlist.get(0), Some("m1x2".to_string()));
+        assert_eq!(hostlist.get(4), Some("n2".to_string()));
+        assert_eq!(hostlist.get(5), None);
+        assert_eq!(hostlist.get(usize::MAX), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_chunks() -> Result<()> {
+        let tests: [(&str, usize, &[&str]); 6] = [
+            ("n[1-10]", 4, &["n[1-4]", "n[5-8]", "n[9-10]"]),
+            ("n[1-10]", 5, &["n[1-5]", "n[6-10]"]),
+            ("n[1-3]", 10, &["n[1-3]"]),
+            (
+                "m[1-2]x[1-3],n[1-3]",
+                4,
+                &["m[1]x[1-3],m[2]x[1]", "m[2]x[2-3],n[1-2]", "n[3]"],
+            ),
+            ("login1,n[1,5-6]", 2, &["login1,n[1]", "n[5-6]"]),
+            ("", 3, &[]),
+        ];
+        for (expr, size, expected) in tests {
+            let chunks = Hostlist::new(expr)?.chunks(size)?;
+            let chunks: Vec<String> = chunks.iter().map(ToString::to_string).collect();
+            assert_eq!(chunks, expected, "{expr}, {size}");
+        }
+
+        // Every host once, in order, even after iterating
+        let mut hostlist = Hostlist::new("r[1-2]n[1,5-6]x[8-9],login1")?;
+        let hosts = hostlist.to_vec();
+        hostlist.next();
+        let chunked: Vec<String> = hostlist.chunks(5)?.into_iter().flatten().collect();
+        assert_eq!(chunked, hosts);
+
+        // Far too many hosts to expand
+        let chunks = Hostlist::new("n[1-1000000]m[1-1000]")?.chunks(400_000_000)?;
+        let chunks: Vec<String> = chunks.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            chunks,
+            [
+                "n[1-400000]m[1-1000]",
+                "n[400001-800000]m[1-1000]",
+                "n[800001-1000000]m[1-1000]"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_split_into() -> Result<()> {
+        let tests: [(&str, usize, Split, &[&str]); 7] = [
+            (
+                "n[1-10]",
+                3,
+                Split::Contiguous,
+                &["n[1-4]", "n[5-7]", "n[8-10]"],
+            ),
+            (
+                "n[1-10]",
+                3,
+                Split::RoundRobin,
+                &["n[1-10/3]", "n[2-8/3]", "n[3-9/3]"],
+            ),
+            ("n[1-2]", 3, Split::Contiguous, &["n[1]", "n[2]", ""]),
+            ("n[1-2]", 3, Split::RoundRobin, &["n[1]", "n[2]", ""]),
+            (
+                "m[1-2]x[1-2],n[1-2]",
+                2,
+                Split::Contiguous,
+                &["m[1]x[1-2],m[2]x[1]", "m[2]x[2],n[1-2]"],
+            ),
+            (
+                "m[1-2]x[1-2],n[1-2]",
+                2,
+                Split::RoundRobin,
+                &["m[1-2]x[1],n[1]", "m[1-2]x[2],n[2]"],
+            ),
+            ("", 2, Split::RoundRobin, &["", ""]),
+        ];
+        for (expr, n, split, expected) in tests {
+            let groups = Hostlist::new(expr)?.split_into(n, split)?;
+            let groups: Vec<String> = groups.iter().map(ToString::to_string).collect();
+            assert_eq!(groups, expected, "{expr}, {n}, {split:?}");
+        }
+
+        // Every host once, in balanced groups, even after iterating
+        let mut hostlist = Hostlist::new("r[1-2]n[1,5-6]x[8-9],login1")?;
+        let mut hosts = hostlist.to_vec();
+        hostlist.next();
+        for split in [Split::Contiguous, Split::RoundRobin] {
+            let groups = hostlist.split_into(5, split)?;
+            let lens: Vec<usize> = groups.iter().map(Hostlist::len).collect();
+            assert_eq!(lens, [3, 3, 3, 2, 2], "{split:?}");
+            let mut split_hosts: Vec<String> = groups.into_iter().flatten().collect();
+            if split == Split::RoundRobin {
+                split_hosts.sort();
+                hosts.sort();
+            }
+            assert_eq!(split_hosts, hosts, "{split:?}");
+        }
+
+        // Far too many hosts to expand
+        let groups = Hostlist::new("n[1-1000000]m[1-1000]")?.split_into(2, Split::Contiguous)?;
+        let groups: Vec<String> = groups.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            groups,
+            ["n[1-500000]m[1-1000]", "n[500001-1000000]m[1-1000]"]
+        );
+        let groups = Hostlist::new("n[1-1000000]m[1-1000]")?.split_into(4, Split::RoundRobin)?;
+        let groups: Vec<String> = groups.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            groups,
+            [
+                "n[1-1000000]m[1-997/4]",
+                "n[1-1000000]m[2-998/4]",
+                "n[1-1000000]m[3-999/4]",
+                "n[1-1000000]m[4-1000/4]"
+            ]
+        );
+
+        // Rows that don't divide evenly are dealt one at a time, carrying on where the last
+        // row left off
+        let groups = Hostlist::new("m[1-2]x[1-5]")?.split_into(3, Split::RoundRobin)?;
+        let groups: Vec<String> = groups.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            groups,
+            [
+                "m[1]x[1-4/3],m[2]x[2-5/3]",
+                "m[1]x[2-5/3],m[2]x[3]",
+                "m[1]x[3],m[2]x[1-4/3]"
+            ]
+        );
+
+        let hostlist = Hostlist::new("n[1-3]")?;
+        assert_eq!(
+            hostlist
+                .split_into(0, Split::RoundRobin)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidArgument
+        );
+        assert_eq!(
+            hostlist.chunks(0).unwrap_err().kind(),
+            ErrorKind::InvalidArgument
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_iter_sorted() -> Result<()> {
+        let exprs = [
+            "b1,a[2-3]",
+            "n[1-12],n[1-2]x,login[1-2]",
+            "n[1-2][1-12],n[3-4]0[9-10],n[9-10]5",
+            "r[1-2]n[8-10],r[1-2]n[1-2]-ib",
+            "",
+        ];
+        for expr in exprs {
+            let hostlist = Hostlist::new(expr)?;
+            let sorted: Vec<String> = hostlist.iter_sorted().collect();
+            assert_eq!(sorted, crate::sort_hosts(hostlist.iter())?, "{expr}");
+            assert_eq!(hostlist.iter_sorted().len(), sorted.len(), "{expr}");
+        }
+
+        // Padding kept as written, where a range's values aren't ascending
+        let hostlist = Hostlist::new_with_padding("n[01-09,5],n[10-11]", Padding::Keep)?;
+        let sorted: Vec<String> = hostlist.iter_sorted().collect();
+        assert_eq!(sorted, crate::sort_hosts(hostlist.iter())?);
+
+        // Only the hosts not yet iterated over
+        let mut hostlist = Hostlist::new("n[9-10],m1")?;
+        hostlist.next();
+        let sorted: Vec<String> = hostlist.iter_sorted().collect();
+        assert_eq!(sorted, ["n9", "n10"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_first_and_last_host() -> Result<()> {
+        let mut hostlist = Hostlist::new("m[1-2]x[1-2],n[1-3]")?;
+        assert_eq!(hostlist.first_host(), Some("m1x1".to_string()));
+        assert_eq!(hostlist.last_host(), Some("n3".to_string()));
+
+        // Only the hosts not yet iterated over
+        hostlist.next();
+        hostlist.next_back();
+        assert_eq!(hostlist.first_host(), Some("m1x2".to_string()));
+        assert_eq!(hostlist.last_host(), Some("n2".to_string()));
+        assert_eq!(hostlist.len(), 5);
+
+        hostlist.skip_hosts(4);
+        assert_eq!(hostlist.first_host(), Some("n2".to_string()));
+        assert_eq!(hostlist.last_host(), Some("n2".to_string()));
+        hostlist.next();
+        assert_eq!((hostlist.first_host(), hostlist.last_host()), (None, None));
+
+        let hostlist = Hostlist::new("")?;
+        assert_eq!((hostlist.first_host(), hostlist.last_host()), (None, None));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_position_of() -> Result<()> {
+        for expr in [
+            "n[1-3,7]",
+            "r[1-2]n[1,5-6]x[8-9],login1",
+            "n[08-11],m1",
+            "n[9-10,010]",
+        ] {
+            let hostlist = Hostlist::new(expr)?;
+            for (i, host) in hostlist.iter().enumerate() {
+                assert_eq!(hostlist.position_of(&host), Some(i), "{expr}: {host}");
+            }
+        }
+
+        let mut hostlist = Hostlist::new("m[1-2]x[1-2],n[1-3]")?;
+        hostlist.next();
+        hostlist.next_back();
+        assert_eq!(hostlist.position_of("m1x2"), Some(0));
+        assert_eq!(hostlist.position_of("n2"), Some(4));
+        // Already iterated over, or not in the hostlist
+        for host in ["m1x1", "n3", "n4", "n02", "m1", ""] {
+            assert_eq!(hostlist.position_of(host), None, "{host}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_count_and_last() -> Result<()> {
+        let mut hostlist = Hostlist::new("m[1-2]x[1-2],n[1-3]")?;
+        hostlist.next();
+        hostlist.next_back();
+        assert_eq!(hostlist.clone().count(), 5);
+        assert_eq!(hostlist.iter().count(), 5);
+        assert_eq!(hostlist.clone().last(), Some("n2".to_string()));
+        assert_eq!(hostlist.iter().skip(4).count(), 1);
+        assert_eq!(Hostlist::new("")?.count(), 0);
+
+        // Far too many hosts to iterate through
+        let hostlist = Hostlist::new("n[1-1000000000]m[1-1000],login1")?;
+        assert_eq!(hostlist.iter().count(), 1_000_000_000_001);
+        assert_eq!(hostlist.clone().count(), 1_000_000_000_001);
+        assert_eq!(hostlist.last(), Some("n1000000000m1000".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_nth() -> Result<()> {
+        for expr in ["n[1-3,7]", "r[1-2]n[1,5-6]x[8-9],login1", "n[08-11],m1"] {
+            let hosts: Vec<String> = Hostlist::new(expr)?.collect();
+            for start in 0..hosts.len() {
+                for n in 0..=hosts.len() {
+                    let mut hostlist = Hostlist::new(expr)?;
+                    hostlist.skip_hosts(start);
+                    assert_eq!(hostlist.nth(n), hosts.get(start + n).cloned(), "{expr}");
+                    assert_eq!(
+                        hostlist.collect::<Vec<_>>(),
+                        hosts.get(start + n + 1..).unwrap_or_default(),
+                        "{expr}"
+                    );
+                }
+            }
+        }
+
+        // Skipping after iterating and taking from the back
+        let mut hostlist = Hostlist::new("m[1-2]x[1-2],n[1-3]")?;
+        hostlist.next();
+        hostlist.next_back();
+        hostlist.skip_hosts(2);
+        assert_eq!(hostlist.len(), 3);
+        assert_eq!(hostlist.collect::<Vec<_>>(), ["m2x2", "n1", "n2"]);
+
+        // Far too many hosts to iterate through
+        let mut hostlist = Hostlist::new("a1,n[1-1000000000]m[1-1000]")?;
+        assert_eq!(hostlist.nth(1_000_000), Some("n1000m1000".to_string()));
+        hostlist.skip_hosts(999_998_999_000);
+        assert_eq!(hostlist.next(), Some("n1000000000m1".to_string()));
+        hostlist.skip_hosts(usize::MAX);
+        assert_eq!(hostlist.next(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_size_hint() -> Result<()> {
+        let mut hostlist = Hostlist::new("m[1-2]x[1-3],n[1-4],login1")?;
+        assert_eq!(hostlist.size_hint(), (11, Some(11)));
+        hostlist.next();
+        hostlist.next_back();
+        hostlist.nth(3);
+        assert_eq!(hostlist.size_hint(), (5, Some(5)));
+        assert_eq!(ExactSizeIterator::len(&hostlist), 5);
+        assert_eq!(hostlist.by_ref().count(), 5);
+        assert_eq!(hostlist.size_hint(), (0, Some(0)));
+
+        let hosts: Vec<String> = Hostlist::new("n[1-100]")?.collect();
+        assert!(hosts.capacity() >= 100);
+
+        let huge = Hostlist::new("n[1-1000000000]m[1-1000]")?;
+        assert_eq!(
+            huge.size_hint(),
+            (1_000_000_000_000, Some(1_000_000_000_000))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_host_iter() -> Result<()> {
+        let exprs = [
+            "n[1-3,7]",
+            "r[1-2,5]n[1,5-6]x[8-9],login1",
+            "n[08-11],m1",
+            "",
+        ];
+        for expr in exprs {
+            let hostlist = Hostlist::new(expr)?;
+            let hosts: Vec<String> = hostlist.clone().collect();
+            assert_eq!(hostlist.iter().collect::<Vec<_>>(), hosts, "{expr}");
+            assert_eq!((&hostlist).into_iter().len(), hosts.len(), "{expr}");
+            let mut backward: Vec<String> = hostlist.iter().rev().collect();
+            backward.reverse();
+            assert_eq!(backward, hosts, "{expr}");
+            for n in 0..=hosts.len() {
+                let mut iter = hostlist.iter();
+                assert_eq!(iter.nth(n), hosts.get(n).cloned(), "{expr}");
+                assert_eq!(iter.len(), hosts.len().saturating_sub(n + 1), "{expr}");
+                assert_eq!(iter.next(), hosts.get(n + 1).cloned(), "{expr}");
+            }
+        }
+
+        // Taking from both ends, from where the hostlist's own iteration is up to
+        let mut hostlist = Hostlist::new("m[1-2]x[1-2],n[1-3]")?;
+        hostlist.next();
+        hostlist.next_back();
+        let mut iter = hostlist.iter();
+        assert_eq!(iter.next_back(), Some("n2".to_string()));
+        assert_eq!(iter.next(), Some("m1x2".to_string()));
+        assert_eq!(iter.clone().collect::<Vec<_>>(), ["m2x1", "m2x2", "n1"]);
+        assert_eq!(iter.nth(2), Some("n1".to_string()));
+        assert_eq!((iter.next(), iter.next_back()), (None, None));
+        assert_eq!(hostlist.len(), 5);
+
+        // Reusing one buffer, mixed with the other ways of taking hosts
+        let mut iter = hostlist.iter();
+        let mut buf = String::new();
+        assert!(iter.next_into(&mut buf));
+        assert_eq!(iter.next_back(), Some("n2".to_string()));
+        buf.clear();
+        assert!(iter.next_into(&mut buf));
+        assert_eq!(buf, "m2x1");
+        assert_eq!(iter.nth(1), Some("n1".to_string()));
+        assert!(!iter.next_into(&mut buf));
+        assert_eq!(buf, "m2x1");
+
+        // Far too many hosts to iterate through
+        let hostlist = Hostlist::new("n[1-1000000000]m[1-1000]")?;
+        let mut iter = hostlist.iter();
+        assert_eq!(
+            iter.nth(999_999_999_998),
+            Some("n1000000000m999".to_string())
+        );
+        assert_eq!(hostlist.iter().last(), Some("n1000000000m1000".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_exclude_expr() -> Result<()> {
+        let mut hostlist = Hostlist::new("n[1-10],m[1-3]x[1-3]")?;
+        hostlist.next();
+        hostlist.exclude_expr("n[2-9],m2x2,gpu1")?;
+        assert_eq!(hostlist.len(), 10);
+        assert_eq!(hostlist.next(), Some("m1x1".to_string()));
+        assert!(!hostlist.contains("m2x2") && hostlist.contains("m3x2"));
+
+        assert!(hostlist.exclude_expr("n[1-").is_err());
+        assert_eq!(hostlist.len(), 9);
+
+        hostlist.exclude_expr("")?;
+        assert_eq!(hostlist.len(), 10);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_push_expr_and_host() -> Result<()> {
+        let mut hostlist = Hostlist::new("n[1-4],m[1-2]x[1-2]")?;
+        hostlist.next();
+        hostlist.push_expr("n[3-8],m[1-2]x3")?;
+        assert_eq!(hostlist.to_string(), "m[1-2]x[1-2],m[1-2]x3,n[1-8]");
+        assert_eq!(hostlist.len(), 14);
+        assert_eq!(hostlist.next(), Some("m1x1".to_string()));
+
+        hostlist.push_host("n9")?;
+        hostlist.push_host("login1")?;
+        hostlist.push_host("m3x3")?;
+        assert_eq!(
+            hostlist.to_string(),
+            "login1,m[1-2]x[1-2],m[1-2]x3,m3x3,n[1-9]"
+        );
+
+        // Padding is kept, like in `new()`
+        let mut padded = Hostlist::new("n[1-8]")?;
+        padded.push_host("n09")?;
+        assert_eq!(padded.to_string(), "n[1-8],n09");
+
+        // Text after the number stays after the range
+        let mut suffixed = Hostlist::new("gpu[1-8]-ib")?;
+        suffixed.push_host("gpu9-ib")?;
+        assert_eq!(suffixed.to_string(), "gpu[1-9]-ib");
+
+        let before = hostlist.clone();
+        assert!(hostlist.push_expr("n[1-").is_err());
+        assert!(hostlist.push_host("n[1-2]").is_err());
+        assert!(hostlist.push_host("n1,n2").is_err());
+        assert_eq!(
+            hostlist.push_host(""),
+            Err(Error::InvalidHostname(String::new()))
+        );
+        assert_eq!(hostlist, before);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_remove_host() -> Result<()> {
+        let mut hostlist = Hostlist::new("n[1-5],m[1-2]x[1-3],login1")?;
+        hostlist.next();
+        assert!(hostlist.remove_host("n3")?);
+        assert!(hostlist.remove_host("m2x2")?);
+        assert!(hostlist.remove_host("login1")?);
+        assert_eq!(hostlist.len(), 9);
+        assert_eq!(hostlist.next(), Some("m1x1".to_string()));
+        assert!(!hostlist.contains("m2x2") && hostlist.contains("m2x3"));
+        assert_eq!(hostlist.iter().filter(|h| h.starts_with('n')).count(), 4);
+
+        assert!(!hostlist.remove_host("n3")?);
+        assert!(!hostlist.remove_host("gpu1")?);
+        assert!(hostlist.remove_host("n[1-2]").is_err());
+        assert_eq!(
+            hostlist.remove_host(""),
+            Err(Error::InvalidHostname(String::new()))
+        );
+        assert_eq!(hostlist.len(), 8);
+
+        // Only the host itself goes, even if a larger range shares its prefix
+        let mut hostlist = Hostlist::new("n[1-20]")?;
+        assert!(hostlist.remove_host("n1")?);
+        assert_eq!(hostlist.to_string(), "n[2-20]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_retain() -> Result<()> {
+        let mut hostlist = Hostlist::new("n[1-10],m[1-2]x[1-3],login1")?;
+        hostlist.next();
+        hostlist.retain(|host| !matches!(host, "n1" | "n5" | "n6" | "m2x2" | "login1"))?;
+        assert_eq!(hostlist.len(), 12);
+        assert!(!hostlist.contains("m2x2") && hostlist.contains("m2x3"));
+        assert_eq!(
+            hostlist.iter().filter(|host| host.starts_with('n')).count(),
+            7
+        );
+        assert!(hostlist.to_string().ends_with("n[2-4,7-10]"));
+
+        hostlist.retain(|host| host.starts_with('n'))?;
+        assert_eq!(hostlist.to_string(), "n[2-4,7-10]");
+
+        hostlist.retain(|_| false)?;
+        assert!(hostlist.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_intersects() -> Result<()> {
+        let tests = [
+            ("n[1-10]", "n[10-20]", true),
+            ("n[1-10]", "n[11-20]", false),
+            ("n[1-4]m[1-2]", "n[2-3]m3,n4m2", true),
+            ("n[1-4]m[1-2]", "n[2-3]m3,n5m2", false),
+            ("login1,n[1-3]", "login1", true),
+            ("n[1-3]", "n2", true),
+            ("n[1-3]", "", false),
+            ("n[6,10-13]", "n[1-5,9-13][2]", true),
+            ("n[6,10-11,13]", "n[1-5,9-13][2]", false),
+            ("n[1-20]5", "n1[0-9]5", true),
+        ];
+        for (a, b, expected) in tests {
+            let (a, b) = (Hostlist::new(a)?, Hostlist::new(b)?);
+            assert_eq!(a.intersects(&b)?, expected, "{a} & {b}");
+            assert_eq!(b.intersects(&a)?, expected, "{b} & {a}");
+            assert_eq!(!a.intersection(&b)?.is_empty(), expected, "{a} & {b}");
+            assert_eq!(a.is_disjoint(&b)?, !expected, "{a} & {b}");
+        }
+
+        // Far too many hosts to expand, overlapping only at the end
+        let a = Hostlist::new("n[1-1000000000]m[1-1000]")?;
+        assert!(a.intersects(&Hostlist::new("n1000000000m[1000-2000]")?)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_set_operators() -> Result<()> {
+        let a = Hostlist::new("n[1-6],login")?;
+        let b = Hostlist::new("n[4-9]")?;
+        assert_eq!((&a | &b)?.to_string(), "login,n[1-9]");
+        assert_eq!((&a & &b)?.to_string(), "n[4-6]");
+        assert_eq!((&a - &b)?.to_string(), "login,n[1-3]");
+        assert_eq!((&a ^ &b)?.to_string(), "login,n[1-3,7-9]");
+        assert_eq!(((&a - &b)? | (&b - &a)?)?, (&a ^ &b)?);
+        assert_eq!((a.clone() ^ a)?.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_eq_hosts() -> Result<()> {
+        let equal = [
+            ("node[1-2]", "node1,node2"),
+            ("node[1-2]", "node2,node1,node1"),
+            ("a[1-2]b[1-2]", "a1b1,a2b1,a[1-2]b2"),
+            ("n[1-3],login", "login,n[1-3]"),
+            ("", ""),
+        ];
+        for (a, b) in equal {
+            let (a, b) = (Hostlist::new(a)?, Hostlist::new(b)?);
+            assert!(a.eq_hosts(&b), "{a} == {b}");
+            assert!(b.eq_hosts(&a), "{b} == {a}");
+        }
+
+        let unequal = [
+            ("node[1-2]", "node[1-3]"),
+            ("node1", ""),
+            ("n[1-2]", "n[01-02]"),
+        ];
+        for (a, b) in unequal {
+            let (a, b) = (
+                Hostlist::new_with_padding(a, Padding::Keep)?,
+                Hostlist::new_with_padding(b, Padding::Keep)?,
+            );
+            assert!(!a.eq_hosts(&b), "{a} != {b}");
+            assert!(!b.eq_hosts(&a), "{b} != {a}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_add_sub_str() -> Result<()> {
+        let hostlist = ((Hostlist::new("n[1-4]")? + "n[3-8],m1")? - "n[2,5]")?;
+        assert_eq!(hostlist.to_string(), "m1,n[1,3-4,6-8]");
+        assert!(matches!(hostlist + "n[", Err(Error::ParseError(_))));
+
+        Ok(())
     }
 
     #[test]
-    fn test_hostlist_iter5() -> Result<()> {
-        let mut hostlist: Hostlist = "n[1-5]".parse()?;
+    fn test_hostlist_from_hosts() -> Result<()> {
+        let tests: [(&[&str], &str); 5] = [
+            (&[], ""),
+            (&["n3", "n1", "n2", "n2"], "n[1-3]"),
+            (&["n01", "n02", "login"], "login,n[01-02]"),
+            (&["gpu1-ib", "gpu2-ib", "r1n1"], "gpu[1-2]-ib,r1n1"),
+            (&["a[1]", "a,b"], "a\\,b,a\\[1\\]"),
+        ];
+        for (hosts, expected) in tests {
+            let hostlist = Hostlist::from_hosts(hosts)?;
+            assert_eq!(hostlist.to_string(), expected, "hosts: {hosts:?}");
+            assert_eq!(
+                hostlist.iter().collect::<BTreeSet<_>>(),
+                hosts.iter().map(ToString::to_string).collect(),
+                "hosts: {hosts:?}"
+            );
+        }
 
-        assert_eq!(hostlist.len(), 5);
-        assert_eq!(hostlist.next(), Some("n1".to_string()));
-        assert_eq!(hostlist.len(), 4);
-        assert_eq!(hostlist.next(), Some("n2".to_string()));
-        assert_eq!(hostlist.len(), 3);
-        assert_eq!(hostlist.next(), Some("n3".to_string()));
-        assert_eq!(hostlist.len(), 2);
-        assert_eq!(hostlist.next(), Some("n4".to_string()));
-        assert_eq!(hostlist.len(), 1);
-        assert_eq!(hostlist.next(), Some("n5".to_string()));
-        assert_eq!(hostlist.len(), 0);
-        assert_eq!(hostlist.next(), None);
-        assert_eq!(hostlist.len(), 0);
+        // Built without parsing, but the same as parsing the collapsed expression
+        let hosts = [
+            "n1.dc1.example.com",
+            "n2.dc1.example.com",
+            "n9",
+            "n007",
+            "x",
+            "r1n3",
+        ];
+        let hostlist = Hostlist::from_hosts(hosts)?;
+        assert_eq!(hostlist, Hostlist::new(&collapse_hosts(hosts)?)?);
+        assert_eq!(hostlist.len(), hosts.len());
 
-        #[allow(clippy::useless_conversion)]
-        let mut hostlist_iter = hostlist.into_iter();
-        assert!(hostlist_iter.next().is_none());
+        assert_eq!(
+            Hostlist::from_hosts(["n1", ""]),
+            Err(Error::InvalidHostname(String::new()))
+        );
 
         Ok(())
     }
 
     #[test]
-    fn test_hostlist_iter6() -> Result<()> {
-        let mut hostlist: Hostlist = "n[1-5]".parse()?;
+    fn test_hostlist_from_iterator() -> Result<()> {
+        let hostlist: Hostlist = ["n3", "n1", "n2", "gpu1-ib"].into_iter().collect();
+        assert_eq!(hostlist.to_string(), "gpu1-ib,n[1-3]");
+        let hostlist: Hostlist = Vec::<String>::new().into_iter().collect();
+        assert!(hostlist.is_empty());
 
-        assert_eq!(hostlist.len(), 5);
-        assert_eq!(hostlist.next(), Some("n1".to_string()));
-        assert_eq!(hostlist.len(), 4);
-        assert_eq!(hostlist.next(), Some("n2".to_string()));
-        assert_eq!(hostlist.len(), 3);
-        assert_eq!(hostlist.next(), Some("n3".to_string()));
-        assert_eq!(hostlist.len(), 2);
-        assert_eq!(hostlist.next(), Some("n4".to_string()));
-        assert_eq!(hostlist.len(), 1);
+        let hosts: std::result::Result<Hostlist, &str> =
+            [Ok("n1"), Err("bad line"), Ok("n2")].into_iter().collect();
+        assert_eq!(hosts, Err("bad line"));
 
-        let expected_orig = vec!["n5"];
+        let mut hostlist = Hostlist::new("n[1-8],m[1-2]")?;
+        hostlist.next();
+        hostlist.extend(["n9", "n10", "m1"]);
+        assert_eq!(hostlist.to_string(), "m[1-2],n[1-10]");
+        assert_eq!(hostlist.len(), 12);
+        hostlist.extend(Vec::<String>::new());
+        assert_eq!(hostlist.len(), 12);
 
-        let mut expected = expected_orig.clone();
-        for host in &hostlist {
-            let e = expected.remove(0);
-            assert_eq!(e, host);
+        // Blank names are skipped rather than panicking
+        let hostlist: Hostlist = ["n1", "", "  ", "\t", "n2"].into_iter().collect();
+        assert_eq!(hostlist.to_string(), "n[1-2]");
+        let mut hostlist = Hostlist::new("n[1-2]")?;
+        hostlist.extend(["", "n3", " "]);
+        assert_eq!(hostlist.to_string(), "n[1-3]");
+
+        // Invalid names are an error, leaving the hostlist as it was
+        assert!(hostlist.try_extend(["n4", "n?"]).is_err());
+        assert_eq!(hostlist.to_string(), "n[1-3]");
+        hostlist.try_extend(["n4", ""])?;
+        assert_eq!(hostlist.to_string(), "n[1-4]");
+
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "can't add host names")]
+    fn test_hostlist_extend_invalid() {
+        let mut hostlist = Hostlist::new("n1").unwrap();
+        hostlist.extend(["n2", "n?"]);
+    }
+
+    #[test]
+    fn test_hostlist_union_all() -> Result<()> {
+        let inputs = [
+            vec![],
+            vec!["n[1-3]"],
+            vec!["n[1-3]", "n[2-8]", "n[10]", "m1"],
+            vec!["a[1-2]b[1-2]", "a[2-3]b[1-3]", "a2b2", "a[1-3]"],
+            vec!["n5", "n[1-10]", "n5", "login", "login"],
+        ];
+
+        for input in inputs {
+            let expected = input
+                .iter()
+                .map(|expr| Hostlist::new(expr))
+                .try_fold(Hostlist::new("")?, |acc, hostlist| acc.union(&hostlist?))?;
+            let union = Hostlist::union_all_exprs(&input)?;
+            assert_eq!(union.len(), expected.len(), "input: {input:?}");
+            assert_eq!(
+                union.iter().collect::<BTreeSet<_>>(),
+                expected.iter().collect::<BTreeSet<_>>(),
+                "input: {input:?}"
+            );
         }
-        assert!(expected.is_empty());
+        assert_eq!(
+            Hostlist::union_all_exprs(["n5", "n[1-10]", "n5"])?.to_string(),
+            "n[1-10]"
+        );
 
-        // hostlist is unchanged
-        assert_eq!(hostlist.len(), 1);
+        // Many small hostlists, out of order
+        let exprs: Vec<String> = (0..5_000)
+            .map(|i| format!("n{}", (i * 7919) % 5_000))
+            .collect();
+        let ranged: Vec<String> = (0..5_000).rev().map(|i| format!("n[{i}]")).collect();
+        assert_eq!(Hostlist::union_all_exprs(&exprs)?.len(), 5_000);
+        assert_eq!(Hostlist::union_all_exprs(&ranged)?.to_string(), "n[0-4999]");
 
-        // and can still be modified
-        assert_eq!(hostlist.next(), Some("n5".to_string()));
-        assert_eq!(hostlist.len(), 0);
-        assert_eq!(hostlist.next(), None);
-        assert_eq!(hostlist.len(), 0);
+        assert!(Hostlist::union_all_exprs(["n1", "n["]).is_err());
 
         Ok(())
     }
 
     #[test]
-    fn test_hostlist_invalid() {
+    fn test_hostlist_normalize_elements() -> Result<()> {
         let inputs = [
-            ("[1-3]", "no prefix"),
-            ("node[1-", "unclosed bracket range"),
-            ("node]1-3[", "inverted brackets"),
-            ("node[3-1]", "reversed range"),
-            ("node[1,]", "trailing comma"),
-            ("node[,1]", "leading comma"),
-            ("node[1,,3]", "double comma"),
-            ("node[1-3", "missing closing bracket"),
-            ("node1-3]", "missing opening bracket"),
-            ("node[a-3]", "non-numeric character in range"),
-            ("node[-1-3]", "negative number in range"),
-            ("node[1.5-3]", "non-integer in range"),
-            ("node[1--3]", "double hyphen in range"),
-            ("node[[1-3]]", "nested brackets"),
-            ("node[1:2]", "using colon instead of hyphen for range"),
+            ("", vec![]),
+            ("n[3,1-2],m1", vec!["n[1-3]", "m1"]),
+            (" n[5], n[1-3,2] ,n[5]", vec!["n[5]", "n[1-3]", "n[5]"]),
+            ("n[01-03]x", vec!["n[01-03]x"]),
         ];
 
-        for (input, description) in inputs {
-            let result = input.parse::<Hostlist>();
-            if let Ok(hostlist) = result {
-                panic!("Failure on '{input}' ({description}). Hostlist parsed to: '{hostlist}'.");
-            }
+        for (input, expected) in inputs {
+            assert_eq!(Hostlist::normalize_elements(input)?, expected);
         }
+        assert!(matches!(
+            Hostlist::normalize_elements("n[1-3],m[3-1]"),
+            Err(Error::InElement { index: 1, .. })
+        ));
+
+        Ok(())
     }
 
     #[test]
-    fn test_hostlist_valid() -> Result<()> {
+    fn test_hostlist_padding_round_trip() -> Result<()> {
+        let exprs = [
+            "node[04-06]",
+            "node[098-102]",
+            "n[1-010]",
+            "n[00-10]",
+            "n[9,09,009]",
+            "n[01-03],n[1-3]",
+            "n0[1-3]",
+            "n[1-3]0",
+            "r[01-02]n[001-002]-ib",
+        ];
+        for expr in exprs {
+            let hostlist = Hostlist::new(expr)?;
+            let reparsed = Hostlist::new(&hostlist.to_string())?;
+            assert_eq!(reparsed, hostlist, "{expr}");
+            assert_eq!(reparsed.to_vec(), hostlist.to_vec(), "{expr}");
+            assert_eq!(hostlist, Hostlist::new_with_padding(expr, Padding::Keep)?);
+        }
+
+        assert_eq!(Hostlist::new("node[04-06]")?.to_string(), "node[04-06]");
+        let stripped = Hostlist::new_with_padding("node[04-06]", Padding::Strip)?;
+        assert_eq!(stripped.to_string(), "node[4-6]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_padding_across_width_boundary() -> Result<()> {
+        let hostlist = Hostlist::new_with_padding("node[098-102]", Padding::Keep)?;
+        assert_eq!(hostlist.to_string(), "node[098-102]");
+        assert_eq!(
+            hostlist.to_vec(),
+            ["node098", "node099", "node100", "node101", "node102"]
+        );
+        assert!(hostlist.contains("node098") && hostlist.contains("node100"));
+        assert!(!hostlist.contains("node98") && !hostlist.contains("node0100"));
+        assert_eq!(hostlist.position_of("node101"), Some(3));
+        assert_eq!(hostlist.get(1), Some("node099".to_string()));
+        assert_eq!(hostlist.estimated_output_bytes(), 35);
+
+        // Only padded numbers change width, so an unpadded start doesn't pad the range
+        let hostlist = Hostlist::new_with_padding("node[98-102]", Padding::Keep)?;
+        assert_eq!(hostlist.first_host(), Some("node98".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_new_with_padding() -> Result<()> {
         let inputs = [
-            ("node[1-3]", vec!["node1", "node2", "node3"]),
-            ("node[01-03]", vec!["node1", "node2", "node3"]),
-            ("node[04-06]", vec!["node4", "node5", "node6"]),
-            ("compute[1,3,5]", vec!["compute1", "compute3", "compute5"]),
             (
-                "server[1-3,5,7-9]",
-                vec![
-                    "server1", "server2", "server3", "server5", "server7", "server8", "server9",
-                ],
+                "n[01-03],m[1-2]",
+                Padding::Strip,
+                "m[1-2],n[1-3]",
+                "m1 m2 n1 n2 n3",
             ),
             (
-                "host[1-3]-rack[1-2]",
-                vec![
-                    "host1-rack1",
-                    "host1-rack2",
-                    "host2-rack1",
-                    "host2-rack2",
-                    "host3-rack1",
-                    "host3-rack2",
-                ],
+                "n[01-03],m[1-2]",
+                Padding::Keep,
+                "m[1-2],n[01-03]",
+                "m1 m2 n01 n02 n03",
             ),
             (
-                "node[4-6,8,10-12]",
-                vec![
-                    "node4", "node5", "node6", "node8", "node10", "node11", "node12",
-                ],
+                "n[098-101]",
+                Padding::Keep,
+                "n[098-101]",
+                "n098 n099 n100 n101",
             ),
+            ("n[1,01]", Padding::Keep, "n[1,01]", "n1 n01"),
+            ("n[1,01],n[001]", Padding::Width(2), "n[01]", "n01"),
             (
-                "prefix[1-3]suffix",
-                vec!["prefix1suffix", "prefix2suffix", "prefix3suffix"],
+                "n[8-10]x,n7",
+                Padding::Width(2),
+                "n[08-10]x,n7",
+                "n08x n09x n10x n7",
+            ),
+        ];
+
+        for (input, padding, expected, hosts) in inputs {
+            let hostlist = Hostlist::new_with_padding(input, padding)?;
+            assert_eq!(hostlist.to_string(), expected, "{input} {padding:?}");
+            assert_eq!(hostlist.to_vec().join(" "), hosts, "{input} {padding:?}");
+            assert_eq!(hostlist.len(), hostlist.to_vec().len());
+            assert_eq!(
+                hostlist.estimated_output_bytes(),
+                hostlist.to_vec().iter().map(String::len).sum::<usize>()
+            );
+        }
+
+        let padded = Hostlist::new_with_padding("n[01-10]", Padding::Keep)?;
+        assert!(padded.contains("n05") && padded.contains("n10"));
+        assert!(!padded.contains("n5"));
+        let stripped = Hostlist::new("n[1-5]")?;
+        assert_eq!(padded.intersection(&stripped)?.len(), 0);
+        assert_eq!(padded.difference(&Hostlist::new("n05,n5")?)?.len(), 9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_step() -> Result<()> {
+        let inputs = [
+            ("n[0-10/2]", "n[0-10/2]", "n0 n2 n4 n6 n8 n10"),
+            ("n[1-10/3]", "n[1-10/3]", "n1 n4 n7 n10"),
+            ("n[1-9/4],n20", "n[1-9/4],n20", "n1 n5 n9 n20"),
+            ("n[0-8/4,10-12/2]", "n[0-8/4,10-12/2]", "n0 n4 n8 n10 n12"),
+            ("n[08-12/2]", "n[08-12/2]", "n08 n10 n12"),
+            ("r[0-2/2]n[1-2]", "r[0-2/2]n[1-2]", "r0n1 r0n2 r2n1 r2n2"),
+            ("n[5-6/4]", "n[5]", "n5"),
+            // Values next to a stepped range split it into single values
+            ("n[0-6/2],n[1]", "n[0-2,4,6]", "n0 n1 n2 n4 n6"),
+            ("n[0-6/2,1-7/2]", "n[0-7]", "n0 n1 n2 n3 n4 n5 n6 n7"),
+        ];
+
+        for (input, expected, hosts) in inputs {
+            let hostlist = Hostlist::new(input)?;
+            assert_eq!(hostlist.to_string(), expected, "input: {input}");
+            assert_eq!(hostlist.to_vec().join(" "), hosts, "input: {input}");
+            assert_eq!(hostlist.len(), hostlist.to_vec().len(), "input: {input}");
+            assert_eq!(
+                hostlist.estimated_output_bytes(),
+                hostlist.to_vec().iter().map(String::len).sum::<usize>(),
+                "input: {input}"
+            );
+            assert_eq!(Hostlist::new(expected)?.to_vec(), hostlist.to_vec());
+        }
+
+        let hostlist = Hostlist::new("n[0-10/2]")?;
+        assert_eq!(hostlist.last_host(), Some("n10".to_string()));
+        assert_eq!(HostSet::from_hostlist(&hostlist)?.len(), 6);
+        assert_eq!(hostlist.get(2), Some("n4".to_string()));
+        assert_eq!(hostlist.position_of("n6"), Some(3));
+        assert_eq!(hostlist.position_of("n5"), None);
+        assert!(hostlist.contains("n8") && !hostlist.contains("n7"));
+        assert_eq!(hostlist.to_regex(), "^n(0|2|4|6|8|10)$");
+
+        let odd = Hostlist::new("n[1-9/2]")?;
+        assert_eq!(hostlist.union(&odd)?.to_string(), "n[0-10]");
+        let first = Hostlist::new("n[0-4]")?;
+        assert_eq!(hostlist.intersection(&first)?.to_string(), "n[0,2,4]");
+        assert_eq!(hostlist.difference(&first)?.to_string(), "n[6,8,10]");
+
+        let chunks: Vec<String> = hostlist
+            .chunks(4)?
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(chunks, ["n[0-6/2]", "n[8-10/2]"]);
+
+        assert!(matches!(
+            Hostlist::new("n[0-10/0]"),
+            Err(Error::ParseError(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_letters() -> Result<()> {
+        let inputs = [
+            ("rack[a-c]", "racka,rackb,rackc"),
+            (
+                "rack[a-b]node[1-2]",
+                "rackanode1,rackanode2,rackbnode1,rackbnode2",
             ),
+            ("r[a,c]-n[1-2]", "ra-n1,ra-n2,rc-n1,rc-n2"),
+            ("r[A-B]", "rA,rB"),
+            ("c[y-ab]", "caa,cab,cy,cz"),
+            ("r[a-b]c[x-y]", "racx,racy,rbcx,rbcy"),
+            ("r[b-c],ra", "ra,rb,rc"),
+        ];
+
+        for (input, hosts) in inputs {
+            let hostlist = Hostlist::new(input)?;
+            assert_eq!(hostlist.to_vec().join(","), hosts, "input: {input}");
+        }
+
+        // Elements from letters combine with elements written without them
+        let hostlist = Hostlist::new("rack[a-b]n[1-2],rackan[3-4]")?;
+        assert_eq!(hostlist.to_string(), "rackan[1-4],rackbn[1-2]");
+        assert!(hostlist.contains("rackbn2"));
+
+        assert_eq!(
+            Hostlist::new("r[c-a]").unwrap_err().kind(),
+            ErrorKind::InvalidRangeReversed
+        );
+        assert!(matches!(Hostlist::new("r[a-B]"), Err(Error::ParseError(_))));
+        assert!(matches!(Hostlist::new("r[a-1]"), Err(Error::ParseError(_))));
+        assert!(matches!(
+            Hostlist::new("n1,r[a-zzzzzzzzzzzzzzzz]").unwrap_err(),
+            Error::InElement { index: 1, .. }
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_apply_padding() -> Result<()> {
+        let inputs = [
+            ("n1,n2,n3", 3, "n[001-003]", "n001 n002 n003"),
+            ("n[9-10],n11", 2, "n[09-11]", "n09 n10 n11"),
+            ("r1n[1-2]", 2, "r[01]n[01-02]", "r01n01 r01n02"),
+            ("n[099-100]x5", 2, "n[99-100]x[05]", "n99x05 n100x05"),
+            ("1n2,login", 2, "1n[02],login", "1n02 login"),
             (
-                "node[1-3],server[1-2]",
-                vec!["node1", "node2", "node3", "server1", "server2"],
+                "n99999999999999999999",
+                2,
+                "n99999999999999999999",
+                "n99999999999999999999",
             ),
+        ];
+
+        for (input, width, expected, hosts) in inputs {
+            let hostlist = Hostlist::new(input)?.apply_padding(width)?;
+            assert_eq!(hostlist.to_string(), expected, "input: {input}");
+            assert_eq!(hostlist.to_vec().join(" "), hosts, "input: {input}");
+        }
+
+        // Padding can be removed again from every number
+        let hostlist = Hostlist::new_with_padding("n01,n[02-03]", Padding::Keep)?;
+        assert_eq!(hostlist.apply_padding(0)?.to_string(), "n[1-3]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_pad_to() -> Result<()> {
+        let inputs = [
+            ("n[1-3]", 3, "n[001-003]", "n001 n002 n003"),
+            ("n[9-10]x[1]", 2, "n[09-10]x[01]", "n09x01 n10x01"),
+            ("n[99-100]", 2, "n[99-100]", "n99 n100"),
+            ("n7,m[1-2]", 2, "m[01-02],n7", "m01 m02 n7"),
+        ];
+
+        for (input, width, expected, hosts) in inputs {
+            let hostlist = Hostlist::new(input)?.pad_to(width)?;
+            assert_eq!(hostlist.to_string(), expected);
+            assert_eq!(hostlist.to_vec().join(" "), hosts);
+        }
+
+        // Padding can be removed again, combining elements that only differed in padding
+        let hostlist = Hostlist::new_with_padding("n[01-02],n[3]", Padding::Keep)?;
+        assert_eq!(hostlist.to_string(), "n[01-02,3]");
+        assert_eq!(hostlist.pad_to(0)?.to_string(), "n[1-3]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_iter_parts() -> Result<()> {
+        let inputs = [
+            "",
+            "login",
+            "n[1-3],login[1-2]x",
+            "r[1-2]n[08-10]",
+            "a[1-2]b[1-2]c",
+        ];
+
+        for input in inputs {
+            for padding in [Padding::Strip, Padding::Keep] {
+                let hostlist = Hostlist::new_with_padding(input, padding)?;
+                let hosts: Vec<String> = hostlist
+                    .iter_parts()
+                    .map(|parts| parts.iter().map(ToString::to_string).collect())
+                    .collect();
+                assert_eq!(hosts, hostlist.to_vec(), "input: {input}");
+            }
+        }
+
+        let mut hostlist = Hostlist::new_with_padding("r[1-2]n[08-09]", Padding::Keep)?;
+        hostlist.next();
+        assert_eq!(
+            hostlist.iter_parts().next(),
+            Some(vec![
+                HostPart::Literal("r".into()),
+                HostPart::Number { value: 1, width: 0 },
+                HostPart::Literal("n".into()),
+                HostPart::Number { value: 9, width: 2 },
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_memory_usage() -> Result<()> {
+        let empty = Hostlist::new("")?;
+        assert_eq!(empty.memory_usage(), size_of::<Hostlist>());
+
+        let mut hostlist = Hostlist::new("node[1-3],node[5-7],node[9],login[1-2]")?;
+        let before = hostlist.memory_usage();
+        assert!(before > Hostlist::new("node1")?.memory_usage());
+        hostlist.shrink_to_fit();
+        assert!(hostlist.memory_usage() <= before);
+        assert_eq!(hostlist.to_string(), "login[1-2],node[1-3,5-7,9]");
+
+        // Iterating caches the latest host
+        let shrunk = hostlist.memory_usage();
+        hostlist.next();
+        assert!(hostlist.memory_usage() > shrunk);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_estimated_output_bytes() -> Result<()> {
+        let inputs = [
+            "",
+            "node",
+            "node[1-3],node[5],gpu[1-4]",
+            "n[1-12]m[9-11]x,login1",
+            "rack[1-2]-node[098-101]",
+            "a[1-3]b[1-3]c[1-3]",
+        ];
+
+        for input in inputs {
+            let mut hostlist = Hostlist::new(input)?;
+            let expected: usize = hostlist.to_vec().iter().map(String::len).sum();
+            assert_eq!(
+                hostlist.estimated_output_bytes(),
+                expected,
+                "input: {input}"
+            );
+
+            // Hosts already iterated over still count
+            hostlist.next();
+            assert_eq!(
+                hostlist.estimated_output_bytes(),
+                expected,
+                "input: {input}"
+            );
+        }
+
+        assert_eq!(
+            Hostlist::new("n[1-1000000000]")?.estimated_output_bytes(),
+            9_888_888_899
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_fingerprints() -> Result<()> {
+        let inputs = [
             ("", vec![]),
-            ("singlenode", vec!["singlenode"]),
-            ("node[0-0]", vec!["node0"]),
-            ("node[42]", vec!["node42"]),
+            ("node", vec!["node"]),
+            ("node[1-3],node[5],gpu[1-4]", vec!["gpu[]", "node[]"]),
+            ("n[1-2]m[1-3]x,login1", vec!["login1", "n[]m[]x"]),
         ];
 
         for (input, expected) in inputs {
-            eprintln!("input: \"{input}\"");
-            let hosts = input.parse::<Hostlist>()?.collect::<Vec<_>>();
-            assert_eq!(hosts, expected);
+            let fingerprints = Hostlist::new(input)?.fingerprints();
+            let displayed: Vec<String> = fingerprints.iter().map(ToString::to_string).collect();
+            assert_eq!(displayed, expected, "input: {input}");
+        }
+
+        let fingerprint = &Hostlist::new("n[1-2]m[1-3]")?.fingerprints()[0];
+        assert_eq!(fingerprint.count_ranges(), 2);
+        assert_eq!(
+            fingerprint.components,
+            vec![
+                FingerprintComponent::Static("n".into()),
+                FingerprintComponent::RangePlaceholder,
+                FingerprintComponent::Static("m".into()),
+                FingerprintComponent::RangePlaceholder,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_duplicates_within_element() -> Result<()> {
+        // Numbers made of several ranges, or ranges padded to different widths, can have the same
+        // host more than once (ex: "n111" is "n[1][11]" and "n[11][1]")
+        let tests = [
+            ("n[1-12][1-12]", Padding::Strip, 142),
+            ("n[1-11]1[1-11]", Padding::Strip, 120),
+            ("n[1-10,01-12]", Padding::Keep, 21),
+            ("n[9-10],n[08-12]", Padding::Keep, 6),
+        ];
+        for (expr, padding, len) in tests {
+            let hostlist = Hostlist::new_with_padding(expr, padding)?;
+            assert_eq!(hostlist.len(), len, "{expr}");
+            assert_eq!(
+                hostlist.iter().collect::<BTreeSet<_>>().len(),
+                len,
+                "{expr}"
+            );
+        }
+
+        let outcome = Hostlist::parse_with_warnings("n[1-12][1-12]")?;
+        assert_eq!(
+            outcome.warnings,
+            vec![Warning::DuplicatesRemoved {
+                element: "n[1-12][1-12]".to_string(),
+                count: 2,
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_element_lens() -> Result<()> {
+        let inputs = [
+            ("", vec![]),
+            ("node", vec![1]),
+            ("node[1-3],node[5],gpu[1-4]", vec![4, 4]),
+            ("n[1-2]m[1-3],login1", vec![1, 6]),
+        ];
+
+        for (input, lens) in inputs {
+            let mut hostlist = Hostlist::new(input)?;
+            assert_eq!(hostlist.element_count(), lens.len());
+            assert_eq!(hostlist.element_lens(), lens);
+
+            // Lengths count the hosts not yet iterated over
+            if hostlist.next().is_some() {
+                assert_eq!(hostlist.element_lens()[0], lens[0] - 1);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_iter_expressions() -> Result<()> {
+        let inputs = [
+            ("", vec![]),
+            ("node", vec!["node"]),
+            ("node[1-3],node[5]", vec!["node[1-3,5]"]),
+            (
+                "n[1-2]m[1-3],gpu[1-4],login1",
+                vec!["gpu[1-4]", "login1", "n[1-2]m[1-3]"],
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            let hostlist = Hostlist::new(input)?;
+            assert_eq!(hostlist.iter_expressions().collect::<Vec<_>>(), expected);
         }
 
         Ok(())
@@ -427,9 +4463,9 @@ mod tests {
     fn test_hostlist_display() -> Result<()> {
         let inputs = [
             ("node[1-3]", "node[1-3]"),
-            ("node[01-03]", "node[1-3]"),
-            ("node[04-06]", "node[4-6]"),
-            ("node[04-04]", "node[4]"),
+            ("node[01-03]", "node[01-03]"),
+            ("node[04-06]", "node[04-06]"),
+            ("node[04-04]", "node[04]"),
             ("compute[1,3,5]", "compute[1,3,5]"),
             ("server[1-3,5,7-9]", "server[1-3,5,7-9]"),
             ("host[1-3]-rack[1-2]", "host[1-3]-rack[1-2]"),
@@ -451,4 +4487,20 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_hostlist_beyond_u32() -> Result<()> {
+        let hostlist = Hostlist::new("n[4294967295-4294967297]")?;
+        assert_eq!(
+            hostlist.iter().collect::<Vec<_>>(),
+            ["n4294967295", "n4294967296", "n4294967297"]
+        );
+        assert_eq!(hostlist.to_string(), "n[4294967295-4294967297]");
+        assert_eq!(
+            collapse_hosts(["vm20240101000001", "vm20240101000002"])?,
+            "vm[20240101000001-20240101000002]"
+        );
+
+        Ok(())
+    }
 }
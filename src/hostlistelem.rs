@@ -1,4 +1,6 @@
 use core::fmt;
+use core::fmt::Write;
+use std::borrow::Cow;
 use std::iter::FusedIterator;
 use std::num::ParseIntError;
 
@@ -8,31 +10,48 @@ use crate::Rule;
 use crate::error::{Error, Result};
 use crate::range::Range;
 use crate::simplerange::SimpleRange;
+use crate::warning::Warning;
 
 /// A component of a hostlist expression, `static_elem` or `range` from the pest grammar
 #[derive(Debug, Display, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum Component {
-    #[display("{_0}")]
+    #[display("{}", escape(_0))]
     Static(String),
 
     #[display("{_0}")]
     Range(Range),
 }
 
-#[derive(Clone, Hash, Eq, PartialEq)]
+/// A component of a `Fingerprint`: either a literal part, or a placeholder for a range
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub enum FingerprintComponent {
     Static(String),
     RangePlaceholder,
 }
 
-// A type that uniquely identifies the structure of a hostlist element. Used to combine hostlist
-// elements that are identical other than their range values.
-#[derive(Clone, Hash, Eq, PartialEq)]
+/// A type that uniquely identifies the structure of a hostlist element. Used to combine hostlist
+/// elements that are identical other than their range values. Displays with empty brackets in
+/// place of each range (ex: "node[]-gpu[]" for "node[1-4]-gpu[0-1]").
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Fingerprint {
     pub components: Vec<FingerprintComponent>,
 }
 
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for component in &self.components {
+            match component {
+                FingerprintComponent::Static(s) => f.write_str(&escape(s))?,
+                FingerprintComponent::RangePlaceholder => f.write_str("[]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Fingerprint {
+    /// Returns the number of ranges in the fingerprint
+    #[must_use]
     pub fn count_ranges(&self) -> usize {
         self.components
             .iter()
@@ -41,11 +60,34 @@ impl Fingerprint {
     }
 }
 
+/// A part of a host name: a literal part, or the value of a range (ex: "rack", 3, "-node", and 7
+/// for "rack03-node7" from "rack[01-04]-node[1-8]")
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum HostPart {
+    Literal(String),
+
+    /// A number rendered zero-padded to at least `width` digits
+    Number {
+        value: u64,
+        width: usize,
+    },
+}
+
+impl fmt::Display for HostPart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Literal(s) => f.write_str(s),
+            Self::Number { value, width } => write!(f, "{value:0width$}"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct HostlistElem {
     pub components: Vec<Component>,
     latest: Option<String>,
     len: usize,
+    back: usize, // The number of hosts taken from the end by next_back()
 }
 
 impl fmt::Display for HostlistElem {
@@ -61,52 +103,399 @@ impl fmt::Display for HostlistElem {
 }
 
 impl HostlistElem {
-    pub fn new(hostlist: pest::iterators::Pair<Rule>) -> Result<Self> {
-        let mut components: Vec<Component> = Vec::new();
+    /// Constructs the elements of a parsed expression, recording anything that was normalized away
+    /// in `warnings`. There's one element, or one for each letter of a letter range, since only
+    /// numbers make up ranges (ex: "rack[a-b]n[1-2]" is "rackan[1-2]" and "rackbn[1-2]"). With
+    /// `keep_padding`, zero-padded numbers keep their width (ex: "n[01-03]" is "n01" to "n03"),
+    /// rather than being read as their value.
+    pub fn new(
+        hostlist: pest::iterators::Pair<Rule>,
+        warnings: &mut Vec<Warning>,
+        keep_padding: bool,
+    ) -> Result<Vec<Self>> {
+        let element = hostlist.as_str();
+        // Returns the width to render the number with
+        let mut check_padding = |number: &pest::iterators::Pair<Rule>| {
+            let number = number.as_str();
+            if number.len() > 1 && number.starts_with('0') {
+                if keep_padding {
+                    return number.len();
+                }
+                warnings.push(Warning::PaddingDropped {
+                    element: element.to_string(),
+                    number: number.to_string(),
+                });
+            }
+            0
+        };
+
+        // Number of hosts as written, before overlapping values are removed
+        let mut written_len: usize = 1;
+        // The components of each element, one for each combination of letters
+        let mut variants: Vec<Vec<Component>> = vec![Vec::new()];
         for hostlist_elem in hostlist.into_inner() {
             match hostlist_elem.as_rule() {
-                Rule::static_elem => {
-                    let s = hostlist_elem.as_span().as_str().to_string();
-                    components.push(Component::Static(s));
+                Rule::static_elem | Rule::extended_static_elem => {
+                    let text = unescape(hostlist_elem.as_str());
+                    for components in &mut variants {
+                        push_static(components, &text);
+                    }
                 }
                 Rule::range => {
                     let mut range = Range::new();
+                    let mut range_len: usize = 0;
                     for range_inner in hostlist_elem.into_inner() {
                         match range_inner.as_rule() {
                             r @ Rule::simple_range => {
                                 let mut range_parts = range_inner.into_inner();
-                                let start = get_value(
-                                    &range_parts.next().ok_or(Error::UnexpectedParserState(r))?,
+                                let start =
+                                    range_parts.next().ok_or(Error::UnexpectedParserState(r))?;
+                                let end =
+                                    range_parts.next().ok_or(Error::UnexpectedParserState(r))?;
+                                let step = range_parts.next().map_or(Ok(1), |s| get_value(&s))?;
+                                // A padded bound pads the whole range (ex: "098-102")
+                                let width = check_padding(&start).max(check_padding(&end));
+                                let simple = SimpleRange::with_step(
+                                    get_value(&start)?,
+                                    get_value(&end)?,
+                                    step,
+                                    width,
                                 )?;
-                                let end = get_value(
-                                    &range_parts.next().ok_or(Error::UnexpectedParserState(r))?,
-                                )?;
-                                range.add_range(&SimpleRange::new(start, end)?)?;
+                                range_len = range_len.saturating_add(simple.len());
+                                range.add_range(&simple)?;
                             }
                             Rule::number => {
+                                let width = check_padding(&range_inner);
                                 let val = get_value(&range_inner)?;
-                                range.add_range(&SimpleRange::new(val, val)?)?;
+                                range_len = range_len.saturating_add(1);
+                                range.add_range(&SimpleRange::with_width(val, val, width)?)?;
                             }
                             rule => return Err(Error::UnexpectedParserState(rule)),
                         }
                     }
 
-                    components.push(Component::Range(range));
+                    written_len = written_len.saturating_mul(range_len);
+                    for components in &mut variants {
+                        components.push(Component::Range(range.clone()));
+                    }
+                }
+                Rule::letter_range => {
+                    let letters = get_letters(hostlist_elem)?;
+                    let len = variants
+                        .len()
+                        .checked_mul(letters.len())
+                        .ok_or(Error::HostlistTooLarge)?;
+                    let mut expanded = Vec::with_capacity(len);
+                    for components in &variants {
+                        for letter in &letters {
+                            let mut components = components.clone();
+                            push_static(&mut components, letter);
+                            expanded.push(components);
+                        }
+                    }
+                    variants = expanded;
                 }
                 rule => return Err(Error::UnexpectedParserState(rule)),
             }
         }
 
+        let mut elems = Vec::with_capacity(variants.len());
+        for components in variants {
+            let elem = Self::from_components(components)?;
+            if written_len > elem.len() {
+                warnings.push(Warning::DuplicatesRemoved {
+                    element: element.to_string(),
+                    count: written_len - elem.len(),
+                });
+            }
+            elems.push(elem);
+        }
+
+        Ok(elems)
+    }
+
+    /// Constructs an element from its components, starting iteration from the beginning
+    pub fn from_components(mut components: Vec<Component>) -> Result<Self> {
+        for component in &mut components {
+            if let Component::Range(r) = component {
+                r.reset();
+            }
+        }
+
         let mut elem = Self {
             components,
             latest: None,
             len: 0,
+            back: 0,
         };
         elem.update_len()?;
 
         Ok(elem)
     }
 
+    /// Returns a copy of the element, keeping only the range values for which `pred` returns true
+    pub fn filter_indices(&self, pred: &dyn Fn(u64) -> bool) -> Result<Self> {
+        let components = self
+            .components
+            .iter()
+            .map(|component| match component {
+                Component::Static(s) => Ok(Component::Static(s.clone())),
+                Component::Range(r) => Ok(Component::Range(r.filter_values(pred)?)),
+            })
+            .collect::<Result<_>>()?;
+
+        Self::from_components(components)
+    }
+
+    /// Returns the text every host in this element starts with, up to its first digit or range
+    pub fn literal_prefix(&self) -> &str {
+        match self.components.first() {
+            Some(Component::Static(s)) => s
+                .find(|c: char| c.is_ascii_digit())
+                .map_or(s.as_str(), |i| &s[..i]),
+            _ => "",
+        }
+    }
+
+    /// Returns a copy of the element with each range filled in from its smallest to its largest
+    /// value (ex: "n[1-3,7]" for "n[1-7]")
+    pub fn span(&self) -> Result<Self> {
+        let components = self
+            .components
+            .iter()
+            .map(|component| match component {
+                Component::Static(s) => Ok(Component::Static(s.clone())),
+                Component::Range(r) => Ok(Component::Range(r.span()?)),
+            })
+            .collect::<Result<_>>()?;
+
+        Self::from_components(components)
+    }
+
+    /// Returns a copy of the element with its leading static part replaced by `f(prefix)`
+    pub fn map_prefix(&self, f: &dyn Fn(&str) -> String) -> Result<Self> {
+        let mut components = self.components.clone();
+        if let Some(Component::Static(prefix)) = components.first_mut() {
+            let mapped = f(prefix);
+            let valid = mapped
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c));
+            if mapped.is_empty() || !valid {
+                return Err(Error::InvalidHostname(mapped));
+            }
+            *prefix = mapped;
+        }
+
+        Self::from_components(components)
+    }
+
+    /// Approximate number of bytes allocated on the heap by the element
+    pub fn heap_size(&self) -> usize {
+        let components: usize = self
+            .components
+            .iter()
+            .map(|c| match c {
+                Component::Static(s) => s.capacity(),
+                Component::Range(r) => r.heap_size(),
+            })
+            .sum();
+        self.components.capacity() * size_of::<Component>()
+            + components
+            + self.latest.as_ref().map_or(0, String::capacity)
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.components.shrink_to_fit();
+        for component in &mut self.components {
+            match component {
+                Component::Static(s) => s.shrink_to_fit(),
+                Component::Range(r) => r.shrink_to_fit(),
+            }
+        }
+        if let Some(latest) = &mut self.latest {
+            latest.shrink_to_fit();
+        }
+    }
+
+    /// Returns a copy of the element with every range rendered with at least `width` digits
+    pub fn with_width(&self, width: usize) -> Result<Self> {
+        let mut components = self.components.clone();
+        for component in &mut components {
+            if let Component::Range(r) = component {
+                r.set_width(width)?;
+            }
+        }
+
+        Self::from_components(components)
+    }
+
+    /// Returns a copy of the element with every number rendered with at least `width` digits,
+    /// turning the digits in static parts into single-value ranges (ex: "r[1]n[1-2]" for "r1n[1-2]"
+    /// with a width of 1). Digits at the very start of the element, or too large for a range,
+    /// are left alone.
+    pub fn with_number_width(&self, width: usize) -> Result<Self> {
+        let mut components = Vec::with_capacity(self.components.len());
+        for component in &self.components {
+            match component {
+                Component::Static(s) => {
+                    let mut rest = s.as_str();
+                    while let Some(c) = rest.chars().next() {
+                        let is_digit = c.is_ascii_digit();
+                        let len = rest
+                            .find(|c: char| c.is_ascii_digit() != is_digit)
+                            .unwrap_or(rest.len());
+                        let (part, remaining) = rest.split_at(len);
+                        let value = part.parse::<u64>().ok().filter(|v| *v < u64::MAX);
+                        match value {
+                            Some(value) if is_digit && !components.is_empty() => {
+                                let mut range = Range::new();
+                                range.add_range(&SimpleRange::with_width(value, value, width)?)?;
+                                components.push(Component::Range(range));
+                            }
+                            _ => push_static(&mut components, part),
+                        }
+                        rest = remaining;
+                    }
+                }
+                Component::Range(r) => {
+                    let mut range = r.clone();
+                    range.set_width(width)?;
+                    components.push(Component::Range(range));
+                }
+            }
+        }
+
+        Self::from_components(components)
+    }
+
+    /// Constructs an element for a single host name, with every number after the first character
+    /// as a single-value range (ex: "r[1]n[2]" for "r1n2"), so elements for similar host names
+    /// can be combined along any of their numbers
+    pub fn from_host(host: &str) -> Result<Self> {
+        Self::from_components(vec![Component::Static(host.to_string())])?.with_number_width(0)
+    }
+
+    /// Renders the element like `to_string()`, but with ranges of a single value written without
+    /// brackets (ex: "r1n[1-2]" rather than "r[1]n[1-2]")
+    pub fn to_compact_string(&self) -> String {
+        let mut compact = String::new();
+        for component in &self.components {
+            match component {
+                Component::Range(r) if r.total_len() == 1 => {
+                    if let Some((value, width)) = r.value_at(0) {
+                        // Writing to a String can't fail
+                        let _ = write!(compact, "{value:0width$}");
+                    }
+                }
+                component => compact.push_str(&component.to_string()),
+            }
+        }
+        compact
+    }
+
+    /// Returns a copy of the element, starting iteration from the beginning
+    pub fn restarted(&self) -> Result<Self> {
+        Self::from_components(self.components.clone())
+    }
+
+    /// Returns whether `host` is one of the hosts of this element, including hosts already
+    /// iterated over
+    pub fn contains(&self, host: &str) -> bool {
+        match_components(&self.components, host, &mut Vec::new())
+    }
+
+    /// Returns an element with the same fingerprint as this one containing only `host`, if it's
+    /// one of this element's hosts (ex: "n[3]m[2]" for "n3m2" in "n[1-5]m[1-2]")
+    pub fn reshape(&self, host: &str) -> Result<Option<Self>> {
+        let mut values = Vec::new();
+        if !match_components(&self.components, host, &mut values) {
+            return Ok(None);
+        }
+
+        let mut values = values.into_iter();
+        let mut components = Vec::with_capacity(self.components.len());
+        for component in &self.components {
+            components.push(match component {
+                Component::Static(s) => Component::Static(s.clone()),
+                Component::Range(r) => {
+                    let digits = values
+                        .next()
+                        .ok_or(Error::Internal("no value matched for range".to_string()))?;
+                    let (value, width) = r
+                        .find_rendered(digits)
+                        .ok_or(Error::Internal("matched value not in range".to_string()))?;
+                    let mut range = Range::new();
+                    range.add_range(&SimpleRange::with_width(value, value, width)?)?;
+                    Component::Range(range)
+                }
+            });
+        }
+
+        Self::from_components(components).map(Some)
+    }
+
+    /// Returns the hosts found in both this element and `other`, which must have the same
+    /// fingerprint
+    pub fn intersect(&self, other: &Self) -> Result<Self> {
+        let components = self
+            .components
+            .iter()
+            .zip(&other.components)
+            .map(|pair| match pair {
+                (Component::Range(a), Component::Range(b)) => Ok(Component::Range(a.intersect(b)?)),
+                (component, _) => Ok(component.clone()),
+            })
+            .collect::<Result<_>>()?;
+
+        Self::from_components(components)
+    }
+
+    /// Returns elements covering the hosts in this element that aren't in `other`, which must have
+    /// the same fingerprint. Some elements may be empty.
+    pub fn subtract(&self, other: &Self) -> Result<Vec<Self>> {
+        // A host outside `other` has a first range whose value is outside `other`'s range, so split
+        // off one element per range: outside `other` in that range, inside it in the ranges before.
+        let mut pieces = Vec::new();
+        let mut inside = self.components.clone();
+        for (i, pair) in self.components.iter().zip(&other.components).enumerate() {
+            if let (Component::Range(a), Component::Range(b)) = pair {
+                let mut piece = inside.clone();
+                piece[i] = Component::Range(a.subtract(b)?);
+                pieces.push(Self::from_components(piece)?);
+                inside[i] = Component::Range(a.intersect(b)?);
+            }
+        }
+
+        Ok(pieces)
+    }
+
+    /// Returns elements covering the first `n` hosts of this element, in iteration order. Taking
+    /// part of a multi-range element can take several elements to describe (ex: the first 4 hosts
+    /// of "n[1-2]m[1-3]" are "n1m[1-3]" and "n2m1").
+    pub fn take_hosts(&self, n: usize) -> Result<Vec<Self>> {
+        take_components(&self.components, n)?
+            .into_iter()
+            .map(Self::from_components)
+            .collect()
+    }
+
+    /// Returns elements covering `n` hosts of this element starting with the host at `start`, in
+    /// iteration order (ex: hosts 2 to 4 of "n[1-2]m[1-3]" are "n1m3" and "n2m[1-2]")
+    pub fn slice_hosts(&self, start: usize, n: usize) -> Result<Vec<Self>> {
+        let mut remaining = n;
+        let mut sliced = Vec::new();
+        for components in skip_components(&self.components, start)? {
+            if remaining == 0 {
+                break;
+            }
+            let elem = Self::from_components(components)?;
+            sliced.extend(elem.take_hosts(remaining)?);
+            remaining -= remaining.min(elem.len());
+        }
+
+        Ok(sliced)
+    }
+
     pub const fn len(&self) -> usize {
         self.len
     }
@@ -131,51 +520,313 @@ impl HostlistElem {
         Ok(())
     }
 
-    fn construct_next(&mut self) -> Option<String> {
+    /// Moves every range to its part of the next host, returning `false` once there are no more
+    /// hosts
+    fn advance(&mut self) -> bool {
         if self.len == 0 {
-            return None;
+            self.latest = None;
+            return false;
         }
 
-        // Move the last non-empty iterator forward. If all iterators are empty, we're done.
-        // Build the hostname parts (in reverse) as we go.
-        let mut host_parts = Vec::new();
+        // Move the last non-empty iterator forward, resetting any exhausted iterators after it.
+        // Afterwards, every range's latest value is its part of the next host.
         let mut found_next = false;
         for elem in self.components.iter_mut().rev() {
-            let hostname_component = match elem {
-                Component::Static(s) => s.clone(),
-                Component::Range(r) => {
-                    if found_next {
-                        r.latest()
-                            .or_else(|| r.next())
-                            .unwrap_or_else(|| {
-                                panic!("internal error: no latest or next element in range: {r:?} with len {}", r.len())
-                            })
-                            .to_string()
-                    } else if let Some(num) = r.next() {
-                        found_next = true;
-                        num.to_string()
-                    } else {
-                        r.reset();
-                        r.next()
-                            .unwrap_or_else(|| {
-                                panic!(
-                                    "internal error: no next element in range: {r:?} with len {}",
-                                    r.len()
-                                )
-                            })
-                            .to_string()
+            if let Component::Range(r) = elem {
+                if found_next {
+                    if r.latest().is_none() {
+                        r.next();
                     }
+                } else if r.next().is_some() {
+                    found_next = true;
+                } else {
+                    r.reset();
+                    r.next();
                 }
-            };
+            }
+        }
+        self.len -= 1;
 
-            host_parts.push(hostname_component);
+        true
+    }
+
+    /// Returns the host at `index` in iteration order, including hosts already iterated over,
+    /// without iterating to it. The last range changes fastest, so each range's value is a digit
+    /// of `index` in a mixed radix of the ranges' lengths.
+    pub fn host_at(&self, mut index: usize) -> Option<String> {
+        let mut parts = Vec::with_capacity(self.components.len());
+        for component in self.components.iter().rev() {
+            match component {
+                Component::Static(s) => parts.push(s.clone()),
+                Component::Range(r) => {
+                    let r_len = r.total_len();
+                    let (value, width) = r.value_at(index % r_len)?;
+                    index /= r_len;
+                    parts.push(format!("{value:0width$}"));
+                }
+            }
         }
 
-        let host: String = host_parts.into_iter().rev().collect();
-        self.len -= 1;
+        Some(parts.into_iter().rev().collect())
+    }
+
+    /// Returns the index of `host` in iteration order, including hosts already iterated over,
+    /// without iterating to it, the reverse of `host_at()`
+    pub fn index_of(&self, host: &str) -> Option<usize> {
+        let mut values = Vec::new();
+        if !match_components(&self.components, host, &mut values) {
+            return None;
+        }
+
+        let ranges = self
+            .components
+            .iter()
+            .filter_map(|component| match component {
+                Component::Range(r) => Some(r),
+                Component::Static(_) => None,
+            });
+        ranges.zip(values).try_fold(0usize, |index, (r, digits)| {
+            index
+                .checked_mul(r.total_len())?
+                .checked_add(r.index_of_rendered(digits)?)
+        })
+    }
+
+    /// Moves every range to its part of the host at `index`, as if the hosts before it had been
+    /// iterated over, and returns that host
+    fn seek(&mut self, mut index: usize) -> Option<String> {
+        for component in self.components.iter_mut().rev() {
+            if let Component::Range(r) = component {
+                let r_len = r.total_len();
+                r.seek(index % r_len)?;
+                index /= r_len;
+            }
+        }
+
+        let host = self.host_at_latest();
+        self.latest.clone_from(&host);
+        host
+    }
+
+    /// Returns the host made up of every range's latest value
+    fn host_at_latest(&self) -> Option<String> {
+        let mut host = String::new();
+        for component in &self.components {
+            match component {
+                Component::Static(s) => host.push_str(s),
+                Component::Range(r) => {
+                    let width = r.latest_width();
+                    // Writing to a String can't fail
+                    let _ = write!(host, "{:0width$}", r.latest()?);
+                }
+            }
+        }
         Some(host)
     }
 
+    /// Returns whether iterating over the element gives its hosts in natural order (see
+    /// `sort_hosts()`). That's the case unless a range's values aren't ascending, or a range of
+    /// varying width comes after another range in the same run of digits (ex: "n[1-2][1-10]"
+    /// gives "n110" before "n21").
+    pub fn is_naturally_ordered(&self) -> bool {
+        let mut run_has_range = false;
+        for component in &self.components {
+            match component {
+                Component::Static(s) => {
+                    if !s.bytes().all(|b| b.is_ascii_digit()) {
+                        run_has_range = false;
+                    }
+                }
+                Component::Range(r) => {
+                    if !r.is_ascending() || (run_has_range && !r.has_fixed_width()) {
+                        return false;
+                    }
+                    run_has_range = true;
+                }
+            }
+        }
+        true
+    }
+
+    /// Returns the indices of the hosts not yet taken from either end
+    pub fn remaining(&self) -> std::ops::Range<usize> {
+        let end = self.total_len() - self.back;
+        end - self.len..end
+    }
+
+    /// Returns a cursor at the host at `index`, see `Cursor`
+    pub fn cursor_at(&self, mut index: usize) -> Option<Cursor> {
+        let mut slots = Vec::new();
+        for component in self.components.iter().rev() {
+            if let Component::Range(r) = component {
+                let r_len = r.total_len();
+                let mut offset = index % r_len;
+                index /= r_len;
+                let (i, sub) = r.ranges.iter().enumerate().find(|(_, sub)| {
+                    let sub_len = sub.total_len();
+                    let found = offset < sub_len;
+                    if !found {
+                        offset -= sub_len;
+                    }
+                    found
+                })?;
+                slots.push((i, sub.start + u64::try_from(offset).ok()? * sub.step));
+            }
+        }
+        slots.reverse();
+
+        Some(Cursor { slots })
+    }
+
+    /// Appends the host at `cursor` to `buf`
+    pub fn write_at(&self, cursor: &Cursor, buf: &mut String) {
+        let mut slots = cursor.slots.iter();
+        for component in &self.components {
+            match component {
+                Component::Static(s) => buf.push_str(s),
+                Component::Range(r) => {
+                    if let Some(&(i, value)) = slots.next() {
+                        let width = r.ranges[i].width;
+                        // Writing to a String can't fail
+                        let _ = write!(buf, "{value:0width$}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Moves `cursor` to the next host, with the last range changing fastest
+    pub fn advance_cursor(&self, cursor: &mut Cursor) {
+        let ranges = self
+            .components
+            .iter()
+            .filter_map(|component| match component {
+                Component::Range(r) => Some(r),
+                Component::Static(_) => None,
+            });
+        for (r, (i, value)) in ranges.rev().zip(cursor.slots.iter_mut().rev()) {
+            if *value < r.ranges[*i].end {
+                *value += r.ranges[*i].step;
+                return;
+            }
+            // Move on to the next sub-range, or wrap around and carry into the range before
+            *i = (*i + 1) % r.ranges.len();
+            *value = r.ranges[*i].start;
+            if *i > 0 {
+                return;
+            }
+        }
+    }
+
+    /// Returns the number of hosts in the element, including hosts already iterated over
+    pub(crate) fn total_len(&self) -> usize {
+        self.components
+            .iter()
+            .map(|component| match component {
+                Component::Range(r) => r.total_len(),
+                Component::Static(_) => 1,
+            })
+            .fold(1, usize::saturating_mul)
+    }
+
+    /// Returns the parts of the next host, or `None` once there are no more hosts
+    pub fn next_parts(&mut self) -> Option<Vec<HostPart>> {
+        if !self.advance() {
+            return None;
+        }
+
+        self.components
+            .iter()
+            .map(|component| match component {
+                Component::Static(s) => Some(HostPart::Literal(s.clone())),
+                Component::Range(r) => Some(HostPart::Number {
+                    value: r.latest()?,
+                    width: r.latest_width(),
+                }),
+            })
+            .collect()
+    }
+
+    /// Appends the next host to `buf`, returning `false` once there are no more hosts. Unlike
+    /// `next()`, this doesn't allocate a new `String` for each host.
+    pub fn write_next(&mut self, buf: &mut String) -> bool {
+        if !self.advance() {
+            return false;
+        }
+
+        let start = buf.len();
+        for elem in &self.components {
+            match elem {
+                Component::Static(s) => buf.push_str(s),
+                Component::Range(r) => {
+                    let num = r.latest().unwrap_or_else(|| {
+                        panic!(
+                            "internal error: no latest element in range: {r:?} with len {}",
+                            r.len()
+                        )
+                    });
+                    let width = r.latest_width();
+                    // Writing to a String can't fail
+                    let _ = write!(buf, "{num:0width$}");
+                }
+            }
+        }
+
+        // Reuse the previous host's allocation where possible
+        let host = &buf[start..];
+        match &mut self.latest {
+            Some(latest) => {
+                latest.clear();
+                latest.push_str(host);
+            }
+            None => self.latest = Some(host.to_string()),
+        }
+
+        true
+    }
+
+    /// Returns the total length of every host in this element, including hosts already iterated
+    /// over, without expanding them. Each component contributes its length once for every
+    /// combination of the other components' values.
+    pub fn rendered_bytes(&self) -> usize {
+        let counts_and_bytes: Vec<(usize, usize)> = self
+            .components
+            .iter()
+            .map(|c| match c {
+                Component::Static(s) => (1, s.len()),
+                Component::Range(r) => (r.total_len(), r.rendered_bytes()),
+            })
+            .collect();
+        let hosts = counts_and_bytes
+            .iter()
+            .fold(1, |hosts: usize, (count, _)| hosts.saturating_mul(*count));
+        if hosts == 0 {
+            return 0;
+        }
+        counts_and_bytes
+            .iter()
+            .map(|(count, bytes)| bytes.saturating_mul(hosts / count))
+            .fold(0, usize::saturating_add)
+    }
+
+    /// Returns a regular expression matching exactly the hosts in this element, including hosts
+    /// already iterated over
+    pub fn to_regex(&self) -> String {
+        self.components
+            .iter()
+            .map(|c| match c {
+                Component::Static(s) => s
+                    .chars()
+                    .map(|c| match c {
+                        '.' | '+' | '[' | ']' | '\\' => format!("\\{c}"),
+                        c => c.to_string(),
+                    })
+                    .collect(),
+                Component::Range(r) => r.to_regex(),
+            })
+            .collect()
+    }
+
     pub fn fingerprint(&self) -> Fingerprint {
         Fingerprint {
             components: self
@@ -190,13 +841,48 @@ impl HostlistElem {
     }
 }
 
+/// A position among an element's hosts, as the sub-range and value of each of its ranges, for
+/// iterating over a borrowed element without changing it (see `HostIter`)
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    slots: Vec<(usize, u64)>,
+}
+
 impl Iterator for HostlistElem {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next = self.construct_next();
-        self.latest.clone_from(&next);
-        next
+        let mut host = String::new();
+        self.write_next(&mut host).then_some(host)
+    }
+
+    /// Jumps to the host `n` places ahead by working out where it is from the ranges' lengths,
+    /// rather than iterating through the hosts before it
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        if n >= self.len {
+            self.len = 0;
+            self.latest = None;
+            return None;
+        }
+
+        let front = self.total_len() - self.len - self.back;
+        self.len -= n + 1;
+        self.seek(front + n)
+    }
+}
+
+/// Takes hosts from the end by working out where they are from the ranges' lengths, so this
+/// doesn't iterate through the hosts before them
+impl DoubleEndedIterator for HostlistElem {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let index = self.total_len() - 1 - self.back;
+        self.back += 1;
+        self.len -= 1;
+        self.host_at(index)
     }
 }
 
@@ -204,8 +890,250 @@ impl Iterator for HostlistElem {
 // No additional methods needed, it's a marker trait.
 impl FusedIterator for HostlistElem {}
 
-fn get_value(number: &pest::iterators::Pair<Rule>) -> std::result::Result<u32, ParseIntError> {
-    number.as_str().parse::<u32>()
+/// Splits the first `n` hosts described by `components` into lists of components
+fn take_components(components: &[Component], n: usize) -> Result<Vec<Vec<Component>>> {
+    let Some(i) = components
+        .iter()
+        .position(|c| matches!(c, Component::Range(_)))
+    else {
+        // Static-only components describe a single host
+        return Ok(if n > 0 {
+            vec![components.to_vec()]
+        } else {
+            vec![]
+        });
+    };
+    let Component::Range(range) = &components[i] else {
+        unreachable!("position() found a range");
+    };
+
+    // Each value of the first range is followed by every combination of the remaining ranges
+    let rest_len: usize = components[i + 1..]
+        .iter()
+        .map(|c| match c {
+            Component::Range(r) => r.len(),
+            Component::Static(_) => 1,
+        })
+        .product();
+    if rest_len == 0 || n >= range.len().saturating_mul(rest_len) {
+        return Ok(if n > 0 {
+            vec![components.to_vec()]
+        } else {
+            vec![]
+        });
+    }
+
+    let (full, partial) = (n / rest_len, n % rest_len);
+    let mut taken = Vec::new();
+    if full > 0 {
+        let mut head = components.to_vec();
+        head[i] = Component::Range(range.slice(0, full)?);
+        taken.push(head);
+    }
+    if partial > 0 {
+        let mut prefix = components[..i].to_vec();
+        prefix.push(Component::Range(range.slice(full, 1)?));
+        for tail in take_components(&components[i + 1..], partial)? {
+            let mut elem = prefix.clone();
+            elem.extend(tail);
+            taken.push(elem);
+        }
+    }
+
+    Ok(taken)
+}
+
+/// Splits the hosts described by `components` after the first `n` into lists of components, the
+/// counterpart of `take_components()`
+fn skip_components(components: &[Component], n: usize) -> Result<Vec<Vec<Component>>> {
+    let Some(i) = components
+        .iter()
+        .position(|c| matches!(c, Component::Range(_)))
+    else {
+        // Static-only components describe a single host
+        return Ok(if n == 0 {
+            vec![components.to_vec()]
+        } else {
+            vec![]
+        });
+    };
+    let Component::Range(range) = &components[i] else {
+        unreachable!("position() found a range");
+    };
+
+    let rest_len: usize = components[i + 1..]
+        .iter()
+        .map(|c| match c {
+            Component::Range(r) => r.len(),
+            Component::Static(_) => 1,
+        })
+        .product();
+    if n == 0 {
+        return Ok(vec![components.to_vec()]);
+    }
+    if rest_len == 0 || n >= range.len().saturating_mul(rest_len) {
+        return Ok(vec![]);
+    }
+
+    // The rest of a partly skipped value of the first range, then the values after it
+    let (mut full, partial) = (n / rest_len, n % rest_len);
+    let mut skipped = Vec::new();
+    if partial > 0 {
+        let mut prefix = components[..i].to_vec();
+        prefix.push(Component::Range(range.slice(full, 1)?));
+        for tail in skip_components(&components[i + 1..], partial)? {
+            let mut elem = prefix.clone();
+            elem.extend(tail);
+            skipped.push(elem);
+        }
+        full += 1;
+    }
+    if full < range.len() {
+        let mut tail = components.to_vec();
+        tail[i] = Component::Range(range.slice(full, range.len() - full)?);
+        skipped.push(tail);
+    }
+
+    Ok(skipped)
+}
+
+/// Returns whether `host` is made up of the given components, collecting the digits matched by each
+/// range into `values`. Each possible split of the digits is tried, since static parts can start
+/// with digits (ex: "n[1-20]5").
+fn match_components<'h>(
+    components: &[Component],
+    host: &'h str,
+    values: &mut Vec<&'h str>,
+) -> bool {
+    match components.split_first() {
+        None => host.is_empty(),
+        Some((Component::Static(s), rest)) => host
+            .strip_prefix(s.as_str())
+            .is_some_and(|host| match_components(rest, host, values)),
+        Some((Component::Range(r), rest)) => {
+            let digits = host.bytes().take_while(u8::is_ascii_digit).count();
+            (1..=digits).any(|n| {
+                if !r.contains_rendered(&host[..n]) {
+                    return false;
+                }
+                values.push(&host[..n]);
+                let matched = match_components(rest, &host[n..], values);
+                if !matched {
+                    values.pop();
+                }
+                matched
+            })
+        }
+    }
+}
+
+fn get_value(number: &pest::iterators::Pair<Rule>) -> std::result::Result<u64, ParseIntError> {
+    number.as_str().parse::<u64>()
+}
+
+/// Characters written with a backslash before them in static parts, since they'd otherwise be
+/// part of the hostlist syntax
+pub(crate) const ESCAPED_CHARS: &str = "[],!&\\";
+
+/// Writes literal text the way it appears in an expression (ex: `vm\[prod\]` for "vm[prod]")
+pub(crate) fn escape(s: &str) -> Cow<'_, str> {
+    if !s.contains(|c| ESCAPED_CHARS.contains(c)) {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::with_capacity(s.len() + 2);
+    for c in s.chars() {
+        if ESCAPED_CHARS.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    Cow::Owned(escaped)
+}
+
+/// Reads literal text written in an expression, the reverse of `escape()`
+pub(crate) fn unescape(s: &str) -> Cow<'_, str> {
+    if !s.contains('\\') {
+        return Cow::Borrowed(s);
+    }
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(if c == '\\' {
+            chars.next().unwrap_or(c)
+        } else {
+            c
+        });
+    }
+    Cow::Owned(unescaped)
+}
+
+/// Appends literal text to `components`, joining it onto a static part right before it
+pub(crate) fn push_static(components: &mut Vec<Component>, s: &str) {
+    match components.last_mut() {
+        Some(Component::Static(prev)) => prev.push_str(s),
+        _ => components.push(Component::Static(s.to_string())),
+    }
+}
+
+/// Returns the letters of a `letter_range`, in the order they were written
+fn get_letters(letter_range: pest::iterators::Pair<Rule>) -> Result<Vec<String>> {
+    let mut letters = Vec::new();
+    for range_inner in letter_range.into_inner() {
+        match range_inner.as_rule() {
+            r @ Rule::letter_simple_range => {
+                let mut range_parts = range_inner.into_inner();
+                let start = range_parts.next().ok_or(Error::UnexpectedParserState(r))?;
+                let end = range_parts.next().ok_or(Error::UnexpectedParserState(r))?;
+                letters.extend(letter_span(start.as_str(), end.as_str())?);
+            }
+            Rule::lower_letters | Rule::upper_letters => {
+                letters.push(range_inner.as_str().to_string());
+            }
+            rule => return Err(Error::UnexpectedParserState(rule)),
+        }
+    }
+    Ok(letters)
+}
+
+/// Returns the letters from `start` to `end`, which have the same case, counting like spreadsheet
+/// columns (ex: "y", "z", "aa", "ab" for "y" to "ab")
+fn letter_span(start: &str, end: &str) -> Result<Vec<String>> {
+    // Letters as a number in bijective base 26, where "a" is 1 and "aa" is 27
+    let value = |letters: &str| {
+        letters.bytes().try_fold(0u64, |value, b| {
+            value
+                .checked_mul(26)?
+                .checked_add(u64::from(b.to_ascii_lowercase() - b'a') + 1)
+        })
+    };
+    let (Some(lo), Some(hi)) = (value(start), value(end)) else {
+        return Err(Error::HostlistTooLarge);
+    };
+    if lo > hi {
+        return Err(Error::InvalidBoundsReversed {
+            start: start.to_string(),
+            end: end.to_string(),
+        });
+    }
+
+    let base = if start.starts_with(|c: char| c.is_ascii_uppercase()) {
+        b'A'
+    } else {
+        b'a'
+    };
+    let mut letters = Vec::new();
+    for mut value in lo..=hi {
+        let mut bytes = Vec::new();
+        while value > 0 {
+            value -= 1;
+            // Always less than 26
+            bytes.push(base + u8::try_from(value % 26).unwrap_or(0));
+            value /= 26;
+        }
+        bytes.reverse();
+        letters.push(String::from_utf8(bytes).unwrap_or_default());
+    }
+    Ok(letters)
 }
 
 #[cfg(test)]
@@ -217,7 +1145,7 @@ mod tests {
     #[test]
     fn test_hostlistelem_1() -> Result<()> {
         let mut pairs = HostlistParser::parse(Rule::hostlist, "n[1-5]")?;
-        let mut elems = HostlistElem::new(pairs.next().unwrap())?;
+        let mut elems = HostlistElem::new(pairs.next().unwrap(), &mut Vec::new(), false)?.remove(0);
 
         assert_eq!(elems.len(), 5);
         for i in 1..=5_u32 {
@@ -232,7 +1160,7 @@ mod tests {
     #[test]
     fn test_hostlistelem_2() -> Result<()> {
         let mut pairs = HostlistParser::parse(Rule::hostlist, "n[1-5]m[1-3]")?;
-        let mut elems = HostlistElem::new(pairs.next().unwrap())?;
+        let mut elems = HostlistElem::new(pairs.next().unwrap(), &mut Vec::new(), false)?.remove(0);
         assert_eq!(pairs.next().unwrap().as_rule(), Rule::EOI);
 
         assert_eq!(elems.len(), 15);
@@ -251,7 +1179,8 @@ mod tests {
     #[test]
     fn test_hostlistelem_3() -> Result<()> {
         let mut pairs = HostlistParser::parse(Rule::hostlist, "n[1-3],foo[5-7]")?;
-        let mut hostlist_elem = HostlistElem::new(pairs.next().unwrap())?;
+        let mut hostlist_elem =
+            HostlistElem::new(pairs.next().unwrap(), &mut Vec::new(), false)?.remove(0);
 
         assert_eq!(hostlist_elem.len(), 3);
         for n in 1..=3_u32 {
@@ -260,7 +1189,8 @@ mod tests {
         }
         assert_eq!(hostlist_elem.len(), 0);
 
-        let mut hostlist_elem = HostlistElem::new(pairs.next().unwrap())?;
+        let mut hostlist_elem =
+            HostlistElem::new(pairs.next().unwrap(), &mut Vec::new(), false)?.remove(0);
         assert_eq!(hostlist_elem.len(), 3);
         for n in 5..=7_u32 {
             let elem = hostlist_elem.next();
@@ -276,16 +1206,49 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_letter_span() -> Result<()> {
+        assert_eq!(letter_span("a", "c")?, ["a", "b", "c"]);
+        assert_eq!(letter_span("y", "ab")?, ["y", "z", "aa", "ab"]);
+        assert_eq!(letter_span("AZ", "BA")?, ["AZ", "BA"]);
+        assert_eq!(letter_span("zz", "aaa")?, ["zz", "aaa"]);
+        assert!(matches!(
+            letter_span("b", "a"),
+            Err(Error::InvalidBoundsReversed { .. })
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_hostlistelem_len_overflow() -> Result<()> {
         let inputs = ["n[1-1000][1-1000][1-1000][1-1000][1-1000][1-1000][1-1000]"];
 
         for input in inputs {
             let mut pairs = HostlistParser::parse(Rule::hostlist, input)?;
-            let result = HostlistElem::new(pairs.next().unwrap());
+            let result = HostlistElem::new(pairs.next().unwrap(), &mut Vec::new(), false);
             assert!(matches!(result, Err(Error::HostlistTooLarge)));
         }
 
         Ok(())
     }
+
+    #[test]
+    fn test_hostlistelem_slice_hosts() -> Result<()> {
+        for input in ["n[1-2]m[1-3]", "r[1-2]n[1,5-6]x[8-9]", "login1"] {
+            let mut pairs = HostlistParser::parse(Rule::hostlist, input)?;
+            let elem = HostlistElem::new(pairs.next().unwrap(), &mut Vec::new(), false)?.remove(0);
+            let hosts: Vec<String> = elem.clone().collect();
+            for start in 0..=hosts.len() {
+                for n in 0..=hosts.len() + 1 - start {
+                    let sliced: Vec<String> =
+                        elem.slice_hosts(start, n)?.into_iter().flatten().collect();
+                    let end = (start + n).min(hosts.len());
+                    assert_eq!(sliced, hosts[start..end], "{input}: {start}, {n}");
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,554 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use pest::Parser;
+
+use crate::error::{Error, Result};
+use crate::hostexpr::HostExpr;
+use crate::hostlist::{Hostlist, HostlistParser, Rule};
+use crate::hostlistelem::{Component, HostlistElem};
+use crate::range::Range;
+use crate::simplerange::SimpleRange;
+
+/// A mutable set of hosts, for holding a long-lived collection of hosts that come and go.
+///
+/// Host names are split into a prefix and a numeric suffix, and the suffixes for each prefix are
+/// kept as a tree of disjoint ranges, so runs of consecutive hosts take constant space. Host names
+/// without a numeric suffix are kept as they are.
+/// ```
+/// use hostlist_iter::{HostSet, Hostlist};
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let mut hosts = HostSet::from_hostlist(&Hostlist::new("node[1-100]")?)?;
+///   assert!(hosts.remove("node42"));
+///   assert!(hosts.insert("login1")?);
+///   assert!(!hosts.contains("node42"));
+///   assert_eq!(hosts.len(), 100);
+///   assert_eq!(hosts.to_hostlist()?.to_string(), "login1,node[1-41,43-100]");
+///
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct HostSet {
+    /// Suffix ranges (start to end) by prefix and zero-padded width (0 for unpadded suffixes)
+    ranges: BTreeMap<(String, usize), BTreeMap<u64, u64>>,
+    names: BTreeSet<String>,
+    len: usize,
+}
+
+/// How a host name is stored in a `HostSet`
+enum Key<'a> {
+    Numbered {
+        prefix: &'a str,
+        width: usize,
+        value: u64,
+    },
+    Name(&'a str),
+}
+
+impl HostSet {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs a set with the hosts in `hostlist`, including hosts already iterated over.
+    /// Elements with a single range at the end (ex: "node[1-100]") are added a range at a time.
+    ///
+    /// # Errors
+    /// Will return `Err` if a host's numeric suffix is too large.
+    pub fn from_hostlist(hostlist: &Hostlist) -> Result<Self> {
+        let mut set = Self::new();
+        for elem in &hostlist.hostlist_elems {
+            if let [Component::Static(prefix), Component::Range(range)] = elem.components.as_slice()
+                && !prefix.ends_with(|c: char| c.is_ascii_digit())
+            {
+                for r in range.ranges.iter().flat_map(SimpleRange::unstepped) {
+                    set.insert_range(prefix, &r);
+                }
+                continue;
+            }
+            for host in elem.restarted()? {
+                set.insert(&host)?;
+            }
+        }
+
+        Ok(set)
+    }
+
+    /// Returns a hostlist with the hosts in the set
+    ///
+    /// # Errors
+    /// Will return `Err` if the set has more hosts than fit in a `usize`.
+    pub fn to_hostlist(&self) -> Result<Hostlist> {
+        // Ranges of the same prefix with different padding share an element
+        let mut by_prefix: BTreeMap<&str, Vec<SimpleRange>> = BTreeMap::new();
+        for ((prefix, width), tree) in &self.ranges {
+            let ranges = by_prefix.entry(prefix).or_default();
+            for (&start, &end) in tree {
+                ranges.push(SimpleRange::with_width(start, end, *width)?);
+            }
+        }
+
+        let mut elems = Vec::new();
+        for (prefix, ranges) in by_prefix {
+            if let [r] = ranges.as_slice()
+                && r.start == r.end
+            {
+                let (value, width) = (r.start, r.width);
+                let host = format!("{prefix}{value:0width$}");
+                elems.push(HostlistElem::from_components(vec![Component::Static(
+                    host,
+                )])?);
+                continue;
+            }
+            let mut range = Range::new();
+            for r in &ranges {
+                range.add_range(r)?;
+            }
+            elems.push(HostlistElem::from_components(vec![
+                Component::Static(prefix.to_string()),
+                Component::Range(range),
+            ])?);
+        }
+        for name in &self.names {
+            elems.push(HostlistElem::from_components(vec![Component::Static(
+                name.clone(),
+            )])?);
+        }
+
+        Hostlist::from_elems(elems, &mut Vec::new())
+    }
+
+    /// Adds `host` to the set, returning whether it wasn't already in it
+    ///
+    /// # Errors
+    /// Will return `Err` if `host` isn't a valid host name, or its numeric suffix is too large.
+    pub fn insert(&mut self, host: &str) -> Result<bool> {
+        let added = match split_host(host)? {
+            Key::Numbered {
+                prefix,
+                width,
+                value,
+            } => {
+                let tree = self.ranges.entry((prefix.to_string(), width)).or_default();
+                insert_range(tree, value, value) > 0
+            }
+            Key::Name(name) => self.names.insert(name.to_string()),
+        };
+        if added {
+            self.len += 1;
+        }
+
+        Ok(added)
+    }
+
+    /// Removes `host` from the set, returning whether it was in it
+    pub fn remove(&mut self, host: &str) -> bool {
+        let removed = match split_host(host) {
+            Ok(Key::Numbered {
+                prefix,
+                width,
+                value,
+            }) => {
+                let key = (prefix.to_string(), width);
+                let removed = self
+                    .ranges
+                    .get_mut(&key)
+                    .is_some_and(|tree| remove_value(tree, value));
+                if self.ranges.get(&key).is_some_and(BTreeMap::is_empty) {
+                    self.ranges.remove(&key);
+                }
+                removed
+            }
+            Ok(Key::Name(name)) => self.names.remove(name),
+            Err(_) => false,
+        };
+        if removed {
+            self.len -= 1;
+        }
+
+        removed
+    }
+
+    /// Returns whether `host` is in the set
+    #[must_use]
+    pub fn contains(&self, host: &str) -> bool {
+        match split_host(host) {
+            Ok(Key::Numbered {
+                prefix,
+                width,
+                value,
+            }) => self
+                .ranges
+                .get(&(prefix.to_string(), width))
+                .and_then(|tree| tree.range(..=value).next_back())
+                .is_some_and(|(_, &end)| value <= end),
+            Ok(Key::Name(name)) => self.names.contains(name),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the number of hosts in the set
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the set is empty
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns an iterator over the hosts, grouped by prefix, followed by the host names without
+    /// a numeric suffix
+    pub fn iter(&self) -> impl Iterator<Item = String> + '_ {
+        let numbered = self.ranges.iter().flat_map(|((prefix, width), tree)| {
+            tree.iter().flat_map(move |(&start, &end)| {
+                (start..=end).map(move |value| format!("{prefix}{value:0width$}"))
+            })
+        });
+        numbered.chain(self.names.iter().cloned())
+    }
+
+    /// Adds the values of `range` after `prefix`, keeping the zero-padded values apart
+    fn insert_range(&mut self, prefix: &str, range: &SimpleRange) {
+        let mut start = range.start;
+        if range.width > 1 {
+            // Values with fewer digits than the width are rendered with leading zeros
+            let padded_end = u32::try_from(range.width - 1)
+                .ok()
+                .and_then(|exp| 10u64.checked_pow(exp))
+                .map_or(u64::MAX, |p| p - 1);
+            if start <= padded_end {
+                let end = range.end.min(padded_end);
+                let tree = self
+                    .ranges
+                    .entry((prefix.to_string(), range.width))
+                    .or_default();
+                self.len += insert_range(tree, start, end);
+                if end == range.end {
+                    return;
+                }
+                start = end + 1;
+            }
+        }
+        let tree = self.ranges.entry((prefix.to_string(), 0)).or_default();
+        self.len += insert_range(tree, start, range.end);
+    }
+}
+
+/// Set operations work a range at a time, without going through individual hosts
+/// ```
+/// use hostlist_iter::{HostExpr, HostSet, Hostlist};
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let all = HostSet::from_hostlist(&Hostlist::new("node[1-1000000],login1")?)?;
+///   let down = HostSet::from_hostlist(&Hostlist::new("node[10-19],login1")?)?;
+///   let up = all.difference(&down)?;
+///   assert_eq!(up.len(), 999_990);
+///   assert_eq!(up.to_expression()?, "node[1-9,20-1000000]");
+///
+///   Ok(())
+/// }
+/// ```
+impl HostExpr for HostSet {
+    fn host_count(&self) -> usize {
+        self.len
+    }
+
+    fn contains(&self, host: &str) -> bool {
+        Self::contains(self, host)
+    }
+
+    fn hosts(&self) -> impl Iterator<Item = String> + '_ {
+        self.iter()
+    }
+
+    fn to_expression(&self) -> Result<String> {
+        Ok(self.to_hostlist()?.to_string())
+    }
+
+    fn union(&self, other: &Self) -> Result<Self> {
+        let mut union = self.clone();
+        for (key, tree) in &other.ranges {
+            let union_tree = union.ranges.entry(key.clone()).or_default();
+            for (&start, &end) in tree {
+                union.len += insert_range(union_tree, start, end);
+            }
+        }
+        for name in &other.names {
+            if union.names.insert(name.clone()) {
+                union.len += 1;
+            }
+        }
+
+        Ok(union)
+    }
+
+    fn intersection(&self, other: &Self) -> Result<Self> {
+        // The hosts of this set, without those that aren't in the other one
+        self.difference(&self.difference(other)?)
+    }
+
+    fn difference(&self, other: &Self) -> Result<Self> {
+        let mut difference = self.clone();
+        for (key, tree) in &other.ranges {
+            let Some(difference_tree) = difference.ranges.get_mut(key) else {
+                continue;
+            };
+            for (&start, &end) in tree {
+                difference.len -= remove_range(difference_tree, start, end);
+            }
+            if difference_tree.is_empty() {
+                difference.ranges.remove(key);
+            }
+        }
+        for name in &other.names {
+            if difference.names.remove(name) {
+                difference.len -= 1;
+            }
+        }
+
+        Ok(difference)
+    }
+}
+
+/// Splits a host name into its prefix and numeric suffix. The width is the number of digits when
+/// the suffix is zero-padded (ex: 3 for "n007"), or 0 otherwise.
+fn split_host(host: &str) -> Result<Key<'_>> {
+    if host.is_empty() {
+        return Err(Error::InvalidHostname(host.into()));
+    }
+    HostlistParser::parse(Rule::simple_hostname, host)?;
+
+    let prefix = host.trim_end_matches(|c: char| c.is_ascii_digit());
+    let digits = &host[prefix.len()..];
+    if prefix.is_empty() || digits.is_empty() {
+        return Ok(Key::Name(host));
+    }
+    let value: u64 = digits.parse()?;
+    if value == u64::MAX {
+        return Err(Error::TooLarge(value));
+    }
+    let width = if digits.len() > 1 && digits.starts_with('0') {
+        digits.len()
+    } else {
+        0
+    };
+
+    Ok(Key::Numbered {
+        prefix,
+        width,
+        value,
+    })
+}
+
+/// Adds the values from `start` to `end` to a tree of disjoint ranges, merging ranges that
+/// overlap or touch. Returns how many values weren't already in the tree.
+fn insert_range(tree: &mut BTreeMap<u64, u64>, start: u64, end: u64) -> usize {
+    let overlap = |s: u64, e: u64| {
+        let (lo, hi) = (s.max(start), e.min(end));
+        if lo <= hi { count(lo, hi) } else { 0 }
+    };
+    let mut added = count(start, end);
+    let (mut merged_start, mut merged_end) = (start, end);
+
+    // A range starting before this one that overlaps or touches it
+    if let Some((&s, &e)) = tree.range(..start).next_back()
+        && e.saturating_add(1) >= start
+    {
+        tree.remove(&s);
+        added -= overlap(s, e);
+        merged_start = s;
+        merged_end = merged_end.max(e);
+    }
+
+    // Ranges starting inside this one, or right after it
+    while let Some((&s, &e)) = tree.range(start..).next()
+        && s <= merged_end.saturating_add(1)
+    {
+        tree.remove(&s);
+        added -= overlap(s, e);
+        merged_end = merged_end.max(e);
+    }
+    tree.insert(merged_start, merged_end);
+
+    added
+}
+
+/// Returns the number of values from `start` to `end`, saturating if it doesn't fit in a `usize`
+fn count(start: u64, end: u64) -> usize {
+    usize::try_from(end - start).map_or(usize::MAX, |n| n.saturating_add(1))
+}
+
+/// Removes `value` from a tree of disjoint ranges, returning whether it was in the tree
+fn remove_value(tree: &mut BTreeMap<u64, u64>, value: u64) -> bool {
+    remove_range(tree, value, value) > 0
+}
+
+/// Removes the values from `start` to `end` from a tree of disjoint ranges, splitting the ranges
+/// at either end. Returns how many values were in the tree.
+fn remove_range(tree: &mut BTreeMap<u64, u64>, start: u64, end: u64) -> usize {
+    // A range starting before this one that overlaps it, and the ranges starting inside it
+    let before = tree
+        .range(..start)
+        .next_back()
+        .filter(|&(_, &e)| e >= start)
+        .map(|(&s, &e)| (s, e));
+    let overlapping: Vec<(u64, u64)> = before
+        .into_iter()
+        .chain(tree.range(start..=end).map(|(&s, &e)| (s, e)))
+        .collect();
+
+    let mut removed = 0;
+    for (s, e) in overlapping {
+        tree.remove(&s);
+        removed += count(s.max(start), e.min(end));
+        if s < start {
+            tree.insert(s, start - 1);
+        }
+        if end < e {
+            tree.insert(end + 1, e);
+        }
+    }
+
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hostset_insert_remove() -> Result<()> {
+        let mut set = HostSet::new();
+        assert!(set.is_empty());
+        for host in ["n3", "n1", "n2", "n5", "login", "n01", "r1n4"] {
+            assert!(set.insert(host)?, "host: {host}");
+        }
+        assert!(!set.insert("n2")?);
+        assert!(!set.insert("login")?);
+        assert_eq!(set.len(), 7);
+        assert_eq!(set.to_hostlist()?.to_string(), "login,n[01,1-3,5],r1n4");
+
+        assert!(set.insert("n4")?);
+        assert_eq!(set.to_hostlist()?.to_string(), "login,n[01,1-5],r1n4");
+
+        assert!(set.remove("n3"));
+        assert!(!set.remove("n3"));
+        assert!(set.remove("n01"));
+        assert!(set.remove("login"));
+        assert!(!set.remove("n[1-2]"));
+        assert_eq!(set.len(), 5);
+        assert_eq!(set.to_hostlist()?.to_string(), "n[1-2,4-5],r1n4");
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            ["n1", "n2", "n4", "n5", "r1n4"]
+        );
+
+        assert!(set.contains("n4") && set.contains("r1n4"));
+        assert!(!set.contains("n3") && !set.contains("n04") && !set.contains(""));
+
+        assert!(set.insert("").is_err());
+        assert!(set.insert("n[1-2]").is_err());
+        assert!(set.insert("n4294967295")?);
+        assert!(set.insert("n18446744073709551615").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostset_from_hostlist() -> Result<()> {
+        let exprs = [
+            "n[1-10000]",
+            "n[1-3,5],login,n[3-8]",
+            "n[08-12],n[1-9]",
+            "r[1-2]n[1-3],n1[0-2],gpu[1-3]-ib",
+            "",
+        ];
+        for expr in exprs {
+            let hostlist = Hostlist::new_with_padding(expr, crate::Padding::Keep)?;
+            let set = HostSet::from_hostlist(&hostlist)?;
+            assert_eq!(
+                set.len(),
+                hostlist.to_vec().iter().collect::<BTreeSet<_>>().len(),
+                "expr: {expr}"
+            );
+            for host in hostlist.to_vec() {
+                assert!(set.contains(&host), "expr: {expr}, host: {host}");
+            }
+
+            let round_trip = set.to_hostlist()?;
+            assert_eq!(
+                round_trip.to_vec().into_iter().collect::<BTreeSet<_>>(),
+                hostlist.to_vec().into_iter().collect::<BTreeSet<_>>(),
+                "expr: {expr}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_range() {
+        let mut tree = BTreeMap::new();
+        assert_eq!(insert_range(&mut tree, 5, 10), 6);
+        assert_eq!(insert_range(&mut tree, 20, 30), 11);
+        assert_eq!(insert_range(&mut tree, 8, 22), 9);
+        assert_eq!(tree, BTreeMap::from([(5, 30)]));
+        assert_eq!(insert_range(&mut tree, 31, 31), 1);
+        assert_eq!(insert_range(&mut tree, 1, 3), 3);
+        assert_eq!(insert_range(&mut tree, 4, 4), 1);
+        assert_eq!(tree, BTreeMap::from([(1, 31)]));
+        assert_eq!(insert_range(&mut tree, 0, 40), 10);
+
+        assert!(remove_value(&mut tree, 20));
+        assert!(!remove_value(&mut tree, 20));
+        assert_eq!(tree, BTreeMap::from([(0, 19), (21, 40)]));
+
+        assert_eq!(remove_range(&mut tree, 10, 25), 15);
+        assert_eq!(tree, BTreeMap::from([(0, 9), (26, 40)]));
+        assert_eq!(remove_range(&mut tree, 0, 9), 10);
+        assert_eq!(remove_range(&mut tree, 0, 9), 0);
+        assert_eq!(remove_range(&mut tree, 30, 30), 1);
+        assert_eq!(tree, BTreeMap::from([(26, 29), (31, 40)]));
+        assert_eq!(remove_range(&mut tree, 0, u64::MAX), 14);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_hostset_set_operations() -> Result<()> {
+        let tests = [
+            ("n[1-10],login", "n[5-15],gpu1"),
+            ("n[1-3,7-9],n[01-03]", "n[2-8],n02"),
+            ("r1n[1-4],n[1-2]", "r1n[3-6],m[1-2]"),
+            ("", "n[1-3]"),
+        ];
+        for (a, b) in tests {
+            let (a, b) = (
+                Hostlist::new_with_padding(a, crate::Padding::Keep)?,
+                Hostlist::new_with_padding(b, crate::Padding::Keep)?,
+            );
+            let (set_a, set_b) = (HostSet::from_hostlist(&a)?, HostSet::from_hostlist(&b)?);
+            let checks = [
+                (set_a.union(&set_b)?, a.union(&b)?),
+                (set_a.intersection(&set_b)?, a.intersection(&b)?),
+                (set_a.difference(&set_b)?, a.difference(&b)?),
+                (set_b.difference(&set_a)?, b.difference(&a)?),
+            ];
+            for (set, hostlist) in checks {
+                let expected: BTreeSet<String> = hostlist.to_vec().into_iter().collect();
+                assert_eq!(
+                    set.hosts().collect::<BTreeSet<_>>(),
+                    expected,
+                    "{a} and {b}"
+                );
+                assert_eq!(set.host_count(), expected.len(), "{a} and {b}");
+            }
+        }
+
+        Ok(())
+    }
+}
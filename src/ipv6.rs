@@ -0,0 +1,556 @@
+use core::fmt;
+use std::collections::BTreeMap;
+use std::net::Ipv6Addr;
+use std::str::FromStr;
+
+use pest::Parser;
+use pest::iterators::Pair;
+
+use crate::error::{Error, Result};
+use crate::hostlist::{HostlistParser, Rule};
+use crate::range::Range;
+use crate::simplerange::SimpleRange;
+
+/// The number of 16-bit groups in an IPv6 address
+const GROUPS: usize = 8;
+
+/// A structure representing the addresses in an IPv6 range expression, where each group is a hex
+/// number or a bracketed list of hex ranges (ex: `fd00::[1-ff]`, or `fd00::[1-2]:[a-c,f]`)
+/// ```
+/// use std::net::Ipv6Addr;
+///
+/// use hostlist_iter::Ipv6Hostlist;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let addrs = Ipv6Hostlist::new("fd00::[9-b]")?;
+///   assert_eq!(addrs.len(), 3);
+///   assert_eq!(addrs.iter().next(), Some("fd00::9".parse::<Ipv6Addr>()?));
+///   assert_eq!(addrs.to_strings(), ["fd00::9", "fd00::a", "fd00::b"]);
+///
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Ipv6Hostlist {
+    elems: Vec<Ipv6Elem>,
+}
+
+impl fmt::Display for Ipv6Hostlist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let joined = self
+            .elems
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        f.write_str(&joined)
+    }
+}
+
+impl Ipv6Hostlist {
+    /// Constructs a new `Ipv6Hostlist` from an expression of comma-separated IPv6 address ranges.
+    /// Elements that overlap are split so that each address is in one element, and elements that
+    /// differ in only one group are merged (ex: `fd00::[1-5],fd00::[3-8]` is `fd00::[1-8]`).
+    ///
+    /// # Errors
+    /// Will return `Err` if the expression can't be parsed, an element doesn't have 8 groups
+    /// (after expanding "::"), or there are more addresses than fit in a `usize`.
+    pub fn new(expr: &str) -> Result<Self> {
+        let pairs = HostlistParser::parse(Rule::ipv6_hostlist, expr)?;
+        let mut elems = Vec::new();
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::ipv6_elem => add_disjoint(&mut elems, &Ipv6Elem::new(pair)?)?,
+                Rule::EOI => break,
+                rule => return Err(Error::UnexpectedParserState(rule)),
+            }
+        }
+        merge_elems(&mut elems)?;
+        elems.sort();
+
+        Self::from_elems(elems)
+    }
+
+    /// Checks that the number of addresses in `elems`, which don't overlap, fits in a `usize`
+    fn from_elems(elems: Vec<Ipv6Elem>) -> Result<Self> {
+        elems
+            .iter()
+            .try_fold(0usize, |len, elem| len.checked_add(elem.len))
+            .ok_or(Error::HostlistTooLarge)?;
+
+        Ok(Self { elems })
+    }
+
+    /// Folds addresses into ranges over their last group (ex: `fd00::1` and `fd00::2` into
+    /// `fd00::[1-2]`), so that `to_string()` gives a compact expression
+    ///
+    /// # Errors
+    /// Will return `Err` if there are more addresses with the same first 7 groups than fit in a
+    /// `usize`, which can't happen on 32-bit or larger platforms.
+    /// ```
+    /// use hostlist_iter::Ipv6Hostlist;
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let addrs = ["fd00::2", "fd00::1", "fd00::3", "fd00::a", "fe80::1:0:0:1"];
+    ///   let addrs = Ipv6Hostlist::from_addrs(addrs.iter().map(|a| a.parse().unwrap()))?;
+    ///   assert_eq!(addrs.to_string(), "fd00::[1-3,a],fe80::1:0:0:1");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn from_addrs(addrs: impl IntoIterator<Item = Ipv6Addr>) -> Result<Self> {
+        let mut by_prefix: BTreeMap<[u16; GROUPS - 1], Vec<u64>> = BTreeMap::new();
+        for addr in addrs {
+            let segments = addr.segments();
+            let mut prefix = [0; GROUPS - 1];
+            prefix.copy_from_slice(&segments[..GROUPS - 1]);
+            by_prefix
+                .entry(prefix)
+                .or_default()
+                .push(u64::from(segments[GROUPS - 1]));
+        }
+
+        let mut elems = Vec::new();
+        for (prefix, mut last) in by_prefix {
+            last.sort_unstable();
+            last.dedup();
+            let mut groups: Vec<Range> = prefix
+                .iter()
+                .map(|&segment| single_value(u64::from(segment)))
+                .collect::<Result<_>>()?;
+            let mut range = Range::new();
+            for run in last.chunk_by(|a, b| a + 1 == *b) {
+                range.add_range(&SimpleRange::new(run[0], run[run.len() - 1])?)?;
+            }
+            groups.push(range);
+            elems.push(Ipv6Elem::from_groups(groups)?);
+        }
+
+        Self::from_elems(elems)
+    }
+
+    /// Returns whether there are no addresses
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    /// Returns the number of addresses. Elements don't overlap, so each address is counted once.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        // Checked to fit when the hostlist was constructed
+        self.elems.iter().map(|e| e.len).sum()
+    }
+
+    /// Returns whether `addr` is one of the addresses
+    #[must_use]
+    pub fn contains(&self, addr: Ipv6Addr) -> bool {
+        self.elems.iter().any(|e| e.contains(addr))
+    }
+
+    /// Returns an iterator over the addresses, in numeric order within each element
+    pub fn iter(&self) -> impl Iterator<Item = Ipv6Addr> + '_ {
+        self.elems.iter().flat_map(Ipv6Elem::iter)
+    }
+
+    /// Returns the addresses in their canonical text form, with the longest run of zero groups
+    /// compressed to "::"
+    #[must_use]
+    pub fn to_strings(&self) -> Vec<String> {
+        self.iter().map(|addr| addr.to_string()).collect()
+    }
+}
+
+impl FromStr for Ipv6Hostlist {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::new(s)
+    }
+}
+
+/// A single IPv6 range expression, with the values of each of its 8 groups
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+struct Ipv6Elem {
+    groups: Vec<Range>,
+    len: usize,
+}
+
+impl fmt::Display for Ipv6Elem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let groups: Vec<String> = self.groups.iter().map(hex_group).collect();
+
+        // Like addresses, compress the longest run of two or more zero groups (the first, if
+        // there's a tie)
+        let mut longest = 0..0;
+        let mut start = 0;
+        for (i, group) in groups.iter().enumerate() {
+            if group != "0" {
+                start = i + 1;
+            } else if i + 1 - start > longest.len() {
+                longest = start..i + 1;
+            }
+        }
+        if longest.len() < 2 {
+            return f.write_str(&groups.join(":"));
+        }
+        write!(
+            f,
+            "{}::{}",
+            groups[..longest.start].join(":"),
+            groups[longest.end..].join(":")
+        )
+    }
+}
+
+impl Ipv6Elem {
+    fn new(pair: Pair<Rule>) -> Result<Self> {
+        let element = pair.as_str();
+        let mut head = Vec::new();
+        let mut tail = Vec::new();
+        let mut compressed = false;
+        for group in pair.into_inner() {
+            let groups = if compressed { &mut tail } else { &mut head };
+            match group.as_rule() {
+                Rule::ipv6_compress => compressed = true,
+                Rule::hex_number => groups.push(single_value(parse_hex(group.as_str())?)?),
+                Rule::hex_range => {
+                    let mut range = Range::new();
+                    for range_elem in group.into_inner() {
+                        let bounds = match range_elem.as_rule() {
+                            Rule::hex_simple_range => range_elem
+                                .into_inner()
+                                .map(|n| parse_hex(n.as_str()))
+                                .collect::<Result<Vec<_>>>()?,
+                            Rule::hex_number => vec![parse_hex(range_elem.as_str())?],
+                            rule => return Err(Error::UnexpectedParserState(rule)),
+                        };
+                        let (start, end) = (bounds[0], bounds[bounds.len() - 1]);
+                        range.add_range(&SimpleRange::new(start, end)?)?;
+                    }
+                    groups.push(range);
+                }
+                rule => return Err(Error::UnexpectedParserState(rule)),
+            }
+        }
+
+        let written = head.len() + tail.len();
+        if written > GROUPS
+            || (compressed && written == GROUPS)
+            || (!compressed && written < GROUPS)
+        {
+            return Err(Error::InvalidAddress(element.to_string()));
+        }
+        for _ in written..GROUPS {
+            head.push(single_value(0)?);
+        }
+        head.extend(tail);
+
+        Self::from_groups(head)
+    }
+
+    fn from_groups(groups: Vec<Range>) -> Result<Self> {
+        let len = groups
+            .iter()
+            .try_fold(1usize, |len, group| len.checked_mul(group.total_len()))
+            .ok_or(Error::HostlistTooLarge)?;
+
+        Ok(Self { groups, len })
+    }
+
+    /// Returns the addresses in this element that aren't in `other`, as elements that don't
+    /// overlap: for each group, the addresses whose earlier groups are in both elements and whose
+    /// value for that group is only in this one
+    fn subtract(&self, other: &Self) -> Result<Vec<Self>> {
+        let overlap = self
+            .groups
+            .iter()
+            .zip(&other.groups)
+            .map(|(a, b)| a.intersect(b))
+            .collect::<Result<Vec<_>>>()?;
+        if overlap.iter().any(|group| group.ranges.is_empty()) {
+            return Ok(vec![self.clone()]);
+        }
+
+        let mut pieces = Vec::new();
+        for (i, group) in self.groups.iter().enumerate() {
+            let rest = group.subtract(&other.groups[i])?;
+            if !rest.ranges.is_empty() {
+                let mut groups = overlap[..i].to_vec();
+                groups.push(rest);
+                groups.extend_from_slice(&self.groups[i + 1..]);
+                pieces.push(Self::from_groups(groups)?);
+            }
+        }
+        Ok(pieces)
+    }
+
+    /// Returns the only group that differs from `other`'s, if all the others are the same
+    fn single_difference(&self, other: &Self) -> Option<usize> {
+        let mut differing = self
+            .groups
+            .iter()
+            .zip(&other.groups)
+            .enumerate()
+            .filter(|(_, (a, b))| a.ranges != b.ranges)
+            .map(|(i, _)| i);
+        let i = differing.next()?;
+        differing.next().is_none().then_some(i)
+    }
+
+    fn contains(&self, addr: Ipv6Addr) -> bool {
+        self.groups
+            .iter()
+            .zip(addr.segments())
+            .all(|(group, segment)| {
+                let segment = u64::from(segment);
+                group
+                    .ranges
+                    .iter()
+                    .any(|r| r.start <= segment && segment <= r.end)
+            })
+    }
+
+    /// Returns an iterator over the addresses, with the last group changing fastest
+    fn iter(&self) -> impl Iterator<Item = Ipv6Addr> + '_ {
+        let values: Vec<Vec<u16>> = self
+            .groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|v| u16::try_from(v).unwrap_or(u16::MAX))
+                    .collect()
+            })
+            .collect();
+        let mut indices = Some([0usize; GROUPS]);
+        std::iter::from_fn(move || {
+            let current = indices.as_mut()?;
+            let mut segments = [0u16; GROUPS];
+            for (segment, (group, &i)) in segments.iter_mut().zip(values.iter().zip(&*current)) {
+                *segment = group[i];
+            }
+
+            // Advance to the next address, like an odometer
+            let mut done = true;
+            for (i, group) in current.iter_mut().zip(&values).rev() {
+                *i += 1;
+                if *i < group.len() {
+                    done = false;
+                    break;
+                }
+                *i = 0;
+            }
+            if done {
+                indices = None;
+            }
+
+            Some(Ipv6Addr::from(segments))
+        })
+    }
+}
+
+/// Adds the addresses of `elem` that aren't already in `elems`, keeping the elements disjoint
+fn add_disjoint(elems: &mut Vec<Ipv6Elem>, elem: &Ipv6Elem) -> Result<()> {
+    let mut pieces = vec![elem.clone()];
+    for existing in elems.iter() {
+        let mut remaining = Vec::with_capacity(pieces.len());
+        for piece in &pieces {
+            remaining.extend(piece.subtract(existing)?);
+        }
+        pieces = remaining;
+    }
+    elems.extend(pieces);
+    Ok(())
+}
+
+/// Merges disjoint elements that differ in only one group (ex: `fd00::[1-2]` and `fd00::3`),
+/// until no two elements can be merged
+fn merge_elems(elems: &mut Vec<Ipv6Elem>) -> Result<()> {
+    let mut i = 0;
+    while i < elems.len() {
+        let found =
+            (i + 1..elems.len()).find_map(|j| Some((j, elems[i].single_difference(&elems[j])?)));
+        let Some((j, group)) = found else {
+            i += 1;
+            continue;
+        };
+        let other = elems.swap_remove(j);
+        let mut groups = elems[i].groups.clone();
+        groups[group] = groups[group].union(&other.groups[group])?;
+        elems[i] = Ipv6Elem::from_groups(groups)?;
+        // The merged element may now differ in only one group from an earlier one
+        i = 0;
+    }
+    Ok(())
+}
+
+/// Returns a range with just `value` in it
+fn single_value(value: u64) -> Result<Range> {
+    let mut range = Range::new();
+    range.add_range(&SimpleRange::new(value, value)?)?;
+    Ok(range)
+}
+
+fn parse_hex(digits: &str) -> Result<u64> {
+    Ok(u64::from_str_radix(digits, 16)?)
+}
+
+/// Renders a group's values in lowercase hex, in brackets if there's more than one
+fn hex_group(group: &Range) -> String {
+    let ranges: Vec<String> = group
+        .ranges
+        .iter()
+        .map(|r| {
+            if r.start == r.end {
+                format!("{:x}", r.start)
+            } else {
+                format!("{:x}-{:x}", r.start, r.end)
+            }
+        })
+        .collect();
+    match ranges.as_slice() {
+        [value] if !value.contains('-') => value.clone(),
+        _ => format!("[{}]", ranges.join(",")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    fn addr(s: &str) -> Ipv6Addr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ipv6_expand() -> Result<()> {
+        let tests: [(&str, &[&str]); 6] = [
+            ("", &[]),
+            ("fd00::[1-3]", &["fd00::1", "fd00::2", "fd00::3"]),
+            ("fd00::[9-b]", &["fd00::9", "fd00::a", "fd00::b"]),
+            ("::[0-1]", &["::", "::1"]),
+            (
+                "fd00:[0-1]::[fe-ff]",
+                &["fd00::fe", "fd00::ff", "fd00:1::fe", "fd00:1::ff"],
+            ),
+            (
+                "1:2:3:4:5:6:7:[8,a], fd00::1",
+                &["1:2:3:4:5:6:7:8", "1:2:3:4:5:6:7:a", "fd00::1"],
+            ),
+        ];
+        for (expr, expected) in tests {
+            let addrs = Ipv6Hostlist::new(expr)?;
+            assert_eq!(addrs.to_strings(), expected, "expr: {expr}");
+            assert_eq!(addrs.len(), expected.len(), "expr: {expr}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipv6_display() -> Result<()> {
+        let tests = [
+            ("FD00:0:0:0::[1-FF]", "fd00::[1-ff]"),
+            ("fd00:0:0:1:0:0:0:[1-2]", "fd00:0:0:1::[1-2]"),
+            ("0:0:[0-1]:0:0:0:0:1", "0:0:[0-1]::1"),
+            ("1:0:0:2:0:0:3:[4]", "1::2:0:0:3:4"),
+            ("::", "::"),
+            ("fd00::[1,3-5]", "fd00::[1,3-5]"),
+        ];
+        for (expr, expected) in tests {
+            assert_eq!(Ipv6Hostlist::new(expr)?.to_string(), expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipv6_invalid() {
+        let tests = [
+            ("1:2:3:4:5:6:7", ErrorKind::InvalidAddress),
+            ("1:2:3:4:5:6:7:8:9", ErrorKind::InvalidAddress),
+            ("1:2:3:4::5:6:7:8", ErrorKind::InvalidAddress),
+            ("fd00::[ff-1]", ErrorKind::InvalidRangeReversed),
+            ("fd00::12345", ErrorKind::ParseError),
+            ("fd00:::1", ErrorKind::ParseError),
+            ("fd00::1::2", ErrorKind::ParseError),
+            (
+                "::[0-ffff]:[0-ffff]:[0-ffff]:[0-ffff]:[0-ffff]",
+                ErrorKind::HostlistTooLarge,
+            ),
+        ];
+        for (expr, kind) in tests {
+            let err = Ipv6Hostlist::new(expr).unwrap_err();
+            assert_eq!(err.kind(), kind, "expr: {expr}");
+        }
+    }
+
+    #[test]
+    fn test_ipv6_overlapping() -> Result<()> {
+        let tests = [
+            ("fd00::1,fd00::1", "fd00::1", 1),
+            ("fd00::[1-5],fd00::[3-8]", "fd00::[1-8]", 8),
+            ("fd00::[1-2],fd00::3,fd00::[1-3]", "fd00::[1-3]", 3),
+            ("fd00::[1-3],fd00::[5-6]", "fd00::[1-3,5-6]", 5),
+            (
+                "fd00:[0-1]::[1-2],fd00:1::[2-3]",
+                "fd00:[0-1]::[1-2],fd00:1::3",
+                5,
+            ),
+            (
+                "fd00:[0-1]::[1-2],fd00:[1-2]::[1-2]",
+                "fd00:[0-2]::[1-2]",
+                6,
+            ),
+        ];
+        for (expr, expected, len) in tests {
+            let addrs = Ipv6Hostlist::new(expr)?;
+            assert_eq!(addrs.to_string(), expected, "expr: {expr}");
+            assert_eq!(addrs.len(), len, "expr: {expr}");
+            assert_eq!(addrs.iter().count(), len, "expr: {expr}");
+        }
+
+        // Each element fits in a usize, but not all of them together
+        let expr =
+            "1::[0-ffff]:[0-ffff]:[0-ffff]:[0-fffe],2:1::[0-ffff]:[0-ffff]:[0-ffff]:[0-fffe]";
+        assert_eq!(
+            Ipv6Hostlist::new(expr).unwrap_err().kind(),
+            ErrorKind::HostlistTooLarge
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipv6_contains() -> Result<()> {
+        let addrs = Ipv6Hostlist::new("fd00::[1-ff,200],fe80::1")?;
+        for a in ["fd00::1", "fd00::ff", "fd00::200", "fe80::1"] {
+            assert!(addrs.contains(addr(a)), "addr: {a}");
+        }
+        for a in ["fd00::", "fd00::100", "fd00:1::1", "fe80::2"] {
+            assert!(!addrs.contains(addr(a)), "addr: {a}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipv6_from_addrs() -> Result<()> {
+        let expr = "fd00::[1-ff],fd00:0:1::[a,c-d],fe80::1";
+        let addrs = Ipv6Hostlist::new(expr)?;
+        let mut reversed: Vec<Ipv6Addr> = addrs.iter().collect();
+        reversed.reverse();
+        let folded = Ipv6Hostlist::from_addrs(reversed)?;
+        assert_eq!(folded.to_string(), expr);
+        assert_eq!(folded, addrs);
+
+        let folded = Ipv6Hostlist::from_addrs([addr("::1"), addr("::1")])?;
+        assert_eq!(folded.to_string(), "::1");
+        assert!(Ipv6Hostlist::from_addrs([])?.is_empty());
+
+        Ok(())
+    }
+}
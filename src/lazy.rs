@@ -0,0 +1,201 @@
+use crate::error::Result;
+use crate::hostlist::Hostlist;
+use crate::hostlistelem::HostlistElem;
+
+/// A transformation recorded by `LazyHostlist`, applied by `build()`
+enum Step<'a> {
+    FilterIndices(Box<dyn Fn(u64) -> bool + 'a>),
+    MapPrefix(Box<dyn Fn(&str) -> String + 'a>),
+    Take(usize),
+}
+
+/// A chain of transformations of a `Hostlist`, created with `Hostlist::lazy()`.
+///
+/// Nothing happens until `build()`, which applies each step in order to the hostlist's ranges
+/// rather than to individual hosts, so huge hostlists are never expanded along the way.
+/// ```
+/// use hostlist_iter::Hostlist;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let hostlist = Hostlist::new("node[1-100]")?;
+///   let even = hostlist
+///     .lazy()
+///     .filter_indices(|i| i % 2 == 0)
+///     .map_prefix(|prefix| format!("{prefix}-ib"))
+///     .take(3)
+///     .build()?;
+///   assert_eq!(even.to_string(), "node-ib[2,4,6]");
+///
+///   Ok(())
+/// }
+/// ```
+pub struct LazyHostlist<'a> {
+    elems: Vec<HostlistElem>,
+    steps: Vec<Step<'a>>,
+}
+
+impl<'a> LazyHostlist<'a> {
+    pub(crate) fn new(elems: Vec<HostlistElem>) -> Self {
+        Self {
+            elems,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Keeps only hosts whose numeric indices all satisfy `pred` (ex: `|i| i % 2 == 0` keeps
+    /// "n2m4" but not "n2m3")
+    #[must_use]
+    pub fn filter_indices(mut self, pred: impl Fn(u64) -> bool + 'a) -> Self {
+        self.steps.push(Step::FilterIndices(Box::new(pred)));
+        self
+    }
+
+    /// Replaces the leading literal part of each element (ex: "node" in "node[1-3]") with the
+    /// result of `f`
+    #[must_use]
+    pub fn map_prefix(mut self, f: impl Fn(&str) -> String + 'a) -> Self {
+        self.steps.push(Step::MapPrefix(Box::new(f)));
+        self
+    }
+
+    /// Keeps only the first `n` hosts, in iteration order
+    #[must_use]
+    pub fn take(mut self, n: usize) -> Self {
+        self.steps.push(Step::Take(n));
+        self
+    }
+
+    /// Applies the transformations, producing the resulting `Hostlist`
+    ///
+    /// # Errors
+    /// Will return `Err` if `map_prefix()` produces a prefix that isn't valid in a host name.
+    pub fn build(self) -> Result<Hostlist> {
+        let mut elems = self
+            .elems
+            .into_iter()
+            .map(|elem| HostlistElem::from_components(elem.components))
+            .collect::<Result<Vec<_>>>()?;
+
+        for step in self.steps {
+            elems = match step {
+                Step::FilterIndices(pred) => elems
+                    .iter()
+                    .map(|elem| elem.filter_indices(&pred))
+                    .collect::<Result<_>>()?,
+                Step::MapPrefix(f) => elems
+                    .iter()
+                    .map(|elem| elem.map_prefix(&f))
+                    .collect::<Result<_>>()?,
+                Step::Take(n) => {
+                    // Earlier steps may have changed which elements combine, and their order
+                    let mut remaining = n;
+                    let mut taken = Vec::new();
+                    for elem in Hostlist::from_elems(elems, &mut Vec::new())?.hostlist_elems {
+                        if remaining == 0 {
+                            break;
+                        }
+                        taken.extend(elem.take_hosts(remaining)?);
+                        remaining -= remaining.min(elem.len());
+                    }
+                    taken
+                }
+            };
+        }
+
+        Hostlist::from_elems(elems, &mut Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn test_lazy_filter_indices() -> Result<()> {
+        let tests = [
+            ("n[1-10]", "n[2,4,6,8,10]"),
+            ("n[1-4]m[1-3]", "n[2,4]m[2]"),
+            ("n[1,3]m[2-4]", ""),
+            ("login,n[1-3]", "login,n[2]"),
+        ];
+
+        for (input, expected) in tests {
+            let hostlist = Hostlist::new(input)?;
+            let filtered = hostlist.lazy().filter_indices(|i| i % 2 == 0).build()?;
+            assert_eq!(filtered.to_string(), expected);
+
+            // Same hosts as filtering them one by one
+            let hosts: Vec<String> = hostlist
+                .iter()
+                .filter(|host| Hostlist::new(expected).unwrap().any(|h| &h == host))
+                .collect();
+            assert_eq!(filtered.to_vec(), hosts);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lazy_map_prefix() -> Result<()> {
+        let hostlist = Hostlist::new("node[1-3],nodes[5-6],x[7-8]")?;
+        let mapped = hostlist
+            .lazy()
+            .map_prefix(|prefix| prefix.trim_end_matches('s').to_uppercase())
+            .build()?;
+        // Elements that now share a prefix are combined
+        assert_eq!(mapped.to_string(), "NODE[1-3,5-6],X[7-8]");
+
+        let result = hostlist
+            .lazy()
+            .map_prefix(|_| "bad name".to_string())
+            .build();
+        assert!(matches!(result, Err(Error::InvalidHostname(name)) if name == "bad name"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lazy_take() -> Result<()> {
+        let inputs = [
+            "n[1-2]m[1-3]",
+            "a[1-2]b[1-2]c[1-3],login,z[5,7-9]",
+            "n[1-3]",
+        ];
+
+        for input in inputs {
+            let hostlist = Hostlist::new(input)?;
+            let hosts = hostlist.to_vec();
+            for n in 0..=hosts.len() + 1 {
+                let taken = hostlist.lazy().take(n).build()?;
+                assert_eq!(
+                    taken.to_vec(),
+                    hosts[..n.min(hosts.len())],
+                    "{input} take {n}"
+                );
+            }
+        }
+
+        let taken = Hostlist::new("n[1-2]m[1-3]")?.lazy().take(4).build()?;
+        assert_eq!(taken.to_string(), "n[1]m[1-3],n[2]m[1]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lazy_chain() -> Result<()> {
+        let mut hostlist = Hostlist::new("gpu[1-8],node[1-1000000]")?;
+        // Hosts already iterated over are still included
+        assert_eq!(hostlist.next(), Some("gpu1".to_string()));
+
+        let result = hostlist
+            .lazy()
+            .filter_indices(|i| i > 4)
+            .take(6)
+            .map_prefix(|prefix| format!("{prefix}-ib"))
+            .build()?;
+        assert_eq!(result.to_string(), "gpu-ib[5-8],node-ib[5-6]");
+
+        Ok(())
+    }
+}
@@ -1,17 +1,49 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::BufRead;
+use std::path::Path;
 
 use pest::Parser;
 
+mod digitblocks;
+pub mod digits;
 mod error;
+mod expand;
+pub mod grammar;
+pub mod hex;
+mod hostexpr;
 mod hostlist;
 mod hostlistelem;
+mod hostset;
+mod ipv6;
+mod lazy;
+mod options;
+mod padding;
 mod range;
+mod rangeint;
 mod simplerange;
+pub mod slurm;
+mod split;
+mod suggest;
+mod warning;
 
-pub use crate::error::{Error, Result};
-pub use crate::hostlist::Hostlist;
+pub use crate::error::{Error, ErrorKind, Result};
+pub use crate::expand::MAX_EXPANDED_HOSTS;
+pub use crate::hostexpr::HostExpr;
+pub use crate::hostlist::{HostIter, Hostlist, PartsIter, SortedIter};
+pub use crate::hostlistelem::{Fingerprint, FingerprintComponent, HostPart};
+pub use crate::hostset::HostSet;
+pub use crate::ipv6::Ipv6Hostlist;
+pub use crate::lazy::LazyHostlist;
+pub use crate::options::{CollapseOptions, CollapseOrder, FoldAxis, HostnameChars, ParseOptions};
+pub use crate::padding::Padding;
+pub use crate::split::Split;
+pub use crate::warning::{ParseOutcome, Warning};
 
-use crate::hostlist::{HostlistParser, Rule};
+use crate::hostlist::{HostlistParser, Rule, fold_multi};
+use crate::hostlistelem::{Component, HostlistElem, escape, unescape};
+use crate::range::Range;
+use crate::simplerange::SimpleRange;
 
 /// Expands a hostlist expression into a list of host names
 ///
@@ -32,6 +64,118 @@ pub fn expand_hostlist(expr: &str) -> Result<Vec<String>> {
     Ok(hostlist.into_iter().collect())
 }
 
+/// Counts the hosts in a hostlist expression, without expanding it
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if there are issues parsing the provided hostlist expression.
+/// ```
+/// use hostlist_iter::count_hosts;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   assert_eq!(count_hosts("node[1-100],login[1-2]")?, 102);
+///
+///   Ok(())
+/// }
+/// ```
+pub fn count_hosts(expr: &str) -> Result<usize> {
+    Ok(Hostlist::new(expr)?.len())
+}
+
+/// Returns the hosts in either hostlist expression, as a hostlist expression
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if there are issues parsing either hostlist expression.
+/// ```
+/// use hostlist_iter::union_hostlists;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   assert_eq!(union_hostlists("a[1-9]", "a[5-20]")?, "a[1-20]");
+///
+///   Ok(())
+/// }
+/// ```
+pub fn union_hostlists(a: &str, b: &str) -> Result<String> {
+    Ok(Hostlist::new(a)?.union(&Hostlist::new(b)?)?.to_string())
+}
+
+/// Returns the hosts in both hostlist expressions, as a hostlist expression
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if there are issues parsing either hostlist expression.
+/// ```
+/// use hostlist_iter::intersect_hostlists;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   assert_eq!(intersect_hostlists("a[1-9]", "a[5-20]")?, "a[5-9]");
+///
+///   Ok(())
+/// }
+/// ```
+pub fn intersect_hostlists(a: &str, b: &str) -> Result<String> {
+    Ok(Hostlist::new(a)?
+        .intersection(&Hostlist::new(b)?)?
+        .to_string())
+}
+
+/// Returns the hosts in `a` that aren't in `b`, as a hostlist expression
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if there are issues parsing either hostlist expression.
+/// ```
+/// use hostlist_iter::diff_hostlists;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   assert_eq!(diff_hostlists("a[1-9]", "a[5-20]")?, "a[1-4]");
+///
+///   Ok(())
+/// }
+/// ```
+pub fn diff_hostlists(a: &str, b: &str) -> Result<String> {
+    Ok(Hostlist::new(a)?
+        .difference(&Hostlist::new(b)?)?
+        .to_string())
+}
+
+/// Reads a hostlist from a file, see `parse_reader()` for the format
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if the file can't be read, or its contents can't be parsed.
+pub fn parse_file(path: impl AsRef<Path>) -> Result<Hostlist> {
+    let file = std::fs::File::open(path)?;
+    parse_reader(std::io::BufReader::new(file))
+}
+
+/// Reads a hostlist made up of the hostlist expressions on each line. Everything after a `#` is a
+/// comment, and blank lines are ignored.
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if reading fails, or the contents can't be parsed.
+/// ```
+/// use hostlist_iter::parse_reader;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let nodelist = "# compute nodes\nnode[1-4]\n#node5\n\nnode[6-8],login1  # and a login node\n";
+///   let hostlist = parse_reader(nodelist.as_bytes())?;
+///   assert_eq!(hostlist.to_string(), "login1,node[1-4,6-8]");
+///
+///   Ok(())
+/// }
+/// ```
+pub fn parse_reader(reader: impl BufRead) -> Result<Hostlist> {
+    let mut exprs = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let expr = line.split('#').next().unwrap_or_default();
+        // Tolerate lines ending with the comma that joined them to the next line
+        let expr = expr.trim().trim_matches(',');
+        if !expr.is_empty() {
+            exprs.push(expr.to_string());
+        }
+    }
+
+    Hostlist::new(&exprs.join(","))
+}
+
 /// Collapses a list of host names into a hostlist expression
 ///
 /// # Errors
@@ -48,82 +192,555 @@ pub fn expand_hostlist(expr: &str) -> Result<Vec<String>> {
 /// }
 /// ```
 pub fn collapse_hosts(hosts: impl IntoIterator<Item = impl AsRef<str>>) -> Result<String> {
-    let mut hostlist_elems: Vec<String> = Vec::new();
-    let mut prefix_map: BTreeMap<String, BTreeSet<u32>> = BTreeMap::new();
+    collapse_hosts_formatted(
+        hosts,
+        &CollapseOptions::default().order(CollapseOrder::ByPrefix),
+    )
+}
 
-    for host in hosts {
+/// Collapses a list of host names into a hostlist expression, handling zero-padded numbers as
+/// given by `padding`. With `Padding::Keep`, the default, numbers are only combined with numbers
+/// padded to the same width, so the expression expands back to the same host names when parsed
+/// (by `Hostlist::new()`, Slurm, or `ClusterShell`).
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if any host name cannot be parsed.
+/// ```
+/// use hostlist_iter::{Padding, collapse_hosts, collapse_hosts_with_padding};
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let hosts = ["n001", "n002", "n1", "n099", "n100"];
+///   assert_eq!(collapse_hosts(hosts)?, "n1,n[001-002,099-100]");
+///   assert_eq!(collapse_hosts_with_padding(hosts, Padding::Strip)?, "n[1-2,99-100]");
+///   assert_eq!(collapse_hosts_with_padding(hosts, Padding::Width(2))?, "n[01-02,99-100]");
+///
+///   Ok(())
+/// }
+/// ```
+pub fn collapse_hosts_with_padding(
+    hosts: impl IntoIterator<Item = impl AsRef<str>>,
+    padding: Padding,
+) -> Result<String> {
+    let options = CollapseOptions::default()
+        .order(CollapseOrder::ByPrefix)
+        .padding(padding);
+    collapse_hosts_formatted(hosts, &options)
+}
+
+/// Collapses a list of host names into a hostlist expression, like `collapse_hosts()`, accepting
+/// the host names allowed by `options`
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if any host name cannot be parsed.
+/// ```
+/// use hostlist_iter::{HostnameChars, ParseOptions, collapse_hosts_with_options};
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let options = ParseOptions::default().hostname_chars(HostnameChars::Extended);
+///   let hosts = ["db:primary01", "db:primary02", "db_replica1"];
+///   assert_eq!(collapse_hosts_with_options(hosts, options)?, "db:primary[01-02],db_replica1");
+///
+///   Ok(())
+/// }
+/// ```
+pub fn collapse_hosts_with_options(
+    hosts: impl IntoIterator<Item = impl AsRef<str>>,
+    options: ParseOptions,
+) -> Result<String> {
+    let options = CollapseOptions::default()
+        .order(CollapseOrder::ByPrefix)
+        .parse_options(options);
+    collapse_hosts_formatted(hosts, &options)
+}
+
+/// Collapses a list of host names into a hostlist expression, as given by `options`: which host
+/// names are accepted, how they're folded, and how the expression is written (ex: with spaces
+/// between elements for pdsh, see `Hostlist::new_whitespace_separated()`). The other
+/// `collapse_hosts*` functions are shorthands for this one.
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if any host name cannot be parsed.
+/// ```
+/// use hostlist_iter::{CollapseOptions, CollapseOrder, collapse_hosts_formatted};
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let hosts = ["n3", "login1", "n1", "n2", "gpu5"];
+///   let options = CollapseOptions::default().bracket_single(true).separator(" ");
+///   assert_eq!(collapse_hosts_formatted(hosts, &options)?, "gpu[5] login[1] n[1-3]");
+///
+///   let options = CollapseOptions::default().order(CollapseOrder::FirstSeen);
+///   assert_eq!(collapse_hosts_formatted(hosts, &options)?, "n[1-3],login1,gpu5");
+///
+///   Ok(())
+/// }
+/// ```
+pub fn collapse_hosts_formatted(
+    hosts: impl IntoIterator<Item = impl AsRef<str>>,
+    options: &CollapseOptions,
+) -> Result<String> {
+    if let Some(axis) = options.fold_axis {
+        return collapse_multi(hosts, axis, options);
+    }
+
+    let mut elems = collapse_elems(hosts, options)?;
+    match options.order {
+        CollapseOrder::Sorted => elems.sort_by_cached_key(|elem| natural_key(&elem.first_host)),
+        CollapseOrder::FirstSeen => elems.sort_by_key(|elem| elem.position),
+        CollapseOrder::ByPrefix => (),
+    }
+
+    let elems: Vec<String> = elems.iter().map(|elem| elem.expr(options)).collect();
+    Ok(elems.join(&options.separator))
+}
+
+/// A hostlist element collapsed from host names
+pub(crate) struct CollapsedElem {
+    /// The position of the first host name the element was made from
+    position: usize,
+    /// The element's first host in natural order
+    first_host: String,
+    /// The escaped text before the numbers, or the whole host name without a number
+    prefix: String,
+    /// The numbers, and the width they're rendered with
+    nums: Option<(BTreeSet<u64>, usize)>,
+    /// The escaped text after the numbers
+    suffix: String,
+}
+
+impl CollapsedElem {
+    /// Writes the element as given by `format`
+    fn expr(&self, format: &CollapseOptions) -> String {
+        match &self.nums {
+            Some((nums, width)) => {
+                let range = collapse_range(nums, *width, format);
+                format!("{}{range}{}", self.prefix, self.suffix)
+            }
+            None => self.prefix.clone(),
+        }
+    }
+
+    /// Builds the parsed element the expression would give, without writing and parsing it. With
+    /// `bracket_single`, a single number is a range (ex: "n[1]"), so it can be combined with a range
+    /// of another element.
+    pub(crate) fn to_hostlist_elem(&self, bracket_single: bool) -> Result<HostlistElem> {
+        if self
+            .nums
+            .as_ref()
+            .is_none_or(|(nums, _)| nums.len() == 1 && !bracket_single)
+        {
+            // Written without brackets, so it's parsed as a single static part
+            let host = unescape(&self.first_host).into_owned();
+            return HostlistElem::from_components(vec![Component::Static(host)]);
+        }
+        let mut components = Vec::with_capacity(3);
+        if !self.prefix.is_empty() {
+            components.push(Component::Static(unescape(&self.prefix).into_owned()));
+        }
+        if let Some((nums, width)) = &self.nums {
+            let ranges = nums
+                .iter()
+                .map(|&num| SimpleRange::with_width(num, num, *width))
+                .collect::<Result<Vec<_>>>()?;
+            components.push(Component::Range(Range::from_ranges(ranges)?));
+        }
+        if !self.suffix.is_empty() {
+            components.push(Component::Static(unescape(&self.suffix).into_owned()));
+        }
+        HostlistElem::from_components(components)
+    }
+}
+
+/// Collapses host names into hostlist elements along their last number, as given by `format`.
+/// Elements without a number come first, in the order they were given.
+pub(crate) fn collapse_elems(
+    hosts: impl IntoIterator<Item = impl AsRef<str>>,
+    format: &CollapseOptions,
+) -> Result<Vec<CollapsedElem>> {
+    let options = format.parse;
+    let padding = format.padding;
+    let mut hostlist_elems: Vec<CollapsedElem> = Vec::new();
+    let mut seen_elems: HashSet<String> = HashSet::new();
+    // Numbers by the text before and after them, and the width they're rendered with
+    let mut prefix_map: BTreeMap<(Affixes, usize), BTreeSet<u64>> = BTreeMap::new();
+    let mut first_seen: HashMap<Affixes, usize> = HashMap::new();
+    // The first host of each group of hosts collapsed along their short name
+    let mut split_hosts: HashMap<(Affixes, usize), (usize, String)> = HashMap::new();
+
+    for (position, host) in hosts.into_iter().enumerate() {
+        let Some(host) = collapse_input(host.as_ref(), format)? else {
+            continue;
+        };
         let host = host.as_ref();
-        if host.is_empty() {
-            return Err(Error::InvalidHostname(host.into()));
-        }
-
-        let mut prefix = None;
-        let mut suffix = None;
-        let pairs = HostlistParser::parse(Rule::simple_hostname, host)?;
-        for pair in pairs {
-            match pair.as_rule() {
-                Rule::prefix => prefix = Some(pair.as_str()),
-                Rule::numeric_suffix => suffix = Some(pair.as_str()),
-                Rule::EOI => break,
-                rule => return Err(Error::UnexpectedParserState(rule)),
+
+        check_hostname(host, options.hostname_chars)?;
+        // Characters that are part of the syntax are kept as literal parts of the name
+        let escaped = escape(host);
+        let (name, domain) = split_domain(&escaped);
+        let (prefix, number, suffix) = split_last_number(name);
+
+        if let Some(number) = number {
+            let suffix = format!("{suffix}{domain}");
+            let key = add_number(&mut prefix_map, padding, (prefix, number, &suffix))?;
+            first_seen.entry(key.0.clone()).or_insert(position);
+            if !domain.is_empty() {
+                split_hosts
+                    .entry(key)
+                    .or_insert_with(|| (position, escaped.to_string()));
             }
+        } else if seen_elems.insert(escaped.to_string()) {
+            hostlist_elems.push(CollapsedElem {
+                position,
+                first_host: escaped.to_string(),
+                prefix: escaped.into_owned(),
+                nums: None,
+                suffix: String::new(),
+            });
         }
+    }
+
+    // A short name that doesn't fold with any other is folded along the last number of the whole
+    // name instead (ex: "n1.dc[1-2]" for "n1.dc1" and "n1.dc2")
+    for (key, (position, host)) in split_hosts {
+        if prefix_map.get(&key).is_none_or(|nums| nums.len() > 1) {
+            continue;
+        }
+        prefix_map.remove(&key);
+        if let (prefix, Some(number), suffix) = split_last_number(&host) {
+            let key = add_number(&mut prefix_map, padding, (prefix, number, suffix))?;
+            first_seen
+                .entry(key.0)
+                .and_modify(|first| *first = (*first).min(position))
+                .or_insert(position);
+        }
+    }
 
-        let prefix = prefix
-            .ok_or_else(|| Error::InvalidHostname(host.to_string()))?
-            .to_string();
+    if padding == Padding::Keep {
+        join_padded_widths(&mut prefix_map);
+    }
 
-        if let Some(suffix) = suffix {
-            let suffix = suffix.parse::<u32>()?;
-            prefix_map.entry(prefix).or_default().insert(suffix);
+    for ((affixes, width), nums_set) in prefix_map {
+        let position = first_seen[&affixes];
+        let (prefix, suffix) = affixes;
+        let first = nums_set.first().copied().unwrap_or_default();
+        hostlist_elems.push(CollapsedElem {
+            position,
+            first_host: format!("{prefix}{first:0width$}{suffix}"),
+            prefix,
+            nums: Some((nums_set, width)),
+            suffix,
+        });
+    }
+
+    Ok(hostlist_elems)
+}
+
+/// Adds the number of a host, split into the text before it, the number, and the text after it, to
+/// the numbers with the same text and width, and returns the key it was added under
+fn add_number(
+    prefix_map: &mut BTreeMap<(Affixes, usize), BTreeSet<u64>>,
+    padding: Padding,
+    (prefix, number, suffix): (&str, &str, &str),
+) -> Result<(Affixes, usize)> {
+    let width = match padding {
+        Padding::Keep if number.len() > 1 && number.starts_with('0') => number.len(),
+        Padding::Keep | Padding::Strip => 0,
+        Padding::Width(width) => width,
+    };
+    let key = ((prefix.to_string(), suffix.to_string()), width);
+    prefix_map
+        .entry(key.clone())
+        .or_default()
+        .insert(number.parse::<u64>()?);
+    Ok(key)
+}
+
+/// Checks that every character of `host` is allowed in a host name by `chars`, a character at a
+/// time rather than by running the parser on every name
+fn check_hostname(host: &str, chars: HostnameChars) -> Result<()> {
+    if host.chars().all(|c| chars.allows(c)) {
+        return Ok(());
+    }
+    // Let the parser describe what's wrong with the name
+    HostlistParser::parse(chars.simple_hostname_rule(), &escape(host))?;
+    Err(Error::InvalidHostname(host.to_string()))
+}
+
+/// Reads a host name to collapse as given by `options`: lowercased with `ignore_case`, and
+/// trimmed, or skipped if it's blank, with lenient parsing
+fn collapse_input<'a>(host: &'a str, options: &CollapseOptions) -> Result<Option<Cow<'a, str>>> {
+    let host = if options.parse.lenient {
+        host.trim()
+    } else {
+        host
+    };
+    if host.is_empty() {
+        return if options.parse.lenient {
+            Ok(None)
         } else {
-            hostlist_elems.push(prefix);
-        }
+            Err(Error::InvalidHostname(host.into()))
+        };
+    }
+
+    if options.ignore_case && host.bytes().any(|b| b.is_ascii_uppercase()) {
+        Ok(Some(Cow::Owned(host.to_ascii_lowercase())))
+    } else {
+        Ok(Some(Cow::Borrowed(host)))
+    }
+}
+
+/// Collapses a list of host names into a hostlist expression, folding along every number in the
+/// names rather than only a trailing one, like `ClusterShell`'s `nodeset -f`. The number folded
+/// along first is whichever gives the shortest expression. Zero-padding is dropped, unlike
+/// `collapse_hosts()`.
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if any host name cannot be parsed.
+/// ```
+/// use hostlist_iter::{collapse_hosts, collapse_hosts_multi};
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let hosts = ["r1n1", "r1n2", "r2n1", "r2n2"];
+///   assert_eq!(collapse_hosts(hosts)?, "r1n[1-2],r2n[1-2]");
+///   assert_eq!(collapse_hosts_multi(hosts)?, "r[1-2]n[1-2]");
+///
+///   Ok(())
+/// }
+/// ```
+pub fn collapse_hosts_multi(hosts: impl IntoIterator<Item = impl AsRef<str>>) -> Result<String> {
+    collapse_hosts_multi_with_axis(hosts, FoldAxis::Auto)
+}
+
+/// Collapses a list of host names into a hostlist expression like `collapse_hosts_multi()`, folding
+/// along the numbers chosen by `axis`
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if any host name cannot be parsed.
+/// ```
+/// use hostlist_iter::{FoldAxis, collapse_hosts_multi_with_axis};
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let hosts = ["r1n1", "r2n1", "r3n1", "r1n2"];
+///   assert_eq!(collapse_hosts_multi_with_axis(hosts, FoldAxis::Auto)?, "r1n2,r[1-3]n1");
+///   assert_eq!(collapse_hosts_multi_with_axis(hosts, FoldAxis::Only(1))?, "r1n[1-2],r2n1,r3n1");
+///
+///   Ok(())
+/// }
+/// ```
+pub fn collapse_hosts_multi_with_axis(
+    hosts: impl IntoIterator<Item = impl AsRef<str>>,
+    axis: FoldAxis,
+) -> Result<String> {
+    collapse_hosts_formatted(hosts, &CollapseOptions::default().fold_axis(axis))
+}
+
+/// Collapses host names along the numbers chosen by `axis`, for `collapse_hosts_formatted()`
+fn collapse_multi(
+    hosts: impl IntoIterator<Item = impl AsRef<str>>,
+    axis: FoldAxis,
+    options: &CollapseOptions,
+) -> Result<String> {
+    let mut elems = Vec::new();
+    for host in hosts {
+        let Some(host) = collapse_input(host.as_ref(), options)? else {
+            continue;
+        };
+        check_hostname(&host, options.parse.hostname_chars)?;
+        elems.push(HostlistElem::from_host(&host)?);
     }
 
-    for (prefix, nums_set) in prefix_map {
-        let mut host = prefix;
-        host.push_str(collapse_range(&nums_set).as_str());
-        hostlist_elems.push(host);
+    let collapsed: Vec<String> = fold_multi(elems, axis)?
+        .iter()
+        .map(|elem| {
+            if options.bracket_single {
+                elem.to_string()
+            } else {
+                elem.to_compact_string()
+            }
+        })
+        .collect();
+    Ok(collapsed.join(&options.separator))
+}
+
+/// Splits a host name into its short name and domain (ex: "n1" and ".dc2.example.com"), so that
+/// it's collapsed along the numbers of its short name, unless no other short name folds with it.
+/// Names where either side has no letters (ex: "10.0.0.1") aren't split.
+fn split_domain(host: &str) -> (&str, &str) {
+    let has_letters = |s: &str| s.bytes().any(|b| b.is_ascii_alphabetic());
+    match host.find('.') {
+        Some(i) if has_letters(&host[..i]) && has_letters(&host[i..]) => host.split_at(i),
+        _ => (host, ""),
     }
+}
 
-    Ok(hostlist_elems.join(","))
+/// Splits a host name into the text before its last number, the number, and the text after it
+/// (ex: "gpu", "01", and "-ib" for "gpu01-ib"), as the `simple_hostname` rule would
+fn split_last_number(host: &str) -> (&str, Option<&str>, &str) {
+    let Some(last_digit) = host.rfind(|c: char| c.is_ascii_digit()) else {
+        return (host, None, "");
+    };
+    let end = last_digit + 1;
+    let start = host[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+    (&host[..start], Some(&host[start..end]), &host[end..])
 }
 
-/// Convert an iterator of numbers into a range expression
-fn collapse_range(nums: &BTreeSet<u32>) -> String {
-    let mut collapsed = String::new();
-    let mut in_range = false;
-    let mut needs_brackets = false;
-    let mut prev_num = 0;
-    for (i, num) in nums.iter().enumerate() {
-        if i == 0 {
-            collapsed += &num.to_string();
-        } else if *num == prev_num + 1 {
-            if !in_range {
-                // saw the second number in a range
-                collapsed.push('-');
-                in_range = true;
-                needs_brackets = true;
+/// The text before and after the last number of a host name (ex: "gpu" and "-ib" in "gpu01-ib")
+pub(crate) type Affixes = (String, String);
+
+/// Moves unpadded numbers with as many digits as a padded width of the same prefix into that
+/// width's numbers (ex: "n100" joins "n099", since it's rendered the same way with a width of 3)
+fn join_padded_widths(prefix_map: &mut BTreeMap<(Affixes, usize), BTreeSet<u64>>) {
+    let padded: HashSet<(Affixes, usize)> = prefix_map
+        .keys()
+        .filter(|(_, width)| *width > 0)
+        .cloned()
+        .collect();
+
+    let mut moved = Vec::new();
+    for ((prefix, width), nums) in prefix_map.iter_mut() {
+        if *width > 0 {
+            continue;
+        }
+        nums.retain(|num| {
+            let key = (prefix.clone(), num.to_string().len());
+            if padded.contains(&key) {
+                moved.push((key, *num));
+                return false;
             }
-        } else {
-            if in_range {
-                // previous number was the end of a range
-                collapsed += &prev_num.to_string();
-                in_range = false;
+            true
+        });
+    }
+    for (key, num) in moved {
+        prefix_map.entry(key).or_default().insert(num);
+    }
+    prefix_map.retain(|_, nums| !nums.is_empty());
+}
+
+/// Collapses a list of host names into a hostlist expression, lowercasing them first so that
+/// names differing only in case (ex: "Node1" and "node1") are treated as the same host
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if any host name cannot be parsed.
+/// ```
+/// use hostlist_iter::collapse_hosts_ignore_case;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let hosts = vec!["Node1", "node1", "NODE2", "Login"];
+///   let hostlist = collapse_hosts_ignore_case(hosts)?;
+///   assert_eq!("login,node[1-2]", hostlist);
+///
+///   Ok(())
+/// }
+/// ```
+pub fn collapse_hosts_ignore_case(
+    hosts: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Result<String> {
+    let options = CollapseOptions::default()
+        .order(CollapseOrder::ByPrefix)
+        .ignore_case(true);
+    collapse_hosts_formatted(hosts, &options)
+}
+
+/// Sorts host names in natural order: literal parts are compared as strings, and numeric parts
+/// by value regardless of zero-padding (ex: "n2" before "n010", and "r2n1" before "r10n1").
+/// Duplicates are kept, and names differing only in padding keep a stable order.
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if any host name cannot be parsed.
+/// ```
+/// use hostlist_iter::sort_hosts;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let hosts = sort_hosts(["node10", "node09", "login", "node1"])?;
+///   assert_eq!(hosts, ["login", "node1", "node09", "node10"]);
+///
+///   Ok(())
+/// }
+/// ```
+pub fn sort_hosts(hosts: impl IntoIterator<Item = impl AsRef<str>>) -> Result<Vec<String>> {
+    let mut hosts = hosts
+        .into_iter()
+        .map(|host| {
+            let host = host.as_ref();
+            if host.is_empty() {
+                return Err(Error::InvalidHostname(host.into()));
             }
-            // current number starts a new range
-            collapsed.push(',');
-            needs_brackets = true;
-            collapsed += &num.to_string();
+            HostlistParser::parse(Rule::simple_hostname, host)?;
+            Ok(host.to_string())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    hosts.sort_by_cached_key(|host| natural_key(host));
+    Ok(hosts)
+}
+
+/// A part of a host name, ordered for `sort_hosts()`
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) enum NaturalPart {
+    /// The digits of a number without leading zeros, ordered by length first so that they're
+    /// compared by value (without overflowing)
+    Number(usize, String),
+    Literal(String),
+}
+
+/// Splits a host name into alternating literal and numeric parts
+pub(crate) fn natural_key(host: &str) -> Vec<NaturalPart> {
+    let mut parts = Vec::new();
+    let mut rest = host;
+    while let Some(c) = rest.chars().next() {
+        let is_digit = c.is_ascii_digit();
+        let len = rest
+            .find(|c: char| c.is_ascii_digit() != is_digit)
+            .unwrap_or(rest.len());
+        let (part, remaining) = rest.split_at(len);
+        if is_digit {
+            let digits = part.trim_start_matches('0');
+            parts.push(NaturalPart::Number(digits.len(), digits.to_string()));
+        } else {
+            parts.push(NaturalPart::Literal(part.to_string()));
         }
-        prev_num = *num;
+        rest = remaining;
     }
-    if in_range {
-        collapsed += &prev_num.to_string();
+    parts
+}
+
+/// Convert an iterator of numbers into a range expression, rendering each number with at least
+/// `width` digits, and writing runs with a step and single numbers as given by `format`
+fn collapse_range(nums: &BTreeSet<u64>, width: usize, format: &CollapseOptions) -> String {
+    let render = |num: u64| format!("{num:0width$}");
+    let nums: Vec<u64> = nums.iter().copied().collect();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while start < nums.len() {
+        // The longest run from the start with the same difference between each number
+        let step = nums.get(start + 1).map_or(1, |next| next - nums[start]);
+        let mut end = start;
+        while nums
+            .get(end + 1)
+            .is_some_and(|next| next - nums[end] == step)
+        {
+            end += 1;
+        }
+
+        if end > start && step == 1 {
+            parts.push(format!("{}-{}", render(nums[start]), render(nums[end])));
+        } else if end > start + 1 && format.steps {
+            parts.push(format!(
+                "{}-{}/{step}",
+                render(nums[start]),
+                render(nums[end])
+            ));
+        } else {
+            // Too short to be worth a step, so the next number starts its own run
+            parts.push(render(nums[start]));
+            end = start;
+        }
+        start = end + 1;
     }
 
-    if needs_brackets {
+    let collapsed = parts.join(",");
+    if nums.len() > 1 || (format.bracket_single && !nums.is_empty()) {
         format!("[{collapsed}]")
     } else {
         collapsed
@@ -149,10 +766,34 @@ mod tests {
         ];
 
         for (input, expected) in tests {
-            let mut nums: BTreeSet<u32> = BTreeSet::new();
+            let mut nums: BTreeSet<u64> = BTreeSet::new();
             nums.extend(input);
-            assert_eq!(collapse_range(&nums), expected);
+            assert_eq!(
+                collapse_range(&nums, 0, &CollapseOptions::default()),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_collapse_range_steps() {
+        let tests = [
+            (vec![7], "7"),
+            (vec![2, 4], "[2,4]"),
+            (vec![2, 4, 6, 8], "[2-8/2]"),
+            (vec![1, 2, 3, 5, 7, 9], "[1-3,5-9/2]"),
+            (vec![1, 3, 5, 6, 7], "[1-5/2,6-7]"),
+            (vec![2, 4, 5, 6], "[2,4-6]"),
+            (vec![0, 10, 20, 25], "[0-20/10,25]"),
+        ];
+
+        let format = CollapseOptions::default().steps(true);
+        for (input, expected) in tests {
+            let nums: BTreeSet<u64> = input.into_iter().collect();
+            assert_eq!(collapse_range(&nums, 0, &format), expected);
         }
+        let nums = BTreeSet::from([2, 4, 6]);
+        assert_eq!(collapse_range(&nums, 2, &format), "[02-06/2]");
     }
 
     #[test]
@@ -195,6 +836,14 @@ mod tests {
     fn test_expand_invalid_format() {
         let hostlist = "node[1-5"; // Missing closing bracket
         let result = expand_hostlist(hostlist);
+        assert!(matches!(
+            result,
+            Err(Error::Suggestion { suggestion, source })
+                if suggestion == "node[1-5]" && matches!(*source, Error::ParseError(_))
+        ));
+
+        let hostlist = "node[1-5]x?"; // No recognizable mistake
+        let result = expand_hostlist(hostlist);
         assert!(matches!(result, Err(Error::ParseError(_))));
     }
 
@@ -204,15 +853,20 @@ mod tests {
         let result = expand_hostlist(hostlist);
         assert!(matches!(
             result,
-            Err(Error::InvalidRangeReversed { start: 5, end: 1 })
+            Err(Error::InElement { element, source, .. })
+                if element == "node[5-1]"
+                    && matches!(*source, Error::InvalidRangeReversed { start: 5, end: 1 })
         ));
     }
 
     #[test]
     fn test_expand_range_integer_overflow() {
-        let hostlist = "n[4294967295]";
+        let hostlist = "n[18446744073709551615]";
         let result = expand_hostlist(hostlist);
-        assert!(matches!(result, Err(Error::TooLarge(4_294_967_295))));
+        assert!(matches!(
+            result,
+            Err(Error::InElement { source, .. }) if matches!(*source, Error::TooLarge(u64::MAX))
+        ));
     }
 
     #[test]
@@ -233,6 +887,33 @@ mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_count_hosts() -> Result<()> {
+        assert_eq!(count_hosts("")?, 0);
+        assert_eq!(count_hosts("n[5-7,60]-01, an[1-3,9],server")?, 9);
+        assert_eq!(count_hosts("n[1-1000000]m[1-1000]")?, 1_000_000_000);
+        assert!(matches!(
+            count_hosts("n[1-3"),
+            Err(Error::Suggestion { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hostlist_set_helpers() -> Result<()> {
+        assert_eq!(union_hostlists("n[1-3],m1", "n[3-5]")?, "m1,n[1-5]");
+        assert_eq!(union_hostlists("", "n1")?, "n1");
+        assert_eq!(intersect_hostlists("n[1-3],m1", "n[3-5],m1")?, "m1,n[3]");
+        assert_eq!(intersect_hostlists("n[1-3]", "m[1-3]")?, "");
+        assert_eq!(diff_hostlists("n[1-10]", "n[3-4],m1")?, "n[1-2,5-10]");
+        assert_eq!(diff_hostlists("n[1-3]", "n[1-3]")?, "");
+        assert!(diff_hostlists("n[1-3", "n1").is_err());
+        assert!(union_hostlists("n1", "n[3-1]").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_expand_multi_range() {
         let hostlist = "n[1-2]m[1-3]o[1-2]";
@@ -325,6 +1006,46 @@ mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_parse_reader() -> Result<()> {
+        let tests = [
+            ("", ""),
+            ("# nothing here\n\n   \n", ""),
+            ("n[1-3]\n", "n[1-3]"),
+            ("n[1-3],\nn[4-5]\r\n#n6\n  n7 # straggler\n", "n[1-5],n7"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_reader(input.as_bytes())?.to_string(), expected);
+        }
+
+        assert!(matches!(
+            parse_reader("n[1-3]\nn[5-4]\n".as_bytes()),
+            Err(Error::InElement { index: 1, .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_file() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("hostlist_iter_test_{}", std::process::id()));
+        std::fs::write(&path, "# nodes\nnode[1-2]\nnode3\n")?;
+        let result = parse_file(&path);
+        std::fs::remove_file(&path)?;
+        assert_eq!(result?.to_string(), "node[1-2],node3");
+
+        let missing = parse_file(&path);
+        assert!(matches!(
+            missing,
+            Err(Error::Io {
+                kind: std::io::ErrorKind::NotFound,
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_collapse_hosts() -> Result<()> {
         let tests = [
@@ -343,7 +1064,8 @@ mod tests {
                 vec!["n1", "n2", "n3", "n5", "n6", "foo1"],
                 "foo1,n[1-3,5-6]",
             ),
-            (vec!["n001", "n002", "n003"], "n[1-3]"),
+            (vec!["n001", "n002", "n003"], "n[001-003]"),
+            (vec!["foo", "n1", "foo"], "foo,n1"),
         ];
         for (input, expected) in tests {
             assert_eq!(expected, collapse_hosts(input)?);
@@ -352,10 +1074,410 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_collapse_hosts_formatted() -> Result<()> {
+        let hosts = ["n10-ib", "n2", "login1", "n1", "n02", "n9-ib", "gpu"];
+        let tests = [
+            (
+                CollapseOptions::default(),
+                "gpu,login1,n[1-2],n02,n[9-10]-ib",
+            ),
+            (
+                CollapseOptions::default().bracket_single(true),
+                "gpu,login[1],n[1-2],n[02],n[9-10]-ib",
+            ),
+            (
+                CollapseOptions::default().separator(" "),
+                "gpu login1 n[1-2] n02 n[9-10]-ib",
+            ),
+            (
+                CollapseOptions::default().order(CollapseOrder::FirstSeen),
+                "n[9-10]-ib,n[1-2],n02,login1,gpu",
+            ),
+            (
+                CollapseOptions::default().padding(Padding::Strip),
+                "gpu,login1,n[1-2],n[9-10]-ib",
+            ),
+            (
+                CollapseOptions::default()
+                    .padding(Padding::Strip)
+                    .order(CollapseOrder::FirstSeen),
+                "n[9-10]-ib,n[1-2],login1,gpu",
+            ),
+        ];
+        for (options, expected) in tests {
+            assert_eq!(
+                collapse_hosts_formatted(hosts, &options)?,
+                expected,
+                "options: {options:?}"
+            );
+        }
+
+        // Every collapse_hosts* function is a shorthand for these options
+        let hosts = [
+            "gpu3", "GPU4", " gpu1-ib", "gpu2-ib ", "", "login", "r1n1", "r2n1",
+        ];
+        let lenient = ParseOptions::default().lenient(true);
+        let tests = [
+            (
+                CollapseOptions::default().parse_options(lenient),
+                "GPU4,gpu[1-2]-ib,gpu3,login,r1n1,r2n1",
+            ),
+            (
+                CollapseOptions::default()
+                    .parse_options(lenient)
+                    .ignore_case(true)
+                    .order(CollapseOrder::ByPrefix),
+                "login,gpu[3-4],gpu[1-2]-ib,r1n1,r2n1",
+            ),
+            (
+                CollapseOptions::default()
+                    .parse_options(lenient)
+                    .ignore_case(true)
+                    .fold_axis(FoldAxis::Auto)
+                    .separator(" "),
+                "gpu[1-2]-ib gpu[3-4] login r[1-2]n1",
+            ),
+            (
+                CollapseOptions::default()
+                    .parse_options(lenient)
+                    .fold_axis(FoldAxis::Only(0))
+                    .bracket_single(true),
+                "GPU[4],gpu[1-2]-ib,gpu[3],login,r[1-2]n[1]",
+            ),
+        ];
+        for (options, expected) in tests {
+            assert_eq!(
+                collapse_hosts_formatted(hosts, &options)?,
+                expected,
+                "options: {options:?}"
+            );
+        }
+        let extended = ParseOptions::default().hostname_chars(HostnameChars::Extended);
+        let options = CollapseOptions::default()
+            .parse_options(extended)
+            .fold_axis(FoldAxis::Auto);
+        assert_eq!(
+            collapse_hosts_formatted(["db:r1n1", "db:r2n1"], &options)?,
+            "db:r[1-2]n1"
+        );
+        assert!(collapse_hosts_multi(["db:r1n1"]).is_err());
+
+        // The default format is parsed back to the same hosts
+        let hosts = ["n10-ib", "n2", "login1", "n1", "n02", "n9-ib", "gpu"];
+        let collapsed = collapse_hosts_formatted(hosts, &CollapseOptions::default())?;
+        assert_eq!(Hostlist::new(&collapsed)?.len(), 7);
+        assert_eq!(
+            collapse_hosts_formatted(["n1", ""], &CollapseOptions::default()),
+            Err(Error::InvalidHostname(String::new()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_last_number() -> Result<()> {
+        let hosts = [
+            "n1", "n01-ib", "1a", "42", "login", "r1n2", "a-b.c", "n1.2", r"a\[1\]b", "db:x9",
+        ];
+        for chars in [HostnameChars::Standard, HostnameChars::Extended] {
+            for host in hosts {
+                if !host.chars().all(|c| chars.allows(c)) {
+                    assert!(HostlistParser::parse(chars.simple_hostname_rule(), host).is_err());
+                    continue;
+                }
+                let (mut prefix, mut number, mut suffix) = ("", None, "");
+                for pair in HostlistParser::parse(chars.simple_hostname_rule(), host)? {
+                    match pair.as_rule() {
+                        Rule::prefix | Rule::extended_prefix => prefix = pair.as_str(),
+                        Rule::numeric_suffix | Rule::extended_numeric_suffix => {
+                            number = Some(pair.as_str());
+                        }
+                        Rule::host_suffix | Rule::extended_host_suffix => suffix = pair.as_str(),
+                        _ => (),
+                    }
+                }
+                assert_eq!(split_last_number(host), (prefix, number, suffix), "{host}");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_hosts_interior_numbers() -> Result<()> {
+        let tests = [
+            (vec!["gpu01-ib", "gpu02-ib"], "gpu[01-02]-ib"),
+            (
+                vec!["gpu1-ib", "gpu2-ib", "gpu3", "gpu4"],
+                "gpu[3-4],gpu[1-2]-ib",
+            ),
+            (
+                vec!["n1.example.com", "n2.example.com"],
+                "n[1-2].example.com",
+            ),
+            (vec!["r1n1-ib", "r1n2-ib", "r2n1-ib"], "r1n[1-2]-ib,r2n1-ib"),
+            (vec!["1a", "2a"], "[1-2]a"),
+            (
+                vec![
+                    "n1.dc2.example.com",
+                    "n2.dc2.example.com",
+                    "n1.dc3.example.com",
+                ],
+                "n[1-2].dc2.example.com,n1.dc3.example.com",
+            ),
+            (
+                vec![
+                    "login.dc2.example.com",
+                    "n01-ib.example.com",
+                    "n02-ib.example.com",
+                ],
+                "login.dc2.example.com,n[01-02]-ib.example.com",
+            ),
+            // Short names that don't fold with any other fold along the whole name
+            (vec!["n1.dc1", "n1.dc2"], "n1.dc[1-2]"),
+            (
+                vec!["n1.dc1.example.com", "n1.dc2.example.com"],
+                "n1.dc[1-2].example.com",
+            ),
+            (vec!["n1.dc1", "n2.dc1", "n1.dc2"], "n[1-2].dc1,n1.dc2"),
+            (vec!["10.0.0.1", "10.0.0.2"], "10.0.0.[1-2]"),
+            (vec!["n1.2", "n1.3"], "n1.[2-3]"),
+            (vec!["n1-ib", "n2-eth"], "n2-eth,n1-ib"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(expected, collapse_hosts(&input)?);
+        }
+        assert_eq!(
+            digits::collapse(["gpu01-ib", "gpu02-ib", "gpu3"])?,
+            "gpu3,gpu[01-02]-ib"
+        );
+
+        let hosts = ["gpu01-ib", "gpu02-ib", "gpu1-ib"];
+        assert_eq!(
+            collapse_hosts_with_padding(hosts, Padding::Strip)?,
+            "gpu[1-2]-ib"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_hosts_with_padding() -> Result<()> {
+        let tests: [(&[&str], Padding, &str); 8] = [
+            (&["n001", "n002", "n1"], Padding::Keep, "n1,n[001-002]"),
+            (&["n001", "n002", "n1"], Padding::Strip, "n[1-2]"),
+            (
+                &["n098", "n099", "n100", "n101"],
+                Padding::Keep,
+                "n[098-101]",
+            ),
+            (&["n9", "n10", "n11"], Padding::Keep, "n[9-11]"),
+            (&["n01", "n001", "n1"], Padding::Keep, "n1,n01,n001"),
+            (&["n10", "n010", "login"], Padding::Keep, "login,n10,n010"),
+            (&["n1", "n2", "n10"], Padding::Width(3), "n[001-002,010]"),
+            (&["n1000", "n999"], Padding::Width(3), "n[999-1000]"),
+        ];
+        for (hosts, padding, expected) in tests {
+            let collapsed = collapse_hosts_with_padding(hosts, padding)?;
+            assert_eq!(collapsed, expected, "{hosts:?} {padding:?}");
+        }
+
+        // Kept padding expands back to the same hosts
+        let hosts = ["n1", "n001", "n002", "n099", "n100", "n0100", "r01n1"];
+        let collapsed = collapse_hosts_with_padding(hosts, Padding::Keep)?;
+        let mut expanded = Hostlist::new_with_padding(&collapsed, Padding::Keep)?.to_vec();
+        expanded.sort();
+        let mut sorted = hosts.map(String::from).to_vec();
+        sorted.sort();
+        assert_eq!(expanded, sorted);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_hosts_ignore_case() -> Result<()> {
+        let tests = [
+            (vec!["Node1", "node1"], "node1"),
+            (vec!["Node1", "NODE2", "node3"], "node[1-3]"),
+            (vec!["Login", "login", "n1"], "login,n1"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(expected, collapse_hosts_ignore_case(input)?);
+        }
+
+        // Without ignoring case, differently-cased names stay separate
+        assert_eq!("Node1,node2", collapse_hosts(["Node1", "node2"])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_hosts_with_options() -> Result<()> {
+        let extended = ParseOptions::default().hostname_chars(HostnameChars::Extended);
+        let tests = [
+            (vec!["db_primary1", "db_primary2"], "db_primary[1-2]"),
+            (vec!["db:primary1", "db:primary2"], "db:primary[1-2]"),
+            (vec!["user@n1", "user@n2", "a+b"], "a+b,user@n[1-2]"),
+            (vec!["n1:8080", "n2:8080"], "n1:8080,n2:8080"),
+            (vec!["n1", "n1~", "n2"], "n[1-2],n1~"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(expected, collapse_hosts_with_options(&input, extended)?);
+            let hosts = Hostlist::new_with_options(expected, extended)?.to_vec();
+            assert_eq!(
+                hosts.iter().map(String::as_str).collect::<BTreeSet<_>>(),
+                input.into_iter().collect()
+            );
+        }
+
+        let lenient = ParseOptions::default().lenient(true);
+        assert_eq!(
+            collapse_hosts_with_options(["n1 ", "", "\tn2", "  "], lenient)?,
+            "n[1-2]"
+        );
+        assert!(collapse_hosts_with_options(["n 1"], lenient).is_err());
+
+        assert!(collapse_hosts(["db:primary1"]).is_err());
+        assert!(collapse_hosts_with_options(["db:primary1"], ParseOptions::default()).is_err());
+        for input in ["n 1", "n?1", "n\t1", ""] {
+            assert!(
+                collapse_hosts_with_options([input], extended).is_err(),
+                "input: {input:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_hosts() -> Result<()> {
+        let tests: [(&[&str], &[&str]); 6] = [
+            (&[], &[]),
+            (&["n10", "n9", "n1"], &["n1", "n9", "n10"]),
+            (&["n002", "n1", "n10"], &["n1", "n002", "n10"]),
+            (&["r10n1", "r2n10", "r2n2"], &["r2n2", "r2n10", "r10n1"]),
+            (&["n1", "n", "m", "n1"], &["m", "n", "n1", "n1"]),
+            (
+                &["n99999999999999999999", "n4294967296", "n1"],
+                &["n1", "n4294967296", "n99999999999999999999"],
+            ),
+        ];
+        for (hosts, expected) in tests {
+            assert_eq!(sort_hosts(hosts)?, expected);
+        }
+
+        assert_eq!(
+            sort_hosts(["n1", ""]),
+            Err(Error::InvalidHostname(String::new()))
+        );
+        assert!(sort_hosts(["n[1-2]"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_hosts_multi() -> Result<()> {
+        let tests = [
+            (vec![], ""),
+            (vec!["r1n1", "r1n2", "r2n1", "r2n2"], "r[1-2]n[1-2]"),
+            (vec!["r1n1", "r2n2"], "r1n1,r2n2"),
+            (vec!["r1n1", "r1n2", "r2n1"], "r1n[1-2],r2n1"),
+            (
+                vec!["node2", "node1", "node3", "node5", "login"],
+                "login,node[1-3,5]",
+            ),
+            (vec!["gpu01-ib", "gpu02-ib", "gpu2-ib"], "gpu[1-2]-ib"),
+            (
+                vec!["c1-r1-n1", "c1-r1-n2", "c1-r2-n1", "c1-r2-n2", "c2-r1-n1"],
+                "c1-r[1-2]-n[1-2],c2-r1-n1",
+            ),
+            (vec!["1n1", "1n2"], "1n[1-2]"),
+            (vec!["vm[a]1", "vm[a]2"], r"vm\[a\][1-2]"),
+            (
+                vec!["n99999999999999999999", "n1"],
+                "n1,n99999999999999999999",
+            ),
+        ];
+        for (input, expected) in tests {
+            let collapsed = collapse_hosts_multi(&input)?;
+            assert_eq!(expected, collapsed);
+            // Padding is dropped, so padded names don't expand back to themselves
+            if !input.iter().any(|host| host.contains('0')) {
+                let mut expanded = expand_hostlist(&collapsed)?;
+                expanded.sort();
+                let mut input: Vec<_> = input.iter().map(ToString::to_string).collect();
+                input.sort();
+                assert_eq!(expanded, input);
+            }
+        }
+
+        for input in [vec!["n1", ""], vec!["n 1"], vec!["n?"]] {
+            assert!(collapse_hosts_multi(input).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_hosts_multi_with_axis() -> Result<()> {
+        let grid: Vec<String> = (1..=64)
+            .flat_map(|r| (1..=72).map(move |n| format!("r{r}n{n}")))
+            .collect();
+        let hosts = ["r1n1", "r2n1", "r3n1", "r1n2", "r1n2", "login"];
+        let tests = [
+            (&hosts[..], FoldAxis::Auto, "login,r1n2,r[1-3]n1"),
+            (&hosts, FoldAxis::Only(0), "login,r1n2,r[1-3]n1"),
+            (&hosts, FoldAxis::Only(1), "login,r1n[1-2],r2n1,r3n1"),
+            (&hosts, FoldAxis::Only(2), "login,r1n1,r1n2,r2n1,r3n1"),
+            (&["r1n1", "r1n2", "r2n1"], FoldAxis::Auto, "r1n[1-2],r2n1"),
+            (
+                &["c1-r1-n1", "c1-r2-n1", "c1-r3-n1", "c2-r1-n1", "c1-r1-n2"],
+                FoldAxis::Auto,
+                "c1-r1-n2,c1-r[1-3]-n1,c2-r1-n1",
+            ),
+        ];
+        for (input, axis, expected) in tests {
+            assert_eq!(
+                collapse_hosts_multi_with_axis(input, axis)?,
+                expected,
+                "input: {input:?}, axis: {axis:?}"
+            );
+        }
+
+        assert_eq!(
+            collapse_hosts_multi_with_axis(&grid, FoldAxis::Auto)?,
+            "r[1-64]n[1-72]"
+        );
+        assert_eq!(
+            collapse_hosts_multi_with_axis(&grid, FoldAxis::Only(0))?
+                .split(',')
+                .count(),
+            72
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_hosts_escaped() -> Result<()> {
+        let tests = [
+            (vec!["vm[prod]1", "vm[prod]2"], r"vm\[prod\][1-2]"),
+            (vec!["foo[1-2]"], r"foo\[1-2\]"),
+            (vec!["a,b", "c!d&e", r"f\g"], r"a\,b,c\!d\&e,f\\g"),
+        ];
+        for (input, expected) in tests {
+            let collapsed = collapse_hosts(&input)?;
+            assert_eq!(expected, collapsed);
+            assert_eq!(expand_hostlist(&collapsed)?, input);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_collapse_hosts_invalid() {
         let invalid_inputs = [
-            vec!["foo[1-2]"],
             vec!["node1", "node2?"],
             vec![""],
             vec!["!#*&^!*&#$"],
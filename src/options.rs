@@ -0,0 +1,287 @@
+use crate::hostlist::Rule;
+use crate::hostlistelem::ESCAPED_CHARS;
+use crate::padding::Padding;
+
+/// Options for parsing hostlist expressions and host names, see `Hostlist::new_with_options()`
+/// and `collapse_hosts_with_options()`
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct ParseOptions {
+    /// The characters allowed in host names, outside of ranges
+    pub hostname_chars: HostnameChars,
+
+    /// Whether empty elements (ex: "a,,b" or a trailing comma) are skipped rather than an error,
+    /// and empty or space-padded host names are skipped or trimmed when collapsing
+    pub lenient: bool,
+
+    /// Whether bash-style brace ranges are accepted as well as brackets (ex: "node{1..5}" for
+    /// "node[1-5]", "node{1..9..2}" for "node[1-9/2]", or "node{1,3,5}" for "node[1,3,5]")
+    pub braces: bool,
+}
+
+impl ParseOptions {
+    /// Returns a copy of the options allowing `hostname_chars` in host names
+    #[must_use]
+    pub fn hostname_chars(mut self, hostname_chars: HostnameChars) -> Self {
+        self.hostname_chars = hostname_chars;
+        self
+    }
+
+    /// Returns a copy of the options with lenient parsing turned on or off
+    #[must_use]
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Returns a copy of the options with bash-style brace ranges accepted or not
+    /// ```
+    /// use hostlist_iter::{Hostlist, ParseOptions};
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let options = ParseOptions::default().braces(true);
+    ///   let hostlist = Hostlist::new_with_options("node{1..4},gpu{1,3}", options)?;
+    ///   assert_eq!(hostlist.to_string(), "gpu[1,3],node[1-4]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn braces(mut self, braces: bool) -> Self {
+        self.braces = braces;
+        self
+    }
+
+    /// The rule for a whole hostlist expression
+    pub(crate) fn hostlist_rule(self) -> Rule {
+        match (self.hostname_chars, self.lenient) {
+            (HostnameChars::Standard, false) => Rule::hostlist,
+            (HostnameChars::Standard, true) => Rule::lenient_hostlist,
+            (HostnameChars::Extended, false) => Rule::extended_hostlist,
+            (HostnameChars::Extended, true) => Rule::lenient_extended_hostlist,
+        }
+    }
+}
+
+/// Rewrites bash-style brace ranges as brackets (ex: "n{1..9..2},m{1,3}" as "n[1-9/2],m[1,3]"),
+/// leaving the rest of the expression as it is
+pub(crate) fn braces_to_brackets(expr: &str) -> String {
+    let mut converted = String::with_capacity(expr.len());
+    let mut in_braces = false;
+    // How many ".." the current range has had, since the second one starts its step
+    let mut dots = 0;
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                in_braces = true;
+                dots = 0;
+                converted.push('[');
+            }
+            '}' if in_braces => {
+                in_braces = false;
+                converted.push(']');
+            }
+            ',' if in_braces => {
+                dots = 0;
+                converted.push(',');
+            }
+            '.' if in_braces && chars.peek() == Some(&'.') => {
+                chars.next();
+                converted.push(if dots == 0 { '-' } else { '/' });
+                dots += 1;
+            }
+            c => converted.push(c),
+        }
+    }
+    converted
+}
+
+/// The characters allowed in host names, outside of ranges
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum HostnameChars {
+    /// ASCII letters and digits, "_", "-", and "."
+    #[default]
+    Standard,
+
+    /// `Standard`, plus ":", "+", "~", "@", "%", and "=", which show up in some inventories
+    /// (ex: "db:primary01" or "user@node1")
+    Extended,
+}
+
+impl HostnameChars {
+    /// Whether `c` is allowed in a host name, once characters that are part of the syntax are
+    /// escaped (ex: "[" as `\[`)
+    pub(crate) fn allows(self, c: char) -> bool {
+        c.is_ascii_alphanumeric()
+            || "_-.".contains(c)
+            || ESCAPED_CHARS.contains(c)
+            || (self == Self::Extended && ":+~@%=".contains(c))
+    }
+
+    /// The rule for splitting a single host name into its prefix and numeric suffix
+    pub(crate) fn simple_hostname_rule(self) -> Rule {
+        match self {
+            Self::Standard => Rule::simple_hostname,
+            Self::Extended => Rule::extended_simple_hostname,
+        }
+    }
+}
+
+/// Options for `collapse_hosts_formatted()`: which host names it accepts, how it folds them, and
+/// how it writes the expression, for tools that expect slightly different forms of the same
+/// hostlist
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct CollapseOptions {
+    /// The host names accepted, see `collapse_hosts_with_options()`
+    pub parse: ParseOptions,
+
+    /// Whether host names are lowercased first, see `collapse_hosts_ignore_case()`
+    pub ignore_case: bool,
+
+    /// The numbers folded along, see `collapse_hosts_multi_with_axis()`, or `None` for only the
+    /// last number of each name. When folding along other numbers, zero-padding is dropped, and
+    /// `order`, `padding`, and `steps` don't apply.
+    pub fold_axis: Option<FoldAxis>,
+
+    /// Whether a number on its own is written in brackets (ex: "n[1]" rather than "n1")
+    pub bracket_single: bool,
+
+    /// The text written between elements
+    pub separator: String,
+
+    /// The order the elements are written in
+    pub order: CollapseOrder,
+
+    /// How zero-padded numbers are handled, see `collapse_hosts_with_padding()`
+    pub padding: Padding,
+
+    /// Whether runs of three or more numbers with the same step are written with it (ex:
+    /// "n[2-8/2]" rather than "n[2,4,6,8]")
+    pub steps: bool,
+}
+
+impl Default for CollapseOptions {
+    fn default() -> Self {
+        Self {
+            parse: ParseOptions::default(),
+            ignore_case: false,
+            fold_axis: None,
+            bracket_single: false,
+            separator: ",".into(),
+            order: CollapseOrder::default(),
+            padding: Padding::default(),
+            steps: false,
+        }
+    }
+}
+
+impl CollapseOptions {
+    /// Returns the options accepting the host names allowed by `parse`
+    #[must_use]
+    pub fn parse_options(mut self, parse: ParseOptions) -> Self {
+        self.parse = parse;
+        self
+    }
+
+    /// Returns the options with host names lowercased first or not
+    #[must_use]
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    /// Returns the options folding along the numbers chosen by `axis`
+    /// ```
+    /// use hostlist_iter::{CollapseOptions, FoldAxis, collapse_hosts_formatted};
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hosts = ["r1n1", "r1n2", "r2n1", "r2n2"];
+    ///   let options = CollapseOptions::default().fold_axis(FoldAxis::Auto);
+    ///   assert_eq!(collapse_hosts_formatted(hosts, &options)?, "r[1-2]n[1-2]");
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn fold_axis(mut self, axis: FoldAxis) -> Self {
+        self.fold_axis = Some(axis);
+        self
+    }
+
+    /// Returns the options with single numbers written in brackets or not
+    #[must_use]
+    pub fn bracket_single(mut self, bracket_single: bool) -> Self {
+        self.bracket_single = bracket_single;
+        self
+    }
+
+    /// Returns the options with elements separated by `separator`
+    #[must_use]
+    pub fn separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Returns the options with elements written in `order`
+    #[must_use]
+    pub fn order(mut self, order: CollapseOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Returns the options with zero-padded numbers handled as given by `padding`
+    #[must_use]
+    pub fn padding(mut self, padding: Padding) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Returns the options with runs of numbers written with their step or not
+    /// ```
+    /// use hostlist_iter::{CollapseOptions, Hostlist, collapse_hosts_formatted};
+    ///
+    /// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    ///   let hosts = ["rack2", "rack4", "rack6", "rack8", "rack9"];
+    ///   let options = CollapseOptions::default().steps(true);
+    ///   let collapsed = collapse_hosts_formatted(hosts, &options)?;
+    ///   assert_eq!(collapsed, "rack[2-8/2,9]");
+    ///   assert_eq!(Hostlist::new(&collapsed)?.to_vec(), hosts);
+    ///
+    ///   Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn steps(mut self, steps: bool) -> Self {
+        self.steps = steps;
+        self
+    }
+}
+
+/// The order `collapse_hosts_formatted()` writes elements in
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum CollapseOrder {
+    /// In natural order, like `sort_hosts()` (ex: "login1,n[1-2],n10-ib")
+    #[default]
+    Sorted,
+
+    /// In the order the first host of each element was given
+    FirstSeen,
+
+    /// Elements without a number first, in the order they were given, then by the text before
+    /// and after the numbers, like `collapse_hosts()` (ex: "login,gpu[3-4],gpu[1-2]-ib")
+    ByPrefix,
+}
+
+/// Which numbers of host names `collapse_hosts_multi_with_axis()` folds along
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum FoldAxis {
+    /// Every number, starting with whichever gives the shortest expression, like `ClusterShell`
+    #[default]
+    Auto,
+
+    /// Only the number at this position, counting from 0 for the first number of a name (ex: 1
+    /// for the "n" numbers of "r1n1"). Names with fewer numbers aren't folded.
+    Only(usize),
+}
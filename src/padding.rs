@@ -0,0 +1,15 @@
+/// How zero-padded numbers in a hostlist expression are handled, see `Hostlist::new_with_padding()`
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Padding {
+    /// Numbers keep the padding they were written with (ex: "n[01-03]" is "n01" to "n03"), like
+    /// `Hostlist::new()`. A padded bound pads the whole range, so "n[098-102]" is "n098" to "n102".
+    #[default]
+    Keep,
+
+    /// Numbers are read as their value (ex: "n[01-03]" is "n1" to "n3")
+    Strip,
+
+    /// Every range is rendered with at least this many digits (ex: 3 makes "n[1-3]" into
+    /// "n[001-003]"). Numbers outside of brackets are left alone.
+    Width(usize),
+}
@@ -1,16 +1,19 @@
 use core::fmt;
 use std::collections::HashSet;
+use std::iter::FusedIterator;
 
-use crate::error::Result;
-use crate::simplerange::SimpleRange;
+use crate::error::{Error, Result};
+use crate::rangeint::RangeInt;
+use crate::simplerange::{SimpleRange, SimpleRangeIter};
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
-pub struct Range {
-    pub ranges: Vec<SimpleRange>,
-    latest: Option<u32>, // The most recent value returned by next()
+pub struct Range<T: RangeInt = u64> {
+    pub ranges: Vec<SimpleRange<T>>,
+    latest: Option<T>,   // The most recent value returned by next()
+    latest_width: usize, // The width of the sub-range `latest` came from
 }
 
-impl fmt::Display for Range {
+impl<T: RangeInt> fmt::Display for Range<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let joined = self
             .ranges
@@ -24,19 +27,29 @@ impl fmt::Display for Range {
     }
 }
 
-impl Range {
+impl<T: RangeInt> Range<T> {
     pub const fn new() -> Self {
         Self {
             ranges: Vec::new(),
             latest: None,
+            latest_width: 0,
         }
     }
 
-    pub const fn latest(&self) -> Option<u32> {
+    pub const fn latest(&self) -> Option<T> {
         self.latest
     }
 
-    pub fn add_range(&mut self, range: &SimpleRange) -> Result<()> {
+    /// Minimum number of digits to render `latest()` with
+    pub const fn latest_width(&self) -> usize {
+        self.latest_width
+    }
+
+    pub fn add_range(&mut self, range: &SimpleRange<T>) -> Result<()> {
+        if range.step != T::ONE || self.ranges.iter().any(|r| r.step != T::ONE) {
+            return self.add_stepped_range(range);
+        }
+
         // Shrink this new range until it does not overlap with any existing range
         let mut rangeset = HashSet::new();
         rangeset.insert((range.start, range.end));
@@ -49,6 +62,10 @@ impl Range {
                 //  2. nothing
                 let mut keep = true;
                 for r in &self.ranges {
+                    if r.width != range.width {
+                        // Differently padded values never refer to the same host
+                        continue;
+                    }
                     let (a, b) = (r.start, r.end);
 
                     if lo >= a && hi <= b {
@@ -63,18 +80,18 @@ impl Range {
                         // l--------h
                         //    a--b
                         // Both sides overlap. Save the right side for later, keep checking the left side.
-                        rangeset.insert((b + 1, hi));
-                        hi = a - 1;
+                        rangeset.insert((b + T::ONE, hi));
+                        hi = a - T::ONE;
                     } else if hi >= a && hi <= b {
                         // l-----h
                         //    a------b
                         // Left overlap
-                        hi = a - 1;
+                        hi = a - T::ONE;
                     } else if lo >= a && lo <= b {
                         //    l-----h     l------h
                         // a------b       a---b
                         // Right overlap
-                        lo = b + 1;
+                        lo = b + T::ONE;
                     }
 
                     // l--h                   l--h
@@ -82,10 +99,8 @@ impl Range {
                     // No overlap: continue checking
                 }
 
-                if keep {
-                    if let Ok(range) = SimpleRange::new(lo, hi) {
-                        self.ranges.push(range);
-                    }
+                if keep && let Ok(range) = SimpleRange::with_width(lo, hi, range.width) {
+                    self.ranges.push(range);
                 }
             }
         }
@@ -95,88 +110,606 @@ impl Range {
         Ok(())
     }
 
+    /// Adds `range` when it or one of the sub-ranges has a step. Sub-ranges of the same width that
+    /// overlap or sit next to the added values are split into single values and merged with them,
+    /// and the rest (including `range`, if nothing is next to it) keep their steps.
+    fn add_stepped_range(&mut self, range: &SimpleRange<T>) -> Result<()> {
+        let (mut same_width, mut ranges): (Vec<_>, Vec<_>) = std::mem::take(&mut self.ranges)
+            .into_iter()
+            .partition(|r| r.width == range.width);
+        let touches = |a: &SimpleRange<T>, b: &SimpleRange<T>| {
+            a.start <= b.end + T::ONE && b.start <= a.end + T::ONE
+        };
+
+        let mut added = vec![range.clone()];
+        loop {
+            let (touching, rest): (Vec<_>, Vec<_>) = same_width
+                .into_iter()
+                .partition(|r| added.iter().any(|a| touches(a, r)));
+            same_width = rest;
+            if touching.is_empty() {
+                break;
+            }
+            // Merging may have grown the added values into other sub-ranges, so check again
+            added = Self::from_ranges(
+                added
+                    .iter()
+                    .chain(&touching)
+                    .flat_map(SimpleRange::unstepped),
+            )?
+            .ranges;
+        }
+
+        for r in &mut added {
+            r.reset();
+        }
+        ranges.extend(same_width);
+        ranges.extend(added);
+        ranges.sort_unstable();
+        self.ranges = ranges;
+        Ok(())
+    }
+
+    /// Builds a range from sub-ranges in any order. Overlapping and adjacent sub-ranges are merged
+    /// with one sort and pass, rather than adding them one at a time with `add_range()`.
+    pub fn from_ranges(ranges: impl IntoIterator<Item = SimpleRange<T>>) -> Result<Self> {
+        let mut ranges: Vec<_> = ranges.into_iter().collect();
+        if ranges.iter().any(|r| r.step != T::ONE) {
+            // Stepped sub-ranges are only kept as they are when nothing else is added next to them
+            let mut range = Self::new();
+            for r in &ranges {
+                range.add_range(r)?;
+            }
+            return Ok(range);
+        }
+        ranges.sort_unstable_by_key(|r| (r.width, r.start));
+
+        let mut merged: Vec<SimpleRange<T>> = Vec::with_capacity(ranges.len());
+        for r in ranges {
+            if let Some(last) = merged.last_mut()
+                && last.width == r.width
+                && r.start <= last.end + T::ONE
+            {
+                if r.end > last.end {
+                    *last = SimpleRange::with_width(last.start, r.end, r.width)?;
+                }
+                continue;
+            }
+            merged.push(SimpleRange::with_width(r.start, r.end, r.width)?);
+        }
+
+        merged.sort_unstable();
+        Ok(Self {
+            ranges: merged,
+            latest: None,
+            latest_width: 0,
+        })
+    }
+
     /// Combine contiguous sub-ranges into larger ranges until the minimum remain.
-    /// Assumes ranges are non-overlapping.
+    /// Assumes ranges of the same width are non-overlapping.
     fn condense_ranges(&mut self) -> Result<()> {
-        let mut new_ranges: Vec<SimpleRange> = Vec::new();
-        let mut lo = 0;
+        let mut new_ranges: Vec<SimpleRange<T>> = Vec::new();
+        let mut lo = T::MIN;
         let mut hi = None;
-        self.ranges.sort_unstable();
+        let mut width = 0;
+        self.ranges.sort_unstable_by_key(|r| (r.width, r.start));
         for r in &self.ranges {
             match hi {
                 None => {
                     lo = r.start;
                 }
                 Some(h) => {
-                    if h != r.start - 1 {
+                    if r.width != width || h != r.start - T::ONE {
                         // non-contiguous case: add the previous range to our vec and start a new one
-                        let range = SimpleRange::new(lo, h)?;
+                        let range = SimpleRange::with_width(lo, h, width)?;
                         new_ranges.push(range);
                         lo = r.start;
                     }
                 }
             }
             hi = Some(r.end);
+            width = r.width;
         }
 
         // Add the last range if we ended on a non-contiguous case
         if let Some(h) = hi {
-            let range = SimpleRange::new(lo, h)?;
+            let range = SimpleRange::with_width(lo, h, width)?;
             new_ranges.push(range);
         }
 
+        new_ranges.sort_unstable();
         self.ranges = new_ranges;
 
         Ok(())
     }
 
+    /// Renders every value in the range zero-padded to at least `width` digits
+    pub fn set_width(&mut self, width: usize) -> Result<()> {
+        // Values that were previously distinct may now coincide, so re-add everything
+        let ranges = std::mem::take(&mut self.ranges);
+        for range in ranges {
+            let range = SimpleRange::with_step(range.start, range.end, range.step, width)?;
+            self.add_range(&range)?;
+        }
+        Ok(())
+    }
+
     pub fn len(&self) -> usize {
         self.ranges.iter().map(SimpleRange::len).sum()
     }
 
+    /// Returns the value `digits` is, and the width of the sub-range it's from, if that's how one
+    /// of the range's values is rendered (ex: "7" but not "07", unless padded to a width of 2)
+    pub fn find_rendered(&self, digits: &str) -> Option<(T, usize)> {
+        let value = digits.parse::<T>().ok()?;
+        let natural = value
+            .to_u64()
+            .checked_ilog10()
+            .map_or(1, |log| log as usize + 1);
+        self.ranges
+            .iter()
+            .find(|r| r.contains(value) && natural.max(r.width) == digits.len())
+            .map(|r| (value, r.width))
+    }
+
+    /// Returns the index in iteration order of the value rendered as `digits`, including values
+    /// already iterated over, see `find_rendered()`
+    pub fn index_of_rendered(&self, digits: &str) -> Option<usize> {
+        let (value, width) = self.find_rendered(digits)?;
+        let mut index = 0usize;
+        for r in &self.ranges {
+            if r.contains(value) && r.width == width {
+                return index.checked_add(((value - r.start) / r.step).to_usize());
+            }
+            index = index.saturating_add(r.total_len());
+        }
+        None
+    }
+
+    /// Returns whether `digits` is how one of the range's values is rendered, see `find_rendered()`
+    pub fn contains_rendered(&self, digits: &str) -> bool {
+        self.find_rendered(digits).is_some()
+    }
+
+    /// Returns whether the sub-ranges are in increasing order without overlapping, so iterating
+    /// gives the values sorted by value
+    pub fn is_ascending(&self) -> bool {
+        self.ranges
+            .windows(2)
+            .all(|pair| pair[0].end < pair[1].start)
+    }
+
+    /// Returns whether every value is rendered with the same number of digits (ex: "[08-12]" but
+    /// not "[8-12]")
+    pub fn has_fixed_width(&self) -> bool {
+        let rendered_len = |value: T, width: usize| {
+            let natural = value
+                .to_u64()
+                .checked_ilog10()
+                .map_or(1, |log| log as usize + 1);
+            natural.max(width)
+        };
+        let mut lens = self
+            .ranges
+            .iter()
+            .flat_map(|r| [rendered_len(r.start, r.width), rendered_len(r.end, r.width)]);
+        let first = lens.next();
+        lens.all(|len| Some(len) == first)
+    }
+
+    /// Approximate number of bytes allocated on the heap by the range
+    pub fn heap_size(&self) -> usize {
+        self.ranges.capacity() * size_of::<SimpleRange<T>>()
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.ranges.shrink_to_fit();
+    }
+
+    /// Number of values in the range, including values already iterated over
+    pub fn total_len(&self) -> usize {
+        self.ranges
+            .iter()
+            .map(SimpleRange::total_len)
+            .fold(0, usize::saturating_add)
+    }
+
+    /// Returns the value at `index` in iteration order, including values already iterated over,
+    /// and the width of the sub-range it's from
+    pub fn value_at(&self, mut index: usize) -> Option<(T, usize)> {
+        for r in &self.ranges {
+            let r_len = r.total_len();
+            if index < r_len {
+                return Some((r.start + T::from_usize(index)? * r.step, r.width));
+            }
+            index -= r_len;
+        }
+        None
+    }
+
+    /// Total number of bytes taken by rendering every value in the range
+    pub fn rendered_bytes(&self) -> usize {
+        self.ranges
+            .iter()
+            .map(SimpleRange::rendered_bytes)
+            .fold(0, usize::saturating_add)
+    }
+
+    /// Returns a regular expression matching exactly how the range's values are rendered, in
+    /// parentheses if it has alternatives (ex: "([1-9]|1[0-6])" for [1-16])
+    pub fn to_regex(&self) -> String {
+        let alternatives: Vec<String> = self
+            .ranges
+            .iter()
+            .flat_map(SimpleRange::regex_alternatives)
+            .collect();
+        match alternatives.as_slice() {
+            [alternative] => alternative.clone(),
+            _ => format!("({})", alternatives.join("|")),
+        }
+    }
+
     pub fn reset(&mut self) {
         for r in &mut self.ranges {
             r.reset();
         }
+        self.latest = None;
+    }
+
+    /// Moves the iterator past the value at `index`, counting from the start, so it's `latest()`
+    /// and `next()` continues after it. With `index` past the end, the iterator is left exhausted.
+    pub fn seek(&mut self, mut index: usize) -> Option<T> {
+        self.latest = None;
+        for r in &mut self.ranges {
+            if self.latest.is_some() {
+                r.reset();
+                continue;
+            }
+            let r_len = r.total_len();
+            self.latest = r.seek(index);
+            if self.latest.is_some() {
+                self.latest_width = r.width;
+            } else {
+                index -= r_len;
+            }
+        }
+        self.latest
     }
 
-    pub fn merge(&mut self, other: &Range) -> Result<()> {
+    pub fn merge(&mut self, other: &Self) -> Result<()> {
         for range in &other.ranges {
             self.add_range(range)?;
         }
         Ok(())
     }
 
-    #[cfg(test)]
-    pub fn iter(&self) -> Self {
-        Self {
-            ranges: self.ranges.clone(),
+    /// Returns a new range containing the values found in either range
+    pub fn union(&self, other: &Self) -> Result<Self> {
+        let mut union = Self::new();
+        union.merge(self)?;
+        union.merge(other)?;
+        Ok(union)
+    }
+
+    /// Returns a new range containing the values found in both ranges
+    pub fn intersect(&self, other: &Self) -> Result<Self> {
+        let (lhs, rhs) = (self.unstepped(), other.unstepped());
+        let (lhs, rhs) = (lhs.as_ref().unwrap_or(self), rhs.as_ref().unwrap_or(other));
+        let mut intersection = Self::new();
+
+        let mut widths: Vec<usize> = lhs.ranges.iter().map(|r| r.width).collect();
+        widths.sort_unstable();
+        widths.dedup();
+
+        for width in widths {
+            let lhs: Vec<_> = lhs.ranges.iter().filter(|r| r.width == width).collect();
+            let rhs: Vec<_> = rhs.ranges.iter().filter(|r| r.width == width).collect();
+
+            // Both sides are sorted and non-overlapping, so sweep through them together
+            let (mut i, mut j) = (0, 0);
+            while i < lhs.len() && j < rhs.len() {
+                let (a, b) = (lhs[i], rhs[j]);
+                let lo = a.start.max(b.start);
+                let hi = a.end.min(b.end);
+                if lo <= hi {
+                    let range = SimpleRange::with_width(lo, hi, width)?;
+                    intersection.ranges.push(range);
+                }
+
+                // Advance whichever range finishes first; it can't overlap anything further along
+                if a.end < b.end {
+                    i += 1;
+                } else {
+                    j += 1;
+                }
+            }
+        }
+
+        intersection.ranges.sort_unstable();
+        Ok(intersection)
+    }
+
+    /// Returns a new range containing the values in this range that are not in `other`
+    pub fn subtract(&self, other: &Self) -> Result<Self> {
+        let (lhs, rhs) = (self.unstepped(), other.unstepped());
+        let (lhs, other) = (lhs.as_ref().unwrap_or(self), rhs.as_ref().unwrap_or(other));
+        let mut difference = Self::new();
+
+        for r in &lhs.ranges {
+            let mut lo = r.start;
+            let mut remaining = true;
+            for o in &other.ranges {
+                if o.width != r.width || o.end < lo {
+                    continue;
+                }
+                if o.start > r.end {
+                    break;
+                }
+                if o.start > lo {
+                    // keep the part before the overlapping range
+                    let range = SimpleRange::with_width(lo, o.start - T::ONE, r.width)?;
+                    difference.ranges.push(range);
+                }
+                if o.end >= r.end {
+                    remaining = false;
+                    break;
+                }
+                lo = o.end + T::ONE;
+            }
+            if remaining {
+                let range = SimpleRange::with_width(lo, r.end, r.width)?;
+                difference.ranges.push(range);
+            }
+        }
+
+        Ok(difference)
+    }
+
+    /// Returns a new range from the smallest to the largest value of this range, separately for
+    /// each width (ex: `[1-3,7]` spans `[1-7]`)
+    pub fn span(&self) -> Result<Self> {
+        let mut bounds: Vec<(usize, T, T)> = Vec::new();
+        for r in &self.ranges {
+            match bounds.iter_mut().find(|(width, _, _)| *width == r.width) {
+                Some((_, lo, hi)) => {
+                    *lo = (*lo).min(r.start);
+                    *hi = (*hi).max(r.end);
+                }
+                None => bounds.push((r.width, r.start, r.end)),
+            }
+        }
+
+        let mut span = Self::new();
+        for (width, lo, hi) in bounds {
+            span.ranges.push(SimpleRange::with_width(lo, hi, width)?);
+        }
+        span.ranges.sort_unstable();
+        Ok(span)
+    }
+
+    /// Returns a new range containing the values for which `pred` returns true
+    pub fn filter_values(&self, pred: impl Fn(T) -> bool) -> Result<Self> {
+        let mut filtered = Self::new();
+
+        for r in &self.ranges {
+            // Contiguous run of values that passed, not yet added
+            let mut run: Option<(T, T)> = None;
+            for value in r {
+                if pred(value) && r.step != T::ONE {
+                    filtered
+                        .ranges
+                        .push(SimpleRange::with_width(value, value, r.width)?);
+                } else if pred(value) {
+                    run = Some((run.map_or(value, |(lo, _)| lo), value));
+                } else if let Some((lo, hi)) = run.take() {
+                    filtered
+                        .ranges
+                        .push(SimpleRange::with_width(lo, hi, r.width)?);
+                }
+            }
+            if let Some((lo, hi)) = run {
+                filtered
+                    .ranges
+                    .push(SimpleRange::with_width(lo, hi, r.width)?);
+            }
+        }
+
+        Ok(filtered)
+    }
+
+    /// Returns a new range containing up to `len` consecutive values of this range, beginning with
+    /// the value at index `start` (ex: `[1-3,7-9]` sliced from 2 with length 2 is `[3,7]`)
+    pub fn slice(&self, start: usize, len: usize) -> Result<Self> {
+        let mut slice = Self::new();
+        let (mut skip, mut remaining) = (start, len);
+
+        for r in &self.ranges {
+            if remaining == 0 {
+                break;
+            }
+            let r_len = r.iter().len();
+            if skip >= r_len {
+                skip -= r_len;
+                continue;
+            }
+
+            // Both offsets are within the range, so they fit in T
+            let take = remaining.min(r_len - skip);
+            let offset = |n| {
+                T::from_usize(n).ok_or_else(|| Error::Internal("range offset overflow".to_string()))
+            };
+            let lo = r.start + offset(skip)? * r.step;
+            let hi = lo + offset(take - 1)? * r.step;
+            slice
+                .ranges
+                .push(SimpleRange::with_step(lo, hi, r.step, r.width)?);
+
+            skip = 0;
+            remaining -= take;
+        }
+
+        Ok(slice)
+    }
+
+    /// Returns a new range containing every `n`th value of this range, beginning with the value at
+    /// index `first` (ex: `[1-10]` from 1 every 3 is `[2-8/3]`). Each sub-range keeps its values as
+    /// one stepped range rather than as single values.
+    pub fn every_nth(&self, first: usize, n: usize) -> Result<Self> {
+        let n = n.max(1);
+        let mut picked = Vec::new();
+        let mut base = 0usize;
+
+        for r in &self.ranges {
+            let r_len = r.total_len();
+            // Index within `r` of its first value congruent to `first`, modulo `n`
+            let skip = if first >= base {
+                first - base
+            } else {
+                (n - (base - first) % n) % n
+            };
+            if skip < r_len {
+                let count = (r_len - 1 - skip) / n + 1;
+                // Both offsets are within the range, so they fit in T
+                let offset = |n| {
+                    T::from_usize(n)
+                        .ok_or_else(|| Error::Internal("range offset overflow".to_string()))
+                };
+                let lo = r.start + offset(skip)? * r.step;
+                let step = offset(n)? * r.step;
+                let hi = lo + offset(count - 1)? * step;
+                picked.push(SimpleRange::with_step(lo, hi, step, r.width)?);
+            }
+            base = base.saturating_add(r_len);
+        }
+
+        Self::from_ranges(picked)
+    }
+
+    /// Returns a copy of the range with any stepped sub-ranges split into single values, or `None`
+    /// if it has no steps, for operations that sweep through every value from start to end
+    fn unstepped(&self) -> Option<Self> {
+        if self.ranges.iter().all(|r| r.step == T::ONE) {
+            return None;
+        }
+        let mut ranges: Vec<_> = self
+            .ranges
+            .iter()
+            .flat_map(SimpleRange::unstepped)
+            .collect();
+        ranges.sort_unstable();
+        Some(Self {
+            ranges,
             latest: None,
+            latest_width: 0,
+        })
+    }
+
+    /// Returns a borrowing iterator over all values in the range
+    pub fn iter(&self) -> RangeIter<'_, T> {
+        RangeIter {
+            ranges: self.ranges.iter(),
+            front: None,
+            back: None,
         }
     }
 }
 
-impl Iterator for Range {
-    type Item = u32;
+impl<T: RangeInt> Iterator for Range<T> {
+    type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
         for r in &mut self.ranges {
             if let Some(rnext) = r.next() {
                 self.latest = Some(rnext);
+                self.latest_width = r.width;
                 return Some(rnext);
             }
         }
         None
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len(), Some(self.len()))
+    }
+}
+
+impl<T: RangeInt> ExactSizeIterator for Range<T> {}
+
+impl<T: RangeInt> FusedIterator for Range<T> {}
+
+// This enables:
+//   `for e in &range { ... }`
+//   `for e in range.into_iter() { ... }`
+impl<'a, T: RangeInt> IntoIterator for &'a Range<T> {
+    type Item = T;
+    type IntoIter = RangeIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Borrowing iterator over the values of a `Range`, usable from either end
+#[derive(Debug, Clone)]
+pub struct RangeIter<'a, T: RangeInt = u64> {
+    ranges: std::slice::Iter<'a, SimpleRange<T>>, // Sub-ranges not yet started from either end
+    front: Option<SimpleRangeIter<T>>,
+    back: Option<SimpleRangeIter<T>>,
+}
+
+impl<T: RangeInt> Iterator for RangeIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(next) = self.front.as_mut().and_then(Iterator::next) {
+                return Some(next);
+            }
+            match self.ranges.next() {
+                Some(range) => self.front = Some(range.iter()),
+                // Nothing left in the middle, so continue into whatever the back has left
+                None => return self.back.as_mut().and_then(Iterator::next),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.front.as_ref().map_or(0, ExactSizeIterator::len)
+            + self.ranges.clone().map(|r| r.iter().len()).sum::<usize>()
+            + self.back.as_ref().map_or(0, ExactSizeIterator::len);
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T: RangeInt> DoubleEndedIterator for RangeIter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(next) = self.back.as_mut().and_then(DoubleEndedIterator::next_back) {
+                return Some(next);
+            }
+            match self.ranges.next_back() {
+                Some(range) => self.back = Some(range.iter()),
+                // Nothing left in the middle, so continue into whatever the front has left
+                None => return self.front.as_mut().and_then(DoubleEndedIterator::next_back),
+            }
+        }
+    }
 }
 
+impl<T: RangeInt> ExactSizeIterator for RangeIter<'_, T> {}
+
+// This trait guarantees that once the iterator returns None, it will always return None.
+// No additional methods needed, it's a marker trait.
+impl<T: RangeInt> FusedIterator for RangeIter<'_, T> {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_range_1() -> Result<()> {
-        let mut range = Range::new();
+        let mut range: Range = Range::new();
         assert_eq!(range.len(), 0);
 
         let _ = range.add_range(&SimpleRange::new(1, 5)?);
@@ -195,7 +728,7 @@ mod tests {
 
     #[test]
     fn test_range_iter() -> Result<()> {
-        let mut range = Range::new();
+        let mut range: Range = Range::new();
         assert_eq!(range.len(), 0);
 
         let _ = range.add_range(&SimpleRange::new(1, 5)?);
@@ -204,7 +737,7 @@ mod tests {
         assert_eq!(range.len(), 7);
 
         let expected = 1..=7;
-        let expected: Vec<u32> = expected.collect();
+        let expected: Vec<u64> = expected.collect();
         for (i, elem) in range.iter().enumerate() {
             assert_eq!(expected[i], elem);
         }
@@ -217,9 +750,400 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_range_iter_double_ended() -> Result<()> {
+        let mut range: Range = Range::new();
+        range.add_range(&SimpleRange::new(1, 3)?)?;
+        range.add_range(&SimpleRange::new(7, 8)?)?;
+        range.add_range(&SimpleRange::new(10, 10)?)?;
+
+        let reversed: Vec<u64> = range.iter().rev().collect();
+        assert_eq!(reversed, vec![10, 8, 7, 3, 2, 1]);
+
+        let mut iter = range.iter();
+        assert_eq!(iter.len(), 6);
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next_back(), Some(10));
+        assert_eq!(iter.next_back(), Some(8));
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.next(), Some(7));
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+
+        // A single sub-range consumed from both ends
+        let mut range: Range = Range::new();
+        range.add_range(&SimpleRange::new(1, 4)?)?;
+        let mut iter = range.iter();
+        assert_eq!(iter.next_back(), Some(4));
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next_back(), Some(3));
+        assert_eq!(iter.next_back(), Some(2));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+
+        Ok(())
+    }
+
+    fn range_of(bounds: &[(u64, u64)]) -> Result<Range> {
+        let mut range: Range = Range::new();
+        for &(start, end) in bounds {
+            range.add_range(&SimpleRange::new(start, end)?)?;
+        }
+        Ok(range)
+    }
+
+    #[test]
+    fn test_range_union() -> Result<()> {
+        let tests = [
+            (vec![], vec![], ""),
+            (vec![(1, 3)], vec![], "1-3"),
+            (vec![], vec![(1, 3)], "1-3"),
+            (vec![(1, 3)], vec![(4, 6)], "1-6"),
+            (vec![(1, 3)], vec![(5, 6)], "1-3,5-6"),
+            (vec![(1, 5)], vec![(2, 3)], "1-5"),
+            (vec![(1, 2), (8, 9)], vec![(2, 8)], "1-9"),
+        ];
+
+        for (a, b, expected) in tests {
+            let union = range_of(&a)?.union(&range_of(&b)?)?;
+            assert_eq!(union.to_string(), format!("[{expected}]"));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_intersect() -> Result<()> {
+        let tests = [
+            (vec![], vec![(1, 3)], ""),
+            (vec![(1, 3)], vec![(4, 6)], ""),
+            (vec![(1, 5)], vec![(3, 8)], "3-5"),
+            (vec![(1, 10)], vec![(2, 3), (5, 5), (9, 12)], "2-3,5,9-10"),
+            (vec![(1, 2), (4, 6), (8, 9)], vec![(2, 8)], "2,4-6,8"),
+            (vec![(0, 0)], vec![(0, 0)], "0"),
+        ];
+
+        for (a, b, expected) in tests {
+            let (a, b) = (range_of(&a)?, range_of(&b)?);
+            assert_eq!(a.intersect(&b)?.to_string(), format!("[{expected}]"));
+            assert_eq!(b.intersect(&a)?.to_string(), format!("[{expected}]"));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_value_at() -> Result<()> {
+        let mut range = range_of(&[(1, 3), (7, 9)])?;
+        range.next();
+        let values: Vec<_> = (0..7).map(|i| range.value_at(i)).collect();
+        assert_eq!(
+            values,
+            [1, 2, 3, 7, 8, 9]
+                .map(|v| Some((v, 0)))
+                .into_iter()
+                .chain([None])
+                .collect::<Vec<_>>()
+        );
+
+        let range: Range = Range::from_ranges([SimpleRange::with_width(8, 9, 3)?])?;
+        assert_eq!(range.value_at(1), Some((9, 3)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_step() -> Result<()> {
+        let mut range: Range = Range::new();
+        range.add_range(&SimpleRange::with_step(0, 10, 5, 0)?)?;
+        range.add_range(&SimpleRange::with_step(20, 26, 3, 0)?)?;
+        assert_eq!(range.to_string(), "[0-10/5,20-26/3]");
+        assert_eq!(range.total_len(), 6);
+        assert_eq!(range.value_at(4), Some((23, 0)));
+        assert_eq!(range.index_of_rendered("26"), Some(5));
+        assert_eq!(range.index_of_rendered("21"), None);
+        assert_eq!(range.slice(1, 3)?.to_string(), "[5-10/5,20]");
+        assert_eq!(
+            range.filter_values(|v| v > 3)?.to_string(),
+            "[5,10,20,23,26]"
+        );
+        assert_eq!(range.seek(3), Some(20));
+        assert_eq!(range.next(), Some(23));
+
+        // Adding a value inside a stepped range's span splits it up
+        range.add_range(&SimpleRange::new(4, 6)?)?;
+        assert_eq!(range.to_string(), "[0,4-6,10,20-26/3]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_index_of_rendered() -> Result<()> {
+        let range: Range = Range::from_ranges([
+            SimpleRange::with_width(1, 3, 0)?,
+            SimpleRange::with_width(7, 9, 0)?,
+            SimpleRange::with_width(8, 9, 3)?,
+        ])?;
+        let indices: Vec<_> = ["1", "3", "7", "9", "008", "009", "8", "08", "4", "x"]
+            .iter()
+            .map(|digits| range.index_of_rendered(digits))
+            .collect();
+        assert_eq!(
+            indices,
+            [
+                Some(0),
+                Some(2),
+                Some(3),
+                Some(5),
+                Some(6),
+                Some(7),
+                Some(4),
+                None,
+                None,
+                None
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_is_ascending_and_fixed_width() -> Result<()> {
+        let range = range_of(&[(1, 3), (7, 9)])?;
+        assert!(range.is_ascending() && range.has_fixed_width());
+
+        let range = range_of(&[(7, 12)])?;
+        assert!(range.is_ascending() && !range.has_fixed_width());
+
+        let range: Range = Range::from_ranges([
+            SimpleRange::with_width(1, 9, 2)?,
+            SimpleRange::with_width(5, 5, 0)?,
+        ])?;
+        assert!(!range.is_ascending() && !range.has_fixed_width());
+
+        let range: Range = Range::from_ranges([SimpleRange::with_width(8, 12, 2)?])?;
+        assert!(range.has_fixed_width());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_seek() -> Result<()> {
+        let mut range = range_of(&[(1, 3), (7, 9)])?;
+        assert_eq!(range.seek(3), Some(7));
+        assert_eq!(range.latest(), Some(7));
+        assert_eq!(range.clone().collect::<Vec<_>>(), [8, 9]);
+        assert_eq!(range.seek(0), Some(1));
+        assert_eq!(range.clone().collect::<Vec<_>>(), [2, 3, 7, 8, 9]);
+        assert_eq!(range.seek(5), Some(9));
+        assert_eq!(range.next(), None);
+        assert_eq!(range.seek(6), None);
+        assert_eq!(range.latest(), None);
+        assert_eq!(range.next(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_span() -> Result<()> {
+        let tests = [
+            (vec![], ""),
+            (vec![(1, 3)], "1-3"),
+            (vec![(1, 3), (7, 7)], "1-7"),
+            (vec![(5, 6), (1, 2), (9, 10)], "1-10"),
+        ];
+        for (ranges, expected) in tests {
+            assert_eq!(
+                range_of(&ranges)?.span()?.to_string(),
+                format!("[{expected}]")
+            );
+        }
+
+        let range: Range = Range::from_ranges([
+            SimpleRange::with_width(1, 2, 2)?,
+            SimpleRange::with_width(8, 9, 2)?,
+            SimpleRange::new(5, 5)?,
+        ])?;
+        assert_eq!(range.span()?.to_string(), "[01-09,5]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_subtract() -> Result<()> {
+        let tests = [
+            (vec![], vec![(1, 3)], ""),
+            (vec![(1, 3)], vec![], "1-3"),
+            (vec![(1, 3)], vec![(4, 6)], "1-3"),
+            (vec![(1, 3)], vec![(1, 3)], ""),
+            (vec![(1, 10)], vec![(3, 4)], "1-2,5-10"),
+            (vec![(1, 10)], vec![(0, 1), (10, 12)], "2-9"),
+            (vec![(1, 10)], vec![(2, 2), (4, 5), (7, 7)], "1,3,6,8-10"),
+            (vec![(1, 3), (5, 7)], vec![(3, 5)], "1-2,6-7"),
+            (
+                vec![(0, u64::MAX - 1)],
+                vec![(1, u64::MAX - 2)],
+                "0,18446744073709551614",
+            ),
+        ];
+
+        for (a, b, expected) in tests {
+            let difference = range_of(&a)?.subtract(&range_of(&b)?)?;
+            assert_eq!(difference.to_string(), format!("[{expected}]"));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_filter() -> Result<()> {
+        let tests = [
+            (vec![], ""),
+            (vec![(1, 10)], "2,4,6,8,10"),
+            (vec![(1, 1), (3, 3)], ""),
+            (vec![(1, 2), (4, 5)], "2,4"),
+        ];
+
+        for (bounds, expected) in tests {
+            let filtered = range_of(&bounds)?.filter_values(|i| i % 2 == 0)?;
+            assert_eq!(filtered.to_string(), format!("[{expected}]"));
+        }
+
+        let range = range_of(&[(1, 10), (20, 25)])?;
+        assert_eq!(
+            range.filter_values(|i| i > 5 && i < 23)?.to_string(),
+            "[6-10,20-22]"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_slice() -> Result<()> {
+        let range = range_of(&[(1, 3), (7, 9)])?;
+        let tests = [
+            (0, 0, ""),
+            (0, 6, "1-3,7-9"),
+            (0, 100, "1-3,7-9"),
+            (2, 2, "3,7"),
+            (1, 1, "2"),
+            (3, 3, "7-9"),
+            (5, 1, "9"),
+            (6, 1, ""),
+        ];
+
+        for (start, len, expected) in tests {
+            let slice = range.slice(start, len)?;
+            assert_eq!(slice.to_string(), format!("[{expected}]"));
+            assert_eq!(slice.len(), slice.iter().len());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_every_nth() -> Result<()> {
+        let range = range_of(&[(1, 10)])?;
+        let tests = [
+            (0, 1, "1-10"),
+            (0, 3, "1-10/3"),
+            (1, 3, "2-8/3"),
+            (2, 3, "3-9/3"),
+            (9, 3, "10"),
+            (10, 3, ""),
+            (0, 20, "1"),
+        ];
+        for (first, n, expected) in tests {
+            let picked = range.every_nth(first, n)?;
+            assert_eq!(picked.to_string(), format!("[{expected}]"), "{first}, {n}");
+        }
+
+        // Indices carry on from one sub-range to the next
+        let range = range_of(&[(1, 3), (7, 9)])?;
+        let values: Vec<u64> = range.every_nth(1, 2)?.iter().collect();
+        assert_eq!(values, [2, 7, 9]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_from_ranges() -> Result<()> {
+        let tests = [
+            (vec![], "[]"),
+            (vec![(5, 7), (1, 3)], "[1-3,5-7]"),
+            (vec![(5, 7), (1, 4)], "[1-7]"),
+            (vec![(1, 9), (3, 4), (2, 12), (20, 20)], "[1-12,20]"),
+        ];
+
+        for (ranges, expected) in tests {
+            let ranges = ranges
+                .into_iter()
+                .map(|(start, end)| SimpleRange::new(start, end))
+                .collect::<Result<Vec<SimpleRange>>>()?;
+            let range = Range::from_ranges(ranges.clone())?;
+            assert_eq!(range.to_string(), expected);
+
+            let mut added = Range::new();
+            for r in &ranges {
+                added.add_range(r)?;
+            }
+            assert_eq!(range, added);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_width() -> Result<()> {
+        let mut range: Range = Range::new();
+        range.add_range(&SimpleRange::with_width(1, 64, 3)?)?;
+        range.add_range(&SimpleRange::with_width(65, 128, 3)?)?;
+        assert_eq!(range.to_string(), "[001-128]");
+        assert_eq!(range.len(), 128);
+
+        // Differently padded values are distinct, so they neither merge nor deduplicate
+        range.add_range(&SimpleRange::new(5, 6)?)?;
+        assert_eq!(range.to_string(), "[001-128,5-6]");
+        assert_eq!(range.len(), 130);
+
+        let mut unpadded: Range = Range::new();
+        unpadded.add_range(&SimpleRange::new(1, 10)?)?;
+        assert_eq!(range.intersect(&unpadded)?.to_string(), "[5-6]");
+        assert_eq!(range.subtract(&unpadded)?.to_string(), "[001-128]");
+
+        range.set_width(2)?;
+        assert_eq!(range.to_string(), "[01-128]");
+        assert_eq!(range.len(), 128);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_u64() -> Result<()> {
+        let base = u64::from(u32::MAX);
+        let mut range: Range<u64> = Range::new();
+        range.add_range(&SimpleRange::new(base - 1, base + 1)?)?;
+        range.add_range(&SimpleRange::new(base + 2, base + 5)?)?;
+        assert_eq!(range.len(), 7);
+        assert_eq!(range.to_string(), "[4294967294-4294967300]");
+
+        let mut other: Range<u64> = Range::new();
+        other.add_range(&SimpleRange::new(base, base + 3)?)?;
+        assert_eq!(
+            range.subtract(&other)?.to_string(),
+            "[4294967294,4294967299-4294967300]"
+        );
+        assert_eq!(range.intersect(&other)?.len(), 4);
+
+        Ok(())
+    }
+
     #[test]
     fn test_range_len_limits() -> Result<()> {
-        let mut range = Range::new();
+        let mut range: Range<u32> = Range::new();
 
         range.add_range(&SimpleRange::new(0, 0)?).unwrap();
         assert_eq!(range.len(), 1);
@@ -231,7 +1155,7 @@ mod tests {
 
         assert_eq!(range.len(), u32::MAX as usize);
 
-        let mut range = Range::new();
+        let mut range: Range<u32> = Range::new();
 
         range
             .add_range(&SimpleRange::new(0, u32::MAX - 1)?)
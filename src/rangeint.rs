@@ -0,0 +1,68 @@
+use core::fmt::{Debug, Display};
+use core::hash::Hash;
+use core::ops::{Add, Div, Mul, Rem, Sub};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+/// An unsigned integer type that can be used for the bounds of a `SimpleRange` or `Range`.
+///
+/// This trait is sealed, it's only implemented for the integer types supported by this crate.
+pub trait RangeInt:
+    private::Sealed
+    + Copy
+    + Debug
+    + Display
+    + Hash
+    + Ord
+    + FromStr<Err = ParseIntError>
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Rem<Output = Self>
+{
+    const MIN: Self;
+    const MAX: Self;
+    const ONE: Self;
+
+    /// Widens the value for use in error reporting
+    fn to_u64(self) -> u64;
+
+    /// Converts the value to a `usize`, saturating if it doesn't fit
+    fn to_usize(self) -> usize;
+
+    /// Converts a `usize` to this type, if it fits
+    fn from_usize(n: usize) -> Option<Self>;
+}
+
+macro_rules! impl_range_int {
+    ($($t:ty),*) => {
+        $(
+            impl private::Sealed for $t {}
+
+            impl RangeInt for $t {
+                const MIN: Self = <$t>::MIN;
+                const MAX: Self = <$t>::MAX;
+                const ONE: Self = 1;
+
+                fn to_u64(self) -> u64 {
+                    self.into()
+                }
+
+                fn to_usize(self) -> usize {
+                    usize::try_from(self).unwrap_or(usize::MAX)
+                }
+
+                fn from_usize(n: usize) -> Option<Self> {
+                    Self::try_from(n).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_range_int!(u32, u64);
+
+mod private {
+    pub trait Sealed {}
+}
@@ -2,41 +2,70 @@ use core::fmt;
 use std::iter::FusedIterator;
 
 use crate::error::{Error, Result};
+use crate::rangeint::RangeInt;
 
-/// A simple a-b range, where a <= b
+/// A simple a-b range, where a <= b, optionally with a step (ex: "0-10/2")
 #[derive(Debug, Clone, Eq, Ord, PartialEq, PartialOrd)]
-pub struct SimpleRange {
-    pub start: u32,
-    pub end: u32,
-    current: Option<u32>,
+pub struct SimpleRange<T: RangeInt = u64> {
+    pub start: T,
+    pub end: T,       // The last value, so `end - start` is always a multiple of `step`
+    pub width: usize, // Minimum number of digits to render, zero-padded (0 means no padding)
+    pub step: T,      // Difference between consecutive values, 1 for every value
+    current: Option<T>,
 }
 
-impl fmt::Display for SimpleRange {
+impl<T: RangeInt> fmt::Display for SimpleRange<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.width;
         if self.start == self.end {
-            write!(f, "{}", self.start)
+            write!(f, "{:0width$}", self.start)
+        } else if self.step == T::ONE {
+            write!(f, "{:0width$}-{:0width$}", self.start, self.end)
         } else {
-            write!(f, "{}-{}", self.start, self.end)
+            write!(
+                f,
+                "{:0width$}-{:0width$}/{}",
+                self.start, self.end, self.step
+            )
         }
     }
 }
 
-impl SimpleRange {
-    pub const fn new(start: u32, end: u32) -> Result<Self> {
+impl<T: RangeInt> SimpleRange<T> {
+    pub fn new(start: T, end: T) -> Result<Self> {
+        Self::with_width(start, end, 0)
+    }
+
+    /// Constructs a range whose values are rendered zero-padded to at least `width` digits
+    pub fn with_width(start: T, end: T, width: usize) -> Result<Self> {
+        Self::with_step(start, end, T::ONE, width)
+    }
+
+    /// Constructs a range of every `step`th value from `start` up to `end`, rendered zero-padded
+    /// to at least `width` digits. `end` is lowered to the last value reached (ex: "0-9/2" is
+    /// "0-8/2"), and a step of 0 is read as 1.
+    pub fn with_step(start: T, end: T, step: T, width: usize) -> Result<Self> {
         if start > end {
-            return Err(Error::InvalidRangeReversed { start, end });
+            return Err(Error::InvalidRangeReversed {
+                start: start.to_u64(),
+                end: end.to_u64(),
+            });
         }
 
         // We want the range to be inclusive, and in the iterator we keep track of 'current' by
         // letting it go one higher than 'end', so we don't support 'end' being the max value for
         // the type.
-        if end == u32::MAX {
-            return Err(Error::TooLarge(end));
+        if end == T::MAX {
+            return Err(Error::TooLarge(end.to_u64()));
         }
 
+        let step = step.max(T::ONE);
+        let end = end - (end - start) % step;
         Ok(Self {
             start,
             end,
+            width,
+            step: if start == end { T::ONE } else { step },
             current: Some(start),
         })
     }
@@ -46,107 +75,345 @@ impl SimpleRange {
         self.current = Some(self.start);
     }
 
+    /// Moves the iterator past the value at `index`, counting from the start, and returns that
+    /// value. With `index` past the end, the iterator is left exhausted.
+    pub fn seek(&mut self, index: usize) -> Option<T> {
+        let value = T::from_usize(index)
+            .filter(|offset| *offset <= (self.end - self.start) / self.step)
+            .map(|offset| self.start + offset * self.step);
+        self.current = value
+            .filter(|value| *value < self.end)
+            .map(|value| value + self.step);
+        value
+    }
+
     /// Number of values represented by the range
-    pub const fn len(&self) -> usize {
+    pub fn len(&self) -> usize {
         if let Some(current) = self.current {
-            (self.end - current + 1) as usize
+            ((self.end - current) / self.step)
+                .to_usize()
+                .saturating_add(1)
         } else {
             0
         }
     }
 
+    /// Number of values in the range, including values already iterated over
+    pub fn total_len(&self) -> usize {
+        ((self.end - self.start) / self.step)
+            .to_usize()
+            .saturating_add(1)
+    }
+
+    /// Returns whether `value` is one of the range's values
+    pub fn contains(&self, value: T) -> bool {
+        self.start <= value && value <= self.end && ((value - self.start) % self.step).to_u64() == 0
+    }
+
+    /// Returns the range as single-value ranges if it has a step, or as itself if it doesn't, for
+    /// operations that only work with every value from start to end
+    pub fn unstepped(&self) -> Vec<Self> {
+        if self.step == T::ONE {
+            return vec![self.clone()];
+        }
+        self.iter()
+            .map(|value| Self {
+                start: value,
+                end: value,
+                width: self.width,
+                step: T::ONE,
+                current: Some(value),
+            })
+            .collect()
+    }
+
+    /// Total number of bytes taken by rendering every value in the range (including values already
+    /// iterated over), computed a power of ten at a time rather than value by value
+    pub fn rendered_bytes(&self) -> usize {
+        let (start, end, step) = (self.start.to_u64(), self.end.to_u64(), self.step.to_u64());
+        let mut bytes = 0usize;
+        let mut low = start;
+        let mut digits = 1;
+        let mut next_power = 10u64;
+        while low <= end {
+            while low >= next_power {
+                digits += 1;
+                next_power = next_power.saturating_mul(10);
+            }
+            let high = end.min(next_power - 1);
+            // The first of the range's values from low to high, if any
+            let count = low
+                .checked_add((step - (low - start) % step) % step)
+                .filter(|first| *first <= high)
+                .map_or(0, |first| {
+                    usize::try_from((high - first) / step + 1).unwrap_or(usize::MAX)
+                });
+            bytes = bytes.saturating_add(count.saturating_mul(digits.max(self.width)));
+            low = high + 1;
+        }
+        bytes
+    }
+
+    /// Returns the alternatives of a regular expression matching exactly how the range's values
+    /// are rendered (ex: "[1-9]" and "1[0-6]" for 1-16), a power of ten at a time
+    pub fn regex_alternatives(&self) -> Vec<String> {
+        if self.step != T::ONE {
+            // A step can't be matched a digit at a time, so each value is its own alternative
+            let width = self.width;
+            return self
+                .iter()
+                .map(|value| format!("{value:0width$}"))
+                .collect();
+        }
+        let end = self.end.to_u64();
+        let mut alternatives = Vec::new();
+        let mut low = self.start.to_u64();
+        let mut digits = 1;
+        let mut next_power = 10u64;
+        while low <= end {
+            while low >= next_power {
+                digits += 1;
+                next_power = next_power.saturating_mul(10);
+            }
+            let high = end.min(next_power - 1);
+            let padding = "0".repeat(self.width.saturating_sub(digits));
+            let patterns = digit_patterns(low.to_string().as_bytes(), high.to_string().as_bytes());
+            alternatives.extend(patterns.into_iter().map(|p| format!("{padding}{p}")));
+            low = high + 1;
+        }
+        alternatives
+    }
+
     // Returns an iterator over our range of values
-    pub const fn iter(&self) -> SimpleRangeIter {
+    pub fn iter(&self) -> SimpleRangeIter<T> {
         SimpleRangeIter {
-            current: self.start,
-            end: self.end,
+            front: self.start,
+            back: self.end,
+            step: self.step,
+            len: self.total_len(),
         }
     }
 }
 
+/// Returns regular expressions that together match the numbers from `lo` to `hi`, which are
+/// strings of digits with the same length
+fn digit_patterns(lo: &[u8], hi: &[u8]) -> Vec<String> {
+    let (Some((&first, lo_rest)), Some((&last, hi_rest))) = (lo.split_first(), hi.split_first())
+    else {
+        return vec![String::new()];
+    };
+    let prefixed = |digit: u8, patterns: Vec<String>| {
+        patterns
+            .into_iter()
+            .map(move |p| format!("{}{p}", char::from(digit)))
+    };
+    if first == last {
+        return prefixed(first, digit_patterns(lo_rest, hi_rest)).collect();
+    }
+
+    // Numbers starting with `first` that don't cover every value of the remaining digits, then
+    // every number starting with the digits in between, then numbers starting with `last`
+    let mut patterns = Vec::new();
+    let mut middle_first = first;
+    if lo_rest.iter().any(|&c| c != b'0') {
+        let nines = vec![b'9'; lo_rest.len()];
+        patterns.extend(prefixed(first, digit_patterns(lo_rest, &nines)));
+        middle_first += 1;
+    }
+    let mut middle_last = last;
+    let mut last_patterns = Vec::new();
+    if hi_rest.iter().any(|&c| c != b'9') {
+        let zeros = vec![b'0'; hi_rest.len()];
+        last_patterns.extend(prefixed(last, digit_patterns(&zeros, hi_rest)));
+        middle_last -= 1;
+    }
+    if (middle_first, middle_last) == (b'0', b'9') {
+        patterns.push(any_digits(lo.len()));
+    } else if middle_first <= middle_last {
+        let class = digit_class(middle_first, middle_last);
+        patterns.push(format!("{class}{}", any_digits(lo_rest.len())));
+    }
+    patterns.extend(last_patterns);
+    patterns
+}
+
+/// Returns a regular expression matching any `n` digits
+fn any_digits(n: usize) -> String {
+    match n {
+        0 => String::new(),
+        1 => "[0-9]".to_string(),
+        n => format!("[0-9]{{{n}}}"),
+    }
+}
+
+/// Returns a regular expression matching any digit from `first` to `last`
+fn digit_class(first: u8, last: u8) -> String {
+    let (first, last) = (char::from(first), char::from(last));
+    if first == last {
+        first.to_string()
+    } else if u32::from(last) - u32::from(first) == 1 {
+        format!("[{first}{last}]")
+    } else {
+        format!("[{first}-{last}]")
+    }
+}
+
 // This enables `for e in range { ... }`
-impl Iterator for SimpleRange {
-    type Item = u32;
+impl<T: RangeInt> Iterator for SimpleRange<T> {
+    type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
         if let Some(current) = self.current {
             self.current = if current == self.end {
                 None
             } else {
-                Some(current + 1)
+                Some(current + self.step)
             };
             Some(current)
         } else {
             None
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len(), Some(self.len()))
+    }
 }
 
+impl<T: RangeInt> ExactSizeIterator for SimpleRange<T> {}
+
+impl<T: RangeInt> FusedIterator for SimpleRange<T> {}
+
 // This enables:
 //   `for e in &range { ... }`
 //   `for e in range.into_iter() { ... }`
-impl IntoIterator for &SimpleRange {
-    type Item = u32;
-    type IntoIter = SimpleRangeIter;
+impl<T: RangeInt> IntoIterator for &SimpleRange<T> {
+    type Item = T;
+    type IntoIter = SimpleRangeIter<T>;
 
     fn into_iter(self) -> Self::IntoIter {
-        SimpleRangeIter {
-            current: self.start,
-            end: self.end,
-        }
+        self.iter()
     }
 }
 
-#[derive(Debug)]
-pub struct SimpleRangeIter {
-    current: u32,
-    end: u32,
+/// Borrowing iterator over the values of a `SimpleRange`, usable from either end
+#[derive(Debug, Clone)]
+pub struct SimpleRangeIter<T: RangeInt = u64> {
+    front: T,   // Next value to return from the front
+    back: T,    // Next value to return from the back
+    step: T,    // Difference between consecutive values
+    len: usize, // Number of values left to return from either end
 }
 
-impl Iterator for SimpleRangeIter {
-    type Item = u32;
+impl<T: RangeInt> Iterator for SimpleRangeIter<T> {
+    type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current <= self.end {
-            let result = self.current;
-            self.current += 1; // Guaranteed to not overflow since we don't allow max value
-            Some(result)
-        } else {
-            None
+        if self.len == 0 {
+            return None;
         }
+        let result = self.front;
+        self.len -= 1;
+        if self.len > 0 {
+            self.front = self.front + self.step;
+        }
+        Some(result)
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let remaining = if self.current <= self.end {
-            (self.end - self.current + 1) as usize
-        } else {
-            0
-        };
-        (remaining, Some(remaining))
+        (self.len, Some(self.len))
     }
 }
 
-impl ExactSizeIterator for SimpleRangeIter {
-    fn len(&self) -> usize {
-        if self.current <= self.end {
-            (self.end - self.current + 1) as usize
-        } else {
-            0
+impl<T: RangeInt> DoubleEndedIterator for SimpleRangeIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
         }
+        let result = self.back;
+        self.len -= 1;
+        if self.len > 0 {
+            self.back = self.back - self.step;
+        }
+        Some(result)
     }
 }
 
+impl<T: RangeInt> ExactSizeIterator for SimpleRangeIter<T> {}
+
 // This trait guarantees that once the iterator returns None, it will always return None.
 // No additional methods needed, it's a marker trait.
-impl FusedIterator for SimpleRangeIter {}
+impl<T: RangeInt> FusedIterator for SimpleRangeIter<T> {}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_simple_range_rendered_bytes() -> Result<()> {
+        let inputs = [
+            (0, 0, 0),
+            (0, 9, 0),
+            (5, 12, 0),
+            (1, 1000, 0),
+            (8, 11, 3),
+            (99, 101, 2),
+        ];
+        for (start, end, width) in inputs {
+            let range = SimpleRange::<u32>::with_width(start, end, width)?;
+            let expected: usize = range.iter().map(|v| format!("{v:0width$}").len()).sum();
+            assert_eq!(
+                range.rendered_bytes(),
+                expected,
+                "{start}-{end} width {width}"
+            );
+        }
+
+        let range = SimpleRange::<u64>::new(u64::MAX - 10, u64::MAX - 1)?;
+        assert_eq!(range.rendered_bytes(), 200);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simple_range_regex_alternatives() -> Result<()> {
+        let tests: [(u32, u32, usize, &[&str]); 7] = [
+            (7, 7, 0, &["7"]),
+            (1, 16, 0, &["[1-9]", "1[0-6]"]),
+            (0, 99, 0, &["[0-9]", "[1-9][0-9]"]),
+            (1, 16, 2, &["0[1-9]", "1[0-6]"]),
+            (8, 11, 3, &["00[89]", "01[01]"]),
+            (
+                123,
+                4567,
+                0,
+                &[
+                    "12[3-9]",
+                    "1[3-9][0-9]",
+                    "[2-9][0-9]{2}",
+                    "[1-3][0-9]{3}",
+                    "4[0-4][0-9]{2}",
+                    "45[0-5][0-9]",
+                    "456[0-7]",
+                ],
+            ),
+            (100, 199, 0, &["1[0-9]{2}"]),
+        ];
+        for (start, end, width, expected) in tests {
+            let range = SimpleRange::with_width(start, end, width)?;
+            assert_eq!(
+                range.regex_alternatives(),
+                expected,
+                "{start}-{end} width {width}"
+            );
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_simplerange_len() {
-        let range = SimpleRange::new(1, 1_000_000_000).unwrap();
+        let range = SimpleRange::new(1_u32, 1_000_000_000).unwrap();
         assert_eq!(range.len(), 1_000_000_000);
     }
 
@@ -161,17 +428,80 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_simplerange_display() -> Result<()> {
+        let tests = [
+            ((1_u32, 1, 0), "1"),
+            ((1, 5, 0), "1-5"),
+            ((1, 5, 1), "1-5"),
+            ((1, 5, 3), "001-005"),
+            ((1, 128, 3), "001-128"),
+            ((98, 102, 3), "098-102"),
+            ((1000, 1001, 3), "1000-1001"),
+            ((7, 7, 2), "07"),
+        ];
+
+        for ((start, end, width), expected) in tests {
+            let range = SimpleRange::with_width(start, end, width)?;
+            assert_eq!(range.to_string(), expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simplerange_step() -> Result<()> {
+        let range = SimpleRange::with_step(0_u32, 10, 3, 0)?;
+        assert_eq!(range.to_string(), "0-9/3");
+        assert_eq!(range.len(), 4);
+        assert_eq!(range.iter().collect::<Vec<_>>(), [0, 3, 6, 9]);
+        assert_eq!(range.iter().rev().collect::<Vec<_>>(), [9, 6, 3, 0]);
+        assert_eq!(range.clone().collect::<Vec<_>>(), [0, 3, 6, 9]);
+        assert!(range.contains(6) && !range.contains(7) && !range.contains(12));
+        assert_eq!(range.unstepped().len(), 4);
+        assert_eq!(range.regex_alternatives(), ["0", "3", "6", "9"]);
+
+        let mut range = SimpleRange::with_step(8_u32, 12, 2, 2)?;
+        assert_eq!(range.to_string(), "08-12/2");
+        assert_eq!(range.rendered_bytes(), 6);
+        assert_eq!(range.seek(1), Some(10));
+        assert_eq!(range.next(), Some(12));
+        assert_eq!(range.seek(3), None);
+
+        // A step that never reaches a second value leaves a single value
+        assert_eq!(SimpleRange::with_step(5_u32, 6, 4, 0)?.to_string(), "5");
+        assert_eq!(SimpleRange::with_step(1_u32, 3, 0, 0)?.to_string(), "1-3");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simplerange_u64() -> Result<()> {
+        let toolarge = SimpleRange::new(0, u64::MAX);
+        assert!(matches!(toolarge, Err(Error::TooLarge(u64::MAX))));
+
+        let start = u64::from(u32::MAX) + 1;
+        let range = SimpleRange::new(start, start + 2)?;
+        assert_eq!(range.len(), 3);
+        assert_eq!(range.to_string(), "4294967296-4294967298");
+
+        let values: Vec<u64> = range.iter().rev().collect();
+        assert_eq!(values, vec![start + 2, start + 1, start]);
+
+        Ok(())
+    }
+
     #[test]
     fn test_simplerange_iterator() {
         let mut expected = vec![1, 2, 3, 4, 5];
 
-        let simplerange = SimpleRange::new(1, 5).unwrap();
+        let simplerange = SimpleRange::new(1_u32, 5).unwrap();
         for elem in simplerange {
             let e = expected.remove(0);
             assert_eq!(e, elem);
         }
 
-        let mut simplerange = SimpleRange::new(1, 5).unwrap();
+        let mut simplerange = SimpleRange::new(1_u32, 5).unwrap();
         assert_eq!(simplerange.next(), Some(1));
         assert_eq!(simplerange.next(), Some(2));
         assert_eq!(simplerange.next(), Some(3));
@@ -212,7 +542,7 @@ mod tests {
     #[test]
     fn test_simplerange_intoiter() {
         let expected_orig = vec![1, 2, 3, 4, 5];
-        let simplerange = SimpleRange::new(1, 5).unwrap();
+        let simplerange = SimpleRange::new(1_u32, 5).unwrap();
 
         let mut expected = expected_orig.clone();
         for elem in &simplerange {
@@ -243,8 +573,37 @@ mod tests {
             // indicate 'iter' implements ExactSizeIterator.
         }
 
-        let simplerange = SimpleRange::new(1, 1).unwrap();
+        let simplerange = SimpleRange::new(1_u32, 1).unwrap();
 
         inner(simplerange.iter());
+        inner(simplerange);
+    }
+
+    #[test]
+    fn test_simplerange_iter_double_ended() -> Result<()> {
+        let simplerange = SimpleRange::new(1, 5)?;
+
+        let reversed: Vec<u32> = simplerange.iter().rev().collect();
+        assert_eq!(reversed, vec![5, 4, 3, 2, 1]);
+
+        let mut iter = simplerange.iter();
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next_back(), Some(5));
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next_back(), Some(4));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+
+        // Ranges starting at zero must not underflow when iterated from the back
+        let mut iter = SimpleRange::new(0_u32, 0)?.iter();
+        assert_eq!(iter.next_back(), Some(0));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+
+        Ok(())
     }
 }
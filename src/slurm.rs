@@ -0,0 +1,369 @@
+//! Expansion and folding that match Slurm's own hostlist functions (`scontrol show hostnames` and
+//! `scontrol show hostlist`), for replacing them without changing any output
+//!
+//! Unlike `Hostlist`, hosts stay in the order they were given, duplicates are kept, every number
+//! keeps the width it was written with, and with several ranges in an element the first range
+//! changes slowest (ex: "r[1-2]n[1-2]" is "r1n1", "r1n2", "r2n1", "r2n2"). Elements can be
+//! separated by whitespace as well as commas.
+
+use pest::Parser;
+use pest::iterators::Pair;
+
+use crate::error::{Error, Result};
+use crate::expand::{Part, Values, expand_elements, unsupported};
+use crate::hostlist::{HostlistParser, Rule};
+
+/// Expands a hostlist expression the way `scontrol show hostnames` does
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if there are issues parsing the provided hostlist
+/// expression, if it expands to more than `MAX_EXPANDED_HOSTS` hosts, or `Error::Unsupported` if
+/// it has syntax Slurm doesn't accept (steps, letter ranges, or set operators).
+/// ```
+/// use hostlist_iter::slurm;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let hosts = slurm::expand("n[3,01-02] login")?;
+///   assert_eq!(hosts, ["n3", "n01", "n02", "login"]);
+///
+///   Ok(())
+/// }
+/// ```
+pub fn expand(expr: &str) -> Result<Vec<String>> {
+    let mut pairs = Vec::new();
+    for element in split_elements(expr) {
+        pairs.extend(HostlistParser::parse(Rule::hostlist, element)?);
+    }
+    expand_elements(pairs, element_parts)
+}
+
+/// Folds host names the way `scontrol show hostlist` does: a host only extends the range before
+/// it, and ranges are only bracketed together with the ranges right next to them. Use
+/// `collapse(expand(expr)?)` for the output of `scontrol show hostlist <expr>`.
+///
+/// # Errors
+/// Will return `hostlist_iter::Error` if any host name is empty.
+/// ```
+/// use hostlist_iter::slurm;
+///
+/// fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+///   let hostlist = slurm::collapse(["n3", "n1", "n2", "login", "n4"])?;
+///   assert_eq!(hostlist, "n[3,1-2],login,n4");
+///
+///   Ok(())
+/// }
+/// ```
+pub fn collapse(hosts: impl IntoIterator<Item = impl AsRef<str>>) -> Result<String> {
+    let mut ranges: Vec<HostRange> = Vec::new();
+    for host in hosts {
+        let host = host.as_ref();
+        if host.is_empty() {
+            return Err(Error::InvalidHostname(host.into()));
+        }
+        let range = HostRange::new(host);
+        if let Some(tail) = ranges.last_mut()
+            && tail.extend(&range)
+        {
+            continue;
+        }
+        ranges.push(range);
+    }
+
+    let mut elements = Vec::new();
+    let mut rest = ranges.as_slice();
+    while let Some(first) = rest.first() {
+        let len = rest.iter().take_while(|r| first.within(r)).count().max(1);
+        let (group, remaining) = rest.split_at(len);
+        elements.push(bracketed(group));
+        rest = remaining;
+    }
+
+    Ok(elements.join(","))
+}
+
+/// Splits an expression at the commas and whitespace outside of brackets, skipping empty elements
+fn split_elements(expr: &str) -> impl Iterator<Item = &str> {
+    let mut depth = 0usize;
+    expr.split(move |c: char| {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        depth == 0 && (c == ',' || c.is_whitespace())
+    })
+    .filter(|element| !element.is_empty())
+}
+
+/// A range in brackets, with the number of digits its start was written with
+struct SlurmRange {
+    start: u64,
+    end: u64,
+    width: usize,
+}
+
+impl Values for SlurmRange {
+    fn count(&self) -> Option<usize> {
+        usize::try_from(self.end - self.start).ok()?.checked_add(1)
+    }
+
+    fn for_each(&self, mut f: impl FnMut(&str)) {
+        let width = self.width;
+        for value in self.start..=self.end {
+            f(&format!("{value:0width$}"));
+        }
+    }
+}
+
+fn element_parts(pair: Pair<'_, Rule>) -> Result<Vec<Part<'_, SlurmRange>>> {
+    let mut parts = Vec::new();
+    for component in pair.into_inner() {
+        match component.as_rule() {
+            Rule::static_elem => parts.push(Part::Literal(component.as_str().into())),
+            Rule::range => {
+                let mut ranges = Vec::new();
+                for range_elem in component.into_inner() {
+                    let (start, end) = match range_elem.as_rule() {
+                        Rule::simple_range => {
+                            let text = range_elem.as_str();
+                            let mut bounds = range_elem.into_inner();
+                            let start = bounds.next().map_or("", |n| n.as_str());
+                            let end = bounds.next().map_or("", |n| n.as_str());
+                            if bounds.next().is_some() {
+                                return Err(unsupported("steps", text));
+                            }
+                            (start, end)
+                        }
+                        Rule::number => (range_elem.as_str(), range_elem.as_str()),
+                        rule => return Err(Error::UnexpectedParserState(rule)),
+                    };
+                    let (width, start, end) = (start.len(), start.parse()?, end.parse()?);
+                    if start > end {
+                        return Err(Error::InvalidRangeReversed { start, end });
+                    }
+                    ranges.push(SlurmRange { start, end, width });
+                }
+                parts.push(Part::Ranges(ranges));
+            }
+            Rule::letter_range => return Err(unsupported("letter ranges", component.as_str())),
+            rule => return Err(Error::UnexpectedParserState(rule)),
+        }
+    }
+
+    Ok(parts)
+}
+
+/// A run of hosts with the same prefix and consecutive numeric suffixes, or a single host without
+/// a numeric suffix
+struct HostRange {
+    prefix: String,
+    numbers: Option<(u64, u64)>,
+    width: usize,
+}
+
+impl HostRange {
+    /// Splits a host name into its prefix and numeric suffix, if any
+    fn new(host: &str) -> Self {
+        let prefix = host.trim_end_matches(|c: char| c.is_ascii_digit());
+        let digits = &host[prefix.len()..];
+        match digits.parse::<u64>() {
+            Ok(number) => Self {
+                prefix: prefix.to_string(),
+                numbers: Some((number, number)),
+                width: digits.len(),
+            },
+            Err(_) => Self {
+                prefix: host.to_string(),
+                numbers: None,
+                width: 0,
+            },
+        }
+    }
+
+    /// Extends this range with `next` if `next` continues it, returning whether it did
+    fn extend(&mut self, next: &Self) -> bool {
+        let (Some((start, end)), Some((next_start, next_end))) = (self.numbers, next.numbers)
+        else {
+            return false;
+        };
+        if self.prefix != next.prefix || end.checked_add(1) != Some(next_start) {
+            return false;
+        }
+        let Some(width) = combined_width(start, self.width, next_start, next.width) else {
+            return false;
+        };
+        self.numbers = Some((start, next_end));
+        self.width = width;
+        true
+    }
+
+    /// Returns whether `other` can share this range's brackets
+    fn within(&self, other: &Self) -> bool {
+        self.prefix == other.prefix && self.numbers.is_some() && other.numbers.is_some()
+    }
+}
+
+/// Returns the width for a range starting at `start` with `width` digits extended by a range
+/// starting at `next` with `next_width` digits, if their padding is compatible (ex: "9" and "10",
+/// but not "01" and "2")
+fn combined_width(start: u64, width: usize, next: u64, next_width: usize) -> Option<usize> {
+    if width == next_width {
+        return Some(width);
+    }
+    let padding = |value: u64, width: usize| {
+        let digits = value.checked_ilog10().map_or(1, |log| log as usize + 1);
+        width.saturating_sub(digits)
+    };
+    if padding(start, width) == padding(start, next_width) {
+        Some(next_width)
+    } else if padding(next, next_width) == padding(next, width) {
+        Some(width)
+    } else {
+        None
+    }
+}
+
+/// Renders ranges with the same prefix, in brackets if there's more than one host
+fn bracketed(group: &[HostRange]) -> String {
+    let prefix = &group[0].prefix;
+    let rendered: Vec<String> = group
+        .iter()
+        .filter_map(|range| {
+            let width = range.width;
+            range.numbers.map(|(start, end)| {
+                if start == end {
+                    format!("{start:0width$}")
+                } else {
+                    format!("{start:0width$}-{end:0width$}")
+                }
+            })
+        })
+        .collect();
+    match rendered.as_slice() {
+        [] => prefix.clone(),
+        [number] if !number.contains('-') => format!("{prefix}{number}"),
+        _ => format!("{prefix}[{}]", rendered.join(",")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slurm_expand() -> Result<()> {
+        let tests: [(&str, &[&str]); 9] = [
+            ("", &[]),
+            ("n[1-3]", &["n1", "n2", "n3"]),
+            ("n[3,1-2],n1", &["n3", "n1", "n2", "n1"]),
+            ("n[01-03]", &["n01", "n02", "n03"]),
+            ("n[08-10]", &["n08", "n09", "n10"]),
+            (
+                "n[1-010]",
+                &["n1", "n2", "n3", "n4", "n5", "n6", "n7", "n8", "n9", "n10"],
+            ),
+            ("r[1-2]n[1-2]", &["r1n1", "r1n2", "r2n1", "r2n2"]),
+            (
+                "n[1-2]-ib login\tgpu1",
+                &["n1-ib", "n2-ib", "login", "gpu1"],
+            ),
+            (" n1 ,, n2 ", &["n1", "n2"]),
+        ];
+        for (expr, expected) in tests {
+            assert_eq!(expand(expr)?, expected, "expr: {expr}");
+        }
+
+        assert!(matches!(
+            expand("n[3-1]"),
+            Err(Error::InvalidRangeReversed { start: 3, end: 1 })
+        ));
+        assert!(matches!(expand("n[1-3"), Err(Error::ParseError(_))));
+        assert_eq!(expand("n[0-99999999999]"), Err(Error::HostlistTooLarge));
+
+        // Valid hostlist syntax that `scontrol` doesn't accept
+        let tests = [
+            ("n[0-10/5]", "steps", "0-10/5"),
+            ("rack[a-b]n1", "letter ranges", "[a-b]"),
+            ("n[1-5]!n3", "set operators", "!"),
+        ];
+        for (expr, syntax, text) in tests {
+            assert_eq!(
+                expand(expr),
+                Err(Error::Unsupported {
+                    syntax,
+                    text: text.to_string()
+                }),
+                "expr: {expr}"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Checks the cases in `testdata/scontrol.txt`, each a command (`hostnames` or `hostlist`), its
+    /// argument, and what `scontrol show <command> <argument>` printed, separated by tabs
+    #[test]
+    fn test_slurm_scontrol_output() -> Result<()> {
+        let cases = include_str!("testdata/scontrol.txt")
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for case in cases {
+            let fields: Vec<&str> = case.split('\t').collect();
+            let [command, argument, output] = fields[..] else {
+                panic!("malformed case: {case:?}");
+            };
+            match command {
+                "hostnames" => assert_eq!(
+                    expand(argument)?,
+                    output.split(' ').collect::<Vec<_>>(),
+                    "case: {case:?}"
+                ),
+                "hostlist" => assert_eq!(collapse(expand(argument)?)?, output, "case: {case:?}"),
+                _ => panic!("unknown command: {case:?}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_slurm_collapse() -> Result<()> {
+        let tests: [(&[&str], &str); 11] = [
+            (&[], ""),
+            (&["n1", "n2", "n3"], "n[1-3]"),
+            (&["n3", "n1", "n2"], "n[3,1-2]"),
+            (&["n1", "n1"], "n[1,1]"),
+            (&["n01", "n02", "n3"], "n[01-02,3]"),
+            (&["n9", "n10", "n11"], "n[9-11]"),
+            (&["n098", "n099", "n100"], "n[098-100]"),
+            (&["n1", "login", "n2"], "n1,login,n2"),
+            (&["login", "login"], "login,login"),
+            (&["r1n1", "r1n2", "r2n1"], "r1n[1-2],r2n1"),
+            (&["n1-ib", "n2-ib"], "n1-ib,n2-ib"),
+        ];
+        for (hosts, expected) in tests {
+            assert_eq!(collapse(hosts)?, expected, "hosts: {hosts:?}");
+        }
+
+        assert_eq!(
+            collapse(["n1", ""]),
+            Err(Error::InvalidHostname(String::new()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_slurm_round_trip() -> Result<()> {
+        for expr in [
+            "n[1-3,5,7-9]",
+            "n[01-10],login",
+            "n[5,1-3]",
+            "a[1-2],b[1-2],a3",
+        ] {
+            assert_eq!(collapse(expand(expr)?)?, expr, "expr: {expr}");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,12 @@
+/// How `Hostlist::split_into()` assigns hosts to groups
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Split {
+    /// Each group is a run of consecutive hosts (ex: "n[1-3]" and "n[4-5]" for "n[1-5]"), so the
+    /// groups fold into as few ranges as possible
+    #[default]
+    Contiguous,
+
+    /// Hosts are dealt out in turn (ex: "n[1-5/2]" and "n[2-4/2]" for "n[1-5]"), spreading hosts
+    /// that are next to each other (ex: in the same rack) across the groups
+    RoundRobin,
+}
@@ -0,0 +1,108 @@
+use pest::Parser;
+
+use crate::hostlist::{HostlistParser, Rule};
+
+/// Returns a corrected version of a hostlist expression that failed to parse, if it contains a
+/// recognizable mistake (ex: "node[1:5]", "node{1..5}", or "node[1-5") and the correction parses.
+pub fn suggest(expr: &str) -> Option<String> {
+    let fixed = balance_brackets(&fix_range_separators(&fix_braces(expr)));
+    (fixed != expr && HostlistParser::parse(Rule::hostlist, &fixed).is_ok()).then_some(fixed)
+}
+
+/// Turns shell brace expansions into ranges (ex: "{1..5}" into "[1..5]")
+fn fix_braces(expr: &str) -> String {
+    expr.replace('{', "[").replace('}', "]")
+}
+
+/// Replaces other ways of writing "a-b" inside brackets (ex: "1:5" or "1..5")
+fn fix_range_separators(expr: &str) -> String {
+    let mut fixed = String::with_capacity(expr.len());
+    let mut in_range = false;
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => in_range = true,
+            ']' => in_range = false,
+            ':' if in_range => {
+                fixed.push('-');
+                continue;
+            }
+            '.' if in_range && chars.peek() == Some(&'.') => {
+                chars.next();
+                fixed.push('-');
+                continue;
+            }
+            _ => {}
+        }
+        fixed.push(c);
+    }
+    fixed
+}
+
+/// Adds missing brackets around ranges, and drops brackets that can't belong to one
+fn balance_brackets(expr: &str) -> String {
+    let mut fixed = String::with_capacity(expr.len() + 2);
+    let mut in_range = false;
+    for c in expr.chars() {
+        match c {
+            // Ranges don't nest
+            '[' if in_range => continue,
+            '[' => in_range = true,
+            ']' if in_range => in_range = false,
+            ']' => {
+                // Missing opening bracket: open one before the range values leading up to here
+                let values = fixed
+                    .trim_end_matches(|c: char| c.is_ascii_digit() || ",-".contains(c))
+                    .len();
+                let start = fixed.len() - fixed[values..].trim_start_matches([',', '-']).len();
+                if start == fixed.len() {
+                    continue;
+                }
+                fixed.insert(start, '[');
+            }
+            // Missing closing bracket: a comma followed by a name starts the next element
+            c if in_range && c.is_ascii_alphabetic() && fixed.ends_with(',') => {
+                fixed.pop();
+                fixed.push_str("],");
+                in_range = false;
+            }
+            _ => {}
+        }
+        fixed.push(c);
+    }
+    if in_range {
+        fixed.push(']');
+    }
+    fixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest() {
+        let tests = [
+            ("node[1:5]", Some("node[1-5]")),
+            ("node[1..5]", Some("node[1-5]")),
+            ("node{1..5}", Some("node[1-5]")),
+            ("node{1,3,5}", Some("node[1,3,5]")),
+            ("node{1..5", Some("node[1-5]")),
+            ("node[1-5", Some("node[1-5]")),
+            ("n[1-3],m[2:4", Some("n[1-3],m[2-4]")),
+            ("node[1-3,5,login1", Some("node[1-3,5],login1")),
+            ("node1-3]", Some("node[1-3]")),
+            ("node[[1-3]]", Some("node[1-3]")),
+            ("node[1-3]]", Some("node[1-3]")),
+            ("node[1-3]x]", Some("node[1-3]x")),
+            ("node[a-3]", None),
+            ("node[1.5-3]", None),
+            ("node]1-3[", None),
+            ("node[1-3]", None),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(suggest(input).as_deref(), expected, "input: \"{input}\"");
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use derive_more::Display;
+
+use crate::hostlist::Hostlist;
+
+/// Something about a hostlist expression that was silently normalized while parsing it
+#[non_exhaustive]
+#[derive(Debug, Display, Clone, Eq, PartialEq)]
+pub enum Warning {
+    /// A zero-padded number was read as its value, so the padding won't appear in host names.
+    /// Only `Padding::Strip` drops padding, so `Hostlist::parse_with_warnings()`, which keeps it
+    /// like `Hostlist::new()`, doesn't report it.
+    #[display("zero-padding dropped from \"{number}\" in \"{element}\"")]
+    PaddingDropped { element: String, number: String },
+
+    /// Overlapping ranges described some hosts more than once
+    #[display("{count} duplicate hosts removed from \"{element}\"")]
+    DuplicatesRemoved { element: String, count: usize },
+
+    /// Elements differing only in their range were combined into one
+    #[display("{count} elements combined into \"{combined}\"")]
+    ElementsCombined { combined: String, count: usize },
+}
+
+/// The result of `Hostlist::parse_with_warnings()`
+#[derive(Debug, Clone)]
+pub struct ParseOutcome {
+    pub hostlist: Hostlist,
+    pub warnings: Vec<Warning>,
+}
@@ -0,0 +1,231 @@
+//! Smoke tests running the `hostlist_iter` binary, one or two per subcommand
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the binary with `args` and `stdin`, ignoring any user config file
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_hostlist_iter"))
+        .args(args)
+        .env("XDG_CONFIG_HOME", "/nonexistent")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Runs the binary, checking that it succeeds, and returns its stdout
+fn stdout(args: &[&str], stdin: &str) -> String {
+    let output = run(args, stdin);
+    assert!(
+        output.status.success(),
+        "{args:?}: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(stdout(&["parse", "n[3,1-2],n2"], ""), "n[1-3]\n");
+    assert_eq!(
+        stdout(&["parse", "--pad", "keep", "n[01-02],n3"], ""),
+        "n[01-02],n3\n"
+    );
+
+    let output = run(&["parse", "n[1-"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn test_list() {
+    assert_eq!(stdout(&["list", "n[1-3]"], ""), "n1\nn2\nn3\n");
+    assert_eq!(
+        stdout(&["list", "--sort", "n[10,2]", "m1"], ""),
+        "n2\nn10\nm1\n"
+    );
+    assert_eq!(stdout(&["list", "--null", "n[1-2]"], ""), "n1\0n2\0");
+}
+
+#[test]
+fn test_collapse() {
+    assert_eq!(stdout(&["collapse", "n1", "n3", "n2"], ""), "n[1-3]\n");
+    assert_eq!(stdout(&["collapse"], "n1\n\n n2 \n"), "n[1-2]\n");
+    assert_eq!(stdout(&["collapse", "--null"], "n1\0n2\0"), "n[1-2]\n");
+}
+
+#[test]
+fn test_count() {
+    assert_eq!(stdout(&["count", "n[1-4]", "m1"], ""), "4\n1\n");
+    assert_eq!(
+        stdout(&["count", "--unique", "n[1-4]", "n[3-5]"], ""),
+        "5\n"
+    );
+    assert_eq!(
+        stdout(&["count", "--table", "n[1-4],m1"], ""),
+        "EXPRESSION  HOSTS  PREFIXES  RANGES\nn[1-4],m1       5         2       1\n"
+    );
+}
+
+#[test]
+fn test_diff() {
+    assert_eq!(
+        stdout(&["diff", "--color", "never", "n[1-4]", "n[3-6]"], ""),
+        "- n[1-2]\n+ n[5-6]\n"
+    );
+}
+
+#[test]
+fn test_overlap() {
+    assert_eq!(
+        stdout(&["overlap", "n[1-4]", "n[3-6]"], ""),
+        "1,2: n[3-4]\n"
+    );
+}
+
+#[test]
+fn test_eval() {
+    assert_eq!(
+        stdout(&["eval", "n[1-10] - n[3-4] & n[1-5]"], ""),
+        "n[1-2,5]\n"
+    );
+    assert_eq!(stdout(&["eval", "n[1-2]", "|", "m1"], ""), "m1,n[1-2]\n");
+}
+
+#[test]
+fn test_indices() {
+    assert_eq!(
+        stdout(&["indices", "--prefix", "n", "n[1-3],m5,n7"], ""),
+        "[1-3,7]\n"
+    );
+}
+
+#[test]
+fn test_batch() {
+    let requests = concat!(
+        r#"{"op": "expand", "expr": "n[1-3]"}"#,
+        "\n",
+        r#"{"op": "count", "expr": "n["}"#,
+        "\n",
+    );
+    let output = stdout(&["batch"], requests);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 2, "{output}");
+    assert_eq!(lines[0], r#"{"hosts":["n1","n2","n3"]}"#);
+    assert!(lines[1].starts_with(r#"{"error":"#), "{}", lines[1]);
+}
+
+#[test]
+fn test_groups() {
+    let path =
+        std::env::temp_dir().join(format!("hostlist_iter-cli-groups-{}", std::process::id()));
+    std::fs::write(&path, "# GPU nodes\ngpu: gpu[1-4]\nlogin: login[1-2]\n").unwrap();
+    let groups = path.to_str().unwrap();
+
+    let eval = stdout(&["--groups", groups, "eval", "@gpu | @login - login1"], "");
+    let count = stdout(&["--groups", groups, "count", "@gpu,@login"], "");
+    let unknown = run(&["--groups", groups, "parse", "@compute"], "");
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(eval, "gpu[1-4],login[2]\n");
+    assert_eq!(count, "6\n");
+    assert_eq!(unknown.status.code(), Some(1));
+    assert!(
+        String::from_utf8_lossy(&unknown.stderr).contains("unknown group: \"@compute\""),
+        "{unknown:?}"
+    );
+}
+
+#[test]
+fn test_max_hosts() {
+    assert_eq!(
+        stdout(&["--max-hosts", "3", "list", "n[1-3]"], ""),
+        "n1\nn2\nn3\n"
+    );
+
+    let output = run(&["--max-hosts", "3", "list", "n[1-4]"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "\"n[1-4]\" expands to 4 hosts, more than the maximum of 3\n"
+    );
+
+    // The config file sets a default, which the flag overrides
+    let path =
+        std::env::temp_dir().join(format!("hostlist_iter-cli-config-{}", std::process::id()));
+    std::fs::write(&path, "max_hosts = 3\n").unwrap();
+    let config = path.to_str().unwrap();
+    let limited = run(&["--config", config, "list", "n[1-4]"], "");
+    let overridden = run(
+        &["--config", config, "--max-hosts", "4", "list", "n[1-4]"],
+        "",
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(limited.status.code(), Some(1));
+    assert!(overridden.status.success(), "{overridden:?}");
+}
+
+#[cfg(feature = "net")]
+#[test]
+fn test_probe() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    assert_eq!(
+        stdout(
+            &["probe", "--port", &port, "--timeout", "5s", "127.0.0.1"],
+            ""
+        ),
+        "reachable: 127.0.0.1\nunreachable: \n"
+    );
+}
+
+#[cfg(feature = "serve")]
+#[test]
+fn test_serve() {
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpStream;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_hostlist_iter"))
+        .args(["serve", "--listen", "127.0.0.1:0"])
+        .env("XDG_CONFIG_HOME", "/nonexistent")
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // The server announces the port it was given on stderr
+    let mut line = String::new();
+    BufReader::new(child.stderr.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let addr = line
+        .trim()
+        .trim_start_matches("Listening on http://")
+        .to_string();
+
+    let body = r#"{"expr": "n[1-3]"}"#;
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    write!(
+        stream,
+        "POST /count HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.ends_with(r#"{"count":3}"#), "{response}");
+}